@@ -0,0 +1,45 @@
+-- 任务状态变更记录，由触发器维护；任务删除后历史保留，用于统计
+CREATE TABLE IF NOT EXISTS task_history (
+    id BIGSERIAL PRIMARY KEY,
+    task_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    old_status TEXT,
+    new_status TEXT NOT NULL,
+    changed_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS task_history_task_id_idx ON task_history (task_id, changed_at);
+CREATE INDEX IF NOT EXISTS task_history_user_id_idx ON task_history (user_id, new_status, changed_at);
+
+CREATE OR REPLACE FUNCTION record_task_status() RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        INSERT INTO task_history (task_id, user_id, old_status, new_status, changed_at)
+        VALUES (NEW.id, NEW.user_id, NULL, NEW.status, COALESCE(NEW.created_at, NOW()));
+    ELSIF NEW.status IS DISTINCT FROM OLD.status THEN
+        INSERT INTO task_history (task_id, user_id, old_status, new_status, changed_at)
+        VALUES (NEW.id, NEW.user_id, OLD.status, NEW.status, NOW());
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS tasks_status_history ON tasks;
+CREATE TRIGGER tasks_status_history
+    AFTER INSERT OR UPDATE OF status ON tasks
+    FOR EACH ROW EXECUTE FUNCTION record_task_status();
+
+-- 已有任务：创建时间记一条，已完成的任务用 updated_at 近似完成时间
+INSERT INTO task_history (task_id, user_id, old_status, new_status, changed_at)
+SELECT id, user_id, NULL, CASE WHEN status = 'completed' THEN 'pending' ELSE status END,
+       COALESCE(created_at, NOW())
+FROM tasks
+WHERE NOT EXISTS (SELECT 1 FROM task_history h WHERE h.task_id = tasks.id);
+
+INSERT INTO task_history (task_id, user_id, old_status, new_status, changed_at)
+SELECT id, user_id, 'pending', 'completed', COALESCE(updated_at, NOW())
+FROM tasks
+WHERE status = 'completed'
+  AND NOT EXISTS (
+      SELECT 1 FROM task_history h WHERE h.task_id = tasks.id AND h.new_status = 'completed'
+  );
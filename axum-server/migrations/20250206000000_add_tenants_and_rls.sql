@@ -0,0 +1,143 @@
+CREATE TABLE IF NOT EXISTS organizations (
+    id SERIAL PRIMARY KEY,
+    name TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS users (
+    id SERIAL PRIMARY KEY,
+    organization_id INTEGER NOT NULL REFERENCES organizations (id),
+    email TEXT NOT NULL UNIQUE,
+    name TEXT NOT NULL DEFAULT '',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS users_organization_id_idx ON users (organization_id);
+
+-- 已有数据都归到默认组织，并为出现过的 user_id 补上用户
+INSERT INTO organizations (id, name) VALUES (1, 'Default') ON CONFLICT (id) DO NOTHING;
+SELECT setval('organizations_id_seq', GREATEST((SELECT MAX(id) FROM organizations), 1));
+
+INSERT INTO users (id, organization_id, email)
+SELECT user_id, 1, 'user' || user_id || '@example.com'
+FROM (
+    SELECT 1 AS user_id
+    UNION SELECT user_id FROM tasks
+    UNION SELECT user_id FROM saved_views
+    UNION SELECT user_id FROM calendar_feeds
+    UNION SELECT user_id FROM task_history
+) existing
+ON CONFLICT (id) DO NOTHING;
+SELECT setval('users_id_seq', GREATEST((SELECT MAX(id) FROM users), 1));
+
+-- 租户列默认取当前请求的 app.organization_id
+ALTER TABLE tasks ADD COLUMN IF NOT EXISTS organization_id INTEGER;
+ALTER TABLE task_history ADD COLUMN IF NOT EXISTS organization_id INTEGER;
+ALTER TABLE saved_views ADD COLUMN IF NOT EXISTS organization_id INTEGER;
+ALTER TABLE calendar_feeds ADD COLUMN IF NOT EXISTS organization_id INTEGER;
+
+UPDATE tasks SET organization_id = users.organization_id
+FROM users WHERE users.id = tasks.user_id AND tasks.organization_id IS NULL;
+UPDATE task_history SET organization_id = users.organization_id
+FROM users WHERE users.id = task_history.user_id AND task_history.organization_id IS NULL;
+UPDATE saved_views SET organization_id = users.organization_id
+FROM users WHERE users.id = saved_views.user_id AND saved_views.organization_id IS NULL;
+UPDATE calendar_feeds SET organization_id = users.organization_id
+FROM users WHERE users.id = calendar_feeds.user_id AND calendar_feeds.organization_id IS NULL;
+
+ALTER TABLE tasks
+    ALTER COLUMN organization_id SET DEFAULT NULLIF(current_setting('app.organization_id', true), '')::integer,
+    ALTER COLUMN organization_id SET NOT NULL,
+    ADD CONSTRAINT tasks_organization_id_fkey FOREIGN KEY (organization_id) REFERENCES organizations (id);
+ALTER TABLE task_history
+    ALTER COLUMN organization_id SET DEFAULT NULLIF(current_setting('app.organization_id', true), '')::integer,
+    ALTER COLUMN organization_id SET NOT NULL;
+ALTER TABLE saved_views
+    ALTER COLUMN organization_id SET DEFAULT NULLIF(current_setting('app.organization_id', true), '')::integer,
+    ALTER COLUMN organization_id SET NOT NULL,
+    ADD CONSTRAINT saved_views_organization_id_fkey FOREIGN KEY (organization_id) REFERENCES organizations (id);
+ALTER TABLE calendar_feeds
+    ALTER COLUMN organization_id SET DEFAULT NULLIF(current_setting('app.organization_id', true), '')::integer,
+    ALTER COLUMN organization_id SET NOT NULL,
+    ADD CONSTRAINT calendar_feeds_organization_id_fkey FOREIGN KEY (organization_id) REFERENCES organizations (id);
+
+CREATE INDEX IF NOT EXISTS tasks_organization_id_idx ON tasks (organization_id);
+
+CREATE OR REPLACE FUNCTION record_task_status() RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        INSERT INTO task_history (task_id, user_id, organization_id, old_status, new_status, changed_at)
+        VALUES (NEW.id, NEW.user_id, NEW.organization_id, NULL, NEW.status, COALESCE(NEW.created_at, NOW()));
+    ELSIF NEW.status IS DISTINCT FROM OLD.status THEN
+        INSERT INTO task_history (task_id, user_id, organization_id, old_status, new_status, changed_at)
+        VALUES (NEW.id, NEW.user_id, NEW.organization_id, OLD.status, NEW.status, NOW());
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+-- 请求内通过 SET LOCAL ROLE 切换到这个角色，行级安全策略对它生效。
+-- 连接池本身的角色（表的 owner）不受策略约束，只用于迁移和后台任务。
+DO $$
+BEGIN
+    IF NOT EXISTS (SELECT 1 FROM pg_roles WHERE rolname = 'task_app') THEN
+        CREATE ROLE task_app NOLOGIN;
+    END IF;
+END
+$$;
+
+GRANT task_app TO CURRENT_USER;
+GRANT USAGE ON SCHEMA public TO task_app;
+GRANT SELECT ON organizations TO task_app;
+GRANT SELECT, INSERT, UPDATE, DELETE ON tasks, task_history, saved_views, calendar_feeds, users TO task_app;
+GRANT USAGE, SELECT ON ALL SEQUENCES IN SCHEMA public TO task_app;
+ALTER DEFAULT PRIVILEGES IN SCHEMA public GRANT SELECT, INSERT, UPDATE, DELETE ON TABLES TO task_app;
+ALTER DEFAULT PRIVILEGES IN SCHEMA public GRANT USAGE, SELECT ON SEQUENCES TO task_app;
+
+CREATE OR REPLACE FUNCTION current_organization_id() RETURNS INTEGER AS $$
+    SELECT NULLIF(current_setting('app.organization_id', true), '')::integer
+$$ LANGUAGE sql STABLE;
+
+ALTER TABLE organizations ENABLE ROW LEVEL SECURITY;
+ALTER TABLE users ENABLE ROW LEVEL SECURITY;
+ALTER TABLE tasks ENABLE ROW LEVEL SECURITY;
+ALTER TABLE task_history ENABLE ROW LEVEL SECURITY;
+ALTER TABLE saved_views ENABLE ROW LEVEL SECURITY;
+ALTER TABLE calendar_feeds ENABLE ROW LEVEL SECURITY;
+
+DROP POLICY IF EXISTS tenant_isolation ON organizations;
+CREATE POLICY tenant_isolation ON organizations
+    USING (id = current_organization_id());
+
+DROP POLICY IF EXISTS tenant_isolation ON users;
+CREATE POLICY tenant_isolation ON users
+    USING (organization_id = current_organization_id());
+
+-- 写入时 user_id 也必须是本组织的用户
+DROP POLICY IF EXISTS tenant_isolation ON tasks;
+CREATE POLICY tenant_isolation ON tasks
+    USING (organization_id = current_organization_id())
+    WITH CHECK (
+        organization_id = current_organization_id()
+        AND user_id IN (SELECT id FROM users)
+    );
+
+DROP POLICY IF EXISTS tenant_isolation ON task_history;
+CREATE POLICY tenant_isolation ON task_history
+    USING (organization_id = current_organization_id());
+
+DROP POLICY IF EXISTS tenant_isolation ON saved_views;
+CREATE POLICY tenant_isolation ON saved_views
+    USING (organization_id = current_organization_id())
+    WITH CHECK (
+        organization_id = current_organization_id()
+        AND user_id IN (SELECT id FROM users)
+    );
+
+DROP POLICY IF EXISTS tenant_isolation ON calendar_feeds;
+CREATE POLICY tenant_isolation ON calendar_feeds
+    USING (organization_id = current_organization_id())
+    WITH CHECK (
+        organization_id = current_organization_id()
+        AND user_id IN (SELECT id FROM users)
+    );
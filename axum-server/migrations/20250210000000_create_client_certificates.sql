@@ -0,0 +1,14 @@
+-- 双向 TLS：客户端证书指纹对应到用户，供服务之间调用
+CREATE TABLE IF NOT EXISTS client_certificates (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users (id),
+    organization_id INTEGER NOT NULL REFERENCES organizations (id),
+    name TEXT NOT NULL,
+    fingerprint_sha256 TEXT NOT NULL UNIQUE,
+    scopes TEXT[] NOT NULL CHECK (scopes <@ ARRAY['tasks:read', 'tasks:write', 'admin']),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    last_used_at TIMESTAMPTZ,
+    revoked_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS client_certificates_user_id_idx ON client_certificates (user_id);
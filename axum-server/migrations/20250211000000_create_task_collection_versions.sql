@@ -0,0 +1,33 @@
+-- 每个用户任务列表的版本号，任何任务增删改都会加一，用来生成列表的 ETag。
+-- 由触发器维护，导入、后台任务等所有写入路径都覆盖到
+CREATE TABLE IF NOT EXISTS task_collection_versions (
+    user_id INTEGER PRIMARY KEY,
+    version BIGINT NOT NULL DEFAULT 0,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE OR REPLACE FUNCTION bump_task_collection_version(target INTEGER) RETURNS VOID AS $$
+    INSERT INTO task_collection_versions (user_id, version, updated_at)
+    VALUES (target, 1, NOW())
+    ON CONFLICT (user_id) DO UPDATE
+        SET version = task_collection_versions.version + 1, updated_at = NOW();
+$$ LANGUAGE sql;
+
+CREATE OR REPLACE FUNCTION record_task_collection_change() RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        PERFORM bump_task_collection_version(OLD.user_id);
+    ELSE
+        PERFORM bump_task_collection_version(NEW.user_id);
+        IF TG_OP = 'UPDATE' AND OLD.user_id IS DISTINCT FROM NEW.user_id THEN
+            PERFORM bump_task_collection_version(OLD.user_id);
+        END IF;
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS tasks_collection_version ON tasks;
+CREATE TRIGGER tasks_collection_version
+    AFTER INSERT OR UPDATE OR DELETE ON tasks
+    FOR EACH ROW EXECUTE FUNCTION record_task_collection_change();
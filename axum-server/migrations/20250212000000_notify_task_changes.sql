@@ -0,0 +1,25 @@
+-- 任务增删改在提交时通过 NOTIFY 广播，供 GraphQL 订阅和各实例的读缓存使用。
+-- payload 只带 id 和归属，订阅方按自己的权限重新查询任务内容
+CREATE OR REPLACE FUNCTION notify_task_change() RETURNS TRIGGER AS $$
+DECLARE
+    changed tasks%ROWTYPE;
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        changed := OLD;
+    ELSE
+        changed := NEW;
+    END IF;
+    PERFORM pg_notify('task_changes', json_build_object(
+        'op', lower(TG_OP),
+        'id', changed.id,
+        'user_id', changed.user_id,
+        'organization_id', changed.organization_id
+    )::text);
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS tasks_notify_change ON tasks;
+CREATE TRIGGER tasks_notify_change
+    AFTER INSERT OR UPDATE OR DELETE ON tasks
+    FOR EACH ROW EXECUTE FUNCTION notify_task_change();
@@ -0,0 +1,8 @@
+-- access token 的签名密钥。最新的未过期密钥用来签名，其余未过期的只用来校验，
+-- 轮换后旧密钥保留一个 access token 有效期，已经签发的 token 不会立刻失效
+CREATE TABLE IF NOT EXISTS jwt_signing_keys (
+    id SERIAL PRIMARY KEY,
+    secret TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at TIMESTAMPTZ
+);
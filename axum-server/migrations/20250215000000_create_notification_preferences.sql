@@ -0,0 +1,23 @@
+-- 每个用户的邮件通知设置，第一次用到时按默认值创建
+CREATE TABLE IF NOT EXISTS notification_preferences (
+    user_id INTEGER PRIMARY KEY REFERENCES users (id) ON DELETE CASCADE,
+    assignments BOOLEAN NOT NULL DEFAULT TRUE,
+    mentions BOOLEAN NOT NULL DEFAULT TRUE,
+    reminders BOOLEAN NOT NULL DEFAULT TRUE,
+    daily_digest BOOLEAN NOT NULL DEFAULT FALSE,
+    -- 每封邮件的退订链接都要带上它，所以保存明文；它只能用来关闭通知
+    unsubscribe_token TEXT NOT NULL UNIQUE,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- 已经发出的通知。dedupe_key 防止同一条提醒或摘要重复发送，同时记录渲染用的模板版本
+CREATE TABLE IF NOT EXISTS sent_notifications (
+    id BIGSERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    dedupe_key TEXT NOT NULL UNIQUE,
+    template TEXT NOT NULL,
+    template_version INTEGER NOT NULL,
+    sent_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS sent_notifications_user_id_idx ON sent_notifications (user_id);
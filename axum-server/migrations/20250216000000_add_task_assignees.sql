@@ -0,0 +1,94 @@
+-- 任务的报告人（创建者）和负责人。负责人和 tags 一样存成数组，所有 SELECT * 都会带上，
+-- 分配变化就是对任务本身的一次更新，版本号、NOTIFY 和 updated_at 都跟着变
+ALTER TABLE tasks ADD COLUMN IF NOT EXISTS reporter_id INTEGER REFERENCES users (id);
+ALTER TABLE tasks ADD COLUMN IF NOT EXISTS assignee_ids INTEGER[] NOT NULL DEFAULT '{}';
+
+UPDATE tasks SET reporter_id = user_id WHERE reporter_id IS NULL;
+ALTER TABLE tasks ALTER COLUMN reporter_id SET NOT NULL;
+
+CREATE INDEX IF NOT EXISTS tasks_assignee_ids_idx ON tasks USING GIN (assignee_ids);
+
+-- 没有指定报告人时就是所有者，导入和直接写 SQL 的路径不用关心这一列
+CREATE OR REPLACE FUNCTION default_task_reporter() RETURNS TRIGGER AS $$
+BEGIN
+    NEW.reporter_id := COALESCE(NEW.reporter_id, NEW.user_id);
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS tasks_default_reporter ON tasks;
+CREATE TRIGGER tasks_default_reporter
+    BEFORE INSERT ON tasks
+    FOR EACH ROW EXECUTE FUNCTION default_task_reporter();
+
+-- 报告人和负责人也必须是本组织的用户
+DROP POLICY IF EXISTS tenant_isolation ON tasks;
+CREATE POLICY tenant_isolation ON tasks
+    USING (organization_id = current_organization_id())
+    WITH CHECK (
+        organization_id = current_organization_id()
+        AND user_id IN (SELECT id FROM users)
+        AND reporter_id IN (SELECT id FROM users)
+        AND assignee_ids <@ ARRAY(SELECT id FROM users)
+    );
+
+-- 负责人的任务列表里也有这个任务，变化前后的负责人都要更新版本号。
+-- 插入时 OLD、删除时 NEW 为 NULL
+CREATE OR REPLACE FUNCTION task_audience(old_task tasks, new_task tasks) RETURNS INTEGER[] AS $$
+    SELECT COALESCE(array_agg(DISTINCT id ORDER BY id), '{}')
+    FROM unnest(
+        ARRAY[old_task.user_id, new_task.user_id]
+        || COALESCE(old_task.assignee_ids, '{}')
+        || COALESCE(new_task.assignee_ids, '{}')
+    ) AS id
+    WHERE id IS NOT NULL
+$$ LANGUAGE sql IMMUTABLE;
+
+CREATE OR REPLACE FUNCTION record_task_collection_change() RETURNS TRIGGER AS $$
+DECLARE
+    target INTEGER;
+BEGIN
+    -- 按 id 顺序加锁，并发更新不会死锁
+    FOREACH target IN ARRAY task_audience(OLD, NEW) LOOP
+        PERFORM bump_task_collection_version(target);
+    END LOOP;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION notify_task_change() RETURNS TRIGGER AS $$
+DECLARE
+    changed tasks%ROWTYPE;
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        changed := OLD;
+    ELSE
+        changed := NEW;
+    END IF;
+    PERFORM pg_notify('task_changes', json_build_object(
+        'op', lower(TG_OP),
+        'id', changed.id,
+        'user_id', changed.user_id,
+        'organization_id', changed.organization_id,
+        'audience', task_audience(OLD, NEW)
+    )::text);
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+-- 站内通知，目前由分配和取消分配产生。任务删除后通知一起删除
+CREATE TABLE IF NOT EXISTS notifications (
+    id BIGSERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users (id),
+    kind TEXT NOT NULL,
+    task_id INTEGER REFERENCES tasks (id) ON DELETE CASCADE,
+    actor_id INTEGER REFERENCES users (id),
+    -- 产生通知时的任务标题
+    title TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    read_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS notifications_user_id_idx ON notifications (user_id, id DESC);
+CREATE INDEX IF NOT EXISTS notifications_task_id_idx ON notifications (task_id);
+CREATE INDEX IF NOT EXISTS notifications_unread_idx ON notifications (user_id) WHERE read_at IS NULL;
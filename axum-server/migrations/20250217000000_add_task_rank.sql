@@ -0,0 +1,58 @@
+-- 看板列（状态）里的手动顺序，见 src/rank.rs。按字节比较，所以用 C 排序规则
+ALTER TABLE tasks ADD COLUMN IF NOT EXISTS rank TEXT COLLATE "C";
+
+-- 比 previous 大的最小步进，和 rank::after 相同；previous 为空时是列里的第一个任务
+CREATE OR REPLACE FUNCTION task_rank_after(previous TEXT) RETURNS TEXT AS $$
+DECLARE
+    digits CONSTANT TEXT := '0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz';
+    padded TEXT;
+    digit INTEGER;
+BEGIN
+    IF previous IS NULL THEN
+        RETURN 'V';
+    END IF;
+    padded := previous || repeat('0', GREATEST(4 - length(previous), 0));
+    FOR i IN REVERSE length(padded)..1 LOOP
+        digit := strpos(digits, substr(padded, i, 1));
+        IF digit < length(digits) THEN
+            RETURN substr(padded, 1, i - 1) || substr(digits, digit + 1, 1);
+        END IF;
+    END LOOP;
+    RETURN previous || 'V';
+END;
+$$ LANGUAGE plpgsql IMMUTABLE;
+
+-- 排到列的最后
+CREATE OR REPLACE FUNCTION next_task_rank(org INTEGER, column_status TEXT) RETURNS TEXT AS $$
+    SELECT task_rank_after(MAX(rank)) FROM tasks
+    WHERE organization_id = org AND status = column_status
+$$ LANGUAGE sql STABLE;
+
+CREATE INDEX IF NOT EXISTS tasks_rank_idx ON tasks (organization_id, status, rank);
+
+-- 已有的任务按 id 排在各自的列里
+DO $$
+DECLARE
+    task RECORD;
+BEGIN
+    FOR task IN SELECT id, organization_id, status FROM tasks WHERE rank IS NULL ORDER BY id LOOP
+        UPDATE tasks SET rank = next_task_rank(task.organization_id, task.status)
+        WHERE id = task.id;
+    END LOOP;
+END
+$$;
+
+ALTER TABLE tasks ALTER COLUMN rank SET NOT NULL;
+
+-- 没有指定 rank 的新任务排到列的最后，导入和直接写 SQL 的路径不用关心这一列
+CREATE OR REPLACE FUNCTION default_task_rank() RETURNS TRIGGER AS $$
+BEGIN
+    NEW.rank := COALESCE(NEW.rank, next_task_rank(NEW.organization_id, NEW.status));
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS tasks_default_rank ON tasks;
+CREATE TRIGGER tasks_default_rank
+    BEFORE INSERT ON tasks
+    FOR EACH ROW EXECUTE FUNCTION default_task_rank();
@@ -0,0 +1,53 @@
+-- 预估时间和已记录时间都按秒。time_spent_seconds 由 time_entries 上的触发器维护，
+-- 只统计已经停止的记录
+ALTER TABLE tasks ADD COLUMN IF NOT EXISTS estimate_seconds INTEGER CHECK (estimate_seconds > 0);
+ALTER TABLE tasks ADD COLUMN IF NOT EXISTS time_spent_seconds BIGINT NOT NULL DEFAULT 0;
+
+-- 计时器和手动录入的时间。ended_at 为空表示计时器还在走，每个用户最多一条。
+-- 任务删除后记录保留在时间表里（task_id 置空），已经计费的时间不会凭空消失
+CREATE TABLE IF NOT EXISTS time_entries (
+    id BIGSERIAL PRIMARY KEY,
+    organization_id INTEGER NOT NULL REFERENCES organizations (id)
+        DEFAULT NULLIF(current_setting('app.organization_id', true), '')::integer,
+    task_id INTEGER REFERENCES tasks (id) ON DELETE SET NULL,
+    user_id INTEGER NOT NULL REFERENCES users (id),
+    started_at TIMESTAMPTZ NOT NULL,
+    ended_at TIMESTAMPTZ,
+    duration_seconds BIGINT GENERATED ALWAYS AS (
+        FLOOR(EXTRACT(EPOCH FROM (ended_at - started_at)))::BIGINT
+    ) STORED,
+    note TEXT NOT NULL DEFAULT '',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CHECK (ended_at IS NULL OR ended_at > started_at)
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS time_entries_running_idx ON time_entries (user_id)
+    WHERE ended_at IS NULL;
+CREATE INDEX IF NOT EXISTS time_entries_user_id_idx ON time_entries (user_id, started_at);
+CREATE INDEX IF NOT EXISTS time_entries_task_id_idx ON time_entries (task_id, started_at);
+
+ALTER TABLE time_entries ENABLE ROW LEVEL SECURITY;
+
+DROP POLICY IF EXISTS tenant_isolation ON time_entries;
+CREATE POLICY tenant_isolation ON time_entries
+    USING (organization_id = current_organization_id())
+    WITH CHECK (
+        organization_id = current_organization_id()
+        AND user_id IN (SELECT id FROM users)
+    );
+
+-- 记录变化后重新汇总涉及的任务；任务本身的更新会让列表版本号和 NOTIFY 跟着变
+CREATE OR REPLACE FUNCTION record_task_time_spent() RETURNS TRIGGER AS $$
+BEGIN
+    UPDATE tasks SET time_spent_seconds = (
+        SELECT COALESCE(SUM(duration_seconds), 0) FROM time_entries WHERE task_id = tasks.id
+    )
+    WHERE id IN (OLD.task_id, NEW.task_id);
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS time_entries_task_time_spent ON time_entries;
+CREATE TRIGGER time_entries_task_time_spent
+    AFTER INSERT OR UPDATE OR DELETE ON time_entries
+    FOR EACH ROW EXECUTE FUNCTION record_task_time_spent();
@@ -0,0 +1,3 @@
+-- 处理中的记录（status_code 为空）只占用到 locked_until。进程崩溃、请求超时等情况下
+-- 没来得及释放的 key 过后可以被重试重新占用，不用等到 expires_at
+ALTER TABLE idempotency_keys ADD COLUMN IF NOT EXISTS locked_until TIMESTAMPTZ;
@@ -0,0 +1,23 @@
+-- 组织内的角色。token 的 admin scope 只说明 token 能做什么，
+-- 查看其他成员的数据（例如时间表）还要求用户本身是组织管理员
+ALTER TABLE users ADD COLUMN IF NOT EXISTS role TEXT NOT NULL DEFAULT 'member'
+    CHECK (role IN ('member', 'admin'));
+
+-- 已有组织里最早的用户作为管理员
+UPDATE users SET role = 'admin'
+WHERE id IN (SELECT MIN(id) FROM users GROUP BY organization_id);
+
+-- 注册、SSO 和运维命令创建组织时都是先建组织再建用户，组织的第一个用户就是管理员
+CREATE OR REPLACE FUNCTION default_user_role() RETURNS TRIGGER AS $$
+BEGIN
+    IF NOT EXISTS (SELECT 1 FROM users WHERE organization_id = NEW.organization_id) THEN
+        NEW.role := 'admin';
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS users_default_role ON users;
+CREATE TRIGGER users_default_role
+    BEFORE INSERT ON users
+    FOR EACH ROW EXECUTE FUNCTION default_user_role();
@@ -0,0 +1,52 @@
+-- 之后新建的表不再自动授权给 task_app，请求里要访问的表在建表的迁移里单独授权。
+-- 默认权限生效期间建的表在这里收回，只留下请求确实需要的部分
+ALTER DEFAULT PRIVILEGES IN SCHEMA public REVOKE SELECT, INSERT, UPDATE, DELETE ON TABLES FROM task_app;
+ALTER DEFAULT PRIVILEGES IN SCHEMA public REVOKE USAGE, SELECT ON SEQUENCES FROM task_app;
+
+-- 凭据和投递记录只由连接池本身的角色访问
+REVOKE ALL ON sessions, refresh_tokens, personal_access_tokens, jwt_signing_keys,
+    user_identities, oidc_login_states, client_certificates,
+    notification_preferences, sent_notifications
+    FROM task_app;
+
+REVOKE ALL ON ALL SEQUENCES IN SCHEMA public FROM task_app;
+GRANT USAGE ON SEQUENCE tasks_id_seq, task_history_id_seq, saved_views_id_seq,
+    calendar_feeds_id_seq, jobs_id_seq, notifications_id_seq, time_entries_id_seq
+    TO task_app;
+
+-- 用户只能读不含密码的列，也不能改自己或同事的角色
+REVOKE ALL ON users FROM task_app;
+GRANT SELECT (id, organization_id, email, name, created_at, disabled_at, role) ON users TO task_app;
+
+-- 列表版本号由 tasks 上的触发器以当前角色维护；只能读写本组织用户的记录
+REVOKE ALL ON task_collection_versions FROM task_app;
+GRANT SELECT, INSERT, UPDATE ON task_collection_versions TO task_app;
+
+ALTER TABLE task_collection_versions ENABLE ROW LEVEL SECURITY;
+
+DROP POLICY IF EXISTS tenant_isolation ON task_collection_versions;
+CREATE POLICY tenant_isolation ON task_collection_versions
+    USING (user_id IN (SELECT id FROM users));
+
+-- 请求里只会和业务数据一起入队（例如看板重排），不能读取或修改其它任务。
+-- ON CONFLICT 判断唯一键需要读 unique_key 和 status，RETURNING 需要读 id
+REVOKE ALL ON jobs FROM task_app;
+GRANT INSERT ON jobs TO task_app;
+GRANT SELECT (id, unique_key, status) ON jobs TO task_app;
+
+-- 请求里只在分配时写入，收件人和操作人都必须是本组织的用户。列表和已读走连接池本身的角色
+REVOKE ALL ON notifications FROM task_app;
+GRANT INSERT ON notifications TO task_app;
+
+ALTER TABLE notifications ENABLE ROW LEVEL SECURITY;
+
+DROP POLICY IF EXISTS tenant_isolation ON notifications;
+CREATE POLICY tenant_isolation ON notifications
+    USING (user_id IN (SELECT id FROM users))
+    WITH CHECK (
+        user_id IN (SELECT id FROM users)
+        AND actor_id IN (SELECT id FROM users)
+    );
+
+REVOKE ALL ON time_entries FROM task_app;
+GRANT SELECT, INSERT, UPDATE, DELETE ON time_entries TO task_app;
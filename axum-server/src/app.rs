@@ -1,14 +1,114 @@
-use axum::Router;
-use sqlx::PgPool;
-use std::sync::Arc;
-use tokio::net::TcpListener;
+use axum::{
+    extract::DefaultBodyLimit,
+    http::{header, HeaderName, HeaderValue, Method, StatusCode},
+    middleware, Router,
+};
+use tower::ServiceBuilder;
+use tower_http::{
+    compression::CompressionLayer,
+    cors::{AllowOrigin, CorsLayer},
+    set_header::SetResponseHeaderLayer,
+    timeout::TimeoutLayer,
+};
 
-use crate::routes::{create_routes, task_routes};
+use crate::config::HttpConfig;
+use crate::middleware::track_metrics;
+use crate::routes::{
+    auth_routes, calendar_routes, create_routes, graphql_routes, health_routes,
+    notification_routes, task_routes,
+};
+use crate::state::AppState;
+
+pub fn create_app(state: AppState) -> Router {
+    let http = &state.config.http;
 
-pub fn create_app(pool: PgPool) -> Router {
-    let shared_pool = Arc::new(pool);
     Router::new()
         .merge(create_routes())
-        .merge(task_routes())
-        .with_state(shared_pool)
+        .merge(health_routes())
+        .merge(auth_routes(&state))
+        .merge(task_routes(&state))
+        .merge(calendar_routes(&state))
+        .merge(graphql_routes(&state))
+        .merge(notification_routes(&state))
+        // 导入接口自己设置了更大的上限，会覆盖这里
+        .layer(DefaultBodyLimit::max(http.body_limit))
+        .layer(TimeoutLayer::with_status_code(
+            StatusCode::REQUEST_TIMEOUT,
+            http.request_timeout,
+        ))
+        .layer(middleware::from_fn_with_state(state.clone(), track_metrics))
+        .layer(
+            ServiceBuilder::new()
+                .option_layer(cors(http))
+                .layer(SetResponseHeaderLayer::if_not_present(
+                    header::X_CONTENT_TYPE_OPTIONS,
+                    HeaderValue::from_static("nosniff"),
+                ))
+                .layer(SetResponseHeaderLayer::if_not_present(
+                    header::X_FRAME_OPTIONS,
+                    HeaderValue::from_static("DENY"),
+                ))
+                .layer(SetResponseHeaderLayer::if_not_present(
+                    header::REFERRER_POLICY,
+                    HeaderValue::from_static("no-referrer"),
+                ))
+                .layer(SetResponseHeaderLayer::if_not_present(
+                    header::CONTENT_SECURITY_POLICY,
+                    http.content_security_policy.clone(),
+                ))
+                .option_layer((http.hsts_max_age > 0).then(|| {
+                    SetResponseHeaderLayer::if_not_present(
+                        header::STRICT_TRANSPORT_SECURITY,
+                        HeaderValue::try_from(format!("max-age={}", http.hsts_max_age))
+                            .expect("max-age is a valid header value"),
+                    )
+                }))
+                // gzip / br / zstd，按 Accept-Encoding 协商
+                .layer(CompressionLayer::new()),
+        )
+        .with_state(state)
+}
+
+fn cors(http: &HttpConfig) -> Option<CorsLayer> {
+    if http.cors_origins.is_empty() {
+        return None;
+    }
+    let origins = if http.cors_origins.iter().any(|origin| origin == "*") {
+        AllowOrigin::any()
+    } else {
+        AllowOrigin::list(http.cors_origins.iter().map(|origin| {
+            HeaderValue::from_str(origin).expect("origins are checked by Config::from_env")
+        }))
+    };
+
+    // 使用 bearer token 而不是 cookie，不需要 allow_credentials
+    Some(
+        CorsLayer::new()
+            .allow_origin(origins)
+            .allow_methods([
+                Method::GET,
+                Method::POST,
+                Method::PUT,
+                Method::PATCH,
+                Method::DELETE,
+            ])
+            .allow_headers([
+                header::AUTHORIZATION,
+                header::CONTENT_TYPE,
+                header::IF_NONE_MATCH,
+                header::IF_MODIFIED_SINCE,
+                HeaderName::from_static("idempotency-key"),
+            ])
+            .expose_headers([
+                header::ETAG,
+                header::LAST_MODIFIED,
+                header::RETRY_AFTER,
+                HeaderName::from_static("ratelimit-limit"),
+                HeaderName::from_static("ratelimit-remaining"),
+                HeaderName::from_static("ratelimit-reset"),
+                HeaderName::from_static("ratelimit-policy"),
+                HeaderName::from_static("idempotent-replayed"),
+            ])
+            .max_age(std::time::Duration::from_secs(3600)),
+    )
 }
@@ -1,23 +1,187 @@
-use crate::error::AppError;
+use crate::config::Config;
+use crate::error::{AppError, HttpError};
+use crate::services::auth_service;
+use crate::tls::ClientCertificate;
 use async_trait::async_trait;
-use axum::extract::FromRequestParts;
-use axum::http::request::Parts;
-use axum::http::StatusCode;
+use axum::extract::{FromRef, FromRequestParts};
+use axum::http::{header, request::Parts, StatusCode};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::{fmt, str::FromStr, sync::Arc};
 
-#[derive(Debug)]
+/// personal access token 的前缀，用来和 JWT 区分
+pub const PERSONAL_TOKEN_PREFIX: &str = "pat_";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Scope {
+    #[serde(rename = "tasks:read")]
+    TasksRead,
+    #[serde(rename = "tasks:write")]
+    TasksWrite,
+    /// 管理 session 和 token
+    #[serde(rename = "admin")]
+    Admin,
+}
+
+impl Scope {
+    pub const ALL: [Scope; 3] = [Scope::TasksRead, Scope::TasksWrite, Scope::Admin];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Scope::TasksRead => "tasks:read",
+            Scope::TasksWrite => "tasks:write",
+            Scope::Admin => "admin",
+        }
+    }
+
+    /// `tasks:write` 包含 `tasks:read`，`admin` 包含全部
+    pub fn grants(self, required: Scope) -> bool {
+        self == required
+            || self == Scope::Admin
+            || (self == Scope::TasksWrite && required == Scope::TasksRead)
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Scope {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Scope::ALL
+            .into_iter()
+            .find(|scope| scope.as_str() == s)
+            .ok_or_else(|| anyhow::anyhow!("unknown scope: {s}"))
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct AuthUser {
     pub user_id: i32,
+    pub organization_id: i32,
+    /// 通过 access token 登录时对应的 session
+    pub session_id: Option<i64>,
+    /// session 拥有全部权限，personal access token 只有创建时选择的
+    pub scopes: Vec<Scope>,
+}
+
+impl AuthUser {
+    pub fn has_scope(&self, required: Scope) -> bool {
+        self.scopes.iter().any(|scope| scope.grants(required))
+    }
+
+    pub fn require_scope(&self, required: Scope) -> Result<(), HttpError> {
+        if self.has_scope(required) {
+            Ok(())
+        } else {
+            Err(HttpError::new(
+                StatusCode::FORBIDDEN,
+                format!("token is missing the {required} scope"),
+            ))
+        }
+    }
+}
+
+/// 校验 access token 或 personal access token。
+/// 不经过请求头的入口（GraphQL websocket 的 `connection_init`）也用它
+pub async fn authenticate_bearer(
+    pool: &PgPool,
+    config: &Config,
+    token: &str,
+) -> Result<AuthUser, AppError> {
+    if token.starts_with(PERSONAL_TOKEN_PREFIX) {
+        return Ok(auth_service::authenticate_personal_token(pool, token)
+            .await?
+            .ok_or_else(|| HttpError::unauthorized("invalid, expired or revoked token"))?);
+    }
+
+    if let Some(kid) = auth_service::access_token_kid(token) {
+        config.auth.keys.reload_for_unknown(pool, &kid).await?;
+    }
+    let claims = auth_service::decode_access_token(&config.auth, token)
+        .map_err(|_| HttpError::unauthorized("invalid or expired access token"))?;
+
+    // access token 本身无状态，登出或撤销 session 后需要在这里拦住
+    if !auth_service::session_is_active(pool, claims.sub, claims.sid).await? {
+        return Err(HttpError::unauthorized("session has been revoked").into());
+    }
+
+    Ok(AuthUser {
+        user_id: claims.sub,
+        organization_id: claims.org,
+        session_id: Some(claims.sid),
+        scopes: Scope::ALL.to_vec(),
+    })
 }
 
 #[async_trait]
 impl<S> FromRequestParts<S> for AuthUser
 where
+    Arc<PgPool>: FromRef<S>,
+    Arc<Config>: FromRef<S>,
     S: Send + Sync,
 {
     type Rejection = AppError;
 
     async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
-        let user_id = 1;
-        Ok(AuthUser { user_id })
+        // 限流、scope 检查和 handler 都会提取一次，只查一次数据库
+        if let Some(user) = parts.extensions.get::<AuthUser>() {
+            return Ok(user.clone());
+        }
+
+        let bearer = parts
+            .headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(str::trim);
+
+        let pool = Arc::<PgPool>::from_ref(state);
+        let user = match bearer {
+            Some(token) => {
+                let config = Arc::<Config>::from_ref(state);
+                authenticate_bearer(pool.as_ref(), &config, token).await?
+            }
+            // 双向 TLS 的客户端没有 token 时，用登记过的客户端证书
+            None => {
+                let certificate = parts
+                    .extensions
+                    .get::<ClientCertificate>()
+                    .ok_or_else(|| HttpError::unauthorized("missing bearer token"))?;
+                auth_service::authenticate_client_certificate(pool.as_ref(), certificate)
+                    .await?
+                    .ok_or_else(|| {
+                        HttpError::unauthorized("client certificate is not registered")
+                    })?
+            }
+        };
+
+        parts.extensions.insert(user.clone());
+        Ok(user)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn broader_scopes_grant_narrower_ones() {
+        assert!(Scope::TasksWrite.grants(Scope::TasksRead));
+        assert!(Scope::Admin.grants(Scope::TasksWrite));
+        assert!(!Scope::TasksRead.grants(Scope::TasksWrite));
+        assert!(!Scope::TasksWrite.grants(Scope::Admin));
+    }
+
+    #[test]
+    fn scopes_round_trip_through_strings() {
+        for scope in Scope::ALL {
+            assert_eq!(scope.as_str().parse::<Scope>().unwrap(), scope);
+        }
+        assert!("tasks:delete".parse::<Scope>().is_err());
     }
 }
@@ -0,0 +1,32 @@
+use std::{future::Future, time::Duration};
+use tokio_util::{sync::CancellationToken, task::TaskTracker};
+
+/// 后台任务：共享一个取消信号，关闭时统一等待退出
+#[derive(Clone, Default)]
+pub struct BackgroundJobs {
+    token: CancellationToken,
+    tracker: TaskTracker,
+}
+
+impl BackgroundJobs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn spawn<F, Fut>(&self, job: F)
+    where
+        F: FnOnce(CancellationToken) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.tracker.spawn(job(self.token.child_token()));
+    }
+
+    /// 通知所有任务停止，返回是否在超时前全部退出
+    pub async fn shutdown(&self, timeout: Duration) -> bool {
+        self.token.cancel();
+        self.tracker.close();
+        tokio::time::timeout(timeout, self.tracker.wait())
+            .await
+            .is_ok()
+    }
+}
@@ -0,0 +1,369 @@
+//! 运维命令行，和服务端共用 `Config` 与 service/repository 代码
+
+use anyhow::Context;
+use axum_server::{
+    config::Config,
+    db::{routing::DbRouter, task_repo, tenant, user_repo},
+    error::AppError,
+    jwt_keys,
+    models::{
+        task::CreateTask,
+        user::{User, USER_ROLES},
+    },
+    services::{
+        import_export_service::{self, Format},
+        user_service,
+    },
+    token,
+};
+use chrono::{Duration as ChronoDuration, Utc};
+use clap::{Parser, Subcommand};
+use futures::TryStreamExt;
+use sqlx::PgPool;
+use std::{
+    io::{self, BufRead, Read, Write},
+    path::PathBuf,
+    process::ExitCode,
+    sync::Arc,
+    time::Duration,
+};
+
+#[derive(Parser)]
+#[command(
+    name = "axum-server-admin",
+    about = "Operate the task server's database"
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Apply pending database migrations
+    Migrate,
+    /// Create a user, in a new organization unless --organization is given
+    CreateUser {
+        #[arg(long)]
+        email: String,
+        #[arg(long, default_value = "")]
+        name: String,
+        #[arg(long)]
+        organization: Option<i32>,
+        /// Read the password from the first line of stdin instead of generating one
+        #[arg(long)]
+        password_stdin: bool,
+    },
+    /// Block a user from signing in and revoke their sessions
+    DisableUser {
+        #[arg(long)]
+        email: String,
+    },
+    /// Allow a disabled user to sign in again
+    EnableUser {
+        #[arg(long)]
+        email: String,
+    },
+    /// Make a user an organization admin or a regular member
+    SetRole {
+        #[arg(long)]
+        email: String,
+        #[arg(long, value_parser = USER_ROLES)]
+        role: String,
+    },
+    /// Set a new password and revoke the user's sessions
+    ResetPassword {
+        #[arg(long)]
+        email: String,
+        /// Read the password from the first line of stdin instead of generating one
+        #[arg(long)]
+        password_stdin: bool,
+    },
+    /// Start signing access tokens with a new key; running servers pick it up automatically
+    RotateJwtKey {
+        /// How long tokens signed with the previous keys stay valid (default: ACCESS_TOKEN_TTL_SECS)
+        #[arg(long)]
+        grace_secs: Option<u64>,
+    },
+    /// Create a demo user (if missing) and fill it with sample tasks and a saved view
+    Seed {
+        #[arg(long, default_value = "demo@example.com")]
+        email: String,
+    },
+    /// Write all tasks of a user to a file or stdout
+    ExportTasks {
+        #[arg(long)]
+        email: String,
+        #[arg(long, default_value = "json")]
+        format: Format,
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Import tasks for a user from a file or stdin and print the per-row report
+    ImportTasks {
+        #[arg(long)]
+        email: String,
+        #[arg(long, default_value = "json")]
+        format: Format,
+        #[arg(long)]
+        input: Option<PathBuf>,
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(cli.command).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {:#}", e.0);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(command: Command) -> Result<(), AppError> {
+    let config = Config::from_env()?;
+    let pool = PgPool::connect(&config.database_url)
+        .await
+        .context("failed to connect to DATABASE_URL")?;
+
+    match command {
+        Command::Migrate => {
+            sqlx::migrate!().run(&pool).await?;
+            println!("migrations applied");
+        }
+        Command::CreateUser {
+            email,
+            name,
+            organization,
+            password_stdin,
+        } => {
+            let (password, generated) = read_or_generate_password(password_stdin)?;
+            let user =
+                user_service::create_user(&pool, &email, &name, password.clone(), organization)
+                    .await?;
+            print_user("created", &user);
+            if generated {
+                println!("password: {password}");
+            }
+        }
+        Command::DisableUser { email } => {
+            print_user(
+                "disabled",
+                &user_service::set_disabled(&pool, &email, true).await?,
+            );
+        }
+        Command::EnableUser { email } => {
+            print_user(
+                "enabled",
+                &user_service::set_disabled(&pool, &email, false).await?,
+            );
+        }
+        Command::SetRole { email, role } => {
+            print_user(
+                &format!("{role} role set for"),
+                &user_service::set_role(&pool, &email, &role).await?,
+            );
+        }
+        Command::ResetPassword {
+            email,
+            password_stdin,
+        } => {
+            let (password, generated) = read_or_generate_password(password_stdin)?;
+            let user = user_service::reset_password(&pool, &email, password.clone()).await?;
+            print_user("password reset for", &user);
+            if generated {
+                println!("password: {password}");
+            }
+        }
+        Command::RotateJwtKey { grace_secs } => {
+            let grace = grace_secs
+                .map(Duration::from_secs)
+                .unwrap_or(config.auth.access_token_ttl);
+            let id = jwt_keys::rotate(&pool, &config.auth.jwt_secret, grace).await?;
+            println!(
+                "new signing key {id}; previous keys stop verifying in {}s",
+                grace.as_secs()
+            );
+        }
+        Command::Seed { email } => seed(&pool, &email).await?,
+        Command::ExportTasks {
+            email,
+            format,
+            output,
+        } => {
+            let user = user_service::find_user(&pool, &email).await?;
+            let mut out: Box<dyn Write> = match &output {
+                Some(path) => Box::new(io::BufWriter::new(
+                    std::fs::File::create(path)
+                        .with_context(|| format!("failed to create {}", path.display()))?,
+                )),
+                None => Box::new(io::stdout().lock()),
+            };
+            // 管理命令只连主库
+            let db = DbRouter::new(Arc::new(pool.clone()), Vec::new(), &config.database);
+            let mut stream = std::pin::pin!(import_export_service::export_stream(
+                Arc::new(db),
+                user_service::act_as(&user),
+                format,
+            ));
+            while let Some(chunk) = stream.try_next().await? {
+                out.write_all(&chunk)?;
+            }
+            out.flush()?;
+        }
+        Command::ImportTasks {
+            email,
+            format,
+            input,
+            dry_run,
+        } => {
+            let user = user_service::find_user(&pool, &email).await?;
+            let body = match &input {
+                Some(path) => std::fs::read(path)
+                    .with_context(|| format!("failed to read {}", path.display()))?,
+                None => {
+                    let mut body = Vec::new();
+                    io::stdin().read_to_end(&mut body)?;
+                    body
+                }
+            };
+            let report = import_export_service::import_tasks(
+                &pool,
+                &user_service::act_as(&user),
+                format,
+                &body,
+                dry_run,
+            )
+            .await?;
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        }
+    }
+
+    pool.close().await;
+    Ok(())
+}
+
+fn print_user(action: &str, user: &User) {
+    println!(
+        "{action} user {} <{}> (organization {})",
+        user.id, user.email, user.organization_id
+    );
+}
+
+/// 密码不放在命令行参数里，避免出现在 shell 历史和进程列表中
+fn read_or_generate_password(from_stdin: bool) -> anyhow::Result<(String, bool)> {
+    if !from_stdin {
+        return Ok((token::generate()[..20].to_owned(), true));
+    }
+    let mut line = String::new();
+    io::stdin().lock().read_line(&mut line)?;
+    Ok((line.trim_end_matches(['\r', '\n']).to_owned(), false))
+}
+
+/// 演示数据：几个分类、优先级和状态都不同的任务，外加一个共享视图
+async fn seed(pool: &PgPool, email: &str) -> Result<(), AppError> {
+    let user = match user_repo::find_by_email(pool, email).await? {
+        Some(user) => user,
+        None => {
+            let (password, _) = read_or_generate_password(false)?;
+            let user =
+                user_service::create_user(pool, email, "Demo", password.clone(), None).await?;
+            print_user("created", &user);
+            println!("password: {password}");
+            user
+        }
+    };
+
+    let now = Utc::now();
+    let samples = [
+        (
+            "Write quarterly report",
+            "work",
+            4,
+            3,
+            "pending",
+            &["report"][..],
+        ),
+        (
+            "Review pull requests",
+            "work",
+            3,
+            1,
+            "in_progress",
+            &["code-review"],
+        ),
+        (
+            "Plan team offsite",
+            "work",
+            2,
+            14,
+            "pending",
+            &["planning", "team"],
+        ),
+        (
+            "Fix login bug",
+            "work",
+            5,
+            -1,
+            "pending",
+            &["bug", "urgent"],
+        ),
+        ("Buy groceries", "personal", 2, 0, "completed", &["errand"]),
+        (
+            "Book dentist appointment",
+            "personal",
+            3,
+            7,
+            "pending",
+            &["health"],
+        ),
+        ("Renew passport", "personal", 4, 30, "pending", &["travel"]),
+        ("Clean the garage", "home", 1, 10, "pending", &[]),
+    ];
+
+    let auth_user = user_service::act_as(&user);
+    let mut tx = tenant::begin(pool, &auth_user).await?;
+    for (title, category, priority, due_in_days, status, tags) in samples {
+        let task = task_repo::insert_task(
+            &mut *tx,
+            user.id,
+            &CreateTask {
+                title: title.to_owned(),
+                description: format!("Demo task: {title}"),
+                category: category.to_owned(),
+                priority,
+                due_date: now + ChronoDuration::days(due_in_days),
+                recurrence: None,
+                tags: tags.iter().map(|tag| tag.to_string()).collect(),
+                estimate_seconds: None,
+            },
+        )
+        .await?;
+        if status != "pending" {
+            sqlx::query(
+                "UPDATE tasks SET status = $2, rank = next_task_rank(organization_id, $2)
+                 WHERE id = $1",
+            )
+            .bind(task.id)
+            .bind(status)
+            .execute(&mut *tx)
+            .await?;
+        }
+    }
+    sqlx::query(
+        "INSERT INTO saved_views (user_id, name, query, shared)
+         VALUES ($1, 'Urgent work', 'status:open category:work priority>=4', true)
+         ON CONFLICT (user_id, name) DO NOTHING",
+    )
+    .bind(user.id)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    println!("seeded {} tasks for {}", samples.len(), user.email);
+    Ok(())
+}
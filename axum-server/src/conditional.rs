@@ -0,0 +1,142 @@
+use axum::{
+    body::{Body, Bytes},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use sha2::{Digest, Sha256};
+
+const HTTP_DATE_FORMAT: &str = "%a, %d %b %Y %H:%M:%S GMT";
+
+/// 条件请求用的校验器：`ETag` 和 `Last-Modified`
+#[derive(Debug, Clone)]
+pub struct Validators {
+    pub etag: String,
+    pub last_modified: Option<DateTime<Utc>>,
+}
+
+impl Validators {
+    /// 由若干决定响应内容的部分生成弱 ETag（压缩后字节不同，所以用弱校验）
+    pub fn new<I, P>(parts: I, last_modified: Option<DateTime<Utc>>) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<[u8]>,
+    {
+        let mut hasher = Sha256::new();
+        for part in parts {
+            hasher.update(part.as_ref());
+            hasher.update([0]);
+        }
+        let digest = hex::encode(hasher.finalize());
+        Self {
+            etag: format!("W/\"{}\"", &digest[..32]),
+            last_modified,
+        }
+    }
+
+    /// 客户端缓存仍然有效。有 `If-None-Match` 时忽略 `If-Modified-Since`
+    pub fn is_fresh(&self, headers: &HeaderMap) -> bool {
+        if let Some(if_none_match) = headers.get(header::IF_NONE_MATCH) {
+            let Ok(if_none_match) = if_none_match.to_str() else {
+                return false;
+            };
+            let ours = strip_weak(&self.etag);
+            return if_none_match
+                .split(',')
+                .map(str::trim)
+                .any(|tag| tag == "*" || strip_weak(tag) == ours);
+        }
+
+        match (self.last_modified, headers.get(header::IF_MODIFIED_SINCE)) {
+            (Some(last_modified), Some(since)) => since
+                .to_str()
+                .ok()
+                .and_then(|since| NaiveDateTime::parse_from_str(since, HTTP_DATE_FORMAT).ok())
+                .is_some_and(|since| last_modified.timestamp() <= since.and_utc().timestamp()),
+            _ => false,
+        }
+    }
+
+    fn apply(&self, headers: &mut HeaderMap) {
+        if let Ok(etag) = HeaderValue::from_str(&self.etag) {
+            headers.insert(header::ETAG, etag);
+        }
+        if let Some(last_modified) = self.last_modified {
+            let value = last_modified.format(HTTP_DATE_FORMAT).to_string();
+            if let Ok(value) = HeaderValue::from_str(&value) {
+                headers.insert(header::LAST_MODIFIED, value);
+            }
+        }
+        // 允许客户端缓存，但每次使用前都要重新校验
+        headers.insert(
+            header::CACHE_CONTROL,
+            HeaderValue::from_static("private, no-cache"),
+        );
+    }
+
+    /// 客户端缓存有效时返回 304，否则返回 JSON 正文
+    pub fn respond(&self, headers: &HeaderMap, json: Bytes) -> Response {
+        let mut response = if self.is_fresh(headers) {
+            StatusCode::NOT_MODIFIED.into_response()
+        } else {
+            (
+                [(header::CONTENT_TYPE, "application/json")],
+                Body::from(json),
+            )
+                .into_response()
+        };
+        self.apply(response.headers_mut());
+        response
+    }
+}
+
+fn strip_weak(tag: &str) -> &str {
+    tag.strip_prefix("W/").unwrap_or(tag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn headers(name: header::HeaderName, value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(name, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    #[test]
+    fn etag_comparison_is_weak() {
+        let validators = Validators::new(["tasks", "1"], None);
+        let strong = validators.etag.trim_start_matches("W/").to_owned();
+
+        assert!(validators.is_fresh(&headers(header::IF_NONE_MATCH, &validators.etag)));
+        assert!(validators.is_fresh(&headers(
+            header::IF_NONE_MATCH,
+            &format!("\"other\", {strong}")
+        )));
+        assert!(!validators.is_fresh(&headers(header::IF_NONE_MATCH, "\"other\"")));
+        assert_ne!(validators.etag, Validators::new(["tasks", "2"], None).etag);
+    }
+
+    #[test]
+    fn if_modified_since_uses_second_precision() {
+        let modified = Utc.with_ymd_and_hms(2025, 2, 20, 12, 0, 0).unwrap()
+            + chrono::Duration::milliseconds(300);
+        let validators = Validators::new(["x"], Some(modified));
+
+        assert!(validators.is_fresh(&headers(
+            header::IF_MODIFIED_SINCE,
+            "Thu, 20 Feb 2025 12:00:00 GMT"
+        )));
+        assert!(!validators.is_fresh(&headers(
+            header::IF_MODIFIED_SINCE,
+            "Thu, 20 Feb 2025 11:59:59 GMT"
+        )));
+        // If-None-Match 优先
+        let mut both = headers(header::IF_MODIFIED_SINCE, "Thu, 20 Feb 2025 12:00:00 GMT");
+        both.insert(header::IF_NONE_MATCH, HeaderValue::from_static("\"other\""));
+        assert!(!validators.is_fresh(&both));
+    }
+}
@@ -0,0 +1,6 @@
+pub mod notification_repo;
+pub mod routing;
+pub mod task_repo;
+pub mod tenant;
+pub mod user_repo;
+pub mod view_repo;
@@ -0,0 +1,187 @@
+use sqlx::PgExecutor;
+
+use crate::{
+    models::notification::{
+        Notification, NotificationCategory, NotificationKind, NotificationPreferences,
+        UpdateNotificationPreferences,
+    },
+    token,
+};
+
+/// 还没有设置过的用户按默认值创建一条
+pub async fn get_preferences<'e, E>(
+    executor: E,
+    user_id: i32,
+) -> Result<NotificationPreferences, sqlx::Error>
+where
+    E: PgExecutor<'e>,
+{
+    sqlx::query_as::<_, NotificationPreferences>(
+        "WITH inserted AS (
+             INSERT INTO notification_preferences (user_id, unsubscribe_token) VALUES ($1, $2)
+             ON CONFLICT (user_id) DO NOTHING
+             RETURNING *
+         )
+         SELECT * FROM inserted
+         UNION ALL
+         SELECT * FROM notification_preferences WHERE user_id = $1",
+    )
+    .bind(user_id)
+    .bind(token::generate())
+    .fetch_one(executor)
+    .await
+}
+
+/// 调用前需要先用 `get_preferences` 确保记录存在
+pub async fn update_preferences<'e, E>(
+    executor: E,
+    user_id: i32,
+    payload: &UpdateNotificationPreferences,
+) -> Result<NotificationPreferences, sqlx::Error>
+where
+    E: PgExecutor<'e>,
+{
+    sqlx::query_as::<_, NotificationPreferences>(
+        "UPDATE notification_preferences
+         SET assignments = COALESCE($2, assignments),
+             mentions = COALESCE($3, mentions),
+             reminders = COALESCE($4, reminders),
+             daily_digest = COALESCE($5, daily_digest),
+             updated_at = NOW()
+         WHERE user_id = $1
+         RETURNING *",
+    )
+    .bind(user_id)
+    .bind(payload.assignments)
+    .bind(payload.mentions)
+    .bind(payload.reminders)
+    .bind(payload.daily_digest)
+    .fetch_one(executor)
+    .await
+}
+
+/// 退订链接对应的邮箱，用于确认页面
+pub async fn find_email_by_unsubscribe_token<'e, E>(
+    executor: E,
+    token: &str,
+) -> Result<Option<String>, sqlx::Error>
+where
+    E: PgExecutor<'e>,
+{
+    sqlx::query_scalar(
+        "SELECT u.email FROM notification_preferences p JOIN users u ON u.id = p.user_id
+         WHERE p.unsubscribe_token = $1",
+    )
+    .bind(token)
+    .fetch_optional(executor)
+    .await
+}
+
+/// 关闭一个类别，`category` 为空时关闭全部。token 无效时返回 `false`
+pub async fn unsubscribe<'e, E>(
+    executor: E,
+    token: &str,
+    category: Option<NotificationCategory>,
+) -> Result<bool, sqlx::Error>
+where
+    E: PgExecutor<'e>,
+{
+    let result = sqlx::query(
+        "UPDATE notification_preferences
+         SET assignments = assignments AND NOT ($2::text IS NULL OR $2 = 'assignments'),
+             mentions = mentions AND NOT ($2::text IS NULL OR $2 = 'mentions'),
+             reminders = reminders AND NOT ($2::text IS NULL OR $2 = 'reminders'),
+             daily_digest = daily_digest AND NOT ($2::text IS NULL OR $2 = 'daily_digest'),
+             updated_at = NOW()
+         WHERE unsubscribe_token = $1",
+    )
+    .bind(token)
+    .bind(category.map(NotificationCategory::as_str))
+    .execute(executor)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// 给多个用户写入同一条站内通知
+pub async fn insert_notifications<'e, E>(
+    executor: E,
+    user_ids: &[i32],
+    kind: NotificationKind,
+    task_id: i32,
+    actor_id: i32,
+    title: &str,
+) -> Result<(), sqlx::Error>
+where
+    E: PgExecutor<'e>,
+{
+    sqlx::query(
+        "INSERT INTO notifications (user_id, kind, task_id, actor_id, title)
+         SELECT user_id, $2, $3, $4, $5 FROM unnest($1::int[]) AS user_id",
+    )
+    .bind(user_ids)
+    .bind(kind.as_str())
+    .bind(task_id)
+    .bind(actor_id)
+    .bind(title)
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+/// 最新的在前
+pub async fn list_notifications<'e, E>(
+    executor: E,
+    user_id: i32,
+    unread_only: bool,
+    limit: i64,
+) -> Result<Vec<Notification>, sqlx::Error>
+where
+    E: PgExecutor<'e>,
+{
+    sqlx::query_as::<_, Notification>(
+        "SELECT id, kind, task_id, actor_id, title, created_at, read_at
+         FROM notifications
+         WHERE user_id = $1 AND (NOT $2 OR read_at IS NULL)
+         ORDER BY id DESC
+         LIMIT $3",
+    )
+    .bind(user_id)
+    .bind(unread_only)
+    .bind(limit)
+    .fetch_all(executor)
+    .await
+}
+
+/// 已读的通知保持第一次读的时间
+pub async fn mark_read<'e, E>(
+    executor: E,
+    user_id: i32,
+    notification_id: i64,
+) -> Result<Option<Notification>, sqlx::Error>
+where
+    E: PgExecutor<'e>,
+{
+    sqlx::query_as::<_, Notification>(
+        "UPDATE notifications SET read_at = COALESCE(read_at, NOW())
+         WHERE id = $1 AND user_id = $2
+         RETURNING id, kind, task_id, actor_id, title, created_at, read_at",
+    )
+    .bind(notification_id)
+    .bind(user_id)
+    .fetch_optional(executor)
+    .await
+}
+
+/// 返回这次标记的数量
+pub async fn mark_all_read<'e, E>(executor: E, user_id: i32) -> Result<u64, sqlx::Error>
+where
+    E: PgExecutor<'e>,
+{
+    let result = sqlx::query(
+        "UPDATE notifications SET read_at = NOW() WHERE user_id = $1 AND read_at IS NULL",
+    )
+    .bind(user_id)
+    .execute(executor)
+    .await?;
+    Ok(result.rows_affected())
+}
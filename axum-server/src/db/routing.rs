@@ -0,0 +1,199 @@
+use sqlx::{
+    postgres::{PgConnectOptions, PgPoolOptions},
+    PgPool, Postgres, Transaction,
+};
+use std::{
+    collections::HashMap,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    auth::AuthUser,
+    config::{DatabaseConfig, PoolConfig},
+    db::tenant,
+};
+
+/// 记录的写入超过这个数量时清理已经过了窗口的用户
+const RECENT_WRITES_PURGE_AT: usize = 10_000;
+
+/// 把只读请求分给健康的副本，写入和其它请求都用主库。
+///
+/// 副本在第一次健康检查通过前不接收请求；没有健康的副本、或者用户在
+/// `read_your_writes` 窗口内写过数据时读主库。写入时间只记在本进程里，
+/// 多个实例时需要负载均衡按用户保持会话，否则窗口只覆盖同一个实例
+pub struct DbRouter {
+    primary: Arc<PgPool>,
+    replicas: Vec<Replica>,
+    max_lag: Duration,
+    read_your_writes: Duration,
+    recent_writes: Mutex<HashMap<i32, Instant>>,
+}
+
+struct Replica {
+    /// 日志里用的主机名，不带密码
+    host: String,
+    pool: PgPool,
+    healthy: AtomicBool,
+}
+
+impl DbRouter {
+    pub fn new(primary: Arc<PgPool>, replicas: Vec<PgPool>, config: &DatabaseConfig) -> Self {
+        Self {
+            primary,
+            replicas: replicas
+                .into_iter()
+                .map(|pool| Replica {
+                    host: pool.connect_options().get_host().to_owned(),
+                    pool,
+                    healthy: AtomicBool::new(false),
+                })
+                .collect(),
+            max_lag: config.replica_max_lag,
+            read_your_writes: config.read_your_writes,
+            recent_writes: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn primary(&self) -> &Arc<PgPool> {
+        &self.primary
+    }
+
+    /// 写入提交之后调用，之后的窗口内该用户的读请求走主库
+    pub fn record_write(&self, user_id: i32) {
+        if self.replicas.is_empty() {
+            return;
+        }
+        let mut writes = self.recent_writes.lock().unwrap();
+        if writes.len() >= RECENT_WRITES_PURGE_AT {
+            let window = self.read_your_writes;
+            writes.retain(|_, written_at| written_at.elapsed() < window);
+        }
+        writes.insert(user_id, Instant::now());
+    }
+
+    /// 同一个用户固定分到同一个副本，一个请求里的几次查询看到的复制进度一致
+    fn replica_for(&self, user_id: i32) -> Option<&Replica> {
+        let recently_written = self
+            .recent_writes
+            .lock()
+            .unwrap()
+            .get(&user_id)
+            .is_some_and(|written_at| written_at.elapsed() < self.read_your_writes);
+        if recently_written {
+            return None;
+        }
+        let healthy: Vec<&Replica> = self
+            .replicas
+            .iter()
+            .filter(|replica| replica.healthy.load(Ordering::Relaxed))
+            .collect();
+        if healthy.is_empty() {
+            return None;
+        }
+        Some(healthy[user_id.unsigned_abs() as usize % healthy.len()])
+    }
+
+    /// 只读的租户事务。副本连不上时标记为不健康并改用主库
+    pub async fn begin_read(
+        &self,
+        user: &AuthUser,
+    ) -> Result<Transaction<'static, Postgres>, sqlx::Error> {
+        if let Some(replica) = self.replica_for(user.user_id) {
+            match tenant::begin(&replica.pool, user).await {
+                Ok(tx) => return Ok(tx),
+                Err(e) => {
+                    eprintln!(
+                        "replica {} failed, reading from the primary: {e}",
+                        replica.host
+                    );
+                    replica.healthy.store(false, Ordering::Relaxed);
+                }
+            }
+        }
+        tenant::begin(&self.primary, user).await
+    }
+
+    /// 检查每个副本是否可用、复制延迟是否在 `max_lag` 以内
+    pub async fn check_replicas(&self) {
+        for replica in &self.replicas {
+            let healthy = match replication_lag(&replica.pool).await {
+                Ok(lag) if lag <= self.max_lag.as_secs_f64() => true,
+                Ok(lag) => {
+                    eprintln!("replica {} is {lag:.1}s behind", replica.host);
+                    false
+                }
+                Err(e) => {
+                    eprintln!("replica {} health check failed: {e}", replica.host);
+                    false
+                }
+            };
+            let was_healthy = replica.healthy.swap(healthy, Ordering::Relaxed);
+            if healthy && !was_healthy {
+                println!("replica {} is healthy", replica.host);
+            }
+        }
+    }
+
+    pub async fn close(&self) {
+        for replica in &self.replicas {
+            replica.pool.close().await;
+        }
+    }
+}
+
+/// 副本已经应用完收到的 WAL 时延迟为 0，否则是最后应用的事务到现在的时间。
+/// 主库上这些函数返回 NULL，也算作没有延迟
+async fn replication_lag(pool: &PgPool) -> Result<f64, sqlx::Error> {
+    sqlx::query_scalar(
+        "SELECT COALESCE(
+             CASE WHEN pg_last_wal_receive_lsn() = pg_last_wal_replay_lsn() THEN 0
+                  ELSE EXTRACT(EPOCH FROM now() - pg_last_xact_replay_timestamp())
+             END, 0)::float8",
+    )
+    .fetch_one(pool)
+    .await
+}
+
+pub fn pool_options(config: &PoolConfig) -> PgPoolOptions {
+    PgPoolOptions::new()
+        .max_connections(config.max_connections)
+        .min_connections(config.min_connections)
+        .acquire_timeout(config.acquire_timeout)
+}
+
+/// 副本的连接池在第一次使用时才建立连接，某个副本暂时不可用不影响启动
+pub fn connect_replicas(config: &DatabaseConfig) -> Result<Vec<PgPool>, sqlx::Error> {
+    config
+        .replica_urls
+        .iter()
+        .map(|url| {
+            let options = PgConnectOptions::from_str(url)?;
+            Ok(pool_options(&config.replica_pool).connect_lazy_with(options))
+        })
+        .collect()
+}
+
+pub async fn check_periodically(
+    router: Arc<DbRouter>,
+    interval: Duration,
+    token: CancellationToken,
+) {
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        tokio::select! {
+            _ = token.cancelled() => return,
+            _ = ticker.tick() => {}
+        }
+        // 副本没有响应时不能拖住关闭
+        tokio::select! {
+            _ = token.cancelled() => return,
+            _ = router.check_replicas() => {}
+        }
+    }
+}
@@ -0,0 +1,29 @@
+use sqlx::PgExecutor;
+
+use crate::models::task::{CreateTask, Task};
+
+pub async fn insert_task<'e, E>(
+    executor: E,
+    user_id: i32,
+    payload: &CreateTask,
+) -> Result<Task, sqlx::Error>
+where
+    E: PgExecutor<'e>,
+{
+    sqlx::query_as::<_, Task>(
+        "INSERT INTO tasks (title, description, category, priority, due_date, user_id, recurrence, tags, estimate_seconds, status, created_at, updated_at)
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, 'pending', NOW(), NOW())
+         RETURNING *"
+    )
+    .bind(&payload.title)
+    .bind(&payload.description)
+    .bind(&payload.category)
+    .bind(payload.priority)
+    .bind(payload.due_date)
+    .bind(user_id)
+    .bind(&payload.recurrence)
+    .bind(&payload.tags)
+    .bind(payload.estimate_seconds)
+    .fetch_one(executor)
+    .await
+}
@@ -0,0 +1,31 @@
+use sqlx::{PgPool, Postgres, Transaction};
+
+use crate::auth::AuthUser;
+
+/// 行级安全策略作用的角色，见 `migrations/*_add_tenants_and_rls.sql`
+pub const TENANT_ROLE: &str = "task_app";
+
+/// 开启一个绑定到当前用户和组织的事务。
+///
+/// 事务内切换到 `task_app` 角色并 `SET LOCAL` 租户信息，所有查询都受
+/// 行级安全策略约束：即使 SQL 漏写了 `user_id`/`organization_id` 条件，
+/// 也读不到、改不了其他组织的数据。事务结束后设置自动失效。
+pub async fn begin(
+    pool: &PgPool,
+    user: &AuthUser,
+) -> Result<Transaction<'static, Postgres>, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query(&format!("SET LOCAL ROLE {TENANT_ROLE}"))
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query(
+        "SELECT set_config('app.user_id', $1, true), set_config('app.organization_id', $2, true)",
+    )
+    .bind(user.user_id.to_string())
+    .bind(user.organization_id.to_string())
+    .execute(&mut *tx)
+    .await?;
+
+    Ok(tx)
+}
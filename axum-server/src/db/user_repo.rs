@@ -0,0 +1,61 @@
+use sqlx::PgExecutor;
+
+use crate::models::user::User;
+
+pub async fn create_organization<'e, E>(executor: E, name: &str) -> Result<i32, sqlx::Error>
+where
+    E: PgExecutor<'e>,
+{
+    sqlx::query_scalar("INSERT INTO organizations (name) VALUES ($1) RETURNING id")
+        .bind(name)
+        .fetch_one(executor)
+        .await
+}
+
+/// 邮箱已经注册时返回 `None`
+pub async fn insert_user<'e, E>(
+    executor: E,
+    organization_id: i32,
+    email: &str,
+    name: &str,
+    password_hash: &str,
+) -> Result<Option<i32>, sqlx::Error>
+where
+    E: PgExecutor<'e>,
+{
+    sqlx::query_scalar(
+        "INSERT INTO users (organization_id, email, name, password_hash) VALUES ($1, $2, $3, $4)
+         ON CONFLICT (email) DO NOTHING
+         RETURNING id",
+    )
+    .bind(organization_id)
+    .bind(email)
+    .bind(name)
+    .bind(password_hash)
+    .fetch_optional(executor)
+    .await
+}
+
+pub async fn find_by_email<'e, E>(executor: E, email: &str) -> Result<Option<User>, sqlx::Error>
+where
+    E: PgExecutor<'e>,
+{
+    sqlx::query_as::<_, User>("SELECT * FROM users WHERE email = $1")
+        .bind(email.trim().to_lowercase())
+        .fetch_optional(executor)
+        .await
+}
+
+/// 不读 `password_hash`，在租户事务里也能用
+pub async fn find_by_id<'e, E>(executor: E, id: i32) -> Result<Option<User>, sqlx::Error>
+where
+    E: PgExecutor<'e>,
+{
+    sqlx::query_as::<_, User>(
+        "SELECT id, organization_id, email, name, created_at, disabled_at, role
+         FROM users WHERE id = $1",
+    )
+    .bind(id)
+    .fetch_optional(executor)
+    .await
+}
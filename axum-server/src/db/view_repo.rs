@@ -0,0 +1,21 @@
+use sqlx::PgExecutor;
+
+use crate::models::view::SavedView;
+
+/// 自己的视图，或者别人共享出来的视图
+pub async fn find_visible<'e, E>(
+    executor: E,
+    user_id: i32,
+    view_id: i32,
+) -> Result<Option<SavedView>, sqlx::Error>
+where
+    E: PgExecutor<'e>,
+{
+    sqlx::query_as::<_, SavedView>(
+        "SELECT * FROM saved_views WHERE id = $1 AND (user_id = $2 OR shared)",
+    )
+    .bind(view_id)
+    .bind(user_id)
+    .fetch_optional(executor)
+    .await
+}
@@ -0,0 +1,380 @@
+//! 邮件通知：分配、提及、到期提醒和每日摘要。
+//!
+//! 通知先作为 `SendNotification` 任务进入 `email` 队列，发送时再检查用户的
+//! 通知设置，所以用户退订后已经排队的邮件也不会再发出
+
+mod mailer;
+mod templates;
+
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, NaiveTime, Timelike, Utc};
+use lettre::message::Mailbox;
+use minijinja::{context, Value};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgExecutor, PgPool};
+use std::{sync::Arc, time::Duration};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    config::Config,
+    db::{notification_repo, tenant, user_repo},
+    jobs::{self, EnqueueOptions, Job, JobContext},
+    models::{notification::NotificationCategory, task::Task, user::User},
+    services::user_service,
+};
+
+pub use mailer::{Mailer, SmtpTls};
+pub use templates::{current_version, render, RenderedEmail};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Notification {
+    Assignment {
+        task_id: i32,
+        title: String,
+        assigned_by: String,
+    },
+    Mention {
+        task_id: i32,
+        title: String,
+        mentioned_by: String,
+        excerpt: String,
+    },
+    /// 发送时任务已经完成、删除或改了截止时间就不再提醒
+    Reminder {
+        task_id: i32,
+        due_date: DateTime<Utc>,
+    },
+    /// 截止到这一天结束（UTC）还没完成的任务，发送时查询
+    DailyDigest { date: NaiveDate },
+}
+
+impl Notification {
+    pub fn template(&self) -> &'static str {
+        match self {
+            Self::Assignment { .. } => "assignment",
+            Self::Mention { .. } => "mention",
+            Self::Reminder { .. } => "reminder",
+            Self::DailyDigest { .. } => "daily_digest",
+        }
+    }
+
+    pub fn category(&self) -> NotificationCategory {
+        match self {
+            Self::Assignment { .. } => NotificationCategory::Assignments,
+            Self::Mention { .. } => NotificationCategory::Mentions,
+            Self::Reminder { .. } => NotificationCategory::Reminders,
+            Self::DailyDigest { .. } => NotificationCategory::DailyDigest,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SendNotification {
+    pub user_id: i32,
+    pub notification: Notification,
+    /// 入队时的模板版本，发布新版本后已经排队的邮件仍按旧版本渲染
+    pub template_version: u32,
+    /// 相同 key 的通知只发一次；不设置时按任务 id 去重，防止重试时重复发送
+    pub dedupe_key: Option<String>,
+}
+
+#[async_trait]
+impl Job for SendNotification {
+    const KIND: &'static str = "email.notification";
+    const QUEUE: &'static str = "email";
+    const MAX_ATTEMPTS: i32 = 8;
+
+    async fn run(self, ctx: &JobContext) -> anyhow::Result<()> {
+        // 关闭邮件后还在队列里的通知直接丢弃
+        let Some(config) = &ctx.config.email else {
+            return Ok(());
+        };
+        let pool = &ctx.pool;
+        let Some(user) = user_repo::find_by_id(pool, self.user_id).await? else {
+            return Ok(());
+        };
+        let category = self.notification.category();
+        if user.disabled_at.is_some()
+            || !notification_repo::get_preferences(pool, user.id)
+                .await?
+                .allows(category)
+        {
+            return Ok(());
+        }
+        let Some(details) = details(ctx, &user, &self.notification).await? else {
+            return Ok(());
+        };
+
+        // 记录和发送放在同一个事务里：发送失败时回滚，重试还能再发；
+        // 同一个 key 的另一个任务会在唯一索引上等待，然后跳过
+        let template = self.notification.template();
+        let dedupe_key = self
+            .dedupe_key
+            .unwrap_or_else(|| format!("job:{}", ctx.job_id));
+        let mut tx = pool.begin().await?;
+        let recorded = sqlx::query(
+            "INSERT INTO sent_notifications (user_id, dedupe_key, template, template_version)
+             VALUES ($1, $2, $3, $4)
+             ON CONFLICT (dedupe_key) DO NOTHING",
+        )
+        .bind(user.id)
+        .bind(&dedupe_key)
+        .bind(template)
+        .bind(self.template_version as i32)
+        .execute(&mut *tx)
+        .await?;
+        if recorded.rows_affected() == 0 {
+            return Ok(());
+        }
+
+        let token = notification_repo::get_preferences(&mut *tx, user.id)
+            .await?
+            .unsubscribe_token;
+        let unsubscribe_url = format!(
+            "{}/unsubscribe/{token}?category={}",
+            ctx.config.public_url.trim_end_matches('/'),
+            category.as_str()
+        );
+        let name = if user.name.is_empty() {
+            &user.email
+        } else {
+            &user.name
+        };
+        let email = render(
+            template,
+            self.template_version,
+            context! { name, unsubscribe_url, ..details },
+        )?;
+        let to = Mailbox::new(
+            (!user.name.is_empty()).then(|| user.name.clone()),
+            user.email.parse()?,
+        );
+        Mailer::new(config)?
+            .send(to, &email, &unsubscribe_url)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+/// 模板里除了收件人和退订链接之外的变量；返回 `None` 表示不再需要发送
+async fn details(
+    ctx: &JobContext,
+    user: &User,
+    notification: &Notification,
+) -> anyhow::Result<Option<Value>> {
+    let task_url = |id: i32| {
+        format!(
+            "{}/api/tasks/{id}",
+            ctx.config.public_url.trim_end_matches('/')
+        )
+    };
+    let details = match notification {
+        Notification::Assignment {
+            task_id,
+            title,
+            assigned_by,
+        } => context! {
+            assigned_by,
+            task => context! { title, due_date => due_date(&ctx.pool, user, *task_id).await? },
+            task_url => task_url(*task_id),
+        },
+        Notification::Mention {
+            task_id,
+            title,
+            mentioned_by,
+            excerpt,
+        } => context! {
+            mentioned_by,
+            excerpt,
+            task => context! { title },
+            task_url => task_url(*task_id),
+        },
+        Notification::Reminder { task_id, due_date } => {
+            let mut tx = tenant::begin(&ctx.pool, &user_service::act_as(user)).await?;
+            let task = sqlx::query_as::<_, Task>("SELECT * FROM tasks WHERE id = $1")
+                .bind(task_id)
+                .fetch_optional(&mut *tx)
+                .await?;
+            tx.commit().await?;
+            match task {
+                Some(task) if task.status != "completed" && task.due_date == *due_date => {
+                    context! {
+                        task => context! { title => task.title, due_date => format_time(task.due_date) },
+                        task_url => task_url(task.id),
+                    }
+                }
+                _ => return Ok(None),
+            }
+        }
+        Notification::DailyDigest { date } => {
+            let end_of_day = date
+                .succ_opt()
+                .unwrap_or(*date)
+                .and_time(NaiveTime::MIN)
+                .and_utc();
+            let mut tx = tenant::begin(&ctx.pool, &user_service::act_as(user)).await?;
+            let tasks = sqlx::query_as::<_, Task>(
+                "SELECT * FROM tasks
+                 WHERE user_id = $1 AND status <> 'completed' AND due_date < $2
+                 ORDER BY due_date, id",
+            )
+            .bind(user.id)
+            .bind(end_of_day)
+            .fetch_all(&mut *tx)
+            .await?;
+            tx.commit().await?;
+            if tasks.is_empty() {
+                return Ok(None);
+            }
+            let now = Utc::now();
+            let tasks: Vec<_> = tasks
+                .into_iter()
+                .map(|task| {
+                    context! {
+                        title => task.title,
+                        due_date => format_time(task.due_date),
+                        overdue => task.due_date < now,
+                        url => task_url(task.id),
+                    }
+                })
+                .collect();
+            context! { date => date.to_string(), tasks }
+        }
+    };
+    Ok(Some(details))
+}
+
+/// 分配通知里附带截止时间，任务已经删除时为空
+async fn due_date(pool: &PgPool, user: &User, task_id: i32) -> anyhow::Result<String> {
+    let mut tx = tenant::begin(pool, &user_service::act_as(user)).await?;
+    let due_date: Option<DateTime<Utc>> =
+        sqlx::query_scalar("SELECT due_date FROM tasks WHERE id = $1")
+            .bind(task_id)
+            .fetch_optional(&mut *tx)
+            .await?;
+    tx.commit().await?;
+    Ok(due_date.map(format_time).unwrap_or_default())
+}
+
+fn format_time(time: DateTime<Utc>) -> String {
+    time.format("%Y-%m-%d %H:%M UTC").to_string()
+}
+
+/// 给用户发送一条通知，返回入队的任务 id。传入事务时和业务数据一起提交；
+/// 没有配置邮件、或者相同 `dedupe_key` 的通知还在排队时返回 `None`
+pub async fn notify<'e, E>(
+    executor: E,
+    config: &Config,
+    user_id: i32,
+    notification: Notification,
+    dedupe_key: Option<String>,
+) -> anyhow::Result<Option<i64>>
+where
+    E: PgExecutor<'e>,
+{
+    if config.email.is_none() {
+        return Ok(None);
+    }
+    let template_version = current_version(notification.template())
+        .ok_or_else(|| anyhow::anyhow!("no template for {}", notification.template()))?;
+    let job = SendNotification {
+        user_id,
+        notification,
+        template_version,
+        dedupe_key: dedupe_key.clone(),
+    };
+    let options = EnqueueOptions {
+        unique_key: dedupe_key,
+        ..Default::default()
+    };
+    jobs::enqueue(executor, &job, options).await
+}
+
+/// 为快到期的任务和开启了每日摘要的用户排队邮件，返回入队的数量。
+/// 多个进程同时运行时靠 `unique_key` 和 `sent_notifications` 去重
+pub async fn schedule_due(
+    pool: &PgPool,
+    config: &Config,
+    now: DateTime<Utc>,
+) -> anyhow::Result<usize> {
+    let Some(email) = &config.email else {
+        return Ok(0);
+    };
+    let mut scheduled = 0;
+
+    let reminders: Vec<(i32, i32, DateTime<Utc>)> = sqlx::query_as(
+        "SELECT t.id, t.user_id, t.due_date
+         FROM tasks t
+         JOIN users u ON u.id = t.user_id AND u.disabled_at IS NULL
+         LEFT JOIN notification_preferences p ON p.user_id = t.user_id
+         WHERE t.status <> 'completed'
+           AND t.due_date > $1 AND t.due_date <= $1 + make_interval(secs => $2)
+           AND COALESCE(p.reminders, TRUE)
+           AND NOT EXISTS (
+               SELECT 1 FROM sent_notifications s
+               -- 和 `DateTime::timestamp` 一样向下取整，直接转 BIGINT 会四舍五入
+               WHERE s.dedupe_key = 'reminder:' || t.id || ':' || FLOOR(EXTRACT(EPOCH FROM t.due_date))::BIGINT
+           )",
+    )
+    .bind(now)
+    .bind(email.reminder_lead.as_secs_f64())
+    .fetch_all(pool)
+    .await?;
+    for (task_id, user_id, due_date) in reminders {
+        let key = format!("reminder:{task_id}:{}", due_date.timestamp());
+        let notification = Notification::Reminder { task_id, due_date };
+        if notify(pool, config, user_id, notification, Some(key))
+            .await?
+            .is_some()
+        {
+            scheduled += 1;
+        }
+    }
+
+    if now.hour() >= email.digest_hour {
+        let date = now.date_naive();
+        let users: Vec<i32> = sqlx::query_scalar(
+            "SELECT p.user_id
+             FROM notification_preferences p
+             JOIN users u ON u.id = p.user_id AND u.disabled_at IS NULL
+             WHERE p.daily_digest
+               AND NOT EXISTS (
+                   SELECT 1 FROM sent_notifications s
+                   WHERE s.dedupe_key = 'digest:' || p.user_id || ':' || $1
+               )",
+        )
+        .bind(date.to_string())
+        .fetch_all(pool)
+        .await?;
+        for user_id in users {
+            let key = format!("digest:{user_id}:{date}");
+            let notification = Notification::DailyDigest { date };
+            if notify(pool, config, user_id, notification, Some(key))
+                .await?
+                .is_some()
+            {
+                scheduled += 1;
+            }
+        }
+    }
+
+    Ok(scheduled)
+}
+
+pub async fn schedule_periodically(pool: PgPool, config: Arc<Config>, token: CancellationToken) {
+    if config.email.is_none() {
+        return;
+    }
+    let mut ticker = tokio::time::interval(Duration::from_secs(60));
+    loop {
+        tokio::select! {
+            _ = token.cancelled() => return,
+            _ = ticker.tick() => {}
+        }
+        if let Err(e) = schedule_due(&pool, &config, Utc::now()).await {
+            eprintln!("failed to schedule email notifications: {e:#}");
+        }
+    }
+}
@@ -0,0 +1,123 @@
+use minijinja::{Environment, UndefinedBehavior, Value};
+use std::sync::LazyLock;
+
+/// 一个模板的一个版本：主题、纯文本和 HTML 正文
+struct TemplateVersion {
+    name: &'static str,
+    version: u32,
+    files: [(&'static str, &'static str); 3],
+}
+
+macro_rules! template_version {
+    ($name:literal, $version:literal) => {
+        TemplateVersion {
+            name: $name,
+            version: $version,
+            files: [
+                template_version!(@file $name, $version, "subject.txt"),
+                template_version!(@file $name, $version, "body.txt"),
+                template_version!(@file $name, $version, "body.html"),
+            ],
+        }
+    };
+    (@file $name:literal, $version:literal, $file:literal) => {
+        (
+            concat!($name, "/v", $version, "/", $file),
+            include_str!(concat!(
+                "../../templates/email/", $name, "/v", $version, "/", $file
+            )),
+        )
+    };
+}
+
+/// 修改模板时新增一个版本而不是改旧文件：已经入队的邮件仍按入队时的版本渲染，
+/// 旧版本要保留到用它入队的任务都处理完
+static VERSIONS: &[TemplateVersion] = &[
+    template_version!("assignment", 1),
+    template_version!("mention", 1),
+    template_version!("reminder", 1),
+    template_version!("daily_digest", 1),
+];
+
+/// `.html` 结尾的模板自动转义
+static TEMPLATES: LazyLock<Environment<'static>> = LazyLock::new(|| {
+    let mut env = Environment::new();
+    env.set_undefined_behavior(UndefinedBehavior::Strict);
+    for version in VERSIONS {
+        for (path, source) in version.files {
+            env.add_template(path, source)
+                .unwrap_or_else(|e| panic!("invalid email template {path}: {e}"));
+        }
+    }
+    env
+});
+
+#[derive(Debug)]
+pub struct RenderedEmail {
+    pub subject: String,
+    pub text: String,
+    pub html: String,
+}
+
+/// 新入队的邮件使用的版本
+pub fn current_version(template: &str) -> Option<u32> {
+    VERSIONS
+        .iter()
+        .filter(|version| version.name == template)
+        .map(|version| version.version)
+        .max()
+}
+
+pub fn render(template: &str, version: u32, context: Value) -> anyhow::Result<RenderedEmail> {
+    let render = |file: &str| -> anyhow::Result<String> {
+        let path = format!("{template}/v{version}/{file}");
+        Ok(TEMPLATES.get_template(&path)?.render(&context)?)
+    };
+    Ok(RenderedEmail {
+        // 主题不能换行
+        subject: render("subject.txt")?
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" "),
+        text: render("body.txt")?,
+        html: render("body.html")?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use minijinja::context;
+
+    #[test]
+    fn every_template_has_a_current_version() {
+        for name in ["assignment", "mention", "reminder", "daily_digest"] {
+            assert_eq!(current_version(name), Some(1), "{name}");
+        }
+        assert_eq!(current_version("missing"), None);
+    }
+
+    #[test]
+    fn escapes_html_but_not_plain_text() {
+        let email = render(
+            "assignment",
+            1,
+            context! {
+                name => "Ada",
+                assigned_by => "Bob",
+                task => context! { title => "Fix <b>login</b>", due_date => "2025-03-01 09:00 UTC" },
+                task_url => "http://localhost:3000/api/tasks/1",
+                unsubscribe_url => "http://localhost:3000/unsubscribe/t?category=assignments",
+            },
+        )
+        .unwrap();
+        assert_eq!(email.subject, r#"Bob assigned you "Fix <b>login</b>""#);
+        assert!(email.text.contains("Fix <b>login</b>"));
+        assert!(email.html.contains("Fix &lt;b&gt;login&lt;"));
+    }
+
+    #[test]
+    fn missing_variables_are_errors() {
+        assert!(render("reminder", 1, context! { name => "Ada" }).is_err());
+    }
+}
@@ -1,16 +1,71 @@
 use axum::{http::StatusCode, response::IntoResponse, Json};
 use serde_json::json;
+use std::fmt;
+
+use crate::task_query::ParseError;
 
 #[derive(Debug)]
 pub struct AppError(pub anyhow::Error);
 
+/// 需要返回非 500 状态码时使用，`AppError` 会按这里的 status 响应
+#[derive(Debug)]
+pub struct HttpError {
+    pub status: StatusCode,
+    pub message: String,
+}
+
+impl HttpError {
+    pub fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+        }
+    }
+
+    pub fn bad_request(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, message)
+    }
+
+    pub fn unauthorized(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::UNAUTHORIZED, message)
+    }
+
+    pub fn forbidden(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::FORBIDDEN, message)
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::NOT_FOUND, message)
+    }
+
+    pub fn unprocessable(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::UNPROCESSABLE_ENTITY, message)
+    }
+}
+
+impl fmt::Display for HttpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for HttpError {}
+
 impl IntoResponse for AppError {
     fn into_response(self) -> axum::response::Response {
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({"error": self.0.to_string()})),
-        )
-            .into_response()
+        if let Some(e) = self.0.downcast_ref::<ParseError>() {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": e.to_string(), "position": e.position})),
+            )
+                .into_response();
+        }
+
+        let status = match self.0.downcast_ref::<HttpError>() {
+            Some(e) => e.status,
+            None => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, Json(json!({"error": self.0.to_string()}))).into_response()
     }
 }
 
@@ -0,0 +1,67 @@
+use async_graphql::dataloader::Loader;
+use std::{collections::HashMap, sync::Arc};
+
+use crate::{
+    auth::AuthUser,
+    models::{task::TaskStatusChange, user::UserProfile},
+    services::task_service::TaskService,
+};
+
+/// DataLoader 要求错误可以 clone
+pub type LoadError = Arc<anyhow::Error>;
+
+/// 把同一次查询里所有 `owner`、`reporter` 和 `assignees` 字段合并成一条 SQL
+pub struct UserLoader {
+    tasks: TaskService,
+    auth_user: AuthUser,
+}
+
+impl UserLoader {
+    pub fn new(tasks: TaskService, auth_user: AuthUser) -> Self {
+        Self { tasks, auth_user }
+    }
+}
+
+impl Loader<i32> for UserLoader {
+    type Value = UserProfile;
+    type Error = LoadError;
+
+    async fn load(&self, keys: &[i32]) -> Result<HashMap<i32, UserProfile>, LoadError> {
+        let users = self
+            .tasks
+            .users_by_id(&self.auth_user, keys)
+            .await
+            .map_err(|e| Arc::new(e.0))?;
+        Ok(users.into_iter().map(|user| (user.id, user)).collect())
+    }
+}
+
+/// 把同一次查询里所有 `history` 字段合并成一条 SQL，key 是任务 id
+pub struct HistoryLoader {
+    tasks: TaskService,
+    auth_user: AuthUser,
+}
+
+impl HistoryLoader {
+    pub fn new(tasks: TaskService, auth_user: AuthUser) -> Self {
+        Self { tasks, auth_user }
+    }
+}
+
+impl Loader<i32> for HistoryLoader {
+    type Value = Vec<TaskStatusChange>;
+    type Error = LoadError;
+
+    async fn load(&self, keys: &[i32]) -> Result<HashMap<i32, Vec<TaskStatusChange>>, LoadError> {
+        let history = self
+            .tasks
+            .history_for_tasks(&self.auth_user, keys)
+            .await
+            .map_err(|e| Arc::new(e.0))?;
+        let mut by_task: HashMap<i32, Vec<TaskStatusChange>> = HashMap::new();
+        for change in history {
+            by_task.entry(change.task_id).or_default().push(change);
+        }
+        Ok(by_task)
+    }
+}
@@ -0,0 +1,82 @@
+//! `/graphql`：和 REST 接口共用 `TaskService`，scope 在每个字段上检查
+
+mod loader;
+mod mutation;
+mod query;
+mod subscription;
+
+pub use loader::{HistoryLoader, UserLoader};
+pub use mutation::MutationRoot;
+pub use query::QueryRoot;
+pub use subscription::{SubscriptionRoot, TaskChangeEvent};
+
+use async_graphql::{dataloader::DataLoader, Context, Data, ErrorExtensions, Schema};
+use axum::http::StatusCode;
+
+use crate::{
+    auth::{AuthUser, Scope},
+    config::GraphqlConfig,
+    error::{AppError, HttpError},
+    services::task_service::TaskService,
+    task_events::TaskEvents,
+    task_query::ParseError,
+};
+
+pub type TaskSchema = Schema<QueryRoot, MutationRoot, SubscriptionRoot>;
+
+pub fn build_schema(tasks: TaskService, events: TaskEvents, config: &GraphqlConfig) -> TaskSchema {
+    Schema::build(QueryRoot, MutationRoot, SubscriptionRoot)
+        .data(tasks)
+        .data(events)
+        .limit_depth(config.max_depth)
+        .limit_complexity(config.max_complexity)
+        .finish()
+}
+
+/// 每个请求（或 websocket 连接）单独的数据：当前用户和按用户隔离的 DataLoader
+pub fn request_data(tasks: &TaskService, auth_user: AuthUser) -> Data {
+    let mut data = Data::default();
+    data.insert(DataLoader::new(
+        UserLoader::new(tasks.clone(), auth_user.clone()),
+        tokio::spawn,
+    ));
+    data.insert(DataLoader::new(
+        HistoryLoader::new(tasks.clone(), auth_user.clone()),
+        tokio::spawn,
+    ));
+    data.insert(auth_user);
+    data
+}
+
+/// 取出当前用户并检查 scope，规则和 REST 路由上的 `require_scope` 一致
+fn authorize<'a>(ctx: &Context<'a>, scope: Scope) -> async_graphql::Result<&'a AuthUser> {
+    let auth_user = ctx
+        .data::<AuthUser>()
+        .map_err(|_| graphql_error(HttpError::unauthorized("missing bearer token").into()))?;
+    auth_user
+        .require_scope(scope)
+        .map_err(|e| graphql_error(e.into()))?;
+    Ok(auth_user)
+}
+
+/// 错误信息和 REST 相同，HTTP 状态放在 `extensions.code`
+pub(crate) fn graphql_error(err: AppError) -> async_graphql::Error {
+    if let Some(e) = err.0.downcast_ref::<ParseError>() {
+        return async_graphql::Error::new(e.to_string()).extend_with(|_, extensions| {
+            extensions.set("code", "BAD_REQUEST");
+            extensions.set("position", e.position);
+        });
+    }
+
+    let status = match err.0.downcast_ref::<HttpError>() {
+        Some(e) => e.status,
+        None => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    let code = status
+        .canonical_reason()
+        .unwrap_or("Unknown")
+        .to_uppercase()
+        .replace(' ', "_");
+    async_graphql::Error::new(err.0.to_string())
+        .extend_with(|_, extensions| extensions.set("code", code))
+}
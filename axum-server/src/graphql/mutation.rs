@@ -0,0 +1,157 @@
+use async_graphql::{Context, Object, Result};
+
+use super::{authorize, graphql_error};
+use crate::{
+    auth::Scope,
+    models::{
+        task::{CreateTask, MoveTask, Task, UpdateTask},
+        time_entry::{CreateTimeEntry, StartTimer, TimeEntry},
+        view::{CreateView, SavedView, UpdateView},
+    },
+    services::task_service::{AssigneeChange, TaskService},
+};
+
+pub struct MutationRoot;
+
+#[Object]
+impl MutationRoot {
+    async fn create_task(&self, ctx: &Context<'_>, input: CreateTask) -> Result<Task> {
+        let auth_user = authorize(ctx, Scope::TasksWrite)?;
+        ctx.data::<TaskService>()?
+            .create_task(auth_user, &input)
+            .await
+            .map_err(graphql_error)
+    }
+
+    /// 没有传的字段保持不变，`recurrence` 传空字符串表示取消重复
+    async fn update_task(&self, ctx: &Context<'_>, id: i32, input: UpdateTask) -> Result<Task> {
+        let auth_user = authorize(ctx, Scope::TasksWrite)?;
+        ctx.data::<TaskService>()?
+            .update_task(auth_user, id, input)
+            .await
+            .map_err(graphql_error)
+    }
+
+    /// 看板拖动，同时修改列和位置
+    async fn move_task(&self, ctx: &Context<'_>, id: i32, input: MoveTask) -> Result<Task> {
+        let auth_user = authorize(ctx, Scope::TasksWrite)?;
+        ctx.data::<TaskService>()?
+            .move_task(auth_user, id, &input)
+            .await
+            .map_err(graphql_error)
+    }
+
+    async fn delete_task(&self, ctx: &Context<'_>, id: i32) -> Result<bool> {
+        let auth_user = authorize(ctx, Scope::TasksWrite)?;
+        ctx.data::<TaskService>()?
+            .delete_task(auth_user, id)
+            .await
+            .map_err(graphql_error)?;
+        Ok(true)
+    }
+
+    /// 追加负责人，已经是负责人的忽略
+    async fn assign_task(&self, ctx: &Context<'_>, id: i32, user_ids: Vec<i32>) -> Result<Task> {
+        let auth_user = authorize(ctx, Scope::TasksWrite)?;
+        ctx.data::<TaskService>()?
+            .change_assignees(auth_user, id, AssigneeChange::Add(user_ids))
+            .await
+            .map_err(graphql_error)
+    }
+
+    async fn unassign_task(&self, ctx: &Context<'_>, id: i32, user_id: i32) -> Result<Task> {
+        let auth_user = authorize(ctx, Scope::TasksWrite)?;
+        ctx.data::<TaskService>()?
+            .change_assignees(auth_user, id, AssigneeChange::Remove(user_id))
+            .await
+            .map_err(graphql_error)
+    }
+
+    /// 用新的列表替换所有负责人
+    async fn set_task_assignees(
+        &self,
+        ctx: &Context<'_>,
+        id: i32,
+        user_ids: Vec<i32>,
+    ) -> Result<Task> {
+        let auth_user = authorize(ctx, Scope::TasksWrite)?;
+        ctx.data::<TaskService>()?
+            .change_assignees(auth_user, id, AssigneeChange::Set(user_ids))
+            .await
+            .map_err(graphql_error)
+    }
+
+    /// 已经有计时器在走时先停下
+    async fn start_timer(
+        &self,
+        ctx: &Context<'_>,
+        task_id: i32,
+        #[graphql(default)] note: String,
+    ) -> Result<TimeEntry> {
+        let auth_user = authorize(ctx, Scope::TasksWrite)?;
+        ctx.data::<TaskService>()?
+            .start_timer(auth_user, task_id, &StartTimer { note })
+            .await
+            .map_err(graphql_error)
+    }
+
+    async fn stop_timer(&self, ctx: &Context<'_>) -> Result<TimeEntry> {
+        let auth_user = authorize(ctx, Scope::TasksWrite)?;
+        ctx.data::<TaskService>()?
+            .stop_timer(auth_user)
+            .await
+            .map_err(graphql_error)
+    }
+
+    async fn add_time_entry(
+        &self,
+        ctx: &Context<'_>,
+        task_id: i32,
+        input: CreateTimeEntry,
+    ) -> Result<TimeEntry> {
+        let auth_user = authorize(ctx, Scope::TasksWrite)?;
+        ctx.data::<TaskService>()?
+            .add_time_entry(auth_user, task_id, &input)
+            .await
+            .map_err(graphql_error)
+    }
+
+    async fn delete_time_entry(&self, ctx: &Context<'_>, id: i64) -> Result<bool> {
+        let auth_user = authorize(ctx, Scope::TasksWrite)?;
+        ctx.data::<TaskService>()?
+            .delete_time_entry(auth_user, id)
+            .await
+            .map_err(graphql_error)?;
+        Ok(true)
+    }
+
+    async fn create_view(&self, ctx: &Context<'_>, input: CreateView) -> Result<SavedView> {
+        let auth_user = authorize(ctx, Scope::TasksWrite)?;
+        ctx.data::<TaskService>()?
+            .create_view(auth_user, &input)
+            .await
+            .map_err(graphql_error)
+    }
+
+    async fn update_view(
+        &self,
+        ctx: &Context<'_>,
+        id: i32,
+        input: UpdateView,
+    ) -> Result<SavedView> {
+        let auth_user = authorize(ctx, Scope::TasksWrite)?;
+        ctx.data::<TaskService>()?
+            .update_view(auth_user, id, input)
+            .await
+            .map_err(graphql_error)
+    }
+
+    async fn delete_view(&self, ctx: &Context<'_>, id: i32) -> Result<bool> {
+        let auth_user = authorize(ctx, Scope::TasksWrite)?;
+        ctx.data::<TaskService>()?
+            .delete_view(auth_user, id)
+            .await
+            .map_err(graphql_error)?;
+        Ok(true)
+    }
+}
@@ -0,0 +1,111 @@
+use async_graphql::{dataloader::DataLoader, ComplexObject, Context, Object, Result};
+
+use super::{authorize, graphql_error, HistoryLoader, UserLoader};
+use crate::{
+    auth::Scope,
+    models::{
+        task::{Task, TaskFilter, TaskStatusChange},
+        time_entry::TimeEntry,
+        user::UserProfile,
+        view::SavedView,
+    },
+    services::task_service::TaskService,
+};
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// 和 `GET /api/tasks` 的过滤条件相同
+    #[graphql(complexity = "10 * child_complexity")]
+    async fn tasks(&self, ctx: &Context<'_>, filter: Option<TaskFilter>) -> Result<Vec<Task>> {
+        let auth_user = authorize(ctx, Scope::TasksRead)?;
+        ctx.data::<TaskService>()?
+            .list_tasks(auth_user, &filter.unwrap_or_default())
+            .await
+            .map_err(graphql_error)
+    }
+
+    async fn task(&self, ctx: &Context<'_>, id: i32) -> Result<Task> {
+        let auth_user = authorize(ctx, Scope::TasksRead)?;
+        ctx.data::<TaskService>()?
+            .get_task(auth_user, id)
+            .await
+            .map_err(graphql_error)
+    }
+
+    #[graphql(complexity = "10 * child_complexity")]
+    async fn views(&self, ctx: &Context<'_>) -> Result<Vec<SavedView>> {
+        let auth_user = authorize(ctx, Scope::TasksRead)?;
+        ctx.data::<TaskService>()?
+            .list_views(auth_user)
+            .await
+            .map_err(graphql_error)
+    }
+
+    async fn view(&self, ctx: &Context<'_>, id: i32) -> Result<SavedView> {
+        let auth_user = authorize(ctx, Scope::TasksRead)?;
+        ctx.data::<TaskService>()?
+            .get_view(auth_user, id)
+            .await
+            .map_err(graphql_error)
+    }
+
+    /// 正在走的计时器
+    async fn timer(&self, ctx: &Context<'_>) -> Result<Option<TimeEntry>> {
+        let auth_user = authorize(ctx, Scope::TasksRead)?;
+        ctx.data::<TaskService>()?
+            .running_timer(auth_user)
+            .await
+            .map_err(graphql_error)
+    }
+
+    async fn me(&self, ctx: &Context<'_>) -> Result<Option<UserProfile>> {
+        let auth_user = authorize(ctx, Scope::TasksRead)?;
+        Ok(ctx
+            .data::<DataLoader<UserLoader>>()?
+            .load_one(auth_user.user_id)
+            .await?)
+    }
+}
+
+#[ComplexObject]
+impl Task {
+    async fn owner(&self, ctx: &Context<'_>) -> Result<Option<UserProfile>> {
+        Ok(ctx
+            .data::<DataLoader<UserLoader>>()?
+            .load_one(self.user_id)
+            .await?)
+    }
+
+    async fn reporter(&self, ctx: &Context<'_>) -> Result<Option<UserProfile>> {
+        Ok(ctx
+            .data::<DataLoader<UserLoader>>()?
+            .load_one(self.reporter_id)
+            .await?)
+    }
+
+    /// 顺序和 `assigneeIds` 相同
+    #[graphql(complexity = "5 * child_complexity")]
+    async fn assignees(&self, ctx: &Context<'_>) -> Result<Vec<UserProfile>> {
+        let mut users = ctx
+            .data::<DataLoader<UserLoader>>()?
+            .load_many(self.assignee_ids.iter().copied())
+            .await?;
+        Ok(self
+            .assignee_ids
+            .iter()
+            .filter_map(|id| users.remove(id))
+            .collect())
+    }
+
+    /// 状态变化记录，最早的在前
+    #[graphql(complexity = "5 * child_complexity")]
+    async fn history(&self, ctx: &Context<'_>) -> Result<Vec<TaskStatusChange>> {
+        Ok(ctx
+            .data::<DataLoader<HistoryLoader>>()?
+            .load_one(self.id)
+            .await?
+            .unwrap_or_default())
+    }
+}
@@ -0,0 +1,59 @@
+use async_graphql::{Context, Result, SimpleObject, Subscription};
+use futures::Stream;
+use tokio::sync::broadcast::error::RecvError;
+
+use super::authorize;
+use crate::{
+    auth::Scope,
+    models::task::Task,
+    services::task_service::TaskService,
+    task_events::{ChangeOp, TaskEvents},
+};
+
+#[derive(SimpleObject)]
+pub struct TaskChangeEvent {
+    pub op: ChangeOp,
+    pub task_id: i32,
+    /// 变化之后的任务；删除时为空
+    pub task: Option<Task>,
+}
+
+pub struct SubscriptionRoot;
+
+#[Subscription]
+impl SubscriptionRoot {
+    /// 当前用户拥有或负责的任务被创建、修改或删除时推送，来源包括 REST、GraphQL 和其它实例。
+    /// 被取消分配时也会收到一次，`task` 为空
+    async fn task_changed(&self, ctx: &Context<'_>) -> Result<impl Stream<Item = TaskChangeEvent>> {
+        let auth_user = authorize(ctx, Scope::TasksRead)?.clone();
+        let tasks = ctx.data::<TaskService>()?.clone();
+        let mut changes = ctx.data::<TaskEvents>()?.subscribe();
+
+        Ok(async_stream::stream! {
+            loop {
+                let change = match changes.recv().await {
+                    Ok(change) => change,
+                    // 跟不上时跳过丢失的事件，客户端可以重新查询
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => break,
+                };
+                if !change.concerns(auth_user.user_id)
+                    || change.organization_id != auth_user.organization_id
+                {
+                    continue;
+                }
+
+                // 通知只带 id，用订阅者的身份重新读取；读取时已被删除则为空
+                let task = match change.op {
+                    ChangeOp::Delete => None,
+                    _ => tasks.get_task_from_primary(&auth_user, change.id).await.ok(),
+                };
+                yield TaskChangeEvent {
+                    op: change.op,
+                    task_id: change.id,
+                    task,
+                };
+            }
+        })
+    }
+}
@@ -0,0 +1,279 @@
+//! gRPC 版的任务接口（`tasks.v1.TaskService`），给内部服务调用。
+//! 和 REST、GraphQL 共用 `TaskService` 以及 token 校验，scope 规则也相同
+
+// tonic 的接口约定就是返回 `Status`，没法装箱
+#![allow(clippy::result_large_err)]
+
+/// 由 `proto/tasks.proto` 生成，修改 proto 后需要重新生成，见 README
+pub mod pb {
+    include!("tasks.v1.rs");
+}
+
+use chrono::{DateTime, Utc};
+use futures::{Stream, StreamExt};
+use sqlx::PgPool;
+use std::{future::Future, pin::Pin, sync::Arc, time::SystemTime};
+use tokio::net::TcpListener;
+use tonic::{
+    metadata::MetadataMap,
+    transport::{server::TcpIncoming, Server},
+    Code, Request, Response, Status,
+};
+
+use crate::{
+    auth::{self, AuthUser, Scope},
+    config::Config,
+    error::{AppError, HttpError},
+    models::task::{CreateTask, Task, TaskFilter, UpdateTask},
+    services::task_service::TaskService,
+    state::AppState,
+    task_query::ParseError,
+};
+use pb::task_service_server::{self, TaskServiceServer};
+
+pub struct GrpcTasks {
+    tasks: TaskService,
+    pool: Arc<PgPool>,
+    config: Arc<Config>,
+}
+
+impl GrpcTasks {
+    pub fn new(state: &AppState) -> Self {
+        Self {
+            tasks: TaskService::new(
+                state.db.clone(),
+                state.config.clone(),
+                state.metrics.clone(),
+                state.task_cache.clone(),
+            ),
+            pool: state.pool.clone(),
+            config: state.config.clone(),
+        }
+    }
+
+    /// 从 `authorization` metadata 取 token，校验方式和 HTTP 的 `Authorization` 头一样
+    async fn authorize(&self, metadata: &MetadataMap, scope: Scope) -> Result<AuthUser, Status> {
+        let token = metadata
+            .get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(str::trim)
+            .ok_or_else(|| Status::unauthenticated("missing bearer token"))?;
+        let auth_user = auth::authenticate_bearer(&self.pool, &self.config, token)
+            .await
+            .map_err(status)?;
+        auth_user
+            .require_scope(scope)
+            .map_err(|e| status(e.into()))?;
+        Ok(auth_user)
+    }
+}
+
+/// 在 `listener` 上提供 gRPC 服务，`signal` 完成后停止接受新连接并等待处理中的调用结束
+pub async fn serve(
+    listener: TcpListener,
+    state: &AppState,
+    signal: impl Future<Output = ()>,
+) -> anyhow::Result<()> {
+    let incoming =
+        TcpIncoming::from_listener(listener, true, None).map_err(|e| anyhow::anyhow!(e))?;
+    Server::builder()
+        .add_service(TaskServiceServer::new(GrpcTasks::new(state)))
+        .serve_with_incoming_shutdown(incoming, signal)
+        .await?;
+    Ok(())
+}
+
+type TaskStream = Pin<Box<dyn Stream<Item = Result<pb::Task, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl task_service_server::TaskService for GrpcTasks {
+    async fn create_task(
+        &self,
+        request: Request<pb::CreateTaskRequest>,
+    ) -> Result<Response<pb::Task>, Status> {
+        let auth_user = self
+            .authorize(request.metadata(), Scope::TasksWrite)
+            .await?;
+        let payload = CreateTask::try_from(request.into_inner())?;
+        let task = self
+            .tasks
+            .create_task(&auth_user, &payload)
+            .await
+            .map_err(status)?;
+        Ok(Response::new(task.into()))
+    }
+
+    async fn get_task(
+        &self,
+        request: Request<pb::GetTaskRequest>,
+    ) -> Result<Response<pb::Task>, Status> {
+        let auth_user = self.authorize(request.metadata(), Scope::TasksRead).await?;
+        let task = self
+            .tasks
+            .get_task(&auth_user, request.get_ref().id)
+            .await
+            .map_err(status)?;
+        Ok(Response::new(task.into()))
+    }
+
+    type ListTasksStream = TaskStream;
+
+    /// 查询语法或视图的错误在第一条消息的位置以 status 返回
+    async fn list_tasks(
+        &self,
+        request: Request<pb::ListTasksRequest>,
+    ) -> Result<Response<Self::ListTasksStream>, Status> {
+        let auth_user = self.authorize(request.metadata(), Scope::TasksRead).await?;
+        let request = request.into_inner();
+        let filter = TaskFilter {
+            category: request.category,
+            priority: request.priority,
+            status: request.status,
+            q: request.q,
+            view: request.view,
+            ..Default::default()
+        };
+        let stream = self
+            .tasks
+            .stream_tasks(auth_user, filter)
+            .map(|task| task.map(pb::Task::from).map_err(status));
+        Ok(Response::new(Box::pin(stream)))
+    }
+
+    async fn update_task(
+        &self,
+        request: Request<pb::UpdateTaskRequest>,
+    ) -> Result<Response<pb::Task>, Status> {
+        let auth_user = self
+            .authorize(request.metadata(), Scope::TasksWrite)
+            .await?;
+        let request = request.into_inner();
+        let id = request.id;
+        let payload = UpdateTask::try_from(request)?;
+        let task = self
+            .tasks
+            .update_task(&auth_user, id, payload)
+            .await
+            .map_err(status)?;
+        Ok(Response::new(task.into()))
+    }
+
+    async fn delete_task(
+        &self,
+        request: Request<pb::DeleteTaskRequest>,
+    ) -> Result<Response<pb::DeleteTaskResponse>, Status> {
+        let auth_user = self
+            .authorize(request.metadata(), Scope::TasksWrite)
+            .await?;
+        self.tasks
+            .delete_task(&auth_user, request.get_ref().id)
+            .await
+            .map_err(status)?;
+        Ok(Response::new(pb::DeleteTaskResponse {}))
+    }
+}
+
+/// 错误信息和 REST 相同，HTTP 状态换成对应的 gRPC code；
+/// 其他错误（数据库等）只记日志，不把内部信息返回给调用方
+fn status(err: AppError) -> Status {
+    if let Some(e) = err.0.downcast_ref::<ParseError>() {
+        return Status::invalid_argument(e.to_string());
+    }
+
+    let Some(e) = err.0.downcast_ref::<HttpError>() else {
+        eprintln!("gRPC call failed: {:#}", err.0);
+        return Status::internal("internal error");
+    };
+    let code = match e.status.as_u16() {
+        400 | 422 => Code::InvalidArgument,
+        401 => Code::Unauthenticated,
+        403 => Code::PermissionDenied,
+        404 => Code::NotFound,
+        409 => Code::AlreadyExists,
+        429 => Code::ResourceExhausted,
+        _ => Code::Internal,
+    };
+    Status::new(code, e.message.clone())
+}
+
+fn timestamp(time: DateTime<Utc>) -> prost_types::Timestamp {
+    SystemTime::from(time).into()
+}
+
+/// 超出 chrono 范围的秒数和不在 0..1e9 的纳秒都算无效
+fn parse_timestamp(
+    field: &str,
+    timestamp: prost_types::Timestamp,
+) -> Result<DateTime<Utc>, Status> {
+    u32::try_from(timestamp.nanos)
+        .ok()
+        .filter(|nanos| *nanos < 1_000_000_000)
+        .and_then(|nanos| DateTime::from_timestamp(timestamp.seconds, nanos))
+        .ok_or_else(|| Status::invalid_argument(format!("{field} is not a valid timestamp")))
+}
+
+impl From<Task> for pb::Task {
+    fn from(task: Task) -> Self {
+        Self {
+            id: task.id,
+            title: task.title,
+            description: task.description,
+            category: task.category,
+            priority: task.priority,
+            status: task.status,
+            due_date: Some(timestamp(task.due_date)),
+            created_at: Some(timestamp(task.created_at)),
+            updated_at: Some(timestamp(task.updated_at)),
+            user_id: task.user_id,
+            recurrence: task.recurrence,
+            tags: task.tags,
+            reporter_id: task.reporter_id,
+            assignee_ids: task.assignee_ids,
+            rank: task.rank,
+            estimate_seconds: task.estimate_seconds,
+            time_spent_seconds: task.time_spent_seconds,
+        }
+    }
+}
+
+impl TryFrom<pb::CreateTaskRequest> for CreateTask {
+    type Error = Status;
+
+    fn try_from(request: pb::CreateTaskRequest) -> Result<Self, Status> {
+        let due_date = request
+            .due_date
+            .ok_or_else(|| Status::invalid_argument("due_date is required"))?;
+        Ok(Self {
+            title: request.title,
+            description: request.description,
+            category: request.category,
+            priority: request.priority,
+            due_date: parse_timestamp("due_date", due_date)?,
+            recurrence: request.recurrence,
+            tags: request.tags,
+            estimate_seconds: request.estimate_seconds,
+        })
+    }
+}
+
+impl TryFrom<pb::UpdateTaskRequest> for UpdateTask {
+    type Error = Status;
+
+    fn try_from(request: pb::UpdateTaskRequest) -> Result<Self, Status> {
+        Ok(Self {
+            title: request.title,
+            description: request.description,
+            category: request.category,
+            priority: request.priority,
+            status: request.status,
+            due_date: request
+                .due_date
+                .map(|due_date| parse_timestamp("due_date", due_date))
+                .transpose()?,
+            recurrence: request.recurrence,
+            tags: request.tags.map(|list| list.tags),
+            estimate_seconds: request.estimate_seconds,
+        })
+    }
+}
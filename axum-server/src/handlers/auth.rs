@@ -0,0 +1,265 @@
+use crate::{
+    auth::AuthUser,
+    config::Config,
+    error::{AppError, HttpError},
+    models::{
+        token::{
+            ClientCertificateMapping, CreateClientCertificateMapping, CreatePersonalAccessToken,
+            CreatedPersonalAccessToken, PersonalAccessToken,
+        },
+        user::{LoginRequest, RefreshRequest, RegisterRequest, Session, TokenResponse},
+    },
+    services::{
+        auth_service::{self, ClientInfo},
+        oidc_service::OidcClient,
+    },
+    state::AppState,
+};
+use axum::{
+    extract::{ConnectInfo, Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::Redirect,
+    Json,
+};
+use serde::Deserialize;
+use serde_json::json;
+use sqlx::PgPool;
+use std::{net::SocketAddr, sync::Arc};
+
+fn client_info(headers: &HeaderMap, addr: Option<ConnectInfo<SocketAddr>>) -> ClientInfo {
+    ClientInfo {
+        user_agent: headers
+            .get(header::USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.chars().take(512).collect()),
+        ip_address: addr.map(|ConnectInfo(addr)| addr.ip().to_string()),
+    }
+}
+
+pub async fn register(
+    State(pool): State<Arc<PgPool>>,
+    State(config): State<Arc<Config>>,
+    addr: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    Json(payload): Json<RegisterRequest>,
+) -> Result<(StatusCode, Json<TokenResponse>), AppError> {
+    let tokens = auth_service::register(
+        pool.as_ref(),
+        &config.auth,
+        &payload.email,
+        payload.password,
+        payload.name.trim(),
+        client_info(&headers, addr),
+    )
+    .await?;
+
+    Ok((StatusCode::CREATED, Json(tokens)))
+}
+
+pub async fn login(
+    State(pool): State<Arc<PgPool>>,
+    State(config): State<Arc<Config>>,
+    addr: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    Json(payload): Json<LoginRequest>,
+) -> Result<Json<TokenResponse>, AppError> {
+    let tokens = auth_service::login(
+        pool.as_ref(),
+        &config.auth,
+        &payload.email,
+        payload.password,
+        client_info(&headers, addr),
+    )
+    .await?;
+
+    Ok(Json(tokens))
+}
+
+pub async fn refresh(
+    State(pool): State<Arc<PgPool>>,
+    State(config): State<Arc<Config>>,
+    Json(payload): Json<RefreshRequest>,
+) -> Result<Json<TokenResponse>, AppError> {
+    let tokens = auth_service::refresh(pool.as_ref(), &config.auth, &payload.refresh_token).await?;
+
+    Ok(Json(tokens))
+}
+
+pub async fn logout(
+    auth_user: AuthUser,
+    State(pool): State<Arc<PgPool>>,
+) -> Result<StatusCode, AppError> {
+    if let Some(session_id) = auth_user.session_id {
+        auth_service::revoke_session(pool.as_ref(), auth_user.user_id, session_id, "logout")
+            .await?;
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn get_sessions(
+    auth_user: AuthUser,
+    State(pool): State<Arc<PgPool>>,
+) -> Result<Json<Vec<Session>>, AppError> {
+    let sessions =
+        auth_service::list_sessions(pool.as_ref(), auth_user.user_id, auth_user.session_id).await?;
+
+    Ok(Json(sessions))
+}
+
+/// 退出其它设备，保留当前 session
+pub async fn revoke_other_sessions(
+    auth_user: AuthUser,
+    State(pool): State<Arc<PgPool>>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let revoked =
+        auth_service::revoke_other_sessions(pool.as_ref(), auth_user.user_id, auth_user.session_id)
+            .await?;
+
+    Ok(Json(json!({ "revoked": revoked })))
+}
+
+pub async fn revoke_session(
+    auth_user: AuthUser,
+    State(pool): State<Arc<PgPool>>,
+    Path(session_id): Path<i64>,
+) -> Result<StatusCode, AppError> {
+    let revoked = auth_service::revoke_session(
+        pool.as_ref(),
+        auth_user.user_id,
+        session_id,
+        "revoked_by_user",
+    )
+    .await?;
+
+    if !revoked {
+        return Err(HttpError::not_found("Session not found").into());
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn create_token(
+    auth_user: AuthUser,
+    State(pool): State<Arc<PgPool>>,
+    Json(payload): Json<CreatePersonalAccessToken>,
+) -> Result<(StatusCode, Json<CreatedPersonalAccessToken>), AppError> {
+    payload
+        .validate()
+        .map_err(|errors| HttpError::unprocessable(errors.join("; ")))?;
+
+    let token = auth_service::create_personal_token(pool.as_ref(), &auth_user, &payload).await?;
+
+    Ok((StatusCode::CREATED, Json(token)))
+}
+
+pub async fn get_tokens(
+    auth_user: AuthUser,
+    State(pool): State<Arc<PgPool>>,
+) -> Result<Json<Vec<PersonalAccessToken>>, AppError> {
+    let tokens = auth_service::list_personal_tokens(pool.as_ref(), auth_user.user_id).await?;
+
+    Ok(Json(tokens))
+}
+
+pub async fn revoke_token(
+    auth_user: AuthUser,
+    State(pool): State<Arc<PgPool>>,
+    Path(token_id): Path<i32>,
+) -> Result<StatusCode, AppError> {
+    if !auth_service::revoke_personal_token(pool.as_ref(), auth_user.user_id, token_id).await? {
+        return Err(HttpError::not_found("Token not found").into());
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn create_client_certificate(
+    auth_user: AuthUser,
+    State(pool): State<Arc<PgPool>>,
+    Json(payload): Json<CreateClientCertificateMapping>,
+) -> Result<(StatusCode, Json<ClientCertificateMapping>), AppError> {
+    payload
+        .validate()
+        .map_err(|errors| HttpError::unprocessable(errors.join("; ")))?;
+
+    let mapping =
+        auth_service::create_client_certificate(pool.as_ref(), &auth_user, &payload).await?;
+
+    Ok((StatusCode::CREATED, Json(mapping)))
+}
+
+pub async fn get_client_certificates(
+    auth_user: AuthUser,
+    State(pool): State<Arc<PgPool>>,
+) -> Result<Json<Vec<ClientCertificateMapping>>, AppError> {
+    let mappings = auth_service::list_client_certificates(pool.as_ref(), auth_user.user_id).await?;
+
+    Ok(Json(mappings))
+}
+
+pub async fn revoke_client_certificate(
+    auth_user: AuthUser,
+    State(pool): State<Arc<PgPool>>,
+    Path(certificate_id): Path<i32>,
+) -> Result<StatusCode, AppError> {
+    if !auth_service::revoke_client_certificate(pool.as_ref(), auth_user.user_id, certificate_id)
+        .await?
+    {
+        return Err(HttpError::not_found("Client certificate not found").into());
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+fn oidc_client(state: &AppState) -> Result<&OidcClient, HttpError> {
+    state
+        .oidc
+        .as_deref()
+        .ok_or_else(|| HttpError::not_found("single sign-on is not configured"))
+}
+
+/// 跳转到身份提供方登录
+pub async fn oidc_login(State(state): State<AppState>) -> Result<Redirect, AppError> {
+    let url = oidc_client(&state)?
+        .authorization_url(state.pool.as_ref())
+        .await?;
+
+    Ok(Redirect::to(&url))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OidcCallback {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub error: Option<String>,
+    pub error_description: Option<String>,
+}
+
+pub async fn oidc_callback(
+    State(state): State<AppState>,
+    addr: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    Query(callback): Query<OidcCallback>,
+) -> Result<Json<TokenResponse>, AppError> {
+    let client = oidc_client(&state)?;
+    if let Some(error) = callback.error {
+        let description = callback.error_description.unwrap_or_default();
+        return Err(HttpError::unauthorized(format!("login failed: {error} {description}")).into());
+    }
+    let (Some(code), Some(login_state)) = (callback.code, callback.state) else {
+        return Err(HttpError::bad_request("missing code or state").into());
+    };
+
+    let tokens = client
+        .complete_login(
+            state.pool.as_ref(),
+            &state.config.auth,
+            &code,
+            &login_state,
+            client_info(&headers, addr),
+        )
+        .await?;
+
+    Ok(Json(tokens))
+}
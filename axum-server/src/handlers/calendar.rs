@@ -0,0 +1,112 @@
+use crate::{
+    auth::AuthUser,
+    db::tenant,
+    error::{AppError, HttpError},
+    ical::{render_calendar, Component},
+    models::task::Task,
+    state::AppState,
+    token,
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::header,
+    response::IntoResponse,
+    Json,
+};
+use serde::Deserialize;
+use serde_json::json;
+use sqlx::PgPool;
+use std::sync::Arc;
+
+/// 生成新的订阅地址，旧地址同时失效
+pub async fn create_feed(
+    auth_user: AuthUser,
+    State(state): State<AppState>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let token = token::generate();
+
+    let mut tx = tenant::begin(state.pool.as_ref(), &auth_user).await?;
+    sqlx::query(
+        "UPDATE calendar_feeds SET revoked_at = NOW() WHERE user_id = $1 AND revoked_at IS NULL",
+    )
+    .bind(auth_user.user_id)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query("INSERT INTO calendar_feeds (user_id, token_hash) VALUES ($1, $2)")
+        .bind(auth_user.user_id)
+        .bind(token::hash(&token))
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    Ok(Json(json!({
+        "url": format!("{}/calendar/{}.ics", state.config.public_url, token)
+    })))
+}
+
+pub async fn revoke_feed(
+    auth_user: AuthUser,
+    State(pool): State<Arc<PgPool>>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let mut tx = tenant::begin(pool.as_ref(), &auth_user).await?;
+    let result = sqlx::query(
+        "UPDATE calendar_feeds SET revoked_at = NOW() WHERE user_id = $1 AND revoked_at IS NULL",
+    )
+    .bind(auth_user.user_id)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    if result.rows_affected() == 0 {
+        return Err(HttpError::not_found("Calendar feed not found").into());
+    }
+
+    Ok(Json(json!({
+        "message": "Calendar feed revoked"
+    })))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FeedParams {
+    /// `event` 给不支持 VTODO 的日历应用使用
+    #[serde(default)]
+    pub kind: Option<String>,
+}
+
+pub async fn calendar_feed(
+    State(pool): State<Arc<PgPool>>,
+    Path(file): Path<String>,
+    Query(params): Query<FeedParams>,
+) -> Result<impl IntoResponse, AppError> {
+    let token = file.strip_suffix(".ics").unwrap_or(&file);
+
+    // 禁用的用户的订阅地址和其他凭据一样失效
+    let user_id: i32 = sqlx::query_scalar(
+        "SELECT f.user_id FROM calendar_feeds f
+         JOIN users u ON u.id = f.user_id
+         WHERE f.token_hash = $1 AND f.revoked_at IS NULL AND u.disabled_at IS NULL",
+    )
+    .bind(token::hash(token))
+    .fetch_optional(pool.as_ref())
+    .await?
+    .ok_or_else(|| HttpError::not_found("Calendar feed not found"))?;
+
+    let tasks =
+        sqlx::query_as::<_, Task>("SELECT * FROM tasks WHERE user_id = $1 ORDER BY due_date")
+            .bind(user_id)
+            .fetch_all(pool.as_ref())
+            .await?;
+
+    let component = match params.kind.as_deref() {
+        Some("event") => Component::Event,
+        _ => Component::Todo,
+    };
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/calendar; charset=utf-8"),
+            (header::CACHE_CONTROL, "private, max-age=300"),
+        ],
+        render_calendar(&tasks, component),
+    ))
+}
@@ -0,0 +1,116 @@
+use crate::{
+    auth::{self, AuthUser},
+    config::Config,
+    error::{AppError, HttpError},
+    graphql::{self, graphql_error, TaskSchema},
+    services::task_service::TaskService,
+};
+use async_graphql::http::{WebSocket as GraphqlWebSocket, WebSocketProtocols, WsMessage};
+use axum::{
+    extract::{
+        ws::{CloseFrame, Message, WebSocket, WebSocketUpgrade},
+        State,
+    },
+    http::{header, HeaderMap},
+    response::Response,
+    Json,
+};
+use futures::{future, SinkExt, StreamExt};
+use sqlx::PgPool;
+use std::sync::Arc;
+
+pub async fn graphql(
+    auth_user: AuthUser,
+    State(schema): State<TaskSchema>,
+    State(tasks): State<TaskService>,
+    Json(mut request): Json<async_graphql::Request>,
+) -> Json<async_graphql::Response> {
+    request.data = graphql::request_data(&tasks, auth_user);
+    Json(schema.execute(request).await)
+}
+
+/// 订阅走 websocket，支持 `graphql-transport-ws` 和旧的 `graphql-ws` 协议。
+/// 浏览器不能给 websocket 设置请求头，token 可以放在 `connection_init` 的
+/// `Authorization` 字段里
+pub async fn graphql_ws(
+    auth_user: Option<AuthUser>,
+    State(schema): State<TaskSchema>,
+    State(tasks): State<TaskService>,
+    State(pool): State<Arc<PgPool>>,
+    State(config): State<Arc<Config>>,
+    headers: HeaderMap,
+    upgrade: WebSocketUpgrade,
+) -> Result<Response, AppError> {
+    let protocol = headers
+        .get(header::SEC_WEBSOCKET_PROTOCOL)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| {
+            value
+                .split(',')
+                .find_map(|protocol| protocol.trim().parse::<WebSocketProtocols>().ok())
+        })
+        .ok_or_else(|| HttpError::bad_request("unsupported websocket subprotocol"))?;
+
+    Ok(upgrade
+        .protocols([protocol.sec_websocket_protocol()])
+        .on_upgrade(move |socket| async move {
+            let on_init = move |payload: serde_json::Value| async move {
+                let auth_user = match auth_user {
+                    Some(auth_user) => auth_user,
+                    None => {
+                        let token = payload
+                            .get("Authorization")
+                            .or_else(|| payload.get("authorization"))
+                            .and_then(|value| value.as_str())
+                            .map(|value| value.strip_prefix("Bearer ").unwrap_or(value).trim())
+                            .ok_or_else(|| {
+                                graphql_error(
+                                    HttpError::unauthorized("missing bearer token").into(),
+                                )
+                            })?;
+                        auth::authenticate_bearer(&pool, &config, token)
+                            .await
+                            .map_err(graphql_error)?
+                    }
+                };
+                Ok(graphql::request_data(&tasks, auth_user))
+            };
+            serve_socket(socket, schema, protocol, on_init).await;
+        }))
+}
+
+async fn serve_socket<F, Fut>(
+    socket: WebSocket,
+    schema: TaskSchema,
+    protocol: WebSocketProtocols,
+    on_init: F,
+) where
+    F: FnOnce(serde_json::Value) -> Fut + Send + 'static,
+    Fut: future::Future<Output = async_graphql::Result<async_graphql::Data>> + Send + 'static,
+{
+    let (mut sink, stream) = socket.split();
+    let incoming = stream
+        .take_while(|message| future::ready(message.is_ok()))
+        .filter_map(|message| {
+            future::ready(match message {
+                Ok(Message::Text(text)) => Some(text.into_bytes()),
+                Ok(Message::Binary(bytes)) => Some(bytes),
+                _ => None,
+            })
+        });
+
+    let mut outgoing =
+        GraphqlWebSocket::new(schema, incoming, protocol).on_connection_init(on_init);
+    while let Some(message) = outgoing.next().await {
+        let message = match message {
+            WsMessage::Text(text) => Message::Text(text),
+            WsMessage::Close(code, reason) => Message::Close(Some(CloseFrame {
+                code,
+                reason: reason.into(),
+            })),
+        };
+        if sink.send(message).await.is_err() {
+            break;
+        }
+    }
+}
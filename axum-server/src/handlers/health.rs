@@ -0,0 +1,63 @@
+use crate::{error::AppError, metrics::Metrics, shutdown::Draining};
+use axum::{
+    extract::State,
+    http::{header, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde_json::json;
+use sqlx::PgPool;
+use std::sync::Arc;
+
+pub async fn healthz() -> impl IntoResponse {
+    Json(json!({ "status": "ok" }))
+}
+
+/// 关闭过程中返回 503，处理中的请求仍然会完成
+pub async fn readyz(
+    State(pool): State<Arc<PgPool>>,
+    State(draining): State<Draining>,
+) -> impl IntoResponse {
+    if draining.is_draining() {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({ "status": "draining" })),
+        );
+    }
+    match check_ready(pool.as_ref()).await {
+        Ok(()) => (StatusCode::OK, Json(json!({ "status": "ready" }))),
+        Err(e) => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({ "status": "not_ready", "error": e.to_string() })),
+        ),
+    }
+}
+
+async fn check_ready(pool: &PgPool) -> anyhow::Result<()> {
+    let mut conn = pool.acquire().await?;
+
+    let applied: Vec<i64> =
+        sqlx::query_scalar("SELECT version FROM _sqlx_migrations WHERE success ORDER BY version")
+            .fetch_all(&mut *conn)
+            .await?;
+
+    let pending: Vec<i64> = sqlx::migrate!()
+        .iter()
+        .map(|migration| migration.version)
+        .filter(|version| !applied.contains(version))
+        .collect();
+
+    if !pending.is_empty() {
+        anyhow::bail!("pending migrations: {:?}", pending);
+    }
+
+    Ok(())
+}
+
+pub async fn metrics(
+    State(metrics): State<Arc<Metrics>>,
+    State(pool): State<Arc<PgPool>>,
+) -> Result<impl IntoResponse, AppError> {
+    let body = metrics.render(pool.as_ref())?;
+    Ok(([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], body))
+}
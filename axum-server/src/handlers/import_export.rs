@@ -0,0 +1,80 @@
+use crate::{
+    auth::AuthUser,
+    db::routing::DbRouter,
+    error::AppError,
+    metrics::Metrics,
+    services::import_export_service::{self, Format, ImportReport},
+    task_cache::TaskCache,
+};
+use axum::{
+    body::{Body, Bytes},
+    extract::{Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Deserialize;
+use std::sync::Arc;
+
+#[derive(Debug, Deserialize)]
+pub struct ExportParams {
+    #[serde(default)]
+    pub format: Format,
+}
+
+/// 逐行从数据库读取并写出，不会把所有任务读进内存
+pub async fn export_tasks(
+    auth_user: AuthUser,
+    State(db): State<Arc<DbRouter>>,
+    Query(params): Query<ExportParams>,
+) -> Response {
+    let format = params.format;
+    let stream = import_export_service::export_stream(db, auth_user, format);
+
+    (
+        [
+            (header::CONTENT_TYPE, format.content_type().to_owned()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"tasks.{}\"", format.extension()),
+            ),
+        ],
+        Body::from_stream(stream),
+    )
+        .into_response()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImportParams {
+    #[serde(default)]
+    pub format: Format,
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// 校验每一行；合法的行在同一个事务里写入，`dry_run=true` 时只校验
+pub async fn import_tasks(
+    auth_user: AuthUser,
+    State(db): State<Arc<DbRouter>>,
+    State(metrics): State<Arc<Metrics>>,
+    State(cache): State<Arc<TaskCache>>,
+    Query(params): Query<ImportParams>,
+    body: Bytes,
+) -> Result<Json<ImportReport>, AppError> {
+    let report = import_export_service::import_tasks(
+        db.primary(),
+        &auth_user,
+        params.format,
+        &body,
+        params.dry_run,
+    )
+    .await?;
+
+    if !report.dry_run {
+        db.record_write(auth_user.user_id);
+        cache.invalidate(auth_user.user_id);
+        metrics.tasks_created.inc_by(report.valid as u64);
+    }
+
+    Ok(Json(report))
+}
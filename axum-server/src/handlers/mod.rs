@@ -1 +1,10 @@
+pub mod auth;
+pub mod calendar;
+pub mod graphql;
+pub mod health;
+pub mod import_export;
+pub mod notification;
+pub mod stats;
 pub mod task;
+pub mod time_entry;
+pub mod view;
@@ -0,0 +1,136 @@
+use crate::{
+    auth::AuthUser,
+    db::notification_repo,
+    error::{AppError, HttpError},
+    models::notification::{
+        Notification, NotificationCategory, NotificationFilter, NotificationPreferences,
+        UpdateNotificationPreferences,
+    },
+    services::notification_service,
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderName},
+    response::Html,
+    Json,
+};
+use serde::Deserialize;
+use serde_json::json;
+use sqlx::PgPool;
+use std::sync::Arc;
+
+pub async fn get_preferences(
+    auth_user: AuthUser,
+    State(pool): State<Arc<PgPool>>,
+) -> Result<Json<NotificationPreferences>, AppError> {
+    Ok(Json(
+        notification_service::get_preferences(&pool, &auth_user).await?,
+    ))
+}
+
+pub async fn update_preferences(
+    auth_user: AuthUser,
+    State(pool): State<Arc<PgPool>>,
+    Json(payload): Json<UpdateNotificationPreferences>,
+) -> Result<Json<NotificationPreferences>, AppError> {
+    Ok(Json(
+        notification_service::update_preferences(&pool, &auth_user, &payload).await?,
+    ))
+}
+
+pub async fn get_notifications(
+    auth_user: AuthUser,
+    State(pool): State<Arc<PgPool>>,
+    Query(filter): Query<NotificationFilter>,
+) -> Result<Json<Vec<Notification>>, AppError> {
+    Ok(Json(
+        notification_service::list_notifications(&pool, &auth_user, &filter).await?,
+    ))
+}
+
+pub async fn read_notification(
+    auth_user: AuthUser,
+    State(pool): State<Arc<PgPool>>,
+    Path(notification_id): Path<i64>,
+) -> Result<Json<Notification>, AppError> {
+    Ok(Json(
+        notification_service::mark_read(&pool, &auth_user, notification_id).await?,
+    ))
+}
+
+pub async fn read_all_notifications(
+    auth_user: AuthUser,
+    State(pool): State<Arc<PgPool>>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    let updated = notification_service::mark_all_read(&pool, &auth_user).await?;
+    Ok(Json(json!({ "updated": updated })))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UnsubscribeParams {
+    /// 不传时关闭所有邮件通知
+    pub category: Option<NotificationCategory>,
+}
+
+fn describe(category: Option<NotificationCategory>) -> &'static str {
+    match category {
+        Some(NotificationCategory::Assignments) => "assignment emails",
+        Some(NotificationCategory::Mentions) => "mention emails",
+        Some(NotificationCategory::Reminders) => "reminder emails",
+        Some(NotificationCategory::DailyDigest) => "the daily digest",
+        None => "all notification emails",
+    }
+}
+
+/// 退订页面没有脚本和外部资源，只需要提交表单到本站。
+/// 全局的 `CONTENT_SECURITY_POLICY` 是给 JSON 接口的，这里设置了就不会再加
+const PAGE_CONTENT_SECURITY_POLICY: &str =
+    "default-src 'none'; form-action 'self'; frame-ancestors 'none'";
+
+type Page = ([(HeaderName, &'static str); 1], Html<String>);
+
+fn page(body: String) -> Page {
+    (
+        [(
+            header::CONTENT_SECURITY_POLICY,
+            PAGE_CONTENT_SECURITY_POLICY,
+        )],
+        Html(body),
+    )
+}
+
+/// 邮件里的退订链接。GET 只显示确认按钮，邮件客户端和安全扫描预取链接时不会误退订
+pub async fn unsubscribe_page(
+    State(pool): State<Arc<PgPool>>,
+    Path(token): Path<String>,
+    Query(params): Query<UnsubscribeParams>,
+) -> Result<Page, AppError> {
+    if notification_repo::find_email_by_unsubscribe_token(pool.as_ref(), &token)
+        .await?
+        .is_none()
+    {
+        return Err(HttpError::not_found("Unsubscribe link is invalid").into());
+    }
+    Ok(page(format!(
+        "<!DOCTYPE html><html><body>\
+         <form method=\"post\"><p>Stop receiving {}?</p>\
+         <button type=\"submit\">Unsubscribe</button></form>\
+         </body></html>",
+        describe(params.category)
+    )))
+}
+
+/// 确认按钮和邮件客户端的一键退订（RFC 8058）都 POST 到这里，不需要登录
+pub async fn unsubscribe(
+    State(pool): State<Arc<PgPool>>,
+    Path(token): Path<String>,
+    Query(params): Query<UnsubscribeParams>,
+) -> Result<Page, AppError> {
+    if !notification_repo::unsubscribe(pool.as_ref(), &token, params.category).await? {
+        return Err(HttpError::not_found("Unsubscribe link is invalid").into());
+    }
+    Ok(page(format!(
+        "<!DOCTYPE html><html><body><p>You will no longer receive {}.</p></body></html>",
+        describe(params.category)
+    )))
+}
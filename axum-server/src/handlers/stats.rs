@@ -0,0 +1,160 @@
+use crate::{
+    auth::AuthUser,
+    db::routing::DbRouter,
+    error::{AppError, HttpError},
+    models::stats::{CountBucket, DatePoint, StatsParams, TaskStats, Throughput},
+};
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use chrono::{Duration, Utc};
+use sqlx::PgConnection;
+use std::sync::Arc;
+
+const DEFAULT_RANGE_DAYS: i64 = 30;
+const MAX_RANGE_DAYS: i64 = 366;
+
+pub async fn get_stats(
+    auth_user: AuthUser,
+    State(db): State<Arc<DbRouter>>,
+    Query(params): Query<StatsParams>,
+) -> Result<Json<TaskStats>, AppError> {
+    let to = params.to.unwrap_or_else(|| Utc::now().date_naive());
+    let from = params
+        .from
+        .unwrap_or(to - Duration::days(DEFAULT_RANGE_DAYS - 1));
+    if from > to {
+        return Err(HttpError::bad_request("`from` must not be after `to`").into());
+    }
+    if (to - from).num_days() >= MAX_RANGE_DAYS {
+        return Err(HttpError::bad_request(format!(
+            "date range must be at most {MAX_RANGE_DAYS} days"
+        ))
+        .into());
+    }
+
+    let mut tx = db.begin_read(&auth_user).await?;
+    let user_id = auth_user.user_id;
+
+    let total: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM tasks WHERE user_id = $1")
+        .bind(user_id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+    let by_status = count_by(&mut tx, user_id, "status").await?;
+    let by_priority = count_by(&mut tx, user_id, "priority::text").await?;
+    let by_category = count_by(&mut tx, user_id, "category").await?;
+
+    let overdue: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM tasks
+         WHERE user_id = $1 AND status <> 'completed' AND due_date < NOW()",
+    )
+    .bind(user_id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    let daily = sqlx::query_as::<_, DatePoint>(
+        "SELECT day::date AS date, COUNT(h.id) AS count
+         FROM generate_series($2::date, $3::date, INTERVAL '1 day') AS day
+         LEFT JOIN task_history h
+             ON h.user_id = $1
+            AND h.new_status = 'completed'
+            AND h.changed_at >= day
+            AND h.changed_at < day + INTERVAL '1 day'
+         GROUP BY day
+         ORDER BY day",
+    )
+    .bind(user_id)
+    .bind(from)
+    .bind(to)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let weekly = sqlx::query_as::<_, DatePoint>(
+        "SELECT week::date AS date, COUNT(h.id) AS count
+         FROM generate_series(date_trunc('week', $2::date), $3::date, INTERVAL '1 week') AS week
+         LEFT JOIN task_history h
+             ON h.user_id = $1
+            AND h.new_status = 'completed'
+            AND h.changed_at >= week
+            AND h.changed_at < week + INTERVAL '1 week'
+         GROUP BY week
+         ORDER BY week",
+    )
+    .bind(user_id)
+    .bind(from)
+    .bind(to)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let avg_completion_seconds: Option<f64> = sqlx::query_scalar(
+        "SELECT AVG(EXTRACT(EPOCH FROM (done.completed_at - t.created_at)))::float8
+         FROM tasks t
+         JOIN (
+             SELECT task_id, MIN(changed_at) AS completed_at
+             FROM task_history
+             WHERE user_id = $1 AND new_status = 'completed'
+             GROUP BY task_id
+         ) done ON done.task_id = t.id
+         WHERE t.user_id = $1 AND t.status = 'completed'",
+    )
+    .bind(user_id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    // 某天结束时的状态 = 当时最后一条历史记录的状态
+    let burndown = sqlx::query_as::<_, DatePoint>(
+        "SELECT day::date AS date,
+                (SELECT COUNT(*)
+                 FROM tasks t
+                 WHERE t.user_id = $1
+                   AND t.created_at < day + INTERVAL '1 day'
+                   AND COALESCE(
+                       (SELECT h.new_status
+                        FROM task_history h
+                        WHERE h.task_id = t.id AND h.changed_at < day + INTERVAL '1 day'
+                        ORDER BY h.changed_at DESC, h.id DESC
+                        LIMIT 1),
+                       t.status
+                   ) <> 'completed') AS count
+         FROM generate_series($2::date, $3::date, INTERVAL '1 day') AS day
+         ORDER BY day",
+    )
+    .bind(user_id)
+    .bind(from)
+    .bind(to)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(Json(TaskStats {
+        from,
+        to,
+        total,
+        by_status,
+        by_priority,
+        by_category,
+        overdue,
+        throughput: Throughput { daily, weekly },
+        avg_completion_seconds,
+        burndown,
+    }))
+}
+
+/// `column` 只会是上面写死的几个列名
+async fn count_by(
+    conn: &mut PgConnection,
+    user_id: i32,
+    column: &'static str,
+) -> Result<Vec<CountBucket>, sqlx::Error> {
+    sqlx::query_as::<_, CountBucket>(&format!(
+        "SELECT {column} AS key, COUNT(*) AS count
+         FROM tasks WHERE user_id = $1
+         GROUP BY 1 ORDER BY count DESC, key"
+    ))
+    .bind(user_id)
+    .fetch_all(conn)
+    .await
+}
@@ -1,155 +1,195 @@
 use crate::{
     auth::AuthUser,
-    error::AppError,
-    models::task::{CreateTask, Task, TaskFilter, UpdateTask},
+    conditional::Validators,
+    db::{routing::DbRouter, view_repo},
+    error::{AppError, HttpError},
+    models::task::{AssigneesRequest, CreateTask, MoveTask, Task, TaskFilter, UpdateTask},
+    services::task_service::{AssigneeChange, TaskService},
+    task_cache::TaskCache,
+    task_query::TaskQuery,
 };
-use anyhow::Error;
 use axum::{
-    extract::{Path, Query, State},
+    body::Bytes,
+    extract::{Path, Query, RawQuery, State},
+    http::HeaderMap,
+    response::Response,
     Json,
 };
+use chrono::{DateTime, Utc};
 use serde_json::json;
-use sqlx::{PgPool, QueryBuilder};
 use std::sync::Arc;
 
+/// 列表的 ETag 由用户的任务版本号和请求参数决定，版本号在任务变化时由触发器递增
 pub async fn get_tasks(
-    State(pool): State<Arc<PgPool>>,
-    // 移除 AuthUser 暂时
+    auth_user: AuthUser,
+    State(tasks): State<TaskService>,
+    State(db): State<Arc<DbRouter>>,
+    State(cache): State<Arc<TaskCache>>,
+    RawQuery(raw_query): RawQuery,
+    headers: HeaderMap,
     Query(filter): Query<TaskFilter>,
-) -> Result<Json<Vec<Task>>, AppError> {
-    let mut query = sqlx::QueryBuilder::new("SELECT * FROM tasks WHERE user_id = $1");
+) -> Result<Response, AppError> {
+    let cache_key = format!("list:{}", raw_query.as_deref().unwrap_or_default());
+    if let Some((validators, body)) = cache.get(auth_user.user_id, &cache_key) {
+        return Ok(validators.respond(&headers, body));
+    }
+
+    // 和列表读同一个库，版本号不会比列表新
+    let mut tx = db.begin_read(&auth_user).await?;
+    let (version, mut last_modified): (i64, Option<DateTime<Utc>>) = sqlx::query_as(
+        "SELECT COALESCE(MAX(version), 0), MAX(updated_at)
+         FROM task_collection_versions WHERE user_id = $1",
+    )
+    .bind(auth_user.user_id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    let mut etag_parts = vec![version.to_string(), cache_key.clone()];
+    let mut has_date_condition = false;
 
-    if let Some(category) = filter.category {
-        query.push(" AND category = ");
-        query.push_bind(category);
+    if let Some(view_id) = filter.view {
+        let view = view_repo::find_visible(&mut *tx, auth_user.user_id, view_id)
+            .await?
+            .ok_or_else(|| HttpError::not_found("View not found"))?;
+        has_date_condition |= TaskQuery::parse(&view.query)?.has_date_condition();
+        etag_parts.push(view.updated_at.to_rfc3339());
+        last_modified = last_modified.max(Some(view.updated_at));
     }
+    tx.commit().await?;
 
-    if let Some(priority) = filter.priority {
-        query.push(" AND priority = ");
-        query.push_bind(priority);
+    if let Some(q) = &filter.q {
+        has_date_condition |= TaskQuery::parse(q)?.has_date_condition();
     }
 
-    if let Some(status) = filter.status {
-        query.push(" AND status = ");
-        query.push_bind(status);
+    // 日期条件的结果随时间变化，最多复用一分钟。任务没有修改结果也会变，
+    // 这时不发送 Last-Modified，否则 If-Modified-Since 会一直得到 304
+    if has_date_condition {
+        etag_parts.push((Utc::now().timestamp() / 60).to_string());
+        last_modified = None;
     }
 
-    let tasks = query
-        .build_query_as::<Task>()
-        .bind(1)
-        // 临时使用固定用户ID 1
-        .fetch_all(pool.as_ref())
-        .await
-        .map_err(|e| AppError::from(e))?;
+    let validators = Validators::new(&etag_parts, last_modified);
+    if validators.is_fresh(&headers) {
+        return Ok(validators.respond(&headers, Bytes::new()));
+    }
+
+    let tasks = tasks.list_tasks(&auth_user, &filter).await?;
+
+    let body = Bytes::from(serde_json::to_vec(&tasks)?);
+    cache.insert(
+        auth_user.user_id,
+        cache_key,
+        validators.clone(),
+        body.clone(),
+    );
 
-    Ok(Json(tasks))
+    Ok(validators.respond(&headers, body))
 }
 
 pub async fn create_task(
     auth_user: AuthUser,
-    State(pool): State<Arc<PgPool>>,
+    State(tasks): State<TaskService>,
     Json(payload): Json<CreateTask>,
 ) -> Result<Json<Task>, AppError> {
-    let task = sqlx::query_as::<_, Task>(
-        "INSERT INTO tasks (title, description, category, priority, due_date, user_id, status, created_at, updated_at)
-         VALUES ($1, $2, $3, $4, $5, $6, 'pending', NOW(), NOW())
-         RETURNING *"
-    )
-    .bind(&payload.title)
-    .bind(&payload.description)
-    .bind(&payload.category)
-    .bind(payload.priority)
-    .bind(payload.due_date)
-    .bind(auth_user.user_id)
-    .fetch_one(pool.as_ref())
-    .await?;
-
-    Ok(Json(task))
+    Ok(Json(tasks.create_task(&auth_user, &payload).await?))
 }
 
 pub async fn get_task(
     auth_user: AuthUser,
-    State(pool): State<Arc<PgPool>>,
+    State(tasks): State<TaskService>,
+    State(cache): State<Arc<TaskCache>>,
+    headers: HeaderMap,
     Path(task_id): Path<i32>,
-) -> Result<Json<Task>, AppError> {
-    let task = sqlx::query_as::<_, Task>("SELECT * FROM tasks WHERE id = $1 AND user_id = $2")
-        .bind(task_id)
-        .bind(auth_user.user_id)
-        .fetch_one(pool.as_ref())
-        .await?;
+) -> Result<Response, AppError> {
+    let cache_key = format!("task:{task_id}");
+    if let Some((validators, body)) = cache.get(auth_user.user_id, &cache_key) {
+        return Ok(validators.respond(&headers, body));
+    }
 
-    Ok(Json(task))
+    let task = tasks.get_task(&auth_user, task_id).await?;
+
+    let validators = Validators::new(
+        [
+            task.id.to_string(),
+            task.updated_at.timestamp_micros().to_string(),
+        ],
+        Some(task.updated_at),
+    );
+    let body = Bytes::from(serde_json::to_vec(&task)?);
+    cache.insert(
+        auth_user.user_id,
+        cache_key,
+        validators.clone(),
+        body.clone(),
+    );
+
+    Ok(validators.respond(&headers, body))
 }
 
 pub async fn update_task(
     auth_user: AuthUser,
-    State(pool): State<Arc<PgPool>>,
+    State(tasks): State<TaskService>,
     Path(task_id): Path<i32>,
     Json(payload): Json<UpdateTask>,
 ) -> Result<Json<Task>, AppError> {
-    let mut query = QueryBuilder::new("UPDATE tasks SET updated_at = NOW()");
-
-    if let Some(title) = payload.title {
-        query.push(", title = ");
-        query.push_bind(title);
-    }
-
-    if let Some(description) = payload.description {
-        query.push(", description = ");
-        query.push_bind(description);
-    }
-
-    if let Some(category) = payload.category {
-        query.push(", category = ");
-        query.push_bind(category);
-    }
-
-    if let Some(priority) = payload.priority {
-        query.push(", priority = ");
-        query.push_bind(priority);
-    }
-
-    if let Some(status) = payload.status {
-        query.push(", status = ");
-        query.push_bind(status);
-    }
-
-    if let Some(due_date) = payload.due_date {
-        query.push(", due_date = ");
-        query.push_bind(due_date);
-    }
-
-    query.push(" WHERE id = ");
-    query.push_bind(task_id);
-    query.push(" AND user_id = ");
-    query.push_bind(auth_user.user_id);
-    query.push(" RETURNING *");
-
-    let task = query
-        .build_query_as::<Task>()
-        .fetch_one(pool.as_ref())
-        // ✅ 使用 pool.as_ref()
-        .await?;
+    Ok(Json(tasks.update_task(&auth_user, task_id, payload).await?))
+}
 
-    Ok(Json(task))
+/// 看板拖动：同时修改列和位置
+pub async fn move_task(
+    auth_user: AuthUser,
+    State(tasks): State<TaskService>,
+    Path(task_id): Path<i32>,
+    Json(payload): Json<MoveTask>,
+) -> Result<Json<Task>, AppError> {
+    Ok(Json(tasks.move_task(&auth_user, task_id, &payload).await?))
 }
 
 pub async fn delete_task(
     auth_user: AuthUser,
-    State(pool): State<Arc<PgPool>>,
+    State(tasks): State<TaskService>,
     Path(task_id): Path<i32>,
 ) -> Result<Json<serde_json::Value>, AppError> {
-    let result = sqlx::query("DELETE FROM tasks WHERE id = $1 AND user_id = $2")
-        .bind(task_id)
-        .bind(auth_user.user_id)
-        .execute(pool.as_ref())
-        .await?;
-
-    if result.rows_affected() == 0 {
-        return Err(AppError(Error::msg("Task not found")));
-    }
+    tasks.delete_task(&auth_user, task_id).await?;
 
     Ok(Json(json!({
         "message": "Task deleted successfully"
     })))
 }
+
+/// 追加负责人，已经是负责人的忽略
+pub async fn add_assignees(
+    auth_user: AuthUser,
+    State(tasks): State<TaskService>,
+    Path(task_id): Path<i32>,
+    Json(payload): Json<AssigneesRequest>,
+) -> Result<Json<Task>, AppError> {
+    let change = AssigneeChange::Add(payload.user_ids);
+    Ok(Json(
+        tasks.change_assignees(&auth_user, task_id, change).await?,
+    ))
+}
+
+/// 用新的列表替换所有负责人，用于转交任务
+pub async fn set_assignees(
+    auth_user: AuthUser,
+    State(tasks): State<TaskService>,
+    Path(task_id): Path<i32>,
+    Json(payload): Json<AssigneesRequest>,
+) -> Result<Json<Task>, AppError> {
+    let change = AssigneeChange::Set(payload.user_ids);
+    Ok(Json(
+        tasks.change_assignees(&auth_user, task_id, change).await?,
+    ))
+}
+
+pub async fn remove_assignee(
+    auth_user: AuthUser,
+    State(tasks): State<TaskService>,
+    Path((task_id, user_id)): Path<(i32, i32)>,
+) -> Result<Json<Task>, AppError> {
+    let change = AssigneeChange::Remove(user_id);
+    Ok(Json(
+        tasks.change_assignees(&auth_user, task_id, change).await?,
+    ))
+}
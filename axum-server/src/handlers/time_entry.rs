@@ -0,0 +1,149 @@
+use crate::{
+    auth::AuthUser,
+    error::AppError,
+    models::time_entry::{
+        CreateTimeEntry, StartTimer, TimeEntry, TimesheetEntry, TimesheetFormat, TimesheetParams,
+    },
+    services::task_service::TaskService,
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Serialize;
+use serde_json::json;
+
+pub async fn get_timer(
+    auth_user: AuthUser,
+    State(tasks): State<TaskService>,
+) -> Result<Json<Option<TimeEntry>>, AppError> {
+    Ok(Json(tasks.running_timer(&auth_user).await?))
+}
+
+/// 请求体可以省略
+pub async fn start_timer(
+    auth_user: AuthUser,
+    State(tasks): State<TaskService>,
+    Path(task_id): Path<i32>,
+    payload: Option<Json<StartTimer>>,
+) -> Result<Json<TimeEntry>, AppError> {
+    let Json(payload) = payload.unwrap_or_default();
+    Ok(Json(
+        tasks.start_timer(&auth_user, task_id, &payload).await?,
+    ))
+}
+
+pub async fn stop_timer(
+    auth_user: AuthUser,
+    State(tasks): State<TaskService>,
+) -> Result<Json<TimeEntry>, AppError> {
+    Ok(Json(tasks.stop_timer(&auth_user).await?))
+}
+
+pub async fn get_time_entries(
+    auth_user: AuthUser,
+    State(tasks): State<TaskService>,
+    Path(task_id): Path<i32>,
+) -> Result<Json<Vec<TimeEntry>>, AppError> {
+    Ok(Json(tasks.list_time_entries(&auth_user, task_id).await?))
+}
+
+pub async fn create_time_entry(
+    auth_user: AuthUser,
+    State(tasks): State<TaskService>,
+    Path(task_id): Path<i32>,
+    Json(payload): Json<CreateTimeEntry>,
+) -> Result<Json<TimeEntry>, AppError> {
+    Ok(Json(
+        tasks.add_time_entry(&auth_user, task_id, &payload).await?,
+    ))
+}
+
+pub async fn delete_time_entry(
+    auth_user: AuthUser,
+    State(tasks): State<TaskService>,
+    Path(entry_id): Path<i64>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    tasks.delete_time_entry(&auth_user, entry_id).await?;
+    Ok(Json(json!({
+        "message": "Time entry deleted successfully"
+    })))
+}
+
+/// `format=csv` 时每条记录一行，用于导入计费系统
+pub async fn get_timesheet(
+    auth_user: AuthUser,
+    State(tasks): State<TaskService>,
+    Query(params): Query<TimesheetParams>,
+) -> Result<Response, AppError> {
+    let timesheet = tasks.timesheet(&auth_user, &params).await?;
+    if let TimesheetFormat::Json = params.format {
+        return Ok(Json(timesheet).into_response());
+    }
+
+    // 没有记录时也输出表头
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(Vec::new());
+    writer.write_record(CSV_HEADER)?;
+    for entry in &timesheet.entries {
+        writer.serialize(CsvEntry::from(entry))?;
+    }
+    let body = writer.into_inner().map_err(|e| e.into_error())?;
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_owned()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!(
+                    "attachment; filename=\"timesheet-{}-{}-{}.csv\"",
+                    timesheet.user_id, timesheet.from, timesheet.to
+                ),
+            ),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+const CSV_HEADER: [&str; 8] = [
+    "date",
+    "task_id",
+    "task_title",
+    "started_at",
+    "ended_at",
+    "duration_seconds",
+    "hours",
+    "note",
+];
+
+#[derive(Serialize)]
+struct CsvEntry<'a> {
+    date: NaiveDate,
+    task_id: Option<i32>,
+    task_title: Option<&'a str>,
+    started_at: DateTime<Utc>,
+    ended_at: DateTime<Utc>,
+    duration_seconds: i64,
+    /// 两位小数，计费系统一般按小时导入
+    hours: String,
+    note: &'a str,
+}
+
+impl<'a> From<&'a TimesheetEntry> for CsvEntry<'a> {
+    fn from(entry: &'a TimesheetEntry) -> Self {
+        Self {
+            date: entry.date,
+            task_id: entry.task_id,
+            task_title: entry.task_title.as_deref(),
+            started_at: entry.started_at,
+            ended_at: entry.ended_at,
+            duration_seconds: entry.duration_seconds,
+            hours: format!("{:.2}", entry.duration_seconds as f64 / 3600.0),
+            note: &entry.note,
+        }
+    }
+}
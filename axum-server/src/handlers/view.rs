@@ -0,0 +1,55 @@
+use crate::{
+    auth::AuthUser,
+    error::AppError,
+    models::view::{CreateView, SavedView, UpdateView},
+    services::task_service::TaskService,
+};
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use serde_json::json;
+
+pub async fn get_views(
+    auth_user: AuthUser,
+    State(tasks): State<TaskService>,
+) -> Result<Json<Vec<SavedView>>, AppError> {
+    Ok(Json(tasks.list_views(&auth_user).await?))
+}
+
+pub async fn get_view(
+    auth_user: AuthUser,
+    State(tasks): State<TaskService>,
+    Path(view_id): Path<i32>,
+) -> Result<Json<SavedView>, AppError> {
+    Ok(Json(tasks.get_view(&auth_user, view_id).await?))
+}
+
+pub async fn create_view(
+    auth_user: AuthUser,
+    State(tasks): State<TaskService>,
+    Json(payload): Json<CreateView>,
+) -> Result<Json<SavedView>, AppError> {
+    Ok(Json(tasks.create_view(&auth_user, &payload).await?))
+}
+
+pub async fn update_view(
+    auth_user: AuthUser,
+    State(tasks): State<TaskService>,
+    Path(view_id): Path<i32>,
+    Json(payload): Json<UpdateView>,
+) -> Result<Json<SavedView>, AppError> {
+    Ok(Json(tasks.update_view(&auth_user, view_id, payload).await?))
+}
+
+pub async fn delete_view(
+    auth_user: AuthUser,
+    State(tasks): State<TaskService>,
+    Path(view_id): Path<i32>,
+) -> Result<Json<serde_json::Value>, AppError> {
+    tasks.delete_view(&auth_user, view_id).await?;
+
+    Ok(Json(json!({
+        "message": "View deleted successfully"
+    })))
+}
@@ -0,0 +1,157 @@
+use chrono::{DateTime, Utc};
+
+use crate::models::task::{Task, MAX_PRIORITY, MIN_PRIORITY};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Component {
+    Todo,
+    Event,
+}
+
+/// 按 RFC 5545 生成日历，每个任务一个 VTODO 或 VEVENT
+pub fn render_calendar(tasks: &[Task], component: Component) -> String {
+    let mut out = String::new();
+    line(&mut out, "BEGIN:VCALENDAR");
+    line(&mut out, "VERSION:2.0");
+    line(&mut out, "PRODID:-//axum-server//tasks//EN");
+    line(&mut out, "CALSCALE:GREGORIAN");
+    line(&mut out, "X-WR-CALNAME:Tasks");
+
+    for task in tasks {
+        let name = match component {
+            Component::Todo => "VTODO",
+            Component::Event => "VEVENT",
+        };
+        line(&mut out, &format!("BEGIN:{name}"));
+        line(&mut out, &format!("UID:task-{}@axum-server", task.id));
+        line(&mut out, &format!("DTSTAMP:{}", timestamp(task.updated_at)));
+        line(&mut out, &format!("CREATED:{}", timestamp(task.created_at)));
+        line(
+            &mut out,
+            &format!("LAST-MODIFIED:{}", timestamp(task.updated_at)),
+        );
+        line(&mut out, &format!("SUMMARY:{}", escape(&task.title)));
+        if !task.description.is_empty() {
+            line(
+                &mut out,
+                &format!("DESCRIPTION:{}", escape(&task.description)),
+            );
+        }
+        let categories: Vec<String> = std::iter::once(&task.category)
+            .chain(&task.tags)
+            .map(|category| escape(category))
+            .collect();
+        line(&mut out, &format!("CATEGORIES:{}", categories.join(",")));
+        line(&mut out, &format!("PRIORITY:{}", priority(task.priority)));
+
+        match component {
+            Component::Todo => {
+                line(&mut out, &format!("DUE:{}", timestamp(task.due_date)));
+                line(&mut out, &format!("STATUS:{}", todo_status(&task.status)));
+                if task.status == "completed" {
+                    line(
+                        &mut out,
+                        &format!("COMPLETED:{}", timestamp(task.updated_at)),
+                    );
+                    line(&mut out, "PERCENT-COMPLETE:100");
+                }
+            }
+            Component::Event => {
+                line(&mut out, &format!("DTSTART:{}", timestamp(task.due_date)));
+                line(&mut out, "STATUS:CONFIRMED");
+                line(&mut out, "TRANSP:TRANSPARENT");
+            }
+        }
+
+        if let Some(rule) = task.recurrence.as_deref().and_then(rrule) {
+            line(&mut out, &format!("RRULE:{rule}"));
+        }
+        line(&mut out, &format!("END:{name}"));
+    }
+
+    line(&mut out, "END:VCALENDAR");
+    out
+}
+
+fn timestamp(value: DateTime<Utc>) -> String {
+    value.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+fn todo_status(status: &str) -> &'static str {
+    match status {
+        "in_progress" => "IN-PROCESS",
+        "completed" => "COMPLETED",
+        _ => "NEEDS-ACTION",
+    }
+}
+
+/// iCal 的 PRIORITY 是 1 (最高) 到 9 (最低)，任务的 priority 越大越紧急
+fn priority(value: i32) -> i32 {
+    let value = value.clamp(MIN_PRIORITY, MAX_PRIORITY);
+    let span = (MAX_PRIORITY - MIN_PRIORITY).max(1);
+    1 + (MAX_PRIORITY - value) * 8 / span
+}
+
+fn rrule(recurrence: &str) -> Option<&'static str> {
+    match recurrence {
+        "daily" => Some("FREQ=DAILY"),
+        "weekly" => Some("FREQ=WEEKLY"),
+        "monthly" => Some("FREQ=MONTHLY"),
+        "yearly" => Some("FREQ=YEARLY"),
+        _ => None,
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            ';' => out.push_str("\\;"),
+            ',' => out.push_str("\\,"),
+            '\n' => out.push_str("\\n"),
+            '\r' => {}
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// 每行最多 75 个字节，超出部分折行并以空格开头，不拆开 UTF-8 字符
+fn line(out: &mut String, content: &str) {
+    let mut width = 0;
+    for c in content.chars() {
+        if width + c.len_utf8() > 75 {
+            out.push_str("\r\n ");
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out.push_str("\r\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_text_values() {
+        assert_eq!(escape("a,b;c\\d\ne"), "a\\,b\\;c\\\\d\\ne");
+    }
+
+    #[test]
+    fn folds_long_lines() {
+        let mut out = String::new();
+        line(&mut out, &format!("SUMMARY:{}", "é".repeat(60)));
+        for physical in out.split("\r\n").filter(|l| !l.is_empty()) {
+            assert!(physical.len() <= 75, "{physical}");
+        }
+    }
+
+    #[test]
+    fn maps_priority_to_ical_scale() {
+        assert_eq!(priority(MAX_PRIORITY), 1);
+        assert_eq!(priority(MIN_PRIORITY), 9);
+    }
+}
@@ -0,0 +1,272 @@
+use axum::{
+    body::{to_bytes, Body, Bytes},
+    extract::{FromRequest, Request, State},
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use sqlx::{FromRow, PgPool};
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+use crate::{auth::AuthUser, error::AppError, state::AppState};
+
+pub const IDEMPOTENCY_KEY: &str = "idempotency-key";
+const MAX_KEY_LEN: usize = 255;
+const MAX_RESPONSE_BYTES: usize = 10 * 1024 * 1024;
+
+#[derive(FromRow)]
+struct StoredResponse {
+    request_hash: String,
+    status_code: Option<i16>,
+    content_type: Option<String>,
+    response_body: Option<Vec<u8>>,
+}
+
+/// 支持 `Idempotency-Key` 请求头：同一用户同一个 key 的重试直接返回第一次的响应。
+/// 处理中的 key 最多占用 `REQUEST_TIMEOUT_SECS`，之后的重试可以重新占用
+pub async fn idempotency(
+    State(state): State<AppState>,
+    auth_user: Option<AuthUser>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let key = match request.headers().get(IDEMPOTENCY_KEY) {
+        Some(value) => value.to_str().unwrap_or_default().to_owned(),
+        None => return Ok(next.run(request).await),
+    };
+    // 未登录的请求交给 handler 自己拒绝
+    let Some(auth_user) = auth_user else {
+        return Ok(next.run(request).await);
+    };
+    if key.is_empty() || key.len() > MAX_KEY_LEN {
+        return Ok(error_response(
+            StatusCode::BAD_REQUEST,
+            "Idempotency-Key must be 1-255 visible characters",
+        ));
+    }
+
+    // 按路由自己的 DefaultBodyLimit 读取，超过时返回 413
+    let (parts, body) = request.into_parts();
+    let body = match Bytes::from_request(Request::from_parts(parts.clone(), body), &state).await {
+        Ok(body) => body,
+        Err(rejection) => return Ok(rejection.into_response()),
+    };
+    let request_hash = {
+        let mut hasher = Sha256::new();
+        hasher.update(parts.method.as_str());
+        hasher.update(parts.uri.path());
+        // 查询参数也决定了请求的含义，例如导入的 `dry_run`
+        hasher.update(b"?");
+        hasher.update(parts.uri.query().unwrap_or_default());
+        hasher.update(b"\n");
+        hasher.update(&body);
+        hex::encode(hasher.finalize())
+    };
+
+    let pool = state.pool.as_ref();
+    let ttl = state.config.idempotency_ttl;
+    let lock = state.config.http.request_timeout;
+
+    let Some(claimed_at) = claim(pool, auth_user.user_id, &key, &request_hash, ttl, lock).await?
+    else {
+        let stored = sqlx::query_as::<_, StoredResponse>(
+            "SELECT request_hash, status_code, content_type, response_body
+             FROM idempotency_keys WHERE user_id = $1 AND key = $2",
+        )
+        .bind(auth_user.user_id)
+        .bind(&key)
+        .fetch_one(pool)
+        .await?;
+
+        return Ok(replay(stored, &request_hash));
+    };
+    let mut claim = Claim {
+        pool: pool.clone(),
+        user_id: auth_user.user_id,
+        key,
+        claimed_at,
+        stored: false,
+    };
+
+    let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+
+    // 5xx 不缓存，让客户端可以用同一个 key 重试
+    if response.status().is_server_error() {
+        claim.release().await?;
+        return Ok(response);
+    }
+
+    let (parts, body) = response.into_parts();
+    let body = to_bytes(body, MAX_RESPONSE_BYTES).await?;
+    let content_type = parts
+        .headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok());
+
+    // 占用已经超时、被别的请求重新占用时不覆盖它
+    sqlx::query(
+        "UPDATE idempotency_keys SET status_code = $4, content_type = $5, response_body = $6
+         WHERE user_id = $1 AND key = $2 AND created_at = $3",
+    )
+    .bind(claim.user_id)
+    .bind(&claim.key)
+    .bind(claim.claimed_at)
+    .bind(parts.status.as_u16() as i16)
+    .bind(content_type)
+    .bind(body.as_ref())
+    .execute(pool)
+    .await?;
+    claim.stored = true;
+
+    Ok(Response::from_parts(parts, Body::from(body)))
+}
+
+/// 抢占 key，返回这次占用的时间。已过期的记录、以及处理中但超过 `lock` 的记录
+/// 可以被重新占用；返回 None 表示 key 正在被使用或已经有响应
+async fn claim(
+    pool: &PgPool,
+    user_id: i32,
+    key: &str,
+    request_hash: &str,
+    ttl: Duration,
+    lock: Duration,
+) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+    sqlx::query_scalar(
+        "INSERT INTO idempotency_keys (user_id, key, request_hash, expires_at, locked_until)
+         VALUES ($1, $2, $3, NOW() + make_interval(secs => $4), NOW() + make_interval(secs => $5))
+         ON CONFLICT (user_id, key) DO UPDATE SET
+             request_hash = EXCLUDED.request_hash,
+             status_code = NULL,
+             content_type = NULL,
+             response_body = NULL,
+             created_at = NOW(),
+             expires_at = EXCLUDED.expires_at,
+             locked_until = EXCLUDED.locked_until
+         WHERE idempotency_keys.expires_at < NOW()
+            OR (idempotency_keys.status_code IS NULL
+                AND COALESCE(idempotency_keys.locked_until, idempotency_keys.created_at) < NOW())
+         RETURNING created_at",
+    )
+    .bind(user_id)
+    .bind(key)
+    .bind(request_hash)
+    .bind(ttl.as_secs_f64())
+    .bind(lock.as_secs_f64())
+    .fetch_optional(pool)
+    .await
+}
+
+/// 处理中的占用。没有保存响应就被丢弃时（5xx、客户端断开导致 handler 被取消、
+/// panic、保存失败）删除记录，重试不用等 `locked_until`
+struct Claim {
+    pool: PgPool,
+    user_id: i32,
+    key: String,
+    claimed_at: DateTime<Utc>,
+    stored: bool,
+}
+
+impl Claim {
+    async fn release(&mut self) -> Result<(), sqlx::Error> {
+        release(&self.pool, self.user_id, &self.key, self.claimed_at).await?;
+        self.stored = true;
+        Ok(())
+    }
+}
+
+impl Drop for Claim {
+    fn drop(&mut self) {
+        if self.stored {
+            return;
+        }
+        // drop 里不能等待，交给运行时
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let (pool, user_id, key, claimed_at) = (
+            self.pool.clone(),
+            self.user_id,
+            std::mem::take(&mut self.key),
+            self.claimed_at,
+        );
+        runtime.spawn(async move {
+            if let Err(e) = release(&pool, user_id, &key, claimed_at).await {
+                eprintln!("failed to release idempotency key: {e}");
+            }
+        });
+    }
+}
+
+async fn release(
+    pool: &PgPool,
+    user_id: i32,
+    key: &str,
+    claimed_at: DateTime<Utc>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "DELETE FROM idempotency_keys
+         WHERE user_id = $1 AND key = $2 AND created_at = $3 AND status_code IS NULL",
+    )
+    .bind(user_id)
+    .bind(key)
+    .bind(claimed_at)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+fn replay(stored: StoredResponse, request_hash: &str) -> Response {
+    if stored.request_hash != request_hash {
+        return error_response(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "Idempotency-Key was already used with a different request",
+        );
+    }
+
+    let Some(status) = stored
+        .status_code
+        .and_then(|code| StatusCode::from_u16(code as u16).ok())
+    else {
+        return error_response(
+            StatusCode::CONFLICT,
+            "a request with this Idempotency-Key is still being processed",
+        );
+    };
+
+    let mut response = (status, stored.response_body.unwrap_or_default()).into_response();
+    let headers = response.headers_mut();
+    if let Some(content_type) = stored
+        .content_type
+        .and_then(|value| HeaderValue::from_str(&value).ok())
+    {
+        headers.insert(header::CONTENT_TYPE, content_type);
+    }
+    headers.insert("idempotent-replayed", HeaderValue::from_static("true"));
+    response
+}
+
+fn error_response(status: StatusCode, message: &str) -> Response {
+    (status, Json(json!({ "error": message }))).into_response()
+}
+
+pub async fn purge_expired(pool: PgPool, token: CancellationToken) {
+    let mut interval = tokio::time::interval(Duration::from_secs(300));
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {
+                if let Err(e) = sqlx::query("DELETE FROM idempotency_keys WHERE expires_at < NOW()")
+                    .execute(&pool)
+                    .await
+                {
+                    eprintln!("failed to purge idempotency keys: {e}");
+                }
+            }
+            _ = token.cancelled() => break,
+        }
+    }
+}
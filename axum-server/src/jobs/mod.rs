@@ -0,0 +1,210 @@
+//! Postgres 上的后台任务队列。
+//!
+//! 任务类型实现 [`Job`]，payload 以 JSON 存在 `jobs` 表里；worker 用
+//! `FOR UPDATE SKIP LOCKED` 领取到期的任务，失败后按退避时间重试。
+//! 任务至少执行一次：worker 在执行中途退出时任务会被重新排队，处理逻辑需要能重复执行
+
+mod worker;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use serde::{de::DeserializeOwned, Serialize};
+use sqlx::{PgExecutor, PgPool};
+use std::{collections::HashMap, fmt, str::FromStr, sync::Arc, time::Duration};
+
+use crate::{config::Config, email, rank};
+
+pub use worker::{backoff, reap_stale, run_worker};
+
+pub const DEFAULT_QUEUE: &str = "default";
+
+/// 一种后台任务。结构体本身就是 payload，入队时序列化，执行前反序列化
+#[async_trait]
+pub trait Job: Serialize + DeserializeOwned + Send + Sync + 'static {
+    /// 写进 `jobs.kind`，用来找到处理函数，改名会让已经入队的任务找不到处理函数
+    const KIND: &'static str;
+    const QUEUE: &'static str = DEFAULT_QUEUE;
+    /// 包括第一次在内最多执行几次
+    const MAX_ATTEMPTS: i32 = 5;
+
+    async fn run(self, ctx: &JobContext) -> anyhow::Result<()>;
+
+    /// 第 `attempt` 次失败后等多久再重试
+    fn retry_delay(attempt: i32) -> Duration {
+        backoff(attempt)
+    }
+}
+
+/// 任务执行时可以用到的资源
+#[derive(Clone)]
+pub struct JobContext {
+    pub pool: PgPool,
+    pub config: Arc<Config>,
+    pub job_id: i64,
+    /// 从 1 开始，大于 1 表示之前的执行失败过
+    pub attempt: i32,
+}
+
+type Handler = Arc<
+    dyn Fn(serde_json::Value, JobContext) -> BoxFuture<'static, anyhow::Result<()>> + Send + Sync,
+>;
+
+#[derive(Clone)]
+struct Registered {
+    handler: Handler,
+    retry_delay: fn(i32) -> Duration,
+}
+
+/// 任务类型到处理函数的映射。worker 只领取注册过的类型，
+/// 滚动升级时新类型的任务会留给已经升级的 worker
+#[derive(Clone, Default)]
+pub struct Registry {
+    jobs: HashMap<&'static str, Registered>,
+}
+
+impl fmt::Debug for Registry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Registry")
+            .field("kinds", &self.kinds())
+            .finish()
+    }
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register<J: Job>(mut self) -> Self {
+        let handler: Handler = Arc::new(|payload, ctx| {
+            Box::pin(async move {
+                let job: J = serde_json::from_value(payload)?;
+                job.run(&ctx).await
+            })
+        });
+        self.jobs.insert(
+            J::KIND,
+            Registered {
+                handler,
+                retry_delay: J::retry_delay,
+            },
+        );
+        self
+    }
+
+    pub fn kinds(&self) -> Vec<&'static str> {
+        let mut kinds: Vec<_> = self.jobs.keys().copied().collect();
+        kinds.sort_unstable();
+        kinds
+    }
+
+    fn get(&self, kind: &str) -> Option<&Registered> {
+        self.jobs.get(kind)
+    }
+}
+
+/// 服务进程和 `axum-server worker` 执行的任务类型
+pub fn registry() -> Registry {
+    Registry::new()
+        .register::<email::SendNotification>()
+        .register::<rank::RebalanceRanks>()
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct EnqueueOptions {
+    /// 不设置时立即可以执行
+    pub run_at: Option<DateTime<Utc>>,
+    /// 已经有相同 key 的任务在等待或执行时不再入队
+    pub unique_key: Option<String>,
+    /// 覆盖 `Job::QUEUE`
+    pub queue: Option<String>,
+}
+
+impl EnqueueOptions {
+    pub fn delayed(delay: Duration) -> Self {
+        Self {
+            run_at: Some(Utc::now() + delay),
+            ..Self::default()
+        }
+    }
+}
+
+/// 把任务加入队列，返回任务 id；因为 `unique_key` 重复而没有入队时返回 `None`。
+/// 传入事务时任务和业务数据一起提交，回滚时不会执行
+pub async fn enqueue<'e, J, E>(
+    executor: E,
+    job: &J,
+    options: EnqueueOptions,
+) -> anyhow::Result<Option<i64>>
+where
+    J: Job,
+    E: PgExecutor<'e>,
+{
+    let id = sqlx::query_scalar(
+        "INSERT INTO jobs (queue, kind, payload, max_attempts, run_at, unique_key)
+         VALUES ($1, $2, $3, $4, COALESCE($5, NOW()), $6)
+         ON CONFLICT (unique_key) WHERE status IN ('pending', 'running') DO NOTHING
+         RETURNING id",
+    )
+    .bind(options.queue.as_deref().unwrap_or(J::QUEUE))
+    .bind(J::KIND)
+    .bind(serde_json::to_value(job)?)
+    .bind(J::MAX_ATTEMPTS)
+    .bind(options.run_at)
+    .bind(options.unique_key)
+    .fetch_optional(executor)
+    .await?;
+    Ok(id)
+}
+
+/// 每个队列在一个进程里最多同时执行几个任务，格式为 `default=4,email=2`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueueLimits(pub Vec<(String, usize)>);
+
+impl FromStr for QueueLimits {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut queues = Vec::new();
+        for entry in s
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+        {
+            let (queue, limit) = entry
+                .split_once('=')
+                .ok_or_else(|| anyhow::anyhow!("expected <queue>=<concurrency>, got {entry}"))?;
+            let limit: usize = limit.trim().parse()?;
+            if limit == 0 {
+                anyhow::bail!("concurrency must be non-zero: {entry}");
+            }
+            queues.push((queue.trim().to_owned(), limit));
+        }
+        Ok(Self(queues))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_queue_limits() {
+        let limits: QueueLimits = "default=4, email = 2,".parse().unwrap();
+        assert_eq!(
+            limits.0,
+            [("default".to_owned(), 4), ("email".to_owned(), 2)]
+        );
+        assert!("default".parse::<QueueLimits>().is_err());
+        assert!("default=0".parse::<QueueLimits>().is_err());
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_an_hour() {
+        assert_eq!(backoff(1), Duration::from_secs(10));
+        assert_eq!(backoff(2), Duration::from_secs(20));
+        assert_eq!(backoff(4), Duration::from_secs(80));
+        assert_eq!(backoff(30), Duration::from_secs(3600));
+    }
+}
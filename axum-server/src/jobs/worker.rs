@@ -0,0 +1,227 @@
+use sqlx::{FromRow, PgPool};
+use std::{sync::Arc, time::Duration};
+use tokio::sync::Semaphore;
+use tokio_util::{sync::CancellationToken, task::TaskTracker};
+
+use super::{JobContext, Registry};
+use crate::{config::Config, token};
+
+const MAX_BACKOFF: Duration = Duration::from_secs(3600);
+
+/// 默认的重试间隔：10 秒起每次翻倍，最长一小时
+pub fn backoff(attempt: i32) -> Duration {
+    let exponent = attempt.clamp(1, 16) as u32 - 1;
+    (Duration::from_secs(10) * 2u32.pow(exponent)).min(MAX_BACKOFF)
+}
+
+#[derive(FromRow)]
+struct ClaimedJob {
+    id: i64,
+    kind: String,
+    payload: serde_json::Value,
+    attempts: i32,
+}
+
+struct Worker {
+    pool: PgPool,
+    config: Arc<Config>,
+    registry: Registry,
+    kinds: Vec<String>,
+    /// 写进 `jobs.locked_by`，区分同一个任务被重新排队前后的执行者
+    id: String,
+}
+
+/// 按 `config.jobs` 消费各个队列，同时定期回收卡住的任务，`token` 取消后
+/// 不再领取新任务，等正在执行的任务结束后返回
+pub async fn run_worker(
+    pool: PgPool,
+    config: Arc<Config>,
+    registry: Registry,
+    token: CancellationToken,
+) {
+    let worker = Arc::new(Worker {
+        kinds: registry.kinds().into_iter().map(str::to_owned).collect(),
+        pool,
+        config,
+        registry,
+        id: format!("{}-{}", std::process::id(), &token::generate()[..8]),
+    });
+    if worker.kinds.is_empty() {
+        return;
+    }
+
+    let tracker = TaskTracker::new();
+    for (queue, concurrency) in worker.config.jobs.queues.0.clone() {
+        tracker.spawn(run_queue(worker.clone(), queue, concurrency, token.clone()));
+    }
+    tracker.spawn(reap_periodically(worker.clone(), token));
+    tracker.close();
+    tracker.wait().await;
+}
+
+async fn run_queue(
+    worker: Arc<Worker>,
+    queue: String,
+    concurrency: usize,
+    token: CancellationToken,
+) {
+    let slots = Arc::new(Semaphore::new(concurrency));
+    let running = TaskTracker::new();
+    loop {
+        let permit = tokio::select! {
+            _ = token.cancelled() => break,
+            permit = slots.clone().acquire_owned() => permit.unwrap(),
+        };
+        match claim(&worker, &queue).await {
+            Ok(Some(job)) => {
+                let worker = worker.clone();
+                running.spawn(async move {
+                    execute(&worker, job).await;
+                    drop(permit);
+                });
+                continue;
+            }
+            Ok(None) => {}
+            Err(e) => eprintln!("failed to claim a job from queue {queue}: {e}"),
+        }
+        drop(permit);
+        tokio::select! {
+            _ = token.cancelled() => break,
+            _ = tokio::time::sleep(worker.config.jobs.poll_interval) => {}
+        }
+    }
+    running.close();
+    running.wait().await;
+}
+
+/// 跳过被其它 worker 锁住的行，多个进程同时领取时不会互相等待
+async fn claim(worker: &Worker, queue: &str) -> Result<Option<ClaimedJob>, sqlx::Error> {
+    sqlx::query_as(
+        "UPDATE jobs
+         SET status = 'running', attempts = attempts + 1, locked_by = $3, locked_at = NOW()
+         WHERE id = (
+             SELECT id FROM jobs
+             WHERE queue = $1 AND status = 'pending' AND run_at <= NOW() AND kind = ANY($2)
+             ORDER BY run_at, id
+             LIMIT 1
+             FOR UPDATE SKIP LOCKED
+         )
+         RETURNING id, kind, payload, attempts",
+    )
+    .bind(queue)
+    .bind(&worker.kinds)
+    .bind(&worker.id)
+    .fetch_optional(&worker.pool)
+    .await
+}
+
+async fn execute(worker: &Worker, job: ClaimedJob) {
+    let Some(registered) = worker.registry.get(&job.kind).cloned() else {
+        return;
+    };
+    let ctx = JobContext {
+        pool: worker.pool.clone(),
+        config: worker.config.clone(),
+        job_id: job.id,
+        attempt: job.attempts,
+    };
+    // 放到单独的 task 里执行，panic 也按失败处理
+    let mut handle = tokio::spawn((registered.handler)(job.payload, ctx));
+    let mut heartbeat = tokio::time::interval(worker.config.jobs.stale_after / 3);
+    heartbeat.tick().await;
+    let result = loop {
+        tokio::select! {
+            joined = &mut handle => {
+                break joined.unwrap_or_else(|e| Err(anyhow::anyhow!("job panicked: {e}")));
+            }
+            _ = heartbeat.tick() => {
+                if let Err(e) = sqlx::query(
+                    "UPDATE jobs SET locked_at = NOW() WHERE id = $1 AND locked_by = $2",
+                )
+                .bind(job.id)
+                .bind(&worker.id)
+                .execute(&worker.pool)
+                .await
+                {
+                    eprintln!("failed to refresh lock of job {}: {e}", job.id);
+                }
+            }
+        }
+    };
+
+    // 只更新自己持有的任务：已经被回收并重新排队的任务交给新的执行者
+    let finished = match result {
+        Ok(()) => {
+            sqlx::query(
+                "UPDATE jobs
+                 SET status = 'completed', finished_at = NOW(), last_error = NULL,
+                     locked_by = NULL, locked_at = NULL
+                 WHERE id = $1 AND locked_by = $2",
+            )
+            .bind(job.id)
+            .bind(&worker.id)
+            .execute(&worker.pool)
+            .await
+        }
+        Err(e) => {
+            sqlx::query(
+                "UPDATE jobs
+                 SET status = CASE WHEN attempts >= max_attempts THEN 'failed' ELSE 'pending' END,
+                     finished_at = CASE WHEN attempts >= max_attempts THEN NOW() END,
+                     run_at = NOW() + make_interval(secs => $3),
+                     last_error = $4, locked_by = NULL, locked_at = NULL
+                 WHERE id = $1 AND locked_by = $2",
+            )
+            .bind(job.id)
+            .bind(&worker.id)
+            .bind((registered.retry_delay)(job.attempts).as_secs_f64())
+            .bind(format!("{e:#}"))
+            .execute(&worker.pool)
+            .await
+        }
+    };
+    if let Err(e) = finished {
+        eprintln!("failed to record the result of job {}: {e}", job.id);
+    }
+}
+
+/// 执行中但超过 `stale_after` 没有心跳的任务说明 worker 已经退出，
+/// 还有重试次数的重新排队，否则标记为失败。返回处理的任务数
+pub async fn reap_stale(pool: &PgPool, stale_after: Duration) -> Result<u64, sqlx::Error> {
+    let reaped = sqlx::query(
+        "UPDATE jobs
+         SET status = CASE WHEN attempts >= max_attempts THEN 'failed' ELSE 'pending' END,
+             finished_at = CASE WHEN attempts >= max_attempts THEN NOW() END,
+             run_at = NOW(), last_error = 'worker stopped responding',
+             locked_by = NULL, locked_at = NULL
+         WHERE status = 'running' AND locked_at < NOW() - make_interval(secs => $1)",
+    )
+    .bind(stale_after.as_secs_f64())
+    .execute(pool)
+    .await?;
+    Ok(reaped.rows_affected())
+}
+
+async fn reap_periodically(worker: Arc<Worker>, token: CancellationToken) {
+    let jobs = &worker.config.jobs;
+    let mut ticker = tokio::time::interval(jobs.stale_after / 2);
+    loop {
+        tokio::select! {
+            _ = token.cancelled() => return,
+            _ = ticker.tick() => {}
+        }
+        match reap_stale(&worker.pool, jobs.stale_after).await {
+            Ok(0) => {}
+            Ok(n) => eprintln!("requeued {n} jobs whose worker stopped responding"),
+            Err(e) => eprintln!("failed to reap stale jobs: {e}"),
+        }
+        if let Err(e) =
+            sqlx::query("DELETE FROM jobs WHERE finished_at < NOW() - make_interval(secs => $1)")
+                .bind(jobs.retention.as_secs_f64())
+                .execute(&worker.pool)
+                .await
+        {
+            eprintln!("failed to purge finished jobs: {e}");
+        }
+    }
+}
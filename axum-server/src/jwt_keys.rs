@@ -0,0 +1,188 @@
+use arc_swap::ArcSwap;
+use chrono::{DateTime, Utc};
+use sqlx::{FromRow, PgPool};
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio_util::sync::CancellationToken;
+
+use crate::token;
+
+/// 收到未知 kid 时最多这么频繁地重新查数据库，防止伪造的 token 放大查询
+const MIN_FORCED_RELOAD_INTERVAL: Duration = Duration::from_secs(5);
+
+/// access token 的签名密钥。`id` 为空表示来自 `JWT_SECRET`
+#[derive(Clone, FromRow)]
+pub struct SigningKey {
+    id: Option<i32>,
+    secret: String,
+    expires_at: Option<DateTime<Utc>>,
+}
+
+impl SigningKey {
+    /// 写进 JWT 头的 `kid`
+    pub fn kid(&self) -> Option<String> {
+        self.id.map(|id| id.to_string())
+    }
+
+    pub fn secret(&self) -> &[u8] {
+        self.secret.as_bytes()
+    }
+
+    fn is_live(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.is_none_or(|expires_at| expires_at > now)
+    }
+}
+
+/// 密钥环：`jwt_signing_keys` 里有未过期的密钥时用它们，否则退回 `JWT_SECRET`。
+/// 每个实例定期重新加载，轮换不需要重启
+#[derive(Clone)]
+pub struct KeyRing {
+    fallback: SigningKey,
+    /// 第一个用来签名，全部都可以用来校验
+    current: Arc<ArcSwap<Vec<SigningKey>>>,
+    forced_reload: Arc<Mutex<Option<Instant>>>,
+}
+
+impl fmt::Debug for KeyRing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kids: Vec<_> = self.current.load().iter().map(SigningKey::kid).collect();
+        f.debug_struct("KeyRing").field("kids", &kids).finish()
+    }
+}
+
+impl KeyRing {
+    pub fn new(secret: &str) -> Self {
+        let fallback = SigningKey {
+            id: None,
+            secret: secret.to_owned(),
+            expires_at: None,
+        };
+        Self {
+            current: Arc::new(ArcSwap::from_pointee(vec![fallback.clone()])),
+            fallback,
+            forced_reload: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    pub fn signing_key(&self) -> SigningKey {
+        let now = Utc::now();
+        self.current
+            .load()
+            .iter()
+            .find(|key| key.is_live(now))
+            .unwrap_or(&self.fallback)
+            .clone()
+    }
+
+    /// token 带 kid 时只用对应的密钥；没有 kid 的是轮换前签发的，逐个尝试
+    pub fn verifying_keys(&self, kid: Option<&str>) -> Vec<SigningKey> {
+        let now = Utc::now();
+        self.current
+            .load()
+            .iter()
+            .filter(|key| key.is_live(now))
+            .filter(|key| kid.is_none() || key.kid().as_deref() == kid)
+            .cloned()
+            .collect()
+    }
+
+    pub fn contains(&self, kid: &str) -> bool {
+        self.current
+            .load()
+            .iter()
+            .any(|key| key.kid().as_deref() == Some(kid))
+    }
+
+    pub async fn reload(&self, pool: &PgPool) -> Result<(), sqlx::Error> {
+        let mut keys = sqlx::query_as::<_, SigningKey>(
+            "SELECT id, secret, expires_at FROM jwt_signing_keys
+             WHERE expires_at IS NULL OR expires_at > NOW()
+             ORDER BY expires_at IS NULL DESC, id DESC",
+        )
+        .fetch_all(pool)
+        .await?;
+        if keys.is_empty() {
+            keys.push(self.fallback.clone());
+        }
+        self.current.store(Arc::new(keys));
+        Ok(())
+    }
+
+    /// 其它实例刚轮换过密钥时，本实例可能还没加载到新 kid，这时提前重新加载一次
+    pub async fn reload_for_unknown(&self, pool: &PgPool, kid: &str) -> Result<(), sqlx::Error> {
+        if self.contains(kid) {
+            return Ok(());
+        }
+        {
+            let mut last = self.forced_reload.lock().unwrap();
+            if last.is_some_and(|last| last.elapsed() < MIN_FORCED_RELOAD_INTERVAL) {
+                return Ok(());
+            }
+            *last = Some(Instant::now());
+        }
+        self.reload(pool).await
+    }
+}
+
+pub async fn reload_periodically(
+    keys: KeyRing,
+    pool: PgPool,
+    interval: Duration,
+    token: CancellationToken,
+) {
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        tokio::select! {
+            _ = token.cancelled() => return,
+            _ = ticker.tick() => {}
+        }
+        if let Err(e) = keys.reload(&pool).await {
+            eprintln!("failed to reload JWT signing keys, keeping the old ones: {e}");
+        }
+    }
+}
+
+/// 生成新的签名密钥并返回它的 id。之前的密钥在 `grace` 之后失效，
+/// 这段时间里已经签发的 access token 仍然有效。
+/// 数据库里还没有可用密钥时服务在用 `JWT_SECRET`，把它也记下来一起退役
+pub async fn rotate(
+    pool: &PgPool,
+    current_secret: &str,
+    grace: Duration,
+) -> Result<i32, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    // 并发轮换时排队执行
+    sqlx::query("LOCK TABLE jwt_signing_keys IN SHARE ROW EXCLUSIVE MODE")
+        .execute(&mut *tx)
+        .await?;
+
+    let retired = sqlx::query(
+        "UPDATE jwt_signing_keys
+         SET expires_at = LEAST(
+             COALESCE(expires_at, 'infinity'), NOW() + make_interval(secs => $1))
+         WHERE expires_at IS NULL OR expires_at > NOW()",
+    )
+    .bind(grace.as_secs_f64())
+    .execute(&mut *tx)
+    .await?;
+    if retired.rows_affected() == 0 {
+        sqlx::query(
+            "INSERT INTO jwt_signing_keys (secret, expires_at)
+             VALUES ($1, NOW() + make_interval(secs => $2))",
+        )
+        .bind(current_secret)
+        .bind(grace.as_secs_f64())
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    let id = sqlx::query_scalar("INSERT INTO jwt_signing_keys (secret) VALUES ($1) RETURNING id")
+        .bind(token::generate())
+        .fetch_one(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    Ok(id)
+}
@@ -0,0 +1,29 @@
+pub mod app;
+pub mod auth;
+pub mod background;
+pub mod conditional;
+pub mod config;
+pub mod db;
+pub mod email;
+pub mod error;
+pub mod graphql;
+pub mod grpc;
+pub mod handlers;
+pub mod ical;
+pub mod idempotency;
+pub mod jobs;
+pub mod jwt_keys;
+pub mod metrics;
+pub mod middleware;
+pub mod models;
+pub mod rank;
+pub mod rate_limit;
+pub mod routes;
+pub mod services;
+pub mod shutdown;
+pub mod state;
+pub mod task_cache;
+pub mod task_events;
+pub mod task_query;
+pub mod tls;
+pub mod token;
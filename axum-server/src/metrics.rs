@@ -0,0 +1,84 @@
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, Opts, Registry,
+    TextEncoder,
+};
+use sqlx::PgPool;
+
+pub struct Metrics {
+    registry: Registry,
+    pub http_requests: IntCounterVec,
+    pub http_duration: HistogramVec,
+    pub pool_size: IntGauge,
+    pub pool_idle: IntGauge,
+    pub tasks_created: IntCounter,
+    pub tasks_completed: IntCounter,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry = Registry::new();
+
+        let http_requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "Total number of HTTP requests"),
+            &["method", "route", "status"],
+        )
+        .unwrap();
+        let http_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "HTTP request latency in seconds",
+            ),
+            &["method", "route", "status"],
+        )
+        .unwrap();
+        let pool_size = IntGauge::new(
+            "db_pool_connections",
+            "Connections currently open in the database pool",
+        )
+        .unwrap();
+        let pool_idle = IntGauge::new(
+            "db_pool_idle_connections",
+            "Idle connections in the database pool",
+        )
+        .unwrap();
+        let tasks_created =
+            IntCounter::new("tasks_created_total", "Total number of tasks created").unwrap();
+        let tasks_completed =
+            IntCounter::new("tasks_completed_total", "Total number of tasks completed").unwrap();
+
+        registry.register(Box::new(http_requests.clone())).unwrap();
+        registry.register(Box::new(http_duration.clone())).unwrap();
+        registry.register(Box::new(pool_size.clone())).unwrap();
+        registry.register(Box::new(pool_idle.clone())).unwrap();
+        registry.register(Box::new(tasks_created.clone())).unwrap();
+        registry
+            .register(Box::new(tasks_completed.clone()))
+            .unwrap();
+
+        Self {
+            registry,
+            http_requests,
+            http_duration,
+            pool_size,
+            pool_idle,
+            tasks_created,
+            tasks_completed,
+        }
+    }
+
+    pub fn render(&self, pool: &PgPool) -> anyhow::Result<String> {
+        // 抓取时刷新连接池的 gauge
+        self.pool_size.set(pool.size() as i64);
+        self.pool_idle.set(pool.num_idle() as i64);
+
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+        Ok(String::from_utf8(buffer)?)
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,68 @@
+use axum::{
+    extract::{FromRequestParts, MatchedPath, Request, State},
+    middleware::Next,
+    response::Response,
+};
+use std::{sync::Arc, time::Instant};
+
+use crate::{
+    auth::{AuthUser, Scope},
+    error::AppError,
+    metrics::Metrics,
+    state::AppState,
+};
+
+pub async fn track_metrics(
+    State(metrics): State<Arc<Metrics>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let start = Instant::now();
+    let method = request.method().to_string();
+    // 用路由模板而不是原始路径，避免 label 爆炸
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_owned())
+        .unwrap_or_else(|| "unmatched".to_owned());
+
+    let response = next.run(request).await;
+
+    let status = response.status().as_u16().to_string();
+    let labels = [method.as_str(), route.as_str(), status.as_str()];
+    metrics.http_requests.with_label_values(&labels).inc();
+    metrics
+        .http_duration
+        .with_label_values(&labels)
+        .observe(start.elapsed().as_secs_f64());
+
+    response
+}
+
+#[derive(Clone)]
+pub struct RequiredScope {
+    state: AppState,
+    scope: Scope,
+}
+
+impl RequiredScope {
+    pub fn new(state: &AppState, scope: Scope) -> Self {
+        Self {
+            state: state.clone(),
+            scope,
+        }
+    }
+}
+
+/// 配合 `route_layer` 使用，token 缺少所需 scope 时返回 403
+pub async fn require_scope(
+    State(required): State<RequiredScope>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let (mut parts, body) = request.into_parts();
+    let user = AuthUser::from_request_parts(&mut parts, &required.state).await?;
+    user.require_scope(required.scope)?;
+
+    Ok(next.run(Request::from_parts(parts, body)).await)
+}
@@ -1 +1,7 @@
+pub mod notification;
+pub mod stats;
 pub mod task;
+pub mod time_entry;
+pub mod token;
+pub mod user;
+pub mod view;
@@ -0,0 +1,93 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+/// 邮件通知的类别，用户可以分别关闭，对应 `notification_preferences` 的列
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationCategory {
+    Assignments,
+    Mentions,
+    Reminders,
+    DailyDigest,
+}
+
+impl NotificationCategory {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Assignments => "assignments",
+            Self::Mentions => "mentions",
+            Self::Reminders => "reminders",
+            Self::DailyDigest => "daily_digest",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct NotificationPreferences {
+    #[serde(skip)]
+    pub user_id: i32,
+    pub assignments: bool,
+    pub mentions: bool,
+    pub reminders: bool,
+    pub daily_digest: bool,
+    #[serde(skip)]
+    pub unsubscribe_token: String,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl NotificationPreferences {
+    pub fn allows(&self, category: NotificationCategory) -> bool {
+        match category {
+            NotificationCategory::Assignments => self.assignments,
+            NotificationCategory::Mentions => self.mentions,
+            NotificationCategory::Reminders => self.reminders,
+            NotificationCategory::DailyDigest => self.daily_digest,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct UpdateNotificationPreferences {
+    pub assignments: Option<bool>,
+    pub mentions: Option<bool>,
+    pub reminders: Option<bool>,
+    pub daily_digest: Option<bool>,
+}
+
+/// 站内通知的类型，对应 `notifications.kind`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotificationKind {
+    Assigned,
+    Unassigned,
+}
+
+impl NotificationKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Assigned => "assigned",
+            Self::Unassigned => "unassigned",
+        }
+    }
+}
+
+/// `GET /api/notifications` 里的一条站内通知，`read_at` 为空表示未读
+#[derive(Debug, Serialize, FromRow)]
+pub struct Notification {
+    pub id: i64,
+    pub kind: String,
+    pub task_id: Option<i32>,
+    pub actor_id: Option<i32>,
+    pub title: String,
+    pub created_at: DateTime<Utc>,
+    pub read_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct NotificationFilter {
+    /// 只返回未读的通知
+    #[serde(default)]
+    pub unread: bool,
+    /// 默认 50，最多 200
+    pub limit: Option<i64>,
+}
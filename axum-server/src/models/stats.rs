@@ -0,0 +1,44 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+#[derive(Debug, Deserialize)]
+pub struct StatsParams {
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct CountBucket {
+    pub key: String,
+    pub count: i64,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct DatePoint {
+    pub date: NaiveDate,
+    pub count: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Throughput {
+    pub daily: Vec<DatePoint>,
+    /// 以周一为一周的开始
+    pub weekly: Vec<DatePoint>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TaskStats {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub total: i64,
+    pub by_status: Vec<CountBucket>,
+    pub by_priority: Vec<CountBucket>,
+    pub by_category: Vec<CountBucket>,
+    pub overdue: i64,
+    pub throughput: Throughput,
+    /// 创建到第一次完成的平均秒数，没有完成的任务时为 null
+    pub avg_completion_seconds: Option<f64>,
+    /// 每天结束时仍未完成的任务数
+    pub burndown: Vec<DatePoint>,
+}
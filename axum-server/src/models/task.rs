@@ -1,8 +1,10 @@
+use async_graphql::{InputObject, SimpleObject};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 
-#[derive(Debug, Serialize, Deserialize, FromRow)]
+#[derive(Debug, Serialize, Deserialize, FromRow, SimpleObject)]
+#[graphql(complex)]
 pub struct Task {
     pub id: i32,
     pub title: String,
@@ -14,18 +16,100 @@ pub struct Task {
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub user_id: i32,
+    #[graphql(skip)]
+    pub organization_id: i32,
+    pub recurrence: Option<String>,
+    pub tags: Vec<String>,
+    /// 创建任务的用户
+    pub reporter_id: i32,
+    /// 负责人，所有者和负责人都能查看和修改任务
+    pub assignee_ids: Vec<i32>,
+    /// 在所属状态列里的手动顺序，见 `rank`
+    pub rank: String,
+    /// 预估耗时（秒）
+    pub estimate_seconds: Option<i32>,
+    /// 已停止的时间记录之和（秒），由 `time_entries` 的触发器维护
+    pub time_spent_seconds: i64,
 }
 
-#[derive(Debug, Deserialize)]
+/// `task_history` 里的一次状态变化，创建任务时 `old_status` 为空
+#[derive(Debug, Clone, Serialize, FromRow, SimpleObject)]
+pub struct TaskStatusChange {
+    pub id: i64,
+    pub task_id: i32,
+    pub old_status: Option<String>,
+    pub new_status: String,
+    pub changed_at: DateTime<Utc>,
+}
+
+pub const TASK_STATUSES: [&str; 3] = ["pending", "in_progress", "completed"];
+pub const MIN_PRIORITY: i32 = 1;
+pub const MAX_PRIORITY: i32 = 5;
+pub const RECURRENCES: [&str; 4] = ["daily", "weekly", "monthly", "yearly"];
+const MAX_TITLE_LEN: usize = 200;
+const MAX_DESCRIPTION_LEN: usize = 10_000;
+const MAX_TAG_LEN: usize = 50;
+pub const MAX_ASSIGNEES: usize = 20;
+pub const TASK_SORTS: [&str; 2] = ["id", "rank"];
+/// 预估最多 1000 小时
+pub const MAX_ESTIMATE_SECONDS: i32 = 1000 * 3600;
+
+#[derive(Debug, Deserialize, InputObject)]
+#[graphql(name = "CreateTaskInput")]
 pub struct CreateTask {
     pub title: String,
     pub description: String,
     pub category: String,
     pub priority: i32,
     pub due_date: DateTime<Utc>,
+    pub recurrence: Option<String>,
+    #[serde(default)]
+    #[graphql(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub estimate_seconds: Option<i32>,
 }
 
-#[derive(Debug, Deserialize)]
+impl CreateTask {
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+
+        if self.title.trim().is_empty() {
+            errors.push("title must not be empty".to_owned());
+        } else if self.title.chars().count() > MAX_TITLE_LEN {
+            errors.push(format!("title must be at most {MAX_TITLE_LEN} characters"));
+        }
+        if self.description.chars().count() > MAX_DESCRIPTION_LEN {
+            errors.push(format!(
+                "description must be at most {MAX_DESCRIPTION_LEN} characters"
+            ));
+        }
+        if self.category.trim().is_empty() {
+            errors.push("category must not be empty".to_owned());
+        }
+        if !(MIN_PRIORITY..=MAX_PRIORITY).contains(&self.priority) {
+            errors.push(format!(
+                "priority must be between {MIN_PRIORITY} and {MAX_PRIORITY}"
+            ));
+        }
+        if let Some(recurrence) = &self.recurrence {
+            check_recurrence(recurrence, &mut errors);
+        }
+        check_tags(&self.tags, &mut errors);
+        if let Some(estimate) = self.estimate_seconds {
+            check_estimate(estimate, &mut errors);
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, InputObject)]
+#[graphql(name = "UpdateTaskInput")]
 pub struct UpdateTask {
     pub title: Option<String>,
     pub description: Option<String>,
@@ -33,11 +117,154 @@ pub struct UpdateTask {
     pub priority: Option<i32>,
     pub status: Option<String>,
     pub due_date: Option<DateTime<Utc>>,
+    /// 空字符串表示取消重复
+    pub recurrence: Option<String>,
+    pub tags: Option<Vec<String>>,
+    /// 0 表示取消预估
+    pub estimate_seconds: Option<i32>,
 }
 
-#[derive(Debug, Deserialize)]
+impl UpdateTask {
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+
+        if let Some(title) = &self.title {
+            if title.trim().is_empty() {
+                errors.push("title must not be empty".to_owned());
+            } else if title.chars().count() > MAX_TITLE_LEN {
+                errors.push(format!("title must be at most {MAX_TITLE_LEN} characters"));
+            }
+        }
+        if let Some(description) = &self.description {
+            if description.chars().count() > MAX_DESCRIPTION_LEN {
+                errors.push(format!(
+                    "description must be at most {MAX_DESCRIPTION_LEN} characters"
+                ));
+            }
+        }
+        if let Some(category) = &self.category {
+            if category.trim().is_empty() {
+                errors.push("category must not be empty".to_owned());
+            }
+        }
+        if let Some(priority) = self.priority {
+            if !(MIN_PRIORITY..=MAX_PRIORITY).contains(&priority) {
+                errors.push(format!(
+                    "priority must be between {MIN_PRIORITY} and {MAX_PRIORITY}"
+                ));
+            }
+        }
+        if let Some(status) = &self.status {
+            if !TASK_STATUSES.contains(&status.as_str()) {
+                errors.push(format!(
+                    "status must be one of {}",
+                    TASK_STATUSES.join(", ")
+                ));
+            }
+        }
+        if let Some(recurrence) = self.recurrence.as_deref().filter(|r| !r.is_empty()) {
+            check_recurrence(recurrence, &mut errors);
+        }
+        if let Some(tags) = &self.tags {
+            check_tags(tags, &mut errors);
+        }
+        if let Some(estimate) = self.estimate_seconds.filter(|estimate| *estimate != 0) {
+            check_estimate(estimate, &mut errors);
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+fn check_tags(tags: &[String], errors: &mut Vec<String>) {
+    for tag in tags {
+        if tag.is_empty() || tag.chars().count() > MAX_TAG_LEN {
+            errors.push(format!("tags must be 1-{MAX_TAG_LEN} characters"));
+        } else if tag.contains(|c: char| c.is_whitespace() || c == ',' || c == ';') {
+            errors.push(format!(
+                "tag `{tag}` must not contain spaces, commas or semicolons"
+            ));
+        }
+    }
+}
+
+fn check_estimate(estimate: i32, errors: &mut Vec<String>) {
+    if !(1..=MAX_ESTIMATE_SECONDS).contains(&estimate) {
+        errors.push(format!(
+            "estimate_seconds must be between 1 and {MAX_ESTIMATE_SECONDS}"
+        ));
+    }
+}
+
+fn check_recurrence(recurrence: &str, errors: &mut Vec<String>) {
+    if !RECURRENCES.contains(&recurrence) {
+        errors.push(format!(
+            "recurrence must be one of {}",
+            RECURRENCES.join(", ")
+        ));
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, InputObject)]
 pub struct TaskFilter {
     pub category: Option<String>,
     pub priority: Option<i32>,
     pub status: Option<String>,
+    /// 查询语言，见 `task_query`
+    pub q: Option<String>,
+    /// 保存的视图 id，和 `q` 同时出现时两者都要满足
+    pub view: Option<i32>,
+    /// `me`、用户 id，或者 `none` 表示没有负责人
+    pub assignee: Option<String>,
+    /// `me` 或用户 id
+    pub reporter: Option<String>,
+    /// `id`（默认）或 `rank`：按状态分组，每组里按看板顺序
+    pub sort: Option<String>,
+}
+
+/// 把任务移到某一列的某个位置。`after_id` 和 `before_id` 是移动之后相邻的任务，
+/// 都不传时放到列的最后
+#[derive(Debug, Default, Deserialize, InputObject)]
+#[graphql(name = "MoveTaskInput")]
+pub struct MoveTask {
+    /// 不传时留在当前列
+    pub status: Option<String>,
+    pub after_id: Option<i32>,
+    pub before_id: Option<i32>,
+}
+
+impl MoveTask {
+    pub fn validate(&self, task_id: i32) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+
+        if let Some(status) = &self.status {
+            if !TASK_STATUSES.contains(&status.as_str()) {
+                errors.push(format!(
+                    "status must be one of {}",
+                    TASK_STATUSES.join(", ")
+                ));
+            }
+        }
+        if self.after_id == Some(task_id) || self.before_id == Some(task_id) {
+            errors.push("a task cannot be moved next to itself".to_owned());
+        }
+        if self.after_id.is_some() && self.after_id == self.before_id {
+            errors.push("after_id and before_id must be different tasks".to_owned());
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AssigneesRequest {
+    pub user_ids: Vec<i32>,
 }
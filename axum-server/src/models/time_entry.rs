@@ -0,0 +1,130 @@
+use async_graphql::{InputObject, SimpleObject};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+/// 手动录入的一条记录最长 24 小时
+pub const MAX_ENTRY_SECONDS: i64 = 24 * 3600;
+const MAX_NOTE_LEN: usize = 1000;
+
+/// 计时器或手动录入的一段时间，`ended_at` 为空表示计时器还在走
+#[derive(Debug, Serialize, FromRow, SimpleObject)]
+pub struct TimeEntry {
+    pub id: i64,
+    /// 任务删除后为空
+    pub task_id: Option<i32>,
+    pub user_id: i32,
+    pub started_at: DateTime<Utc>,
+    pub ended_at: Option<DateTime<Utc>>,
+    /// 计时器停止前为空
+    pub duration_seconds: Option<i64>,
+    pub note: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct StartTimer {
+    #[serde(default)]
+    pub note: String,
+}
+
+#[derive(Debug, Deserialize, InputObject)]
+#[graphql(name = "CreateTimeEntryInput")]
+pub struct CreateTimeEntry {
+    pub started_at: DateTime<Utc>,
+    pub ended_at: DateTime<Utc>,
+    #[serde(default)]
+    #[graphql(default)]
+    pub note: String,
+}
+
+impl CreateTimeEntry {
+    pub fn validate(&self, now: DateTime<Utc>) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+
+        if self.ended_at <= self.started_at {
+            errors.push("ended_at must be after started_at".to_owned());
+        } else if self.ended_at - self.started_at > Duration::seconds(MAX_ENTRY_SECONDS) {
+            errors.push(format!(
+                "an entry must be at most {} hours long",
+                MAX_ENTRY_SECONDS / 3600
+            ));
+        }
+        if self.ended_at > now {
+            errors.push("ended_at must not be in the future".to_owned());
+        }
+        check_note(&self.note, &mut errors);
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+pub fn check_note(note: &str, errors: &mut Vec<String>) {
+    if note.chars().count() > MAX_NOTE_LEN {
+        errors.push(format!("note must be at most {MAX_NOTE_LEN} characters"));
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimesheetFormat {
+    #[default]
+    Json,
+    Csv,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TimesheetParams {
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    /// `me`（默认）或用户 id，查看别人的时间表需要组织管理员
+    pub user: Option<String>,
+    #[serde(default)]
+    pub format: TimesheetFormat,
+}
+
+/// 时间表里的一条记录，带上任务标题方便对账
+#[derive(Debug, Serialize, FromRow)]
+pub struct TimesheetEntry {
+    pub id: i64,
+    /// 按 UTC 计算的开始日期
+    pub date: NaiveDate,
+    pub task_id: Option<i32>,
+    pub task_title: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub ended_at: DateTime<Utc>,
+    pub duration_seconds: i64,
+    pub note: String,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct TimesheetDay {
+    pub date: NaiveDate,
+    pub seconds: i64,
+}
+
+/// 每个任务在这段时间里记录的时间，和预估放在一起比较
+#[derive(Debug, Serialize, FromRow)]
+pub struct TimesheetTask {
+    pub task_id: Option<i32>,
+    pub title: Option<String>,
+    pub estimate_seconds: Option<i32>,
+    /// 任务所有记录的总和，不限于这段时间
+    pub time_spent_seconds: Option<i64>,
+    pub seconds: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Timesheet {
+    pub user_id: i32,
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub total_seconds: i64,
+    pub days: Vec<TimesheetDay>,
+    pub tasks: Vec<TimesheetTask>,
+    pub entries: Vec<TimesheetEntry>,
+}
@@ -0,0 +1,89 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+use crate::auth::Scope;
+
+pub const MAX_TOKEN_NAME_LEN: usize = 100;
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct PersonalAccessToken {
+    pub id: i32,
+    pub name: String,
+    pub token_prefix: String,
+    pub scopes: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreatePersonalAccessToken {
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    /// 不传表示永不过期
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl CreatePersonalAccessToken {
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+        if self.name.trim().is_empty() || self.name.chars().count() > MAX_TOKEN_NAME_LEN {
+            errors.push(format!("name must be 1-{MAX_TOKEN_NAME_LEN} characters"));
+        }
+        if self.scopes.is_empty() {
+            errors.push("at least one scope is required".to_owned());
+        }
+        if self.expires_at.is_some_and(|at| at <= Utc::now()) {
+            errors.push("expires_at must be in the future".to_owned());
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// 创建时唯一一次返回明文 token
+#[derive(Debug, Serialize)]
+pub struct CreatedPersonalAccessToken {
+    #[serde(flatten)]
+    pub info: PersonalAccessToken,
+    pub token: String,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct ClientCertificateMapping {
+    pub id: i32,
+    pub name: String,
+    pub fingerprint_sha256: String,
+    pub scopes: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateClientCertificateMapping {
+    pub name: String,
+    /// PEM 格式的客户端证书，只保存它的指纹
+    pub certificate: String,
+    pub scopes: Vec<Scope>,
+}
+
+impl CreateClientCertificateMapping {
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+        if self.name.trim().is_empty() || self.name.chars().count() > MAX_TOKEN_NAME_LEN {
+            errors.push(format!("name must be 1-{MAX_TOKEN_NAME_LEN} characters"));
+        }
+        if self.scopes.is_empty() {
+            errors.push("at least one scope is required".to_owned());
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
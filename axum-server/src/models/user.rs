@@ -0,0 +1,78 @@
+use async_graphql::SimpleObject;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct User {
+    pub id: i32,
+    pub organization_id: i32,
+    pub email: String,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    /// 只有 `find_by_email` 会读；租户事务里的 `task_app` 没有这一列的权限
+    #[serde(skip)]
+    #[sqlx(default)]
+    pub password_hash: Option<String>,
+    pub disabled_at: Option<DateTime<Utc>>,
+    /// `member` 或 `admin`，见 `USER_ROLES`
+    pub role: String,
+}
+
+/// 组织的第一个用户是管理员，之后加入的是普通成员
+pub const USER_ROLES: [&str; 2] = ["member", "admin"];
+
+impl User {
+    pub fn is_admin(&self) -> bool {
+        self.role == "admin"
+    }
+}
+
+/// 对同组织成员可见的用户信息
+#[derive(Debug, Clone, Serialize, FromRow, SimpleObject)]
+#[graphql(name = "User")]
+pub struct UserProfile {
+    pub id: i32,
+    pub email: String,
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RegisterRequest {
+    pub email: String,
+    pub password: String,
+    #[serde(default)]
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LoginRequest {
+    pub email: String,
+    pub password: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TokenResponse {
+    pub access_token: String,
+    pub token_type: &'static str,
+    /// access token 的有效秒数
+    pub expires_in: u64,
+    pub refresh_token: String,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct Session {
+    pub id: i64,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    #[sqlx(default)]
+    pub current: bool,
+}
@@ -0,0 +1,36 @@
+use async_graphql::{InputObject, SimpleObject};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+#[derive(Debug, Serialize, Deserialize, FromRow, SimpleObject)]
+#[graphql(name = "View")]
+pub struct SavedView {
+    pub id: i32,
+    pub user_id: i32,
+    #[graphql(skip)]
+    pub organization_id: i32,
+    pub name: String,
+    pub query: String,
+    pub shared: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, InputObject)]
+#[graphql(name = "CreateViewInput")]
+pub struct CreateView {
+    pub name: String,
+    pub query: String,
+    #[serde(default)]
+    #[graphql(default)]
+    pub shared: bool,
+}
+
+#[derive(Debug, Deserialize, InputObject)]
+#[graphql(name = "UpdateViewInput")]
+pub struct UpdateView {
+    pub name: Option<String>,
+    pub query: Option<String>,
+    pub shared: Option<bool>,
+}
@@ -0,0 +1,269 @@
+//! 看板里任务的手动排序。
+//!
+//! 每个状态列里的任务按 `rank` 排序，rank 是 62 进制的小数部分（`"V"` 表示 0.5），
+//! 按字节比较的顺序就是数值顺序。拖动任务时只在两个相邻的 rank 之间取一个新值，
+//! 不用给整列重新编号；反复插在同一个位置会让 rank 变长，超过 `MAX_LEN` 后由
+//! `RebalanceRanks` 任务把整列重新均匀分布。
+//!
+//! 新任务由数据库里的 `next_task_rank` 排到列的最后，算法和 `after` 相同。
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use sqlx::PgConnection;
+
+use crate::jobs::{Job, JobContext};
+
+const DIGITS: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+const BASE: usize = DIGITS.len();
+/// 追加到最后或者插到最前时，在这一位上加减一，rank 长度基本不变
+const STEP_LEN: usize = 4;
+/// 超过这个长度就重新分布整列
+pub const MAX_LEN: usize = 12;
+
+fn index(digit: u8) -> usize {
+    DIGITS
+        .iter()
+        .position(|d| *d == digit)
+        .expect("rank contains only base-62 digits")
+}
+
+fn to_string(digits: &[usize]) -> String {
+    digits.iter().map(|d| DIGITS[*d] as char).collect()
+}
+
+/// 末尾的 0 不影响数值，去掉后才能直接按字符串比较
+fn trim(mut digits: Vec<usize>) -> Vec<usize> {
+    while digits.last() == Some(&0) {
+        digits.pop();
+    }
+    digits
+}
+
+fn padded(rank: &str) -> Vec<usize> {
+    let mut digits: Vec<usize> = rank.bytes().map(index).collect();
+    if digits.len() < STEP_LEN {
+        digits.resize(STEP_LEN, 0);
+    }
+    digits
+}
+
+/// 比 `rank` 大的最小步进，已经是 `zzzz` 时在后面加一位
+pub fn after(rank: &str) -> String {
+    let mut digits = padded(rank);
+    while let Some(last) = digits.pop() {
+        if last + 1 < BASE {
+            digits.push(last + 1);
+            return to_string(&digits);
+        }
+    }
+    format!("{rank}V")
+}
+
+/// 比 `rank` 小的最大步进，太小时取 0 和它的中点
+pub fn before(rank: &str) -> String {
+    let mut digits = padded(rank);
+    for position in (0..digits.len()).rev() {
+        if digits[position] > 0 {
+            digits[position] -= 1;
+            for digit in &mut digits[position + 1..] {
+                *digit = BASE - 1;
+            }
+            let digits = trim(digits);
+            if !digits.is_empty() {
+                return to_string(&digits);
+            }
+            break;
+        }
+    }
+    midpoint(&[], Some(&rank.bytes().map(index).collect::<Vec<_>>()))
+}
+
+/// 两个 rank 之间的新 rank，任意一边为空表示列的开头或结尾。
+/// `lower` 不小于 `upper` 时返回 `None`
+pub fn between(lower: Option<&str>, upper: Option<&str>) -> Option<String> {
+    match (lower, upper) {
+        (None, None) => Some("V".to_owned()),
+        (Some(lower), None) => Some(after(lower)),
+        (None, Some(upper)) => Some(before(upper)),
+        (Some(lower), Some(upper)) if lower < upper => {
+            let lower: Vec<usize> = lower.bytes().map(index).collect();
+            let upper: Vec<usize> = upper.bytes().map(index).collect();
+            Some(midpoint(&lower, Some(&upper)))
+        }
+        _ => None,
+    }
+}
+
+/// `lower < upper`，`upper` 为空表示 1。结果不以 0 结尾
+fn midpoint(lower: &[usize], upper: Option<&[usize]>) -> String {
+    if let Some(upper) = upper {
+        // 公共前缀原样保留，`lower` 不够长时按 0 补齐
+        let common = upper
+            .iter()
+            .enumerate()
+            .take_while(|(i, digit)| lower.get(*i).copied().unwrap_or(0) == **digit)
+            .count();
+        if common > 0 {
+            let rest = lower.get(common..).unwrap_or(&[]);
+            return to_string(&upper[..common]) + &midpoint(rest, Some(&upper[common..]));
+        }
+    }
+    let low = lower.first().copied().unwrap_or(0);
+    let high = upper.map_or(BASE, |upper| upper[0]);
+    if high - low > 1 {
+        return to_string(&[(low + high) / 2]);
+    }
+    // 首位相邻：`upper` 还有后续位时取它的首位，否则保留 `lower` 的首位继续往后找
+    match upper {
+        Some(upper) if upper.len() > 1 => to_string(&upper[..1]),
+        _ => to_string(&[low]) + &midpoint(lower.get(1..).unwrap_or(&[]), None),
+    }
+}
+
+/// `count` 个均匀分布的 rank，相邻两个之间至少留 62 * 62 个位置
+pub fn spread(count: usize) -> Vec<String> {
+    let slots = count as u128 + 1;
+    let mut len = 1;
+    while (BASE as u128).pow(len) < slots * (BASE * BASE) as u128 {
+        len += 1;
+    }
+    let scale = (BASE as u128).pow(len);
+    (1..slots)
+        .map(|i| {
+            let mut value = i * scale / slots;
+            let mut digits = vec![0; len as usize];
+            for digit in digits.iter_mut().rev() {
+                *digit = (value % BASE as u128) as usize;
+                value /= BASE as u128;
+            }
+            to_string(&trim(digits))
+        })
+        .collect()
+}
+
+/// 调整一列的 rank 前先加锁，拖动和重新分布不会交错执行
+pub async fn lock_column(
+    conn: &mut PgConnection,
+    organization_id: i32,
+    status: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("SELECT pg_advisory_xact_lock($1, hashtext($2))")
+        .bind(organization_id)
+        .bind(status)
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+/// 按现在的顺序（rank 相同时按 id）把整列重新均匀分布，返回任务数量
+pub async fn rebalance(
+    conn: &mut PgConnection,
+    organization_id: i32,
+    status: &str,
+) -> Result<usize, sqlx::Error> {
+    lock_column(&mut *conn, organization_id, status).await?;
+    let ids: Vec<i32> = sqlx::query_scalar(
+        "SELECT id FROM tasks WHERE organization_id = $1 AND status = $2 ORDER BY rank, id",
+    )
+    .bind(organization_id)
+    .bind(status)
+    .fetch_all(&mut *conn)
+    .await?;
+    sqlx::query(
+        "UPDATE tasks SET rank = spread.rank
+         FROM unnest($1::int[], $2::text[]) AS spread (id, rank)
+         WHERE tasks.id = spread.id AND tasks.rank <> spread.rank",
+    )
+    .bind(&ids)
+    .bind(spread(ids.len()))
+    .execute(&mut *conn)
+    .await?;
+    Ok(ids.len())
+}
+
+/// rank 太长时由拖动接口入队，同一列同时只排一个
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RebalanceRanks {
+    pub organization_id: i32,
+    pub status: String,
+}
+
+impl RebalanceRanks {
+    pub fn unique_key(&self) -> String {
+        format!("rank-rebalance:{}:{}", self.organization_id, self.status)
+    }
+}
+
+#[async_trait]
+impl Job for RebalanceRanks {
+    const KIND: &'static str = "tasks.rebalance_ranks";
+
+    async fn run(self, ctx: &JobContext) -> anyhow::Result<()> {
+        let mut tx = ctx.pool.begin().await?;
+        rebalance(&mut tx, self.organization_id, &self.status).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_between(lower: Option<&str>, upper: Option<&str>) -> String {
+        let rank = between(lower, upper).unwrap();
+        assert!(!rank.is_empty() && !rank.ends_with('0'), "{rank}");
+        if let Some(lower) = lower {
+            assert!(lower < rank.as_str(), "{lower} < {rank}");
+        }
+        if let Some(upper) = upper {
+            assert!(rank.as_str() < upper, "{rank} < {upper}");
+        }
+        rank
+    }
+
+    #[test]
+    fn finds_ranks_between_neighbours() {
+        assert_eq!(assert_between(None, None), "V");
+        assert_eq!(assert_between(Some("V"), None), "V001");
+        assert_eq!(assert_between(Some("V00z"), None), "V01");
+        assert_eq!(assert_between(Some("zzzz"), None), "zzzzV");
+        assert_eq!(assert_between(None, Some("V")), "Uzzz");
+        assert_eq!(assert_between(None, Some("0001")), "0000V");
+        assert_eq!(assert_between(Some("A"), Some("C")), "B");
+        assert_eq!(assert_between(Some("A"), Some("B")), "AV");
+        assert_eq!(assert_between(Some("Az"), Some("B")), "AzV");
+        assert_eq!(assert_between(Some("A"), Some("A1")), "A0V");
+        assert_eq!(assert_between(Some("AV"), Some("B1")), "B");
+        assert_eq!(between(Some("B"), Some("A")), None);
+        assert_eq!(between(Some("A"), Some("A")), None);
+    }
+
+    #[test]
+    fn repeated_inserts_stay_ordered() {
+        let mut ranks = vec!["V".to_owned()];
+        for i in 0..500 {
+            // 交替插在最前、最后和第二个位置
+            let rank = match i % 3 {
+                0 => assert_between(None, Some(&ranks[0])),
+                1 => assert_between(Some(ranks.last().unwrap()), None),
+                _ => assert_between(Some(&ranks[0]), Some(&ranks[1])),
+            };
+            ranks.push(rank);
+            ranks.sort();
+        }
+        assert!(ranks.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn spreads_ranks_evenly() {
+        assert!(spread(0).is_empty());
+        for count in [1, 2, 61, 62, 1000] {
+            let ranks = spread(count);
+            assert_eq!(ranks.len(), count);
+            assert!(ranks.windows(2).all(|pair| pair[0] < pair[1]));
+            assert!(ranks.iter().all(|rank| !rank.ends_with('0')));
+            assert!(ranks.iter().all(|rank| rank.len() <= 4), "{count}");
+        }
+    }
+}
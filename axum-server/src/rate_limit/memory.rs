@@ -0,0 +1,84 @@
+use async_trait::async_trait;
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use super::{BucketState, Quota, RateLimitBackend};
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+    full_at: Instant,
+}
+
+/// 单节点使用的内存令牌桶
+#[derive(Default)]
+pub struct MemoryBackend {
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl MemoryBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl RateLimitBackend for MemoryBackend {
+    async fn acquire(&self, key: &str, quota: Quota) -> anyhow::Result<BucketState> {
+        let now = Instant::now();
+        let rate = quota.per_second();
+        let capacity = quota.burst as f64;
+
+        let mut buckets = self.buckets.lock().unwrap();
+        let bucket = buckets.entry(key.to_owned()).or_insert(Bucket {
+            tokens: capacity,
+            updated: now,
+            full_at: now,
+        });
+
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * rate).min(capacity);
+        bucket.updated = now;
+
+        let allowed = bucket.tokens >= 1.0;
+        if allowed {
+            bucket.tokens -= 1.0;
+        }
+        bucket.full_at = now + Duration::from_secs_f64((capacity - bucket.tokens) / rate);
+
+        Ok(BucketState {
+            allowed,
+            tokens: bucket.tokens,
+        })
+    }
+
+    async fn purge(&self) {
+        let now = Instant::now();
+        self.buckets
+            .lock()
+            .unwrap()
+            .retain(|_, bucket| bucket.full_at > now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn rejects_once_burst_is_spent() {
+        let backend = MemoryBackend::new();
+        let quota = Quota {
+            burst: 2,
+            period: Duration::from_secs(60),
+        };
+
+        assert!(backend.acquire("k", quota).await.unwrap().allowed);
+        assert!(backend.acquire("k", quota).await.unwrap().allowed);
+        assert!(!backend.acquire("k", quota).await.unwrap().allowed);
+        assert!(backend.acquire("other", quota).await.unwrap().allowed);
+    }
+}
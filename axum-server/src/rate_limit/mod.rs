@@ -0,0 +1,302 @@
+mod memory;
+#[cfg(feature = "redis")]
+mod redis;
+
+#[cfg(feature = "redis")]
+pub use self::redis::RedisBackend;
+pub use memory::MemoryBackend;
+
+use async_trait::async_trait;
+use axum::{
+    extract::{ConnectInfo, FromRequestParts, Request},
+    http::{request::Parts, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use futures::future::BoxFuture;
+use serde_json::json;
+use std::{
+    convert::Infallible,
+    net::{IpAddr, SocketAddr},
+    str::FromStr,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Duration,
+};
+use tokio_util::sync::CancellationToken;
+use tower::{Layer, Service};
+
+use crate::{auth::AuthUser, config::RateLimitConfig};
+
+/// 令牌桶配额：`burst` 个请求，每 `period` 补满一次
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quota {
+    pub burst: u32,
+    pub period: Duration,
+}
+
+impl Quota {
+    pub fn per_second(&self) -> f64 {
+        self.burst as f64 / self.period.as_secs_f64()
+    }
+}
+
+/// 解析 `<requests>/<seconds>`，例如 `30/60`
+impl FromStr for Quota {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (burst, period) = s
+            .split_once('/')
+            .ok_or_else(|| anyhow::anyhow!("expected <requests>/<seconds>, got {s}"))?;
+        let quota = Quota {
+            burst: burst.trim().parse()?,
+            period: Duration::from_secs(period.trim().parse()?),
+        };
+        if quota.burst == 0 || quota.period.is_zero() {
+            anyhow::bail!("quota must be non-zero: {s}");
+        }
+        Ok(quota)
+    }
+}
+
+/// 扣减一个令牌后桶的状态
+#[derive(Debug, Clone, Copy)]
+pub struct BucketState {
+    pub allowed: bool,
+    pub tokens: f64,
+}
+
+#[async_trait]
+pub trait RateLimitBackend: Send + Sync {
+    async fn acquire(&self, key: &str, quota: Quota) -> anyhow::Result<BucketState>;
+
+    /// 清理已经补满、不再需要保存的桶
+    async fn purge(&self) {}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyStrategy {
+    /// 已登录用户按 `user_id`，否则退回到客户端 IP
+    User,
+    Ip,
+}
+
+impl FromStr for KeyStrategy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "user" => Ok(KeyStrategy::User),
+            "ip" => Ok(KeyStrategy::Ip),
+            other => anyhow::bail!("unknown rate limit key: {other}"),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RateLimiter {
+    backend: Arc<dyn RateLimitBackend>,
+    group: &'static str,
+    quota: Quota,
+    key: KeyStrategy,
+    trusted_proxies: usize,
+}
+
+impl RateLimiter {
+    pub fn new(
+        backend: Arc<dyn RateLimitBackend>,
+        group: &'static str,
+        quota: Quota,
+        key: KeyStrategy,
+        trusted_proxies: usize,
+    ) -> Self {
+        Self {
+            backend,
+            group,
+            quota,
+            key,
+            trusted_proxies,
+        }
+    }
+
+    pub fn layer<S>(self, state: S) -> RateLimitLayer<S> {
+        RateLimitLayer {
+            limiter: Arc::new(self),
+            state,
+        }
+    }
+
+    async fn key_for<S>(&self, parts: &mut Parts, state: &S) -> String
+    where
+        AuthUser: FromRequestParts<S>,
+        S: Send + Sync,
+    {
+        if self.key == KeyStrategy::User {
+            if let Ok(user) = AuthUser::from_request_parts(parts, state).await {
+                return format!("rl:{}:user:{}", self.group, user.user_id);
+            }
+        }
+        format!("rl:{}:ip:{}", self.group, self.client_ip(parts))
+    }
+
+    /// 每层代理把它的对端地址追加到 `X-Forwarded-For` 末尾，左边的部分由客户端随意填写。
+    /// 所以只有从右数第 `trusted_proxies` 个是可信的客户端地址；
+    /// 条目不够或者不是 IP 时说明请求没有经过全部代理，用对端地址
+    fn client_ip(&self, parts: &Parts) -> String {
+        if self.trusted_proxies > 0 {
+            let forwarded = parts
+                .headers
+                .get_all("x-forwarded-for")
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .flat_map(|value| value.split(','))
+                .collect::<Vec<_>>();
+            let ip = forwarded
+                .len()
+                .checked_sub(self.trusted_proxies)
+                .and_then(|index| forwarded[index].trim().parse::<IpAddr>().ok());
+            if let Some(ip) = ip {
+                return ip.to_string();
+            }
+        }
+        parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip().to_string())
+            .unwrap_or_else(|| "unknown".to_owned())
+    }
+
+    fn decision(&self, bucket: BucketState) -> Decision {
+        let rate = self.quota.per_second();
+        let missing = self.quota.burst as f64 - bucket.tokens;
+        Decision {
+            allowed: bucket.allowed,
+            limit: self.quota.burst,
+            remaining: bucket.tokens.floor().max(0.0) as u32,
+            reset: (missing / rate).ceil() as u64,
+            retry_after: (!bucket.allowed)
+                .then(|| ((1.0 - bucket.tokens) / rate).ceil().max(1.0) as u64),
+            policy: format!("{};w={}", self.quota.burst, self.quota.period.as_secs()),
+        }
+    }
+}
+
+struct Decision {
+    allowed: bool,
+    limit: u32,
+    remaining: u32,
+    reset: u64,
+    retry_after: Option<u64>,
+    policy: String,
+}
+
+impl Decision {
+    fn apply_headers(&self, headers: &mut HeaderMap) {
+        headers.insert("ratelimit-limit", HeaderValue::from(self.limit));
+        headers.insert("ratelimit-remaining", HeaderValue::from(self.remaining));
+        headers.insert("ratelimit-reset", HeaderValue::from(self.reset));
+        if let Ok(policy) = HeaderValue::from_str(&self.policy) {
+            headers.insert("ratelimit-policy", policy);
+        }
+        if let Some(retry_after) = self.retry_after {
+            headers.insert("retry-after", HeaderValue::from(retry_after));
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RateLimitLayer<S> {
+    limiter: Arc<RateLimiter>,
+    state: S,
+}
+
+impl<I, S: Clone> Layer<I> for RateLimitLayer<S> {
+    type Service = RateLimitService<I, S>;
+
+    fn layer(&self, inner: I) -> Self::Service {
+        RateLimitService {
+            inner,
+            limiter: self.limiter.clone(),
+            state: self.state.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RateLimitService<I, S> {
+    inner: I,
+    limiter: Arc<RateLimiter>,
+    state: S,
+}
+
+impl<I, S> Service<Request> for RateLimitService<I, S>
+where
+    I: Service<Request, Response = Response, Error = Infallible> + Clone + Send + 'static,
+    I::Future: Send + 'static,
+    S: Clone + Send + Sync + 'static,
+    AuthUser: FromRequestParts<S>,
+{
+    type Response = Response;
+    type Error = Infallible;
+    type Future = BoxFuture<'static, Result<Response, Infallible>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let limiter = self.limiter.clone();
+        let state = self.state.clone();
+
+        Box::pin(async move {
+            let (mut parts, body) = request.into_parts();
+            let key = limiter.key_for(&mut parts, &state).await;
+            let request = Request::from_parts(parts, body);
+
+            // 后端不可用时放行，避免限流器本身造成故障
+            let decision = match limiter.backend.acquire(&key, limiter.quota).await {
+                Ok(bucket) => limiter.decision(bucket),
+                Err(e) => {
+                    eprintln!("rate limiter unavailable: {e}");
+                    return inner.call(request).await;
+                }
+            };
+
+            let mut response = if decision.allowed {
+                inner.call(request).await?
+            } else {
+                (
+                    StatusCode::TOO_MANY_REQUESTS,
+                    Json(json!({"error": "rate limit exceeded"})),
+                )
+                    .into_response()
+            };
+            decision.apply_headers(response.headers_mut());
+            Ok(response)
+        })
+    }
+}
+
+pub async fn connect(config: &RateLimitConfig) -> anyhow::Result<Arc<dyn RateLimitBackend>> {
+    match config.backend.as_str() {
+        "memory" => Ok(Arc::new(MemoryBackend::new())),
+        #[cfg(feature = "redis")]
+        "redis" => Ok(Arc::new(RedisBackend::connect(&config.redis_url).await?)),
+        #[cfg(not(feature = "redis"))]
+        "redis" => anyhow::bail!("built without the `redis` feature"),
+        other => anyhow::bail!("unknown rate limit backend: {other}"),
+    }
+}
+
+pub async fn purge_periodically(backend: Arc<dyn RateLimitBackend>, token: CancellationToken) {
+    let mut interval = tokio::time::interval(Duration::from_secs(60));
+    loop {
+        tokio::select! {
+            _ = interval.tick() => backend.purge().await,
+            _ = token.cancelled() => break,
+        }
+    }
+}
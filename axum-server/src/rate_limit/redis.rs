@@ -0,0 +1,62 @@
+use async_trait::async_trait;
+use redis::{aio::ConnectionManager, Script};
+
+use super::{BucketState, Quota, RateLimitBackend};
+
+// 在 Redis 里原子地完成补充和扣减，时间取 Redis 服务器时钟，多节点共享同一个桶
+const TOKEN_BUCKET: &str = r#"
+local capacity = tonumber(ARGV[1])
+local rate = tonumber(ARGV[2])
+local time = redis.call('TIME')
+local now = tonumber(time[1]) * 1000 + math.floor(tonumber(time[2]) / 1000)
+
+local state = redis.call('HMGET', KEYS[1], 'tokens', 'ts')
+local tokens = tonumber(state[1]) or capacity
+local ts = tonumber(state[2]) or now
+tokens = math.min(capacity, tokens + math.max(0, now - ts) * rate / 1000)
+
+local allowed = 0
+if tokens >= 1 then
+    tokens = tokens - 1
+    allowed = 1
+end
+
+redis.call('HSET', KEYS[1], 'tokens', tostring(tokens), 'ts', now)
+redis.call('PEXPIRE', KEYS[1], math.ceil((capacity - tokens) / rate * 1000) + 1000)
+return {allowed, tostring(tokens)}
+"#;
+
+/// 集群部署时使用的 Redis 兼容后端
+pub struct RedisBackend {
+    connection: ConnectionManager,
+    script: Script,
+}
+
+impl RedisBackend {
+    pub async fn connect(url: &str) -> anyhow::Result<Self> {
+        let client = redis::Client::open(url)?;
+        Ok(Self {
+            connection: ConnectionManager::new(client).await?,
+            script: Script::new(TOKEN_BUCKET),
+        })
+    }
+}
+
+#[async_trait]
+impl RateLimitBackend for RedisBackend {
+    async fn acquire(&self, key: &str, quota: Quota) -> anyhow::Result<BucketState> {
+        let mut connection = self.connection.clone();
+        let (allowed, tokens): (i64, String) = self
+            .script
+            .key(key)
+            .arg(quota.burst)
+            .arg(quota.per_second())
+            .invoke_async(&mut connection)
+            .await?;
+
+        Ok(BucketState {
+            allowed: allowed == 1,
+            tokens: tokens.parse()?,
+        })
+    }
+}
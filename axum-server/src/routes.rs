@@ -1,12 +1,44 @@
+use tower::ServiceBuilder;
+
+use crate::auth::Scope;
+use crate::handlers::{
+    auth::{
+        create_client_certificate, create_token, get_client_certificates, get_sessions, get_tokens,
+        login, logout, oidc_callback, oidc_login, refresh, register, revoke_client_certificate,
+        revoke_other_sessions, revoke_session, revoke_token,
+    },
+    calendar::{calendar_feed, create_feed, revoke_feed},
+    graphql::{graphql, graphql_ws},
+    health::{healthz, metrics, readyz},
+    import_export::{export_tasks, import_tasks},
+    notification::{
+        get_notifications, get_preferences, read_all_notifications, read_notification, unsubscribe,
+        unsubscribe_page, update_preferences,
+    },
+    stats::get_stats,
+    task::{
+        add_assignees, create_task, delete_task, get_task, get_tasks, move_task, remove_assignee,
+        set_assignees, update_task,
+    },
+    time_entry::{
+        create_time_entry, delete_time_entry, get_time_entries, get_timer, get_timesheet,
+        start_timer, stop_timer,
+    },
+    view::{create_view, delete_view, get_view, get_views, update_view},
+};
+use crate::idempotency::idempotency;
+use crate::middleware::{require_scope, RequiredScope};
+use crate::rate_limit::{KeyStrategy, Quota, RateLimitLayer, RateLimiter};
+use crate::state::AppState;
 use axum::{
+    extract::DefaultBodyLimit,
+    middleware,
     response::IntoResponse,
-    routing::{delete, get, post, put},
+    routing::{delete, get, patch, post, put},
     Router,
 };
-use sqlx::PgPool;
-use std::sync::Arc;
 
-use crate::handlers::task::{create_task, delete_task, get_task, get_tasks, update_task};
+const IMPORT_BODY_LIMIT: usize = 10 * 1024 * 1024;
 
 async fn root() -> impl IntoResponse {
     "Hello root"
@@ -24,18 +56,206 @@ async fn foo_bar() -> impl IntoResponse {
     "Hello foo_bar"
 }
 
-pub fn create_routes() -> Router<Arc<PgPool>> {
+fn rate_limit(state: &AppState, group: &'static str, quota: Quota) -> RateLimitLayer<AppState> {
+    let limits = &state.config.rate_limit;
+    RateLimiter::new(
+        state.rate_limit.clone(),
+        group,
+        quota,
+        limits.key,
+        limits.trusted_proxies,
+    )
+    .layer(state.clone())
+}
+
+pub fn create_routes() -> Router<AppState> {
     Router::new()
         .route("/", get(root))
         .route("/foo", get(get_foo).post(post_foo))
         .route("/foo/bar", get(foo_bar))
 }
 
-pub fn task_routes() -> Router<Arc<PgPool>> {
-    Router::new()
+pub fn task_routes(state: &AppState) -> Router<AppState> {
+    let limits = &state.config.rate_limit;
+
+    let reads = Router::new()
         .route("/api/tasks", get(get_tasks))
-        .route("/api/tasks", post(create_task))
+        .route("/api/tasks/export", get(export_tasks))
+        .route("/api/tasks/stats", get(get_stats))
         .route("/api/tasks/:task_id", get(get_task))
+        .route("/api/tasks/:task_id/time-entries", get(get_time_entries))
+        .route("/api/timer", get(get_timer))
+        .route("/api/timesheet", get(get_timesheet))
+        .route("/api/views", get(get_views))
+        .route("/api/views/:view_id", get(get_view))
+        .route_layer(rate_limit(state, "tasks_read", limits.tasks_read))
+        .route_layer(middleware::from_fn_with_state(
+            RequiredScope::new(state, Scope::TasksRead),
+            require_scope,
+        ));
+
+    let writes = Router::new()
+        .route(
+            "/api/tasks",
+            post(create_task).layer(middleware::from_fn_with_state(state.clone(), idempotency)),
+        )
+        .route(
+            "/api/tasks/import",
+            post(import_tasks).layer(
+                ServiceBuilder::new()
+                    .layer(DefaultBodyLimit::max(IMPORT_BODY_LIMIT))
+                    .layer(middleware::from_fn_with_state(state.clone(), idempotency)),
+            ),
+        )
         .route("/api/tasks/:task_id", put(update_task))
         .route("/api/tasks/:task_id", delete(delete_task))
+        .route("/api/tasks/:task_id/move", patch(move_task))
+        .route("/api/tasks/:task_id/timer", post(start_timer))
+        .route("/api/timer/stop", post(stop_timer))
+        .route("/api/tasks/:task_id/time-entries", post(create_time_entry))
+        .route("/api/time-entries/:entry_id", delete(delete_time_entry))
+        .route(
+            "/api/tasks/:task_id/assignees",
+            post(add_assignees).put(set_assignees),
+        )
+        .route(
+            "/api/tasks/:task_id/assignees/:user_id",
+            delete(remove_assignee),
+        )
+        .route("/api/views", post(create_view))
+        .route("/api/views/:view_id", put(update_view))
+        .route("/api/views/:view_id", delete(delete_view))
+        .route_layer(rate_limit(state, "tasks_write", limits.tasks_write))
+        .route_layer(middleware::from_fn_with_state(
+            RequiredScope::new(state, Scope::TasksWrite),
+            require_scope,
+        ));
+
+    reads.merge(writes)
+}
+
+pub fn auth_routes(state: &AppState) -> Router<AppState> {
+    let limits = &state.config.rate_limit;
+
+    // 登录相关接口还没有用户，只能按 IP 限流
+    let public = Router::new()
+        .route("/api/auth/register", post(register))
+        .route("/api/auth/login", post(login))
+        .route("/api/auth/refresh", post(refresh))
+        .route("/api/auth/oidc/login", get(oidc_login))
+        .route("/api/auth/oidc/callback", get(oidc_callback))
+        .route_layer(
+            RateLimiter::new(
+                state.rate_limit.clone(),
+                "auth",
+                limits.auth,
+                KeyStrategy::Ip,
+                limits.trusted_proxies,
+            )
+            .layer(state.clone()),
+        );
+
+    // 管理登录凭据本身需要 admin，避免只读 token 被用来生成更多 token
+    let admin = Router::new()
+        .route(
+            "/api/auth/sessions",
+            get(get_sessions).delete(revoke_other_sessions),
+        )
+        .route("/api/auth/sessions/:session_id", delete(revoke_session))
+        .route("/api/auth/tokens", get(get_tokens).post(create_token))
+        .route("/api/auth/tokens/:token_id", delete(revoke_token))
+        .route(
+            "/api/auth/client-certificates",
+            get(get_client_certificates).post(create_client_certificate),
+        )
+        .route(
+            "/api/auth/client-certificates/:certificate_id",
+            delete(revoke_client_certificate),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            RequiredScope::new(state, Scope::Admin),
+            require_scope,
+        ));
+
+    public.route("/api/auth/logout", post(logout)).merge(admin)
+}
+
+pub fn calendar_routes(state: &AppState) -> Router<AppState> {
+    let manage = Router::new()
+        .route("/api/calendar/feed", post(create_feed).delete(revoke_feed))
+        .route_layer(middleware::from_fn_with_state(
+            RequiredScope::new(state, Scope::TasksRead),
+            require_scope,
+        ));
+
+    Router::new()
+        // 订阅地址不带登录 token，只靠 URL 里的 feed token 鉴权
+        .route("/calendar/:file", get(calendar_feed))
+        .merge(manage)
+        .route_layer(rate_limit(
+            state,
+            "calendar",
+            state.config.rate_limit.tasks_read,
+        ))
+}
+
+pub fn notification_routes(state: &AppState) -> Router<AppState> {
+    let limits = &state.config.rate_limit;
+
+    let read = Router::new()
+        .route("/api/notifications", get(get_notifications))
+        .route("/api/notifications/preferences", get(get_preferences))
+        .route_layer(middleware::from_fn_with_state(
+            RequiredScope::new(state, Scope::TasksRead),
+            require_scope,
+        ));
+    let write = Router::new()
+        .route("/api/notifications/preferences", put(update_preferences))
+        .route("/api/notifications/read", post(read_all_notifications))
+        .route(
+            "/api/notifications/:notification_id/read",
+            post(read_notification),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            RequiredScope::new(state, Scope::TasksWrite),
+            require_scope,
+        ));
+
+    // 退订链接不带登录 token，只靠 URL 里的 token 鉴权，和登录接口一样按 IP 限流
+    let unsubscribe = Router::new()
+        .route(
+            "/unsubscribe/:token",
+            get(unsubscribe_page).post(unsubscribe),
+        )
+        .route_layer(
+            RateLimiter::new(
+                state.rate_limit.clone(),
+                "unsubscribe",
+                limits.auth,
+                KeyStrategy::Ip,
+                limits.trusted_proxies,
+            )
+            .layer(state.clone()),
+        );
+
+    read.merge(write).merge(unsubscribe)
+}
+
+/// scope 在各个字段上检查；查询和修改共用读接口的配额
+pub fn graphql_routes(state: &AppState) -> Router<AppState> {
+    Router::new()
+        .route("/graphql", post(graphql))
+        .route("/graphql/ws", get(graphql_ws))
+        .route_layer(rate_limit(
+            state,
+            "graphql",
+            state.config.rate_limit.tasks_read,
+        ))
+}
+
+pub fn health_routes() -> Router<AppState> {
+    Router::new()
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .route("/metrics", get(metrics))
 }
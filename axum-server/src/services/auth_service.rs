@@ -0,0 +1,600 @@
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use chrono::{Duration, Utc};
+use jsonwebtoken::{
+    decode, decode_header, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgConnection, PgPool};
+use std::sync::OnceLock;
+
+use crate::{
+    auth::{AuthUser, Scope, PERSONAL_TOKEN_PREFIX},
+    config::AuthConfig,
+    db::user_repo,
+    error::{AppError, HttpError},
+    models::{
+        token::{
+            ClientCertificateMapping, CreateClientCertificateMapping, CreatePersonalAccessToken,
+            CreatedPersonalAccessToken, PersonalAccessToken,
+        },
+        user::{Session, TokenResponse},
+    },
+    tls::ClientCertificate,
+    token,
+};
+
+const MIN_PASSWORD_LEN: usize = 8;
+
+/// access token 的内容
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Claims {
+    pub sub: i32,
+    pub org: i32,
+    pub sid: i64,
+    pub iat: i64,
+    pub exp: i64,
+}
+
+/// 登录时记录在 session 上，方便用户辨认
+#[derive(Debug, Default)]
+pub struct ClientInfo {
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+}
+
+pub fn validate_password(password: &str) -> Result<(), HttpError> {
+    if password.chars().count() < MIN_PASSWORD_LEN {
+        return Err(HttpError::unprocessable(format!(
+            "password must be at least {MIN_PASSWORD_LEN} characters"
+        )));
+    }
+    Ok(())
+}
+
+pub async fn hash_password(password: String) -> anyhow::Result<String> {
+    tokio::task::spawn_blocking(move || {
+        let salt = SaltString::generate(&mut OsRng);
+        Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .map(|hash| hash.to_string())
+            .map_err(|e| anyhow::anyhow!("failed to hash password: {e}"))
+    })
+    .await?
+}
+
+/// 用户不存在时也跑一次校验，避免通过响应时间判断邮箱是否注册
+pub async fn verify_password(password: String, hash: Option<String>) -> bool {
+    static DUMMY_HASH: OnceLock<String> = OnceLock::new();
+
+    tokio::task::spawn_blocking(move || {
+        let dummy = DUMMY_HASH.get_or_init(|| {
+            let salt = SaltString::generate(&mut OsRng);
+            Argon2::default()
+                .hash_password(b"dummy password", &salt)
+                .unwrap()
+                .to_string()
+        });
+        let found = hash.is_some();
+        let hash = hash.unwrap_or_else(|| dummy.clone());
+        let verified = PasswordHash::new(&hash)
+            .map(|parsed| {
+                Argon2::default()
+                    .verify_password(password.as_bytes(), &parsed)
+                    .is_ok()
+            })
+            .unwrap_or(false);
+        found && verified
+    })
+    .await
+    .unwrap_or(false)
+}
+
+pub fn encode_access_token(
+    config: &AuthConfig,
+    user_id: i32,
+    organization_id: i32,
+    session_id: i64,
+) -> anyhow::Result<String> {
+    let now = Utc::now();
+    let claims = Claims {
+        sub: user_id,
+        org: organization_id,
+        sid: session_id,
+        iat: now.timestamp(),
+        exp: (now + Duration::from_std(config.access_token_ttl)?).timestamp(),
+    };
+    let key = config.keys.signing_key();
+    let mut header = Header::new(Algorithm::HS256);
+    header.kid = key.kid();
+    Ok(encode(
+        &header,
+        &claims,
+        &EncodingKey::from_secret(key.secret()),
+    )?)
+}
+
+/// access token 头里的 `kid`，轮换前签发的 token 没有
+pub fn access_token_kid(token: &str) -> Option<String> {
+    decode_header(token).ok()?.kid
+}
+
+pub fn decode_access_token(config: &AuthConfig, token: &str) -> anyhow::Result<Claims> {
+    let kid = access_token_kid(token);
+    let mut last_error = anyhow::anyhow!("unknown signing key");
+    for key in config.keys.verifying_keys(kid.as_deref()) {
+        match decode::<Claims>(
+            token,
+            &DecodingKey::from_secret(key.secret()),
+            &Validation::new(Algorithm::HS256),
+        ) {
+            Ok(data) => return Ok(data.claims),
+            Err(e) => last_error = e.into(),
+        }
+    }
+    Err(last_error)
+}
+
+/// 注册新用户，同时为其创建一个组织
+pub async fn register(
+    pool: &PgPool,
+    config: &AuthConfig,
+    email: &str,
+    password: String,
+    name: &str,
+    client: ClientInfo,
+) -> Result<TokenResponse, AppError> {
+    let email = normalize_email(email)?;
+    validate_password(&password)?;
+    let password_hash = hash_password(password).await?;
+
+    let mut tx = pool.begin().await?;
+    let organization_id =
+        user_repo::create_organization(&mut *tx, if name.is_empty() { &email } else { name })
+            .await?;
+    let user_id =
+        user_repo::insert_user(&mut *tx, organization_id, &email, name, &password_hash).await?;
+    let user_id = user_id.ok_or_else(|| HttpError::unprocessable("email is already registered"))?;
+
+    let response = start_session(&mut tx, config, user_id, organization_id, client).await?;
+    tx.commit().await?;
+    Ok(response)
+}
+
+pub async fn login(
+    pool: &PgPool,
+    config: &AuthConfig,
+    email: &str,
+    password: String,
+    client: ClientInfo,
+) -> Result<TokenResponse, AppError> {
+    let user = user_repo::find_by_email(pool, email).await?;
+
+    let hash = user.as_ref().and_then(|user| user.password_hash.clone());
+    let verified = verify_password(password, hash).await;
+    let user = match user {
+        Some(user) if verified && user.disabled_at.is_none() => user,
+        _ => return Err(HttpError::unauthorized("invalid email or password").into()),
+    };
+
+    let mut tx = pool.begin().await?;
+    let response = start_session(&mut tx, config, user.id, user.organization_id, client).await?;
+    tx.commit().await?;
+    Ok(response)
+}
+
+pub(crate) async fn start_session(
+    conn: &mut PgConnection,
+    config: &AuthConfig,
+    user_id: i32,
+    organization_id: i32,
+    client: ClientInfo,
+) -> Result<TokenResponse, AppError> {
+    let session_id: i64 = sqlx::query_scalar(
+        "INSERT INTO sessions (user_id, organization_id, user_agent, ip_address, expires_at)
+         VALUES ($1, $2, $3, $4, NOW() + make_interval(secs => $5))
+         RETURNING id",
+    )
+    .bind(user_id)
+    .bind(organization_id)
+    .bind(client.user_agent)
+    .bind(client.ip_address)
+    .bind(config.refresh_token_ttl.as_secs_f64())
+    .fetch_one(&mut *conn)
+    .await?;
+
+    issue_tokens(conn, config, user_id, organization_id, session_id).await
+}
+
+async fn issue_tokens(
+    conn: &mut PgConnection,
+    config: &AuthConfig,
+    user_id: i32,
+    organization_id: i32,
+    session_id: i64,
+) -> Result<TokenResponse, AppError> {
+    let refresh_token = token::generate();
+    sqlx::query(
+        "INSERT INTO refresh_tokens (session_id, token_hash, expires_at)
+         VALUES ($1, $2, NOW() + make_interval(secs => $3))",
+    )
+    .bind(session_id)
+    .bind(token::hash(&refresh_token))
+    .bind(config.refresh_token_ttl.as_secs_f64())
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(TokenResponse {
+        access_token: encode_access_token(config, user_id, organization_id, session_id)?,
+        token_type: "Bearer",
+        expires_in: config.access_token_ttl.as_secs(),
+        refresh_token,
+    })
+}
+
+#[derive(FromRow)]
+struct RefreshRow {
+    id: i64,
+    session_id: i64,
+    user_id: i32,
+    organization_id: i32,
+    expired: bool,
+    used: bool,
+    session_active: bool,
+}
+
+/// 轮换 refresh token。已经用过的 token 再次出现时，整个 session 被撤销。
+pub async fn refresh(
+    pool: &PgPool,
+    config: &AuthConfig,
+    refresh_token: &str,
+) -> Result<TokenResponse, AppError> {
+    let mut tx = pool.begin().await?;
+
+    let row = sqlx::query_as::<_, RefreshRow>(
+        "SELECT rt.id, rt.session_id, s.user_id, s.organization_id,
+                rt.expires_at <= NOW() AS expired,
+                rt.used_at IS NOT NULL AS used,
+                (s.revoked_at IS NULL AND s.expires_at > NOW() AND u.disabled_at IS NULL)
+                    AS session_active
+         FROM refresh_tokens rt
+         JOIN sessions s ON s.id = rt.session_id
+         JOIN users u ON u.id = s.user_id
+         WHERE rt.token_hash = $1
+         FOR UPDATE OF rt, s",
+    )
+    .bind(token::hash(refresh_token))
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| HttpError::unauthorized("invalid refresh token"))?;
+
+    if row.used {
+        revoke(&mut tx, row.session_id, "refresh_token_reuse").await?;
+        tx.commit().await?;
+        return Err(
+            HttpError::unauthorized("refresh token reuse detected, session revoked").into(),
+        );
+    }
+    if row.expired || !row.session_active {
+        return Err(HttpError::unauthorized("refresh token expired or revoked").into());
+    }
+
+    sqlx::query("UPDATE refresh_tokens SET used_at = NOW() WHERE id = $1")
+        .bind(row.id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("UPDATE sessions SET last_used_at = NOW() WHERE id = $1")
+        .bind(row.session_id)
+        .execute(&mut *tx)
+        .await?;
+
+    let response = issue_tokens(
+        &mut tx,
+        config,
+        row.user_id,
+        row.organization_id,
+        row.session_id,
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(response)
+}
+
+async fn revoke(conn: &mut PgConnection, session_id: i64, reason: &str) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "UPDATE sessions SET revoked_at = NOW(), revoked_reason = $2
+         WHERE id = $1 AND revoked_at IS NULL",
+    )
+    .bind(session_id)
+    .bind(reason)
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// 撤销用户的某个 session，返回是否存在
+pub async fn revoke_session(
+    pool: &PgPool,
+    user_id: i32,
+    session_id: i64,
+    reason: &str,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query(
+        "UPDATE sessions SET revoked_at = NOW(), revoked_reason = $3
+         WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL",
+    )
+    .bind(session_id)
+    .bind(user_id)
+    .bind(reason)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// 撤销除 `keep` 以外的所有 session，返回撤销的数量
+pub async fn revoke_other_sessions(
+    pool: &PgPool,
+    user_id: i32,
+    keep: Option<i64>,
+) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query(
+        "UPDATE sessions SET revoked_at = NOW(), revoked_reason = 'revoked_by_user'
+         WHERE user_id = $1 AND revoked_at IS NULL AND id IS DISTINCT FROM $2",
+    )
+    .bind(user_id)
+    .bind(keep)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+pub async fn list_sessions(
+    pool: &PgPool,
+    user_id: i32,
+    current: Option<i64>,
+) -> Result<Vec<Session>, sqlx::Error> {
+    sqlx::query_as::<_, Session>(
+        "SELECT id, user_agent, ip_address, created_at, last_used_at, expires_at,
+                id IS NOT DISTINCT FROM $2 AS current
+         FROM sessions
+         WHERE user_id = $1 AND revoked_at IS NULL AND expires_at > NOW()
+         ORDER BY last_used_at DESC",
+    )
+    .bind(user_id)
+    .bind(current)
+    .fetch_all(pool)
+    .await
+}
+
+/// access token 对应的 session 是否仍然有效（没有登出、没有被撤销、用户没有被禁用）
+pub async fn session_is_active(
+    pool: &PgPool,
+    user_id: i32,
+    session_id: i64,
+) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar(
+        "SELECT EXISTS (
+             SELECT 1 FROM sessions s JOIN users u ON u.id = s.user_id
+             WHERE s.id = $1 AND s.user_id = $2
+               AND s.revoked_at IS NULL AND s.expires_at > NOW()
+               AND u.disabled_at IS NULL
+         )",
+    )
+    .bind(session_id)
+    .bind(user_id)
+    .fetch_one(pool)
+    .await
+}
+
+/// 创建 personal access token；不能授予调用者自己没有的 scope
+pub async fn create_personal_token(
+    pool: &PgPool,
+    auth_user: &AuthUser,
+    request: &CreatePersonalAccessToken,
+) -> Result<CreatedPersonalAccessToken, AppError> {
+    for scope in &request.scopes {
+        auth_user.require_scope(*scope)?;
+    }
+
+    let token = format!("{PERSONAL_TOKEN_PREFIX}{}", token::generate());
+    let mut scopes: Vec<&str> = request.scopes.iter().map(|scope| scope.as_str()).collect();
+    scopes.sort_unstable();
+    scopes.dedup();
+
+    let info = sqlx::query_as::<_, PersonalAccessToken>(
+        "INSERT INTO personal_access_tokens
+             (user_id, organization_id, name, token_prefix, token_hash, scopes, expires_at)
+         VALUES ($1, $2, $3, $4, $5, $6, $7)
+         RETURNING id, name, token_prefix, scopes, created_at, last_used_at, expires_at",
+    )
+    .bind(auth_user.user_id)
+    .bind(auth_user.organization_id)
+    .bind(request.name.trim())
+    .bind(&token[..PERSONAL_TOKEN_PREFIX.len() + 8])
+    .bind(token::hash(&token))
+    .bind(scopes)
+    .bind(request.expires_at)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(CreatedPersonalAccessToken { info, token })
+}
+
+pub async fn list_personal_tokens(
+    pool: &PgPool,
+    user_id: i32,
+) -> Result<Vec<PersonalAccessToken>, sqlx::Error> {
+    sqlx::query_as::<_, PersonalAccessToken>(
+        "SELECT id, name, token_prefix, scopes, created_at, last_used_at, expires_at
+         FROM personal_access_tokens
+         WHERE user_id = $1 AND revoked_at IS NULL
+         ORDER BY created_at DESC",
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn revoke_personal_token(
+    pool: &PgPool,
+    user_id: i32,
+    token_id: i32,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query(
+        "UPDATE personal_access_tokens SET revoked_at = NOW()
+         WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL",
+    )
+    .bind(token_id)
+    .bind(user_id)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// 登记客户端证书，和 personal access token 一样不能授予调用者没有的 scope
+pub async fn create_client_certificate(
+    pool: &PgPool,
+    auth_user: &AuthUser,
+    request: &CreateClientCertificateMapping,
+) -> Result<ClientCertificateMapping, AppError> {
+    for scope in &request.scopes {
+        auth_user.require_scope(*scope)?;
+    }
+    let certificate = ClientCertificate::from_pem(&request.certificate)
+        .map_err(|e| HttpError::unprocessable(e.to_string()))?;
+    let mut scopes: Vec<&str> = request.scopes.iter().map(|scope| scope.as_str()).collect();
+    scopes.sort_unstable();
+    scopes.dedup();
+
+    sqlx::query_as::<_, ClientCertificateMapping>(
+        "INSERT INTO client_certificates (user_id, organization_id, name, fingerprint_sha256, scopes)
+         VALUES ($1, $2, $3, $4, $5)
+         ON CONFLICT (fingerprint_sha256) DO NOTHING
+         RETURNING id, name, fingerprint_sha256, scopes, created_at, last_used_at",
+    )
+    .bind(auth_user.user_id)
+    .bind(auth_user.organization_id)
+    .bind(request.name.trim())
+    .bind(&certificate.fingerprint)
+    .bind(scopes)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| HttpError::unprocessable("certificate is already registered").into())
+}
+
+pub async fn list_client_certificates(
+    pool: &PgPool,
+    user_id: i32,
+) -> Result<Vec<ClientCertificateMapping>, sqlx::Error> {
+    sqlx::query_as::<_, ClientCertificateMapping>(
+        "SELECT id, name, fingerprint_sha256, scopes, created_at, last_used_at
+         FROM client_certificates
+         WHERE user_id = $1 AND revoked_at IS NULL
+         ORDER BY created_at DESC",
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn revoke_client_certificate(
+    pool: &PgPool,
+    user_id: i32,
+    certificate_id: i32,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query(
+        "UPDATE client_certificates SET revoked_at = NOW()
+         WHERE id = $1 AND user_id = $2 AND revoked_at IS NULL",
+    )
+    .bind(certificate_id)
+    .bind(user_id)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// 按指纹找到登记过的客户端证书。证书链已经在 TLS 握手时校验过
+pub async fn authenticate_client_certificate(
+    pool: &PgPool,
+    certificate: &ClientCertificate,
+) -> Result<Option<AuthUser>, sqlx::Error> {
+    let row = sqlx::query_as::<_, CredentialRow>(
+        "WITH found AS (
+             SELECT c.id, c.user_id, c.organization_id, c.scopes
+             FROM client_certificates c
+             JOIN users u ON u.id = c.user_id
+             WHERE c.fingerprint_sha256 = $1 AND c.revoked_at IS NULL AND u.disabled_at IS NULL
+         ), touched AS (
+             UPDATE client_certificates SET last_used_at = NOW()
+             WHERE id IN (SELECT id FROM found)
+               AND (last_used_at IS NULL OR last_used_at < NOW() - INTERVAL '1 minute')
+         )
+         SELECT user_id, organization_id, scopes FROM found",
+    )
+    .bind(&certificate.fingerprint)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row.map(CredentialRow::into_auth_user))
+}
+
+/// personal access token 和客户端证书查出来的都是这三列
+#[derive(FromRow)]
+struct CredentialRow {
+    user_id: i32,
+    organization_id: i32,
+    scopes: Vec<String>,
+}
+
+impl CredentialRow {
+    fn into_auth_user(self) -> AuthUser {
+        AuthUser {
+            user_id: self.user_id,
+            organization_id: self.organization_id,
+            session_id: None,
+            scopes: self
+                .scopes
+                .iter()
+                .filter_map(|scope| scope.parse::<Scope>().ok())
+                .collect(),
+        }
+    }
+}
+
+/// 校验 personal access token，同时更新 last_used_at（一分钟内只写一次）
+pub async fn authenticate_personal_token(
+    pool: &PgPool,
+    token: &str,
+) -> Result<Option<AuthUser>, sqlx::Error> {
+    let row = sqlx::query_as::<_, CredentialRow>(
+        "WITH found AS (
+             SELECT t.id, t.user_id, t.organization_id, t.scopes
+             FROM personal_access_tokens t
+             JOIN users u ON u.id = t.user_id
+             WHERE t.token_hash = $1
+               AND t.revoked_at IS NULL
+               AND (t.expires_at IS NULL OR t.expires_at > NOW())
+               AND u.disabled_at IS NULL
+         ), touched AS (
+             UPDATE personal_access_tokens SET last_used_at = NOW()
+             WHERE id IN (SELECT id FROM found)
+               AND (last_used_at IS NULL OR last_used_at < NOW() - INTERVAL '1 minute')
+         )
+         SELECT user_id, organization_id, scopes FROM found",
+    )
+    .bind(token::hash(token))
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row.map(CredentialRow::into_auth_user))
+}
+
+pub(crate) fn normalize_email(email: &str) -> Result<String, HttpError> {
+    let email = email.trim().to_lowercase();
+    match email.split_once('@') {
+        Some((local, domain)) if !local.is_empty() && domain.contains('.') => Ok(email),
+        _ => Err(HttpError::unprocessable("email is not valid")),
+    }
+}
@@ -0,0 +1,322 @@
+use async_stream::try_stream;
+use axum::body::Bytes;
+use chrono::{DateTime, Utc};
+use futures::{Stream, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use sqlx::{Acquire, PgPool};
+use std::{str::FromStr, sync::Arc};
+
+use crate::{
+    auth::AuthUser,
+    db::{routing::DbRouter, task_repo, tenant},
+    error::{AppError, HttpError},
+    models::task::{CreateTask, Task},
+};
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    Csv,
+    #[default]
+    Json,
+    Ndjson,
+}
+
+impl Format {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Format::Csv => "text/csv; charset=utf-8",
+            Format::Json => "application/json",
+            Format::Ndjson => "application/x-ndjson",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Csv => "csv",
+            Format::Json => "json",
+            Format::Ndjson => "ndjson",
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(Format::Csv),
+            "json" => Ok(Format::Json),
+            "ndjson" => Ok(Format::Ndjson),
+            _ => anyhow::bail!("unknown format: {s}, expected csv, json or ndjson"),
+        }
+    }
+}
+
+/// 逐行从数据库读取并编码，不会把所有任务读进内存
+pub fn export_stream(
+    db: Arc<DbRouter>,
+    auth_user: AuthUser,
+    format: Format,
+) -> impl Stream<Item = anyhow::Result<Bytes>> {
+    try_stream! {
+        // 只读事务，流结束后丢弃即可
+        let mut tx = db.begin_read(&auth_user).await?;
+        let mut tasks = sqlx::query_as::<_, Task>(
+            "SELECT * FROM tasks WHERE user_id = $1 ORDER BY id",
+        )
+        .bind(auth_user.user_id)
+        .fetch(&mut *tx);
+
+        if let Format::Json = format {
+            yield Bytes::from_static(b"[");
+        }
+
+        let mut first = true;
+        while let Some(task) = tasks.try_next().await? {
+            yield encode(format, &task, first)?;
+            first = false;
+        }
+
+        if let Format::Json = format {
+            yield Bytes::from_static(b"]");
+        }
+    }
+}
+
+fn encode(format: Format, task: &Task, first: bool) -> anyhow::Result<Bytes> {
+    let mut buffer = Vec::new();
+    match format {
+        Format::Csv => {
+            let mut writer = csv::WriterBuilder::new()
+                .has_headers(first)
+                .from_writer(&mut buffer);
+            writer.serialize(CsvTask::from(task))?;
+            writer.flush()?;
+        }
+        Format::Json => {
+            if !first {
+                buffer.push(b',');
+            }
+            serde_json::to_writer(&mut buffer, task)?;
+        }
+        Format::Ndjson => {
+            serde_json::to_writer(&mut buffer, task)?;
+            buffer.push(b'\n');
+        }
+    }
+    Ok(Bytes::from(buffer))
+}
+
+/// CSV 单元格不能放数组，标签用 `;` 连接
+#[derive(Serialize)]
+struct CsvTask<'a> {
+    id: i32,
+    title: &'a str,
+    description: &'a str,
+    category: &'a str,
+    priority: i32,
+    status: &'a str,
+    due_date: DateTime<Utc>,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+    user_id: i32,
+    recurrence: Option<&'a str>,
+    tags: String,
+    estimate_seconds: Option<i32>,
+    time_spent_seconds: i64,
+}
+
+impl<'a> From<&'a Task> for CsvTask<'a> {
+    fn from(task: &'a Task) -> Self {
+        Self {
+            id: task.id,
+            title: &task.title,
+            description: &task.description,
+            category: &task.category,
+            priority: task.priority,
+            status: &task.status,
+            due_date: task.due_date,
+            created_at: task.created_at,
+            updated_at: task.updated_at,
+            user_id: task.user_id,
+            recurrence: task.recurrence.as_deref(),
+            tags: task.tags.join(";"),
+            estimate_seconds: task.estimate_seconds,
+            time_spent_seconds: task.time_spent_seconds,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct CsvRow {
+    title: String,
+    description: String,
+    category: String,
+    priority: i32,
+    due_date: DateTime<Utc>,
+    #[serde(default)]
+    recurrence: Option<String>,
+    #[serde(default)]
+    tags: Option<String>,
+    #[serde(default)]
+    estimate_seconds: Option<i32>,
+}
+
+impl From<CsvRow> for CreateTask {
+    fn from(row: CsvRow) -> Self {
+        Self {
+            title: row.title,
+            description: row.description,
+            category: row.category,
+            priority: row.priority,
+            due_date: row.due_date,
+            recurrence: row.recurrence.filter(|r| !r.is_empty()),
+            tags: row
+                .tags
+                .unwrap_or_default()
+                .split(';')
+                .map(str::trim)
+                .filter(|tag| !tag.is_empty())
+                .map(str::to_owned)
+                .collect(),
+            estimate_seconds: row.estimate_seconds,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RowStatus {
+    Valid,
+    Created,
+    Invalid,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RowReport {
+    /// 从 1 开始。CSV 是表头之后的第几条记录，JSON 是数组里的第几个元素，
+    /// NDJSON 是文件里的行号（空行也计数）
+    pub row: usize,
+    pub status: RowStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<i32>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    pub total: usize,
+    pub valid: usize,
+    pub invalid: usize,
+    pub rows: Vec<RowReport>,
+}
+
+/// 校验每一行；合法的行在同一个事务里写入，`dry_run` 时只校验。
+/// 写入时被数据库拒绝的行和校验失败的行一样报告为 `invalid`
+pub async fn import_tasks(
+    pool: &PgPool,
+    auth_user: &AuthUser,
+    format: Format,
+    body: &[u8],
+    dry_run: bool,
+) -> Result<ImportReport, AppError> {
+    let rows = parse_rows(format, body)?;
+
+    let mut reports: Vec<RowReport> = rows
+        .iter()
+        .map(|(number, row)| {
+            let errors = match row {
+                Ok(task) => task.validate().err().unwrap_or_default(),
+                Err(e) => vec![e.clone()],
+            };
+            RowReport {
+                row: *number,
+                status: if errors.is_empty() {
+                    RowStatus::Valid
+                } else {
+                    RowStatus::Invalid
+                },
+                id: None,
+                errors,
+            }
+        })
+        .collect();
+
+    if !dry_run {
+        let mut tx = tenant::begin(pool, auth_user).await?;
+        for ((_, row), report) in rows.iter().zip(reports.iter_mut()) {
+            let (Ok(task), RowStatus::Valid) = (row, &report.status) else {
+                continue;
+            };
+            // 每行一个 savepoint：数据库拒绝的行只回滚自己，记进这一行的错误
+            let mut savepoint = tx.begin().await?;
+            match task_repo::insert_task(&mut *savepoint, auth_user.user_id, task).await {
+                Ok(created) => {
+                    savepoint.commit().await?;
+                    report.id = Some(created.id);
+                    report.status = RowStatus::Created;
+                }
+                Err(sqlx::Error::Database(e)) => {
+                    savepoint.rollback().await?;
+                    report.status = RowStatus::Invalid;
+                    report
+                        .errors
+                        .push(format!("could not be saved: {}", e.message()));
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+        tx.commit().await?;
+    }
+
+    let valid = reports.iter().filter(|r| r.errors.is_empty()).count();
+    Ok(ImportReport {
+        dry_run,
+        total: reports.len(),
+        valid,
+        invalid: reports.len() - valid,
+        rows: reports,
+    })
+}
+
+type ParsedRow = Result<CreateTask, String>;
+
+/// 每一行带上报告里的行号，见 `RowReport::row`
+fn parse_rows(format: Format, body: &[u8]) -> Result<Vec<(usize, ParsedRow)>, HttpError> {
+    let rows = match format {
+        Format::Csv => {
+            let mut reader = csv::Reader::from_reader(body);
+            reader
+                .deserialize::<CsvRow>()
+                .map(|row| row.map(CreateTask::from).map_err(|e| e.to_string()))
+                .enumerate()
+                .map(|(index, row)| (index + 1, row))
+                .collect()
+        }
+        Format::Json => {
+            let values: Vec<serde_json::Value> = serde_json::from_slice(body).map_err(|e| {
+                HttpError::bad_request(format!("expected a JSON array of tasks: {e}"))
+            })?;
+            values
+                .into_iter()
+                .map(|value| serde_json::from_value(value).map_err(|e| e.to_string()))
+                .enumerate()
+                .map(|(index, row)| (index + 1, row))
+                .collect()
+        }
+        // 先编号再跳过空行，报告里的行号和编辑器里看到的一致
+        Format::Ndjson => body
+            .split(|byte| *byte == b'\n')
+            .enumerate()
+            .filter(|(_, line)| !line.iter().all(u8::is_ascii_whitespace))
+            .map(|(index, line)| {
+                let row = serde_json::from_slice(line).map_err(|e| e.to_string());
+                (index + 1, row)
+            })
+            .collect(),
+    };
+    Ok(rows)
+}
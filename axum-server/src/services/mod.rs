@@ -0,0 +1,6 @@
+pub mod auth_service;
+pub mod import_export_service;
+pub mod notification_service;
+pub mod oidc_service;
+pub mod task_service;
+pub mod user_service;
@@ -0,0 +1,67 @@
+use sqlx::PgPool;
+
+use crate::{
+    auth::AuthUser,
+    db::notification_repo,
+    error::{AppError, HttpError},
+    models::notification::{
+        Notification, NotificationFilter, NotificationPreferences, UpdateNotificationPreferences,
+    },
+};
+
+const DEFAULT_NOTIFICATION_LIMIT: i64 = 50;
+const MAX_NOTIFICATION_LIMIT: i64 = 200;
+
+pub async fn get_preferences(
+    pool: &PgPool,
+    auth_user: &AuthUser,
+) -> Result<NotificationPreferences, AppError> {
+    Ok(notification_repo::get_preferences(pool, auth_user.user_id).await?)
+}
+
+pub async fn update_preferences(
+    pool: &PgPool,
+    auth_user: &AuthUser,
+    payload: &UpdateNotificationPreferences,
+) -> Result<NotificationPreferences, AppError> {
+    let mut tx = pool.begin().await?;
+    notification_repo::get_preferences(&mut *tx, auth_user.user_id).await?;
+    let preferences =
+        notification_repo::update_preferences(&mut *tx, auth_user.user_id, payload).await?;
+    tx.commit().await?;
+    Ok(preferences)
+}
+
+pub async fn list_notifications(
+    pool: &PgPool,
+    auth_user: &AuthUser,
+    filter: &NotificationFilter,
+) -> Result<Vec<Notification>, AppError> {
+    let limit = filter.limit.unwrap_or(DEFAULT_NOTIFICATION_LIMIT);
+    if !(1..=MAX_NOTIFICATION_LIMIT).contains(&limit) {
+        return Err(HttpError::bad_request(format!(
+            "limit must be between 1 and {MAX_NOTIFICATION_LIMIT}"
+        ))
+        .into());
+    }
+    Ok(
+        notification_repo::list_notifications(pool, auth_user.user_id, filter.unread, limit)
+            .await?,
+    )
+}
+
+pub async fn mark_read(
+    pool: &PgPool,
+    auth_user: &AuthUser,
+    notification_id: i64,
+) -> Result<Notification, AppError> {
+    Ok(
+        notification_repo::mark_read(pool, auth_user.user_id, notification_id)
+            .await?
+            .ok_or_else(|| HttpError::not_found("Notification not found"))?,
+    )
+}
+
+pub async fn mark_all_read(pool: &PgPool, auth_user: &AuthUser) -> Result<u64, AppError> {
+    Ok(notification_repo::mark_all_read(pool, auth_user.user_id).await?)
+}
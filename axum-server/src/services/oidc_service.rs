@@ -0,0 +1,371 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use jsonwebtoken::{
+    decode, decode_header,
+    jwk::{AlgorithmParameters, JwkSet},
+    Algorithm, DecodingKey, Validation,
+};
+use rand::RngCore;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use sqlx::{FromRow, PgPool};
+use tokio::sync::{OnceCell, RwLock};
+use url::Url;
+
+use crate::{
+    config::{AuthConfig, OidcConfig},
+    error::{AppError, HttpError},
+    models::user::TokenResponse,
+    services::auth_service::{self, ClientInfo},
+    token,
+};
+
+const LOGIN_STATE_TTL_SECS: f64 = 600.0;
+
+/// 只接受非对称签名，HS* 需要和提供方共享密钥，不适用于 JWKS
+const ALLOWED_ALGORITHMS: [Algorithm; 7] = [
+    Algorithm::RS256,
+    Algorithm::RS384,
+    Algorithm::RS512,
+    Algorithm::PS256,
+    Algorithm::PS384,
+    Algorithm::PS512,
+    Algorithm::ES256,
+];
+
+/// `/.well-known/openid-configuration` 里用到的字段
+#[derive(Debug, Deserialize)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenEndpointResponse {
+    id_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct IdTokenClaims {
+    pub iss: String,
+    pub sub: String,
+    pub nonce: Option<String>,
+    pub email: Option<String>,
+    #[serde(default)]
+    pub email_verified: bool,
+    pub name: Option<String>,
+}
+
+#[derive(FromRow)]
+struct LoginState {
+    nonce: String,
+    code_verifier: String,
+}
+
+/// OpenID Connect 客户端。provider 的元数据在第一次使用时获取，JWKS 遇到未知 `kid` 时刷新
+pub struct OidcClient {
+    config: OidcConfig,
+    http: reqwest::Client,
+    metadata: OnceCell<ProviderMetadata>,
+    jwks: RwLock<JwkSet>,
+}
+
+impl OidcClient {
+    pub fn new(config: OidcConfig) -> Self {
+        Self {
+            config,
+            http: reqwest::Client::new(),
+            metadata: OnceCell::new(),
+            jwks: RwLock::new(JwkSet { keys: Vec::new() }),
+        }
+    }
+
+    async fn metadata(&self) -> anyhow::Result<&ProviderMetadata> {
+        self.metadata
+            .get_or_try_init(|| async {
+                let url = format!(
+                    "{}/.well-known/openid-configuration",
+                    self.config.issuer_url.trim_end_matches('/')
+                );
+                let metadata: ProviderMetadata = self
+                    .http
+                    .get(url)
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .json()
+                    .await?;
+                Ok(metadata)
+            })
+            .await
+    }
+
+    /// 生成跳转到提供方的授权地址，state / nonce / PKCE verifier 存在数据库里
+    pub async fn authorization_url(&self, pool: &PgPool) -> anyhow::Result<String> {
+        let metadata = self.metadata().await?;
+        let state = token::generate();
+        let nonce = token::generate();
+        let mut verifier_bytes = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut verifier_bytes);
+        let code_verifier = URL_SAFE_NO_PAD.encode(verifier_bytes);
+        let code_challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()));
+
+        sqlx::query("DELETE FROM oidc_login_states WHERE expires_at <= NOW()")
+            .execute(pool)
+            .await?;
+        sqlx::query(
+            "INSERT INTO oidc_login_states (state_hash, nonce, code_verifier, expires_at)
+             VALUES ($1, $2, $3, NOW() + make_interval(secs => $4))",
+        )
+        .bind(token::hash(&state))
+        .bind(&nonce)
+        .bind(&code_verifier)
+        .bind(LOGIN_STATE_TTL_SECS)
+        .execute(pool)
+        .await?;
+
+        let mut url = Url::parse(&metadata.authorization_endpoint)?;
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &self.config.client_id)
+            .append_pair("redirect_uri", &self.config.redirect_url)
+            .append_pair("scope", &self.config.scopes)
+            .append_pair("state", &state)
+            .append_pair("nonce", &nonce)
+            .append_pair("code_challenge", &code_challenge)
+            .append_pair("code_challenge_method", "S256");
+        Ok(url.into())
+    }
+
+    /// 处理回调：用授权码换 ID token，校验后登录（必要时关联或创建本地用户）
+    pub async fn complete_login(
+        &self,
+        pool: &PgPool,
+        auth_config: &AuthConfig,
+        code: &str,
+        state: &str,
+        client: ClientInfo,
+    ) -> Result<TokenResponse, AppError> {
+        let login = sqlx::query_as::<_, LoginState>(
+            "DELETE FROM oidc_login_states WHERE state_hash = $1 AND expires_at > NOW()
+             RETURNING nonce, code_verifier",
+        )
+        .bind(token::hash(state))
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| HttpError::bad_request("unknown or expired login state"))?;
+
+        let id_token = self.exchange_code(code, &login.code_verifier).await?;
+        let claims = self.validate_id_token(&id_token, &login.nonce).await?;
+        self.sign_in(pool, auth_config, &claims, client).await
+    }
+
+    async fn exchange_code(&self, code: &str, code_verifier: &str) -> Result<String, AppError> {
+        let metadata = self.metadata().await?;
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", self.config.redirect_url.as_str()),
+            ("client_id", self.config.client_id.as_str()),
+            ("code_verifier", code_verifier),
+        ];
+        if let Some(secret) = &self.config.client_secret {
+            form.push(("client_secret", secret));
+        }
+
+        let response = self
+            .http
+            .post(&metadata.token_endpoint)
+            .form(&form)
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(HttpError::unauthorized(format!(
+                "identity provider rejected the authorization code ({})",
+                response.status()
+            ))
+            .into());
+        }
+        let body: TokenEndpointResponse = response.json().await?;
+        Ok(body.id_token)
+    }
+
+    /// 校验签名、issuer、audience、过期时间和 nonce
+    pub async fn validate_id_token(
+        &self,
+        id_token: &str,
+        nonce: &str,
+    ) -> Result<IdTokenClaims, AppError> {
+        let invalid = |reason: &str| HttpError::unauthorized(format!("invalid ID token: {reason}"));
+
+        let header = decode_header(id_token).map_err(|e| invalid(&e.to_string()))?;
+        if !ALLOWED_ALGORITHMS.contains(&header.alg) {
+            return Err(invalid("unsupported signing algorithm").into());
+        }
+        let kid = header.kid.ok_or_else(|| invalid("missing kid"))?;
+        let key = self.decoding_key(&kid).await?;
+
+        let metadata = self.metadata().await?;
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[&metadata.issuer]);
+        validation.set_audience(&[&self.config.client_id]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+
+        let claims = decode::<IdTokenClaims>(id_token, &key, &validation)
+            .map_err(|e| invalid(&e.to_string()))?
+            .claims;
+        if claims.nonce.as_deref() != Some(nonce) {
+            return Err(invalid("nonce mismatch").into());
+        }
+        Ok(claims)
+    }
+
+    async fn decoding_key(&self, kid: &str) -> Result<DecodingKey, AppError> {
+        if let Some(key) = find_key(&*self.jwks.read().await, kid)? {
+            return Ok(key);
+        }
+
+        // 提供方轮换了签名密钥，重新拉取一次
+        let metadata = self.metadata().await?;
+        let jwks: JwkSet = self
+            .http
+            .get(&metadata.jwks_uri)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let key = find_key(&jwks, kid)?;
+        *self.jwks.write().await = jwks;
+
+        key.ok_or_else(|| HttpError::unauthorized("invalid ID token: unknown signing key").into())
+    }
+
+    /// 按 (issuer, subject) 找到已关联的用户；否则用已验证的邮箱关联 `OIDC_ORGANIZATION_ID`
+    /// 组织里的已有用户，再否则创建新用户
+    async fn sign_in(
+        &self,
+        pool: &PgPool,
+        auth_config: &AuthConfig,
+        claims: &IdTokenClaims,
+        client: ClientInfo,
+    ) -> Result<TokenResponse, AppError> {
+        let mut tx = pool.begin().await?;
+
+        let linked: Option<(i32, i32, bool)> = sqlx::query_as(
+            "UPDATE user_identities i SET last_login_at = NOW()
+             FROM users u
+             WHERE u.id = i.user_id AND i.issuer = $1 AND i.subject = $2
+             RETURNING u.id, u.organization_id, u.disabled_at IS NOT NULL",
+        )
+        .bind(&claims.iss)
+        .bind(&claims.sub)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let (user_id, organization_id, disabled) = match linked {
+            Some(user) => user,
+            None => {
+                let email = claims
+                    .email
+                    .as_deref()
+                    .map(|email| email.trim().to_lowercase())
+                    .filter(|email| !email.is_empty())
+                    .ok_or_else(|| {
+                        HttpError::unprocessable("identity provider did not return an email")
+                    })?;
+
+                // 只在配置的组织里按邮箱关联，别的组织的账号不能被提供方接管
+                let existing: Option<(i32, i32, bool)> = match self.config.organization_id {
+                    Some(organization_id) if claims.email_verified => {
+                        sqlx::query_as(
+                            "SELECT id, organization_id, disabled_at IS NOT NULL
+                         FROM users WHERE email = $1 AND organization_id = $2",
+                        )
+                        .bind(&email)
+                        .bind(organization_id)
+                        .fetch_optional(&mut *tx)
+                        .await?
+                    }
+                    _ => None,
+                };
+
+                let user = match existing {
+                    Some(user) => user,
+                    None => self.provision_user(&mut tx, &email, claims).await?,
+                };
+
+                sqlx::query(
+                    "INSERT INTO user_identities (user_id, issuer, subject, email)
+                     VALUES ($1, $2, $3, $4)",
+                )
+                .bind(user.0)
+                .bind(&claims.iss)
+                .bind(&claims.sub)
+                .bind(&email)
+                .execute(&mut *tx)
+                .await?;
+                user
+            }
+        };
+
+        if disabled {
+            return Err(HttpError::unauthorized("user is disabled").into());
+        }
+
+        let response =
+            auth_service::start_session(&mut tx, auth_config, user_id, organization_id, client)
+                .await?;
+        tx.commit().await?;
+        Ok(response)
+    }
+
+    async fn provision_user(
+        &self,
+        conn: &mut sqlx::PgConnection,
+        email: &str,
+        claims: &IdTokenClaims,
+    ) -> Result<(i32, i32, bool), AppError> {
+        let name = claims.name.as_deref().unwrap_or_default().trim();
+        let organization_id = match self.config.organization_id {
+            Some(id) => id,
+            None => {
+                sqlx::query_scalar("INSERT INTO organizations (name) VALUES ($1) RETURNING id")
+                    .bind(if name.is_empty() { email } else { name })
+                    .fetch_one(&mut *conn)
+                    .await?
+            }
+        };
+
+        let user_id: Option<i32> = sqlx::query_scalar(
+            "INSERT INTO users (organization_id, email, name) VALUES ($1, $2, $3)
+             ON CONFLICT (email) DO NOTHING
+             RETURNING id",
+        )
+        .bind(organization_id)
+        .bind(email)
+        .bind(name)
+        .fetch_optional(&mut *conn)
+        .await?;
+
+        // 邮箱已被本地账号占用，但提供方没有验证过或账号不在配置的组织里，不能自动关联
+        let user_id = user_id.ok_or_else(|| {
+            HttpError::new(
+                axum::http::StatusCode::CONFLICT,
+                "an account with this email already exists and cannot be linked to this identity",
+            )
+        })?;
+        Ok((user_id, organization_id, false))
+    }
+}
+
+fn find_key(jwks: &JwkSet, kid: &str) -> Result<Option<DecodingKey>, AppError> {
+    let Some(jwk) = jwks.find(kid) else {
+        return Ok(None);
+    };
+    if matches!(jwk.algorithm, AlgorithmParameters::OctetKey(_)) {
+        return Err(HttpError::unauthorized("invalid ID token: symmetric signing key").into());
+    }
+    Ok(Some(DecodingKey::from_jwk(jwk)?))
+}
@@ -0,0 +1,1129 @@
+use async_stream::try_stream;
+use axum::extract::FromRef;
+use chrono::{Duration, Utc};
+use futures::{Stream, TryStreamExt};
+use sqlx::{FromRow, PgConnection, Postgres, QueryBuilder, Row};
+use std::{collections::HashSet, sync::Arc};
+
+use crate::{
+    auth::{AuthUser, Scope},
+    config::Config,
+    db::{notification_repo, routing::DbRouter, task_repo, tenant, user_repo, view_repo},
+    email::{self, Notification},
+    error::{AppError, HttpError},
+    jobs::{self, EnqueueOptions},
+    metrics::Metrics,
+    models::{
+        notification::NotificationKind,
+        task::{
+            CreateTask, MoveTask, Task, TaskFilter, TaskStatusChange, UpdateTask, MAX_ASSIGNEES,
+            TASK_SORTS,
+        },
+        time_entry::{
+            check_note, CreateTimeEntry, StartTimer, TimeEntry, Timesheet, TimesheetDay,
+            TimesheetEntry, TimesheetParams, TimesheetTask,
+        },
+        user::UserProfile,
+        view::{CreateView, SavedView, UpdateView},
+    },
+    rank::{self, RebalanceRanks},
+    state::AppState,
+    task_cache::TaskCache,
+    task_query::TaskQuery,
+};
+
+const MAX_VIEW_NAME_LEN: usize = 100;
+const DEFAULT_TIMESHEET_DAYS: i64 = 30;
+const MAX_TIMESHEET_DAYS: i64 = 366;
+
+/// 任务和视图的读写逻辑，REST、GraphQL 共用同一套校验和租户隔离。
+/// scope 检查由各自的入口负责
+#[derive(Clone)]
+pub struct TaskService {
+    db: Arc<DbRouter>,
+    config: Arc<Config>,
+    metrics: Arc<Metrics>,
+    cache: Arc<TaskCache>,
+}
+
+/// 负责人的变化，见 `TaskService::change_assignees`
+#[derive(Debug, Clone)]
+pub enum AssigneeChange {
+    Add(Vec<i32>),
+    Remove(i32),
+    Set(Vec<i32>),
+}
+
+impl AssigneeChange {
+    /// 变化之后的负责人，保持原来的顺序并去重
+    fn apply(self, current: &[i32]) -> Vec<i32> {
+        let mut assignees: Vec<i32> = match self {
+            Self::Add(user_ids) => current.iter().copied().chain(user_ids).collect(),
+            Self::Remove(user_id) => current
+                .iter()
+                .copied()
+                .filter(|id| *id != user_id)
+                .collect(),
+            Self::Set(user_ids) => user_ids,
+        };
+        let mut seen = HashSet::new();
+        assignees.retain(|id| seen.insert(*id));
+        assignees
+    }
+}
+
+impl FromRef<AppState> for TaskService {
+    fn from_ref(state: &AppState) -> Self {
+        Self::new(
+            state.db.clone(),
+            state.config.clone(),
+            state.metrics.clone(),
+            state.task_cache.clone(),
+        )
+    }
+}
+
+impl TaskService {
+    pub fn new(
+        db: Arc<DbRouter>,
+        config: Arc<Config>,
+        metrics: Arc<Metrics>,
+        cache: Arc<TaskCache>,
+    ) -> Self {
+        Self {
+            db,
+            config,
+            metrics,
+            cache,
+        }
+    }
+
+    /// 任务出现在所有者和负责人的列表里，写入后都要清掉
+    fn invalidate(&self, task: &Task) {
+        self.cache.invalidate(task.user_id);
+        for user_id in &task.assignee_ids {
+            self.cache.invalidate(*user_id);
+        }
+    }
+
+    pub async fn list_tasks(
+        &self,
+        auth_user: &AuthUser,
+        filter: &TaskFilter,
+    ) -> Result<Vec<Task>, AppError> {
+        self.stream_tasks(auth_user.clone(), filter.clone())
+            .try_collect()
+            .await
+    }
+
+    /// 按 id 顺序逐行返回任务，不会一次把结果读进内存。
+    /// 查询在一个只读的租户事务里执行，有健康的副本时读副本，流结束后丢弃即可
+    pub fn stream_tasks(
+        &self,
+        auth_user: AuthUser,
+        filter: TaskFilter,
+    ) -> impl Stream<Item = Result<Task, AppError>> + Send + 'static {
+        let db = self.db.clone();
+        try_stream! {
+            let mut tx = db.begin_read(&auth_user).await?;
+
+            let mut queries = Vec::new();
+            if let Some(view_id) = filter.view {
+                let view = view_repo::find_visible(&mut *tx, auth_user.user_id, view_id)
+                    .await?
+                    .ok_or_else(|| HttpError::not_found("View not found"))?;
+                queries.push(TaskQuery::parse(&view.query)?);
+            }
+            if let Some(q) = &filter.q {
+                queries.push(TaskQuery::parse(q)?);
+            }
+
+            let mut query = QueryBuilder::new("SELECT * FROM tasks WHERE ");
+            push_visible(&mut query, auth_user.user_id);
+
+            match filter.assignee.as_deref() {
+                None => {}
+                Some("none") => {
+                    query.push(" AND cardinality(assignee_ids) = 0");
+                }
+                Some(assignee) => {
+                    query.push(" AND ");
+                    query.push_bind(filter_user(assignee, &auth_user)?);
+                    query.push(" = ANY(assignee_ids)");
+                }
+            }
+
+            if let Some(reporter) = &filter.reporter {
+                query.push(" AND reporter_id = ");
+                query.push_bind(filter_user(reporter, &auth_user)?);
+            }
+
+            if let Some(category) = filter.category {
+                query.push(" AND category = ");
+                query.push_bind(category);
+            }
+
+            if let Some(priority) = filter.priority {
+                query.push(" AND priority = ");
+                query.push_bind(priority);
+            }
+
+            if let Some(status) = filter.status {
+                query.push(" AND status = ");
+                query.push_bind(status);
+            }
+
+            let now = Utc::now();
+            for task_query in &queries {
+                task_query.push_sql(&mut query, now);
+            }
+
+            match filter.sort.as_deref() {
+                None | Some("id") => query.push(" ORDER BY id"),
+                Some("rank") => query.push(" ORDER BY status, rank, id"),
+                Some(sort) => Err(HttpError::bad_request(format!(
+                    "sort must be one of {}, got `{sort}`",
+                    TASK_SORTS.join(", ")
+                )))?,
+            };
+
+            let mut tasks = query.build_query_as::<Task>().fetch(&mut *tx);
+            while let Some(task) = tasks.try_next().await? {
+                yield task;
+            }
+        }
+    }
+
+    pub async fn get_task(&self, auth_user: &AuthUser, task_id: i32) -> Result<Task, AppError> {
+        let mut tx = self.db.begin_read(auth_user).await?;
+        let task = visible_task(&mut tx, auth_user, task_id).await?;
+        tx.commit().await?;
+
+        Ok(task)
+    }
+
+    /// 不经过副本，收到变化通知后读取用，副本可能还没有这次变化
+    pub async fn get_task_from_primary(
+        &self,
+        auth_user: &AuthUser,
+        task_id: i32,
+    ) -> Result<Task, AppError> {
+        let mut tx = tenant::begin(self.db.primary(), auth_user).await?;
+        let task = visible_task(&mut tx, auth_user, task_id).await?;
+        tx.commit().await?;
+
+        Ok(task)
+    }
+
+    /// 批量查询用户信息，行级安全策略保证只能看到本组织的用户
+    pub async fn users_by_id(
+        &self,
+        auth_user: &AuthUser,
+        user_ids: &[i32],
+    ) -> Result<Vec<UserProfile>, AppError> {
+        let mut tx = tenant::begin(self.db.primary(), auth_user).await?;
+        let users = sqlx::query_as::<_, UserProfile>(
+            "SELECT id, email, name FROM users WHERE id = ANY($1)",
+        )
+        .bind(user_ids)
+        .fetch_all(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(users)
+    }
+
+    /// 批量查询多个任务的状态变化，按时间排序
+    pub async fn history_for_tasks(
+        &self,
+        auth_user: &AuthUser,
+        task_ids: &[i32],
+    ) -> Result<Vec<TaskStatusChange>, AppError> {
+        let mut tx = tenant::begin(self.db.primary(), auth_user).await?;
+        let history = sqlx::query_as::<_, TaskStatusChange>(
+            "SELECT id, task_id, old_status, new_status, changed_at
+             FROM task_history
+             WHERE task_id = ANY($1)
+               AND task_id IN (SELECT id FROM tasks WHERE user_id = $2 OR $2 = ANY(assignee_ids))
+             ORDER BY changed_at, id",
+        )
+        .bind(task_ids)
+        .bind(auth_user.user_id)
+        .fetch_all(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(history)
+    }
+
+    pub async fn create_task(
+        &self,
+        auth_user: &AuthUser,
+        payload: &CreateTask,
+    ) -> Result<Task, AppError> {
+        payload
+            .validate()
+            .map_err(|errors| HttpError::unprocessable(errors.join("; ")))?;
+
+        let mut tx = tenant::begin(self.db.primary(), auth_user).await?;
+        let task = task_repo::insert_task(&mut *tx, auth_user.user_id, payload).await?;
+        tx.commit().await?;
+        self.db.record_write(auth_user.user_id);
+        self.cache.invalidate(auth_user.user_id);
+
+        self.metrics.tasks_created.inc();
+
+        Ok(task)
+    }
+
+    pub async fn update_task(
+        &self,
+        auth_user: &AuthUser,
+        task_id: i32,
+        payload: UpdateTask,
+    ) -> Result<Task, AppError> {
+        payload
+            .validate()
+            .map_err(|errors| HttpError::unprocessable(errors.join("; ")))?;
+
+        let mut query = QueryBuilder::new("UPDATE tasks SET updated_at = NOW()");
+
+        if let Some(title) = payload.title {
+            query.push(", title = ");
+            query.push_bind(title);
+        }
+
+        if let Some(description) = payload.description {
+            query.push(", description = ");
+            query.push_bind(description);
+        }
+
+        if let Some(category) = payload.category {
+            query.push(", category = ");
+            query.push_bind(category);
+        }
+
+        if let Some(priority) = payload.priority {
+            query.push(", priority = ");
+            query.push_bind(priority);
+        }
+
+        if let Some(status) = payload.status {
+            // 换了列就排到新列的最后
+            query.push(", rank = CASE WHEN tasks.status = ");
+            query.push_bind(status.clone());
+            query.push(" THEN tasks.rank ELSE next_task_rank(tasks.organization_id, ");
+            query.push_bind(status.clone());
+            query.push(") END, status = ");
+            query.push_bind(status);
+        }
+
+        if let Some(due_date) = payload.due_date {
+            query.push(", due_date = ");
+            query.push_bind(due_date);
+        }
+
+        if let Some(recurrence) = payload.recurrence {
+            query.push(", recurrence = ");
+            query.push_bind(Some(recurrence).filter(|r| !r.is_empty()));
+        }
+
+        if let Some(tags) = payload.tags {
+            query.push(", tags = ");
+            query.push_bind(tags);
+        }
+
+        if let Some(estimate) = payload.estimate_seconds {
+            query.push(", estimate_seconds = ");
+            query.push_bind(Some(estimate).filter(|estimate| *estimate != 0));
+        }
+
+        // 取出更新前的状态，用来判断是否刚刚完成
+        query.push(" FROM (SELECT id, status AS previous_status FROM tasks WHERE id = ");
+        query.push_bind(task_id);
+        query.push(" FOR UPDATE) previous WHERE tasks.id = previous.id AND ");
+        push_visible(&mut query, auth_user.user_id);
+        query.push(" RETURNING tasks.*, previous.previous_status");
+
+        let mut tx = tenant::begin(self.db.primary(), auth_user).await?;
+        let row = query
+            .build()
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or_else(|| HttpError::not_found("Task not found"))?;
+        tx.commit().await?;
+        self.db.record_write(auth_user.user_id);
+        let task = Task::from_row(&row)?;
+        self.invalidate(&task);
+        let previous_status: String = row.try_get("previous_status")?;
+
+        if task.status == "completed" && previous_status != "completed" {
+            self.metrics.tasks_completed.inc();
+        }
+
+        Ok(task)
+    }
+
+    /// 只有所有者可以删除，负责人删除时和任务不存在一样返回 404
+    pub async fn delete_task(&self, auth_user: &AuthUser, task_id: i32) -> Result<(), AppError> {
+        let mut tx = tenant::begin(self.db.primary(), auth_user).await?;
+        let task = sqlx::query_as::<_, Task>(
+            "DELETE FROM tasks WHERE id = $1 AND user_id = $2 RETURNING *",
+        )
+        .bind(task_id)
+        .bind(auth_user.user_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| HttpError::not_found("Task not found"))?;
+        tx.commit().await?;
+        self.db.record_write(auth_user.user_id);
+        self.invalidate(&task);
+
+        Ok(())
+    }
+
+    /// 在一个事务里修改任务的列和位置。新的 rank 取在相邻任务之间，
+    /// 太长时排一个重新分布整列的后台任务
+    pub async fn move_task(
+        &self,
+        auth_user: &AuthUser,
+        task_id: i32,
+        payload: &MoveTask,
+    ) -> Result<Task, AppError> {
+        payload
+            .validate(task_id)
+            .map_err(|errors| HttpError::unprocessable(errors.join("; ")))?;
+
+        let mut tx = tenant::begin(self.db.primary(), auth_user).await?;
+        let task = sqlx::query_as::<_, Task>(
+            "SELECT * FROM tasks WHERE id = $1 AND (user_id = $2 OR $2 = ANY(assignee_ids))
+             FOR UPDATE",
+        )
+        .bind(task_id)
+        .bind(auth_user.user_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| HttpError::not_found("Task not found"))?;
+        let status = payload
+            .status
+            .clone()
+            .unwrap_or_else(|| task.status.clone());
+        rank::lock_column(&mut tx, auth_user.organization_id, &status).await?;
+
+        let mut new_rank = self
+            .rank_between(&mut tx, auth_user, &task, &status, payload)
+            .await?;
+        if new_rank.is_none() {
+            // 相邻任务的 rank 相同（并发插入时可能出现），先重新分布再算一次
+            rank::rebalance(&mut tx, auth_user.organization_id, &status).await?;
+            new_rank = self
+                .rank_between(&mut tx, auth_user, &task, &status, payload)
+                .await?;
+        }
+        let new_rank = new_rank.ok_or_else(|| {
+            HttpError::unprocessable("after_id must come before before_id in the column")
+        })?;
+
+        let previous_status = task.status.clone();
+        let task = sqlx::query_as::<_, Task>(
+            "UPDATE tasks SET status = $2, rank = $3, updated_at = NOW() WHERE id = $1 RETURNING *",
+        )
+        .bind(task_id)
+        .bind(&status)
+        .bind(&new_rank)
+        .fetch_one(&mut *tx)
+        .await?;
+        if new_rank.len() > rank::MAX_LEN {
+            let job = RebalanceRanks {
+                organization_id: auth_user.organization_id,
+                status,
+            };
+            let options = EnqueueOptions {
+                unique_key: Some(job.unique_key()),
+                ..Default::default()
+            };
+            jobs::enqueue(&mut *tx, &job, options).await?;
+        }
+        tx.commit().await?;
+        self.db.record_write(auth_user.user_id);
+        self.invalidate(&task);
+
+        if task.status == "completed" && previous_status != "completed" {
+            self.metrics.tasks_completed.inc();
+        }
+
+        Ok(task)
+    }
+
+    /// 只传一边的相邻任务时，另一边取整列里紧挨着的任务
+    async fn rank_between(
+        &self,
+        conn: &mut PgConnection,
+        auth_user: &AuthUser,
+        task: &Task,
+        status: &str,
+        payload: &MoveTask,
+    ) -> Result<Option<String>, AppError> {
+        let after = neighbour_rank(conn, auth_user, status, payload.after_id).await?;
+        let before = neighbour_rank(conn, auth_user, status, payload.before_id).await?;
+
+        let (lower, upper) = match (after, before) {
+            (Some(after), Some(before)) => (Some(after), Some(before)),
+            (Some(after), None) => {
+                let next: Option<String> = sqlx::query_scalar(
+                    "SELECT MIN(rank) FROM tasks WHERE status = $1 AND rank > $2 AND id <> $3",
+                )
+                .bind(status)
+                .bind(&after)
+                .bind(task.id)
+                .fetch_one(&mut *conn)
+                .await?;
+                (Some(after), next)
+            }
+            (None, Some(before)) => {
+                let previous: Option<String> = sqlx::query_scalar(
+                    "SELECT MAX(rank) FROM tasks WHERE status = $1 AND rank < $2 AND id <> $3",
+                )
+                .bind(status)
+                .bind(&before)
+                .bind(task.id)
+                .fetch_one(&mut *conn)
+                .await?;
+                (previous, Some(before))
+            }
+            (None, None) => {
+                let last: Option<String> = sqlx::query_scalar(
+                    "SELECT MAX(rank) FROM tasks WHERE status = $1 AND id <> $2",
+                )
+                .bind(status)
+                .bind(task.id)
+                .fetch_one(&mut *conn)
+                .await?;
+                (last, None)
+            }
+        };
+        Ok(rank::between(lower.as_deref(), upper.as_deref()))
+    }
+
+    /// 所有者和当前负责人都可以修改负责人，新增的必须是本组织未停用的用户。
+    /// 新增和移除的用户各收到一条站内通知，新增的还会收到邮件；操作者自己不通知
+    pub async fn change_assignees(
+        &self,
+        auth_user: &AuthUser,
+        task_id: i32,
+        change: AssigneeChange,
+    ) -> Result<Task, AppError> {
+        let mut tx = tenant::begin(self.db.primary(), auth_user).await?;
+        let task = sqlx::query_as::<_, Task>(
+            "SELECT * FROM tasks WHERE id = $1 AND (user_id = $2 OR $2 = ANY(assignee_ids))
+             FOR UPDATE",
+        )
+        .bind(task_id)
+        .bind(auth_user.user_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| HttpError::not_found("Task not found"))?;
+
+        let assignees = change.apply(&task.assignee_ids);
+        if assignees.len() > MAX_ASSIGNEES {
+            return Err(HttpError::unprocessable(format!(
+                "a task can have at most {MAX_ASSIGNEES} assignees"
+            ))
+            .into());
+        }
+        let added: Vec<i32> = assignees
+            .iter()
+            .copied()
+            .filter(|id| !task.assignee_ids.contains(id))
+            .collect();
+        let removed: Vec<i32> = task
+            .assignee_ids
+            .iter()
+            .copied()
+            .filter(|id| !assignees.contains(id))
+            .collect();
+        if added.is_empty() && removed.is_empty() {
+            tx.commit().await?;
+            return Ok(task);
+        }
+
+        // 行级安全策略只返回本组织的用户
+        let members: Vec<i32> =
+            sqlx::query_scalar("SELECT id FROM users WHERE id = ANY($1) AND disabled_at IS NULL")
+                .bind(&added)
+                .fetch_all(&mut *tx)
+                .await?;
+        if let Some(missing) = added.iter().find(|id| !members.contains(id)) {
+            return Err(HttpError::unprocessable(format!(
+                "user {missing} is not an active member of this organization"
+            ))
+            .into());
+        }
+
+        let updated = sqlx::query_as::<_, Task>(
+            "UPDATE tasks SET assignee_ids = $2, updated_at = NOW() WHERE id = $1 RETURNING *",
+        )
+        .bind(task.id)
+        .bind(&assignees)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let others = |user_ids: Vec<i32>| -> Vec<i32> {
+            user_ids
+                .into_iter()
+                .filter(|id| *id != auth_user.user_id)
+                .collect()
+        };
+        let (added, removed) = (others(added), others(removed));
+        for (user_ids, kind) in [
+            (&added, NotificationKind::Assigned),
+            (&removed, NotificationKind::Unassigned),
+        ] {
+            notification_repo::insert_notifications(
+                &mut *tx,
+                user_ids,
+                kind,
+                updated.id,
+                auth_user.user_id,
+                &updated.title,
+            )
+            .await?;
+        }
+        if !added.is_empty() {
+            let assigned_by = user_repo::find_by_id(&mut *tx, auth_user.user_id)
+                .await?
+                .map(|user| {
+                    if user.name.is_empty() {
+                        user.email
+                    } else {
+                        user.name
+                    }
+                })
+                .unwrap_or_default();
+            for user_id in &added {
+                let notification = Notification::Assignment {
+                    task_id: updated.id,
+                    title: updated.title.clone(),
+                    assigned_by: assigned_by.clone(),
+                };
+                email::notify(&mut *tx, &self.config, *user_id, notification, None).await?;
+            }
+        }
+        tx.commit().await?;
+        self.db.record_write(auth_user.user_id);
+        self.invalidate(&task);
+        self.invalidate(&updated);
+
+        Ok(updated)
+    }
+
+    /// 正在走的计时器，没有时为 `None`
+    pub async fn running_timer(&self, auth_user: &AuthUser) -> Result<Option<TimeEntry>, AppError> {
+        let mut tx = tenant::begin(self.db.primary(), auth_user).await?;
+        let entry = sqlx::query_as::<_, TimeEntry>(
+            "SELECT * FROM time_entries WHERE user_id = $1 AND ended_at IS NULL",
+        )
+        .bind(auth_user.user_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(entry)
+    }
+
+    /// 开始给任务计时。每个用户同时只有一个计时器，已经在走的先停下来
+    pub async fn start_timer(
+        &self,
+        auth_user: &AuthUser,
+        task_id: i32,
+        payload: &StartTimer,
+    ) -> Result<TimeEntry, AppError> {
+        let mut errors = Vec::new();
+        check_note(&payload.note, &mut errors);
+        if !errors.is_empty() {
+            return Err(HttpError::unprocessable(errors.join("; ")).into());
+        }
+
+        let mut tx = tenant::begin(self.db.primary(), auth_user).await?;
+        lock_time_entries(&mut tx, auth_user.user_id).await?;
+        visible_task(&mut tx, auth_user, task_id).await?;
+        let stopped = sqlx::query_scalar::<_, Option<i32>>(
+            "UPDATE time_entries SET ended_at = NOW()
+             WHERE user_id = $1 AND ended_at IS NULL
+             RETURNING task_id",
+        )
+        .bind(auth_user.user_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .flatten();
+        let entry = sqlx::query_as::<_, TimeEntry>(
+            "INSERT INTO time_entries (task_id, user_id, started_at, note)
+             VALUES ($1, $2, NOW(), $3)
+             RETURNING *",
+        )
+        .bind(task_id)
+        .bind(auth_user.user_id)
+        .bind(&payload.note)
+        .fetch_one(&mut *tx)
+        .await?;
+        let changed = task_by_id(&mut tx, stopped).await?;
+        tx.commit().await?;
+        self.db.record_write(auth_user.user_id);
+        if let Some(task) = &changed {
+            self.invalidate(task);
+        }
+
+        Ok(entry)
+    }
+
+    pub async fn stop_timer(&self, auth_user: &AuthUser) -> Result<TimeEntry, AppError> {
+        let mut tx = tenant::begin(self.db.primary(), auth_user).await?;
+        lock_time_entries(&mut tx, auth_user.user_id).await?;
+        let entry = sqlx::query_as::<_, TimeEntry>(
+            "UPDATE time_entries SET ended_at = NOW()
+             WHERE user_id = $1 AND ended_at IS NULL
+             RETURNING *",
+        )
+        .bind(auth_user.user_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| HttpError::not_found("No timer is running"))?;
+        let changed = task_by_id(&mut tx, entry.task_id).await?;
+        tx.commit().await?;
+        self.db.record_write(auth_user.user_id);
+        if let Some(task) = &changed {
+            self.invalidate(task);
+        }
+
+        Ok(entry)
+    }
+
+    /// 手动补录一段时间，不能和自己的其他记录（包括正在走的计时器）重叠，
+    /// 否则同一段时间会被计费两次
+    pub async fn add_time_entry(
+        &self,
+        auth_user: &AuthUser,
+        task_id: i32,
+        payload: &CreateTimeEntry,
+    ) -> Result<TimeEntry, AppError> {
+        payload
+            .validate(Utc::now())
+            .map_err(|errors| HttpError::unprocessable(errors.join("; ")))?;
+
+        let mut tx = tenant::begin(self.db.primary(), auth_user).await?;
+        lock_time_entries(&mut tx, auth_user.user_id).await?;
+        visible_task(&mut tx, auth_user, task_id).await?;
+        let overlapping: Option<i64> = sqlx::query_scalar(
+            "SELECT id FROM time_entries
+             WHERE user_id = $1 AND tstzrange(started_at, ended_at) && tstzrange($2, $3)
+             ORDER BY started_at LIMIT 1",
+        )
+        .bind(auth_user.user_id)
+        .bind(payload.started_at)
+        .bind(payload.ended_at)
+        .fetch_optional(&mut *tx)
+        .await?;
+        if let Some(id) = overlapping {
+            return Err(
+                HttpError::unprocessable(format!("the entry overlaps time entry {id}")).into(),
+            );
+        }
+        let entry = sqlx::query_as::<_, TimeEntry>(
+            "INSERT INTO time_entries (task_id, user_id, started_at, ended_at, note)
+             VALUES ($1, $2, $3, $4, $5)
+             RETURNING *",
+        )
+        .bind(task_id)
+        .bind(auth_user.user_id)
+        .bind(payload.started_at)
+        .bind(payload.ended_at)
+        .bind(&payload.note)
+        .fetch_one(&mut *tx)
+        .await?;
+        let changed = task_by_id(&mut tx, Some(task_id)).await?;
+        tx.commit().await?;
+        self.db.record_write(auth_user.user_id);
+        if let Some(task) = &changed {
+            self.invalidate(task);
+        }
+
+        Ok(entry)
+    }
+
+    /// 只能删除自己的记录，正在走的计时器也可以直接丢弃
+    pub async fn delete_time_entry(
+        &self,
+        auth_user: &AuthUser,
+        entry_id: i64,
+    ) -> Result<(), AppError> {
+        let mut tx = tenant::begin(self.db.primary(), auth_user).await?;
+        let task_id = sqlx::query_scalar::<_, Option<i32>>(
+            "DELETE FROM time_entries WHERE id = $1 AND user_id = $2 RETURNING task_id",
+        )
+        .bind(entry_id)
+        .bind(auth_user.user_id)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| HttpError::not_found("Time entry not found"))?;
+        let changed = task_by_id(&mut tx, task_id).await?;
+        tx.commit().await?;
+        self.db.record_write(auth_user.user_id);
+        if let Some(task) = &changed {
+            self.invalidate(task);
+        }
+
+        Ok(())
+    }
+
+    /// 任务上所有人的时间记录，能看到任务就能看到
+    pub async fn list_time_entries(
+        &self,
+        auth_user: &AuthUser,
+        task_id: i32,
+    ) -> Result<Vec<TimeEntry>, AppError> {
+        let mut tx = tenant::begin(self.db.primary(), auth_user).await?;
+        visible_task(&mut tx, auth_user, task_id).await?;
+        let entries = sqlx::query_as::<_, TimeEntry>(
+            "SELECT * FROM time_entries WHERE task_id = $1 ORDER BY started_at, id",
+        )
+        .bind(task_id)
+        .fetch_all(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(entries)
+    }
+
+    /// 一个用户在一段日期（UTC）里已经停止的时间记录，按天和按任务汇总。
+    /// 查看别人的时间表需要 admin scope，并且当前用户是组织管理员。
+    /// 当前用户看不到的任务不带标题和预估
+    pub async fn timesheet(
+        &self,
+        auth_user: &AuthUser,
+        params: &TimesheetParams,
+    ) -> Result<Timesheet, AppError> {
+        let to = params.to.unwrap_or_else(|| Utc::now().date_naive());
+        let from = params
+            .from
+            .unwrap_or(to - Duration::days(DEFAULT_TIMESHEET_DAYS - 1));
+        if from > to {
+            return Err(HttpError::bad_request("`from` must not be after `to`").into());
+        }
+        if (to - from).num_days() >= MAX_TIMESHEET_DAYS {
+            return Err(HttpError::bad_request(format!(
+                "date range must be at most {MAX_TIMESHEET_DAYS} days"
+            ))
+            .into());
+        }
+        let user_id = match params.user.as_deref() {
+            Some(user) => filter_user(user, auth_user)?,
+            None => auth_user.user_id,
+        };
+        if user_id != auth_user.user_id {
+            auth_user.require_scope(Scope::Admin)?;
+        }
+
+        let mut tx = tenant::begin(self.db.primary(), auth_user).await?;
+        if user_id != auth_user.user_id {
+            // 会话的 token 带全部 scope，不能只看 scope
+            let is_admin = user_repo::find_by_id(&mut *tx, auth_user.user_id)
+                .await?
+                .is_some_and(|user| user.is_admin());
+            if !is_admin {
+                return Err(HttpError::forbidden(
+                    "only organization admins can view other users' timesheets",
+                )
+                .into());
+            }
+        }
+        if user_repo::find_by_id(&mut *tx, user_id).await?.is_none() {
+            return Err(HttpError::not_found("User not found").into());
+        }
+
+        // 开始时间落在 [from, to] 这几天里的记录；任务的可见性和任务列表一致
+        const VISIBLE_TASK: &str = "LEFT JOIN tasks t ON t.id = e.task_id
+            AND (t.user_id = $4 OR $4 = ANY(t.assignee_ids))";
+        const RANGE: &str = "e.user_id = $1 AND e.ended_at IS NOT NULL
+            AND e.started_at >= $2::date::timestamp AT TIME ZONE 'UTC'
+            AND e.started_at < ($3::date + 1)::timestamp AT TIME ZONE 'UTC'";
+        let entries = sqlx::query_as::<_, TimesheetEntry>(&format!(
+            "SELECT e.id, (e.started_at AT TIME ZONE 'UTC')::date AS date, e.task_id,
+                    t.title AS task_title, e.started_at, e.ended_at, e.duration_seconds, e.note
+             FROM time_entries e
+             {VISIBLE_TASK}
+             WHERE {RANGE}
+             ORDER BY e.started_at, e.id"
+        ))
+        .bind(user_id)
+        .bind(from)
+        .bind(to)
+        .bind(auth_user.user_id)
+        .fetch_all(&mut *tx)
+        .await?;
+        let days = sqlx::query_as::<_, TimesheetDay>(&format!(
+            "SELECT day::date AS date, COALESCE(SUM(e.duration_seconds), 0)::BIGINT AS seconds
+             FROM generate_series($2::date, $3::date, INTERVAL '1 day') AS day
+             LEFT JOIN time_entries e
+                 ON {RANGE} AND (e.started_at AT TIME ZONE 'UTC')::date = day::date
+             GROUP BY day
+             ORDER BY day"
+        ))
+        .bind(user_id)
+        .bind(from)
+        .bind(to)
+        .fetch_all(&mut *tx)
+        .await?;
+        let tasks = sqlx::query_as::<_, TimesheetTask>(&format!(
+            "SELECT e.task_id, t.title, t.estimate_seconds, t.time_spent_seconds,
+                    SUM(e.duration_seconds)::BIGINT AS seconds
+             FROM time_entries e
+             {VISIBLE_TASK}
+             WHERE {RANGE}
+             GROUP BY e.task_id, t.id
+             ORDER BY seconds DESC, e.task_id"
+        ))
+        .bind(user_id)
+        .bind(from)
+        .bind(to)
+        .bind(auth_user.user_id)
+        .fetch_all(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(Timesheet {
+            user_id,
+            from,
+            to,
+            total_seconds: days.iter().map(|day| day.seconds).sum(),
+            days,
+            tasks,
+            entries,
+        })
+    }
+
+    pub async fn list_views(&self, auth_user: &AuthUser) -> Result<Vec<SavedView>, AppError> {
+        let mut tx = tenant::begin(self.db.primary(), auth_user).await?;
+        let views = sqlx::query_as::<_, SavedView>(
+            "SELECT * FROM saved_views WHERE user_id = $1 OR shared ORDER BY name, id",
+        )
+        .bind(auth_user.user_id)
+        .fetch_all(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(views)
+    }
+
+    pub async fn get_view(
+        &self,
+        auth_user: &AuthUser,
+        view_id: i32,
+    ) -> Result<SavedView, AppError> {
+        let mut tx = tenant::begin(self.db.primary(), auth_user).await?;
+        let view = view_repo::find_visible(&mut *tx, auth_user.user_id, view_id)
+            .await?
+            .ok_or_else(|| HttpError::not_found("View not found"))?;
+        tx.commit().await?;
+
+        Ok(view)
+    }
+
+    pub async fn create_view(
+        &self,
+        auth_user: &AuthUser,
+        payload: &CreateView,
+    ) -> Result<SavedView, AppError> {
+        check_view_name(&payload.name)?;
+        // 保存前先校验语法
+        TaskQuery::parse(&payload.query)?;
+
+        let mut tx = tenant::begin(self.db.primary(), auth_user).await?;
+        let view = sqlx::query_as::<_, SavedView>(
+            "INSERT INTO saved_views (user_id, name, query, shared)
+             VALUES ($1, $2, $3, $4)
+             ON CONFLICT (user_id, name) DO NOTHING
+             RETURNING *",
+        )
+        .bind(auth_user.user_id)
+        .bind(&payload.name)
+        .bind(&payload.query)
+        .bind(payload.shared)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| HttpError::unprocessable("a view with this name already exists"))?;
+        tx.commit().await?;
+        self.db.record_write(auth_user.user_id);
+
+        Ok(view)
+    }
+
+    pub async fn update_view(
+        &self,
+        auth_user: &AuthUser,
+        view_id: i32,
+        payload: UpdateView,
+    ) -> Result<SavedView, AppError> {
+        let mut query = QueryBuilder::new("UPDATE saved_views SET updated_at = NOW()");
+
+        if let Some(name) = payload.name {
+            check_view_name(&name)?;
+            query.push(", name = ");
+            query.push_bind(name);
+        }
+
+        if let Some(view_query) = payload.query {
+            TaskQuery::parse(&view_query)?;
+            query.push(", query = ");
+            query.push_bind(view_query);
+        }
+
+        if let Some(shared) = payload.shared {
+            query.push(", shared = ");
+            query.push_bind(shared);
+        }
+
+        // 只有创建者可以修改
+        query.push(" WHERE id = ");
+        query.push_bind(view_id);
+        query.push(" AND user_id = ");
+        query.push_bind(auth_user.user_id);
+        query.push(" RETURNING *");
+
+        let mut tx = tenant::begin(self.db.primary(), auth_user).await?;
+        let view = query
+            .build_query_as::<SavedView>()
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or_else(|| HttpError::not_found("View not found"))?;
+        tx.commit().await?;
+        self.db.record_write(auth_user.user_id);
+        self.cache.clear();
+
+        Ok(view)
+    }
+
+    pub async fn delete_view(&self, auth_user: &AuthUser, view_id: i32) -> Result<(), AppError> {
+        let mut tx = tenant::begin(self.db.primary(), auth_user).await?;
+        let result = sqlx::query("DELETE FROM saved_views WHERE id = $1 AND user_id = $2")
+            .bind(view_id)
+            .bind(auth_user.user_id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        self.db.record_write(auth_user.user_id);
+        self.cache.clear();
+
+        if result.rows_affected() == 0 {
+            return Err(HttpError::not_found("View not found").into());
+        }
+
+        Ok(())
+    }
+}
+
+/// 所有者和负责人都能看到任务
+fn push_visible(query: &mut QueryBuilder<'_, Postgres>, user_id: i32) {
+    query.push("(tasks.user_id = ");
+    query.push_bind(user_id);
+    query.push(" OR ");
+    query.push_bind(user_id);
+    query.push(" = ANY(tasks.assignee_ids))");
+}
+
+/// 过滤条件里的用户：`me` 或用户 id
+fn filter_user(value: &str, auth_user: &AuthUser) -> Result<i32, HttpError> {
+    if value == "me" {
+        return Ok(auth_user.user_id);
+    }
+    value
+        .parse()
+        .map_err(|_| HttpError::bad_request(format!("expected `me` or a user id, got `{value}`")))
+}
+
+fn check_view_name(name: &str) -> Result<(), HttpError> {
+    if name.trim().is_empty() || name.chars().count() > MAX_VIEW_NAME_LEN {
+        return Err(HttpError::unprocessable(format!(
+            "name must be 1-{MAX_VIEW_NAME_LEN} characters"
+        )));
+    }
+    Ok(())
+}
+
+/// 同一个用户的计时器和手动记录串行修改，保证只有一个计时器、记录不重叠
+async fn lock_time_entries(conn: &mut PgConnection, user_id: i32) -> Result<(), sqlx::Error> {
+    sqlx::query("SELECT pg_advisory_xact_lock(hashtext('time_entries'), $1)")
+        .bind(user_id)
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
+/// 所有者或负责人能看到的任务，否则 404
+async fn visible_task(
+    conn: &mut PgConnection,
+    auth_user: &AuthUser,
+    task_id: i32,
+) -> Result<Task, AppError> {
+    Ok(sqlx::query_as::<_, Task>(
+        "SELECT * FROM tasks WHERE id = $1 AND (user_id = $2 OR $2 = ANY(assignee_ids))",
+    )
+    .bind(task_id)
+    .bind(auth_user.user_id)
+    .fetch_optional(conn)
+    .await?
+    .ok_or_else(|| HttpError::not_found("Task not found"))?)
+}
+
+/// 时间记录变化后重新读取任务（`time_spent_seconds` 已由触发器更新），用来清缓存
+async fn task_by_id(
+    conn: &mut PgConnection,
+    task_id: Option<i32>,
+) -> Result<Option<Task>, sqlx::Error> {
+    let Some(task_id) = task_id else {
+        return Ok(None);
+    };
+    sqlx::query_as::<_, Task>("SELECT * FROM tasks WHERE id = $1")
+        .bind(task_id)
+        .fetch_optional(conn)
+        .await
+}
+
+/// 拖动时指定的相邻任务，必须在目标列里并且当前用户能看到
+async fn neighbour_rank(
+    conn: &mut PgConnection,
+    auth_user: &AuthUser,
+    status: &str,
+    neighbour_id: Option<i32>,
+) -> Result<Option<String>, AppError> {
+    let Some(neighbour_id) = neighbour_id else {
+        return Ok(None);
+    };
+    let rank: Option<String> = sqlx::query_scalar(
+        "SELECT rank FROM tasks
+         WHERE id = $1 AND status = $2 AND (user_id = $3 OR $3 = ANY(assignee_ids))",
+    )
+    .bind(neighbour_id)
+    .bind(status)
+    .bind(auth_user.user_id)
+    .fetch_optional(conn)
+    .await?;
+    match rank {
+        Some(rank) => Ok(Some(rank)),
+        None => Err(HttpError::unprocessable(format!(
+            "task {neighbour_id} is not in the {status} column"
+        )))?,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn applies_assignee_changes_in_order_without_duplicates() {
+        assert_eq!(AssigneeChange::Add(vec![3, 1, 3]).apply(&[1, 2]), [1, 2, 3]);
+        assert_eq!(AssigneeChange::Remove(1).apply(&[1, 2]), [2]);
+        assert_eq!(AssigneeChange::Remove(5).apply(&[1, 2]), [1, 2]);
+        assert_eq!(AssigneeChange::Set(vec![4, 4, 2]).apply(&[1, 2]), [4, 2]);
+        assert!(AssigneeChange::Set(Vec::new()).apply(&[1]).is_empty());
+    }
+}
@@ -0,0 +1,164 @@
+use sqlx::PgPool;
+
+use crate::{
+    auth::{AuthUser, Scope},
+    db::user_repo,
+    error::{AppError, HttpError},
+    models::user::{User, USER_ROLES},
+    services::auth_service,
+};
+
+/// 运维命令用的用户管理，普通用户通过 `/api/auth/register` 注册
+pub async fn create_user(
+    pool: &PgPool,
+    email: &str,
+    name: &str,
+    password: String,
+    organization_id: Option<i32>,
+) -> Result<User, AppError> {
+    let email = auth_service::normalize_email(email)?;
+    auth_service::validate_password(&password)?;
+    let password_hash = auth_service::hash_password(password).await?;
+
+    let mut tx = pool.begin().await?;
+    let organization_id = match organization_id {
+        Some(id) => {
+            let exists: bool =
+                sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM organizations WHERE id = $1)")
+                    .bind(id)
+                    .fetch_one(&mut *tx)
+                    .await?;
+            if !exists {
+                return Err(HttpError::not_found(format!("organization {id} not found")).into());
+            }
+            id
+        }
+        None => {
+            user_repo::create_organization(&mut *tx, if name.is_empty() { &email } else { name })
+                .await?
+        }
+    };
+    user_repo::insert_user(&mut *tx, organization_id, &email, name, &password_hash)
+        .await?
+        .ok_or_else(|| HttpError::unprocessable("email is already registered"))?;
+    let user = user_repo::find_by_email(&mut *tx, &email)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("user {email} disappeared"))?;
+    tx.commit().await?;
+
+    Ok(user)
+}
+
+pub async fn find_user(pool: &PgPool, email: &str) -> Result<User, AppError> {
+    Ok(user_repo::find_by_email(pool, email)
+        .await?
+        .ok_or_else(|| HttpError::not_found(format!("user {email} not found")))?)
+}
+
+/// 禁用后 session、personal access token、客户端证书和日历订阅地址都不能再用；
+/// 这里同时撤销所有 session，重新启用后需要重新登录，其他凭据恢复可用
+pub async fn set_disabled(pool: &PgPool, email: &str, disabled: bool) -> Result<User, AppError> {
+    let user = find_user(pool, email).await?;
+
+    let mut tx = pool.begin().await?;
+    sqlx::query(
+        "UPDATE users SET disabled_at = CASE WHEN $2 THEN COALESCE(disabled_at, NOW()) END
+         WHERE id = $1",
+    )
+    .bind(user.id)
+    .bind(disabled)
+    .execute(&mut *tx)
+    .await?;
+    if disabled {
+        revoke_all_sessions(&mut tx, user.id, "user_disabled").await?;
+    }
+    tx.commit().await?;
+
+    find_user(pool, email).await
+}
+
+/// 组织里至少要留一个管理员
+pub async fn set_role(pool: &PgPool, email: &str, role: &str) -> Result<User, AppError> {
+    if !USER_ROLES.contains(&role) {
+        return Err(HttpError::bad_request(format!("unknown role `{role}`")).into());
+    }
+    let user = find_user(pool, email).await?;
+
+    let mut tx = pool.begin().await?;
+    // 锁住组织，并发降级时不会把管理员都降掉
+    sqlx::query("SELECT id FROM organizations WHERE id = $1 FOR UPDATE")
+        .bind(user.organization_id)
+        .execute(&mut *tx)
+        .await?;
+    if user.is_admin() && role != "admin" {
+        let admins: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM users WHERE organization_id = $1 AND role = 'admin'",
+        )
+        .bind(user.organization_id)
+        .fetch_one(&mut *tx)
+        .await?;
+        if admins <= 1 {
+            return Err(HttpError::unprocessable(format!(
+                "{email} is the last admin of organization {}",
+                user.organization_id
+            ))
+            .into());
+        }
+    }
+    sqlx::query("UPDATE users SET role = $2 WHERE id = $1")
+        .bind(user.id)
+        .bind(role)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    find_user(pool, email).await
+}
+
+/// 重设密码并撤销所有 session
+pub async fn reset_password(
+    pool: &PgPool,
+    email: &str,
+    password: String,
+) -> Result<User, AppError> {
+    auth_service::validate_password(&password)?;
+    let user = find_user(pool, email).await?;
+    let password_hash = auth_service::hash_password(password).await?;
+
+    let mut tx = pool.begin().await?;
+    sqlx::query("UPDATE users SET password_hash = $2 WHERE id = $1")
+        .bind(user.id)
+        .bind(&password_hash)
+        .execute(&mut *tx)
+        .await?;
+    revoke_all_sessions(&mut tx, user.id, "password_reset").await?;
+    tx.commit().await?;
+
+    Ok(user)
+}
+
+/// 以该用户的身份（全部权限）访问租户数据，比如导入导出
+pub fn act_as(user: &User) -> AuthUser {
+    AuthUser {
+        user_id: user.id,
+        organization_id: user.organization_id,
+        session_id: None,
+        scopes: Scope::ALL.to_vec(),
+    }
+}
+
+async fn revoke_all_sessions(
+    conn: &mut sqlx::PgConnection,
+    user_id: i32,
+    reason: &str,
+) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query(
+        "UPDATE sessions SET revoked_at = NOW(), revoked_reason = $2
+         WHERE user_id = $1 AND revoked_at IS NULL",
+    )
+    .bind(user_id)
+    .bind(reason)
+    .execute(conn)
+    .await?;
+    Ok(result.rows_affected())
+}
@@ -0,0 +1,140 @@
+use anyhow::bail;
+use axum::{extract::ConnectInfo, Router};
+use hyper::body::Incoming;
+use hyper_util::{
+    rt::{TokioExecutor, TokioIo},
+    server::{conn::auto, graceful::GracefulShutdown},
+    service::TowerToHyperService,
+};
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::{net::TcpListener, signal, task::JoinSet};
+use tower::ServiceExt;
+
+/// 等待 SIGINT (Ctrl+C) 或 SIGTERM
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        signal::ctrl_c()
+            .await
+            .expect("failed to install Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        signal::unix::signal(signal::unix::SignalKind::terminate())
+            .expect("failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
+/// 收到关闭信号后置位，`/readyz` 随之返回 503，负载均衡不再把新请求分过来。
+/// 见 `drain_first`
+#[derive(Debug, Clone, Default)]
+pub struct Draining(Arc<AtomicBool>);
+
+impl Draining {
+    pub fn start(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// 收到 `signal` 后先标记为 draining，再等 `delay` 才完成。这段时间里服务器照常接受连接，
+/// 负载均衡有机会看到 `/readyz` 的 503 并摘掉这个实例，之后才停止接受新连接
+pub async fn drain_first<F>(signal: F, draining: Draining, delay: Duration)
+where
+    F: Future<Output = ()>,
+{
+    signal.await;
+    draining.start();
+    if !delay.is_zero() {
+        println!("draining, new connections are accepted for another {delay:?}");
+        tokio::time::sleep(delay).await;
+    }
+}
+
+/// 收到 `signal` 后停止接受新连接，并在 `drain_timeout` 内等待处理中的请求结束。
+/// 超时后中止所有还没结束的连接（请求的 future 被丢弃，占用的数据库连接随之归还）
+/// 并返回错误。
+pub async fn serve_with_shutdown<F>(
+    listener: TcpListener,
+    app: Router,
+    signal: F,
+    drain_timeout: Duration,
+) -> anyhow::Result<()>
+where
+    F: Future<Output = ()> + Send,
+{
+    let graceful = GracefulShutdown::new();
+    let builder = auto::Builder::new(TokioExecutor::new());
+    let mut connections = JoinSet::new();
+    tokio::pin!(signal);
+
+    loop {
+        let (stream, addr) = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    eprintln!("failed to accept connection: {e}");
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    continue;
+                }
+            },
+            _ = &mut signal => break,
+        };
+        // 回收已经结束的连接
+        while connections.try_join_next().is_some() {}
+
+        let service = app
+            .clone()
+            .map_request(move |mut request: hyper::Request<Incoming>| {
+                request.extensions_mut().insert(ConnectInfo(addr));
+                request
+            });
+        let connection = builder.serve_connection_with_upgrades(
+            TokioIo::new(stream),
+            TowerToHyperService::new(service),
+        );
+        let connection = graceful.watch(connection.into_owned());
+        connections.spawn(async move {
+            let _ = connection.await;
+        });
+    }
+
+    drop(listener);
+    drain(graceful, connections, drain_timeout).await
+}
+
+/// 通知所有连接在当前请求结束后关闭；超过 `drain_timeout` 时中止剩下的连接
+pub(crate) async fn drain(
+    graceful: GracefulShutdown,
+    mut connections: JoinSet<()>,
+    drain_timeout: Duration,
+) -> anyhow::Result<()> {
+    if tokio::time::timeout(drain_timeout, graceful.shutdown())
+        .await
+        .is_ok()
+    {
+        return Ok(());
+    }
+    connections.shutdown().await;
+    bail!("in-flight requests did not finish within {drain_timeout:?}")
+}
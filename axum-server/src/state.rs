@@ -0,0 +1,127 @@
+use axum::extract::FromRef;
+use sqlx::PgPool;
+use std::sync::Arc;
+
+use crate::{
+    config::Config,
+    db::routing::DbRouter,
+    graphql::{self, TaskSchema},
+    metrics::Metrics,
+    rate_limit::RateLimitBackend,
+    services::{oidc_service::OidcClient, task_service::TaskService},
+    shutdown::Draining,
+    task_cache::TaskCache,
+    task_events::TaskEvents,
+};
+
+/// 每个订阅者最多积压的任务变化事件
+const TASK_EVENT_CAPACITY: usize = 1024;
+
+#[derive(Clone)]
+pub struct AppState {
+    /// 主库
+    pub pool: Arc<PgPool>,
+    /// 只读请求按它分到副本或主库
+    pub db: Arc<DbRouter>,
+    pub config: Arc<Config>,
+    pub metrics: Arc<Metrics>,
+    pub rate_limit: Arc<dyn RateLimitBackend>,
+    pub oidc: Option<Arc<OidcClient>>,
+    pub task_cache: Arc<TaskCache>,
+    pub task_events: TaskEvents,
+    pub graphql: TaskSchema,
+    pub draining: Draining,
+}
+
+impl AppState {
+    /// 没有只读副本，所有请求都用 `pool`
+    pub fn new(pool: PgPool, config: Config, rate_limit: Arc<dyn RateLimitBackend>) -> Self {
+        Self::with_replicas(pool, Vec::new(), config, rate_limit)
+    }
+
+    pub fn with_replicas(
+        pool: PgPool,
+        replicas: Vec<PgPool>,
+        config: Config,
+        rate_limit: Arc<dyn RateLimitBackend>,
+    ) -> Self {
+        let oidc = config
+            .auth
+            .oidc
+            .clone()
+            .map(|oidc| Arc::new(OidcClient::new(oidc)));
+        let task_cache = Arc::new(TaskCache::new(
+            config.task_cache_ttl,
+            config.task_cache_capacity,
+        ));
+        let pool = Arc::new(pool);
+        let db = Arc::new(DbRouter::new(pool.clone(), replicas, &config.database));
+        let config = Arc::new(config);
+        let metrics = Arc::new(Metrics::new());
+        let task_events = TaskEvents::new(TASK_EVENT_CAPACITY);
+        let graphql = graphql::build_schema(
+            TaskService::new(
+                db.clone(),
+                config.clone(),
+                metrics.clone(),
+                task_cache.clone(),
+            ),
+            task_events.clone(),
+            &config.graphql,
+        );
+        Self {
+            pool,
+            db,
+            config,
+            metrics,
+            rate_limit,
+            oidc,
+            task_cache,
+            task_events,
+            graphql,
+            draining: Draining::default(),
+        }
+    }
+}
+
+impl FromRef<AppState> for Arc<PgPool> {
+    fn from_ref(state: &AppState) -> Self {
+        state.pool.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<DbRouter> {
+    fn from_ref(state: &AppState) -> Self {
+        state.db.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<Metrics> {
+    fn from_ref(state: &AppState) -> Self {
+        state.metrics.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<Config> {
+    fn from_ref(state: &AppState) -> Self {
+        state.config.clone()
+    }
+}
+
+impl FromRef<AppState> for Arc<TaskCache> {
+    fn from_ref(state: &AppState) -> Self {
+        state.task_cache.clone()
+    }
+}
+
+impl FromRef<AppState> for Draining {
+    fn from_ref(state: &AppState) -> Self {
+        state.draining.clone()
+    }
+}
+
+impl FromRef<AppState> for TaskSchema {
+    fn from_ref(state: &AppState) -> Self {
+        state.graphql.clone()
+    }
+}
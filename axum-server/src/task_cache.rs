@@ -0,0 +1,134 @@
+use axum::body::Bytes;
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::conditional::Validators;
+
+/// 进程内的任务读缓存，按 (user_id, 请求) 保存序列化好的 JSON。
+/// 本进程的写接口会立即清掉该用户的缓存；其它实例的写入靠 `task_events::listen`
+/// 收到的数据库通知清掉，通知丢失时最多延迟 `ttl` 才可见
+pub struct TaskCache {
+    ttl: Duration,
+    capacity: usize,
+    entries: Mutex<HashMap<(i32, String), Entry>>,
+}
+
+struct Entry {
+    validators: Validators,
+    body: Bytes,
+    stored_at: Instant,
+}
+
+impl TaskCache {
+    /// `ttl` 为 0 时不缓存
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        Self {
+            ttl,
+            capacity,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        !self.ttl.is_zero() && self.capacity > 0
+    }
+
+    pub fn get(&self, user_id: i32, key: &str) -> Option<(Validators, Bytes)> {
+        if !self.enabled() {
+            return None;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        let cache_key = (user_id, key.to_owned());
+        match entries.get(&cache_key) {
+            Some(entry) if entry.stored_at.elapsed() < self.ttl => {
+                Some((entry.validators.clone(), entry.body.clone()))
+            }
+            Some(_) => {
+                entries.remove(&cache_key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    pub fn insert(&self, user_id: i32, key: String, validators: Validators, body: Bytes) {
+        if !self.enabled() {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= self.capacity {
+            let ttl = self.ttl;
+            entries.retain(|_, entry| entry.stored_at.elapsed() < ttl);
+            // 仍然是满的：直接清空，比维护 LRU 简单，代价只是多几次数据库查询
+            if entries.len() >= self.capacity {
+                entries.clear();
+            }
+        }
+        entries.insert(
+            (user_id, key),
+            Entry {
+                validators,
+                body,
+                stored_at: Instant::now(),
+            },
+        );
+    }
+
+    pub fn invalidate(&self, user_id: i32) {
+        if !self.enabled() {
+            return;
+        }
+        self.entries
+            .lock()
+            .unwrap()
+            .retain(|(owner, _), _| *owner != user_id);
+    }
+
+    /// 共享视图变化会影响其它用户的列表，直接全部清掉
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validators() -> Validators {
+        Validators::new(["v"], None)
+    }
+
+    #[test]
+    fn invalidates_only_the_writer() {
+        let cache = TaskCache::new(Duration::from_secs(60), 10);
+        cache.insert(1, "list:".to_owned(), validators(), Bytes::from("[]"));
+        cache.insert(2, "list:".to_owned(), validators(), Bytes::from("[]"));
+
+        cache.invalidate(1);
+        assert!(cache.get(1, "list:").is_none());
+        assert!(cache.get(2, "list:").is_some());
+    }
+
+    #[test]
+    fn expires_entries_and_respects_capacity() {
+        let cache = TaskCache::new(Duration::from_millis(20), 2);
+        cache.insert(1, "a".to_owned(), validators(), Bytes::new());
+        cache.insert(1, "b".to_owned(), validators(), Bytes::new());
+        cache.insert(1, "c".to_owned(), validators(), Bytes::new());
+        assert!(cache.get(1, "c").is_some());
+        assert!(cache.get(1, "a").is_none());
+
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(cache.get(1, "c").is_none());
+    }
+
+    #[test]
+    fn zero_ttl_disables_the_cache() {
+        let cache = TaskCache::new(Duration::ZERO, 10);
+        cache.insert(1, "a".to_owned(), validators(), Bytes::new());
+        assert!(cache.get(1, "a").is_none());
+    }
+}
@@ -0,0 +1,115 @@
+use async_graphql::Enum;
+use serde::Deserialize;
+use sqlx::{postgres::PgListener, PgPool};
+use std::{sync::Arc, time::Duration};
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+
+use crate::task_cache::TaskCache;
+
+/// 触发器使用的 NOTIFY 频道，见 `migrations/*_notify_task_changes.sql`
+pub const CHANNEL: &str = "task_changes";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Enum)]
+#[serde(rename_all = "lowercase")]
+#[graphql(name = "TaskChangeOp")]
+pub enum ChangeOp {
+    Insert,
+    Update,
+    Delete,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TaskChange {
+    pub op: ChangeOp,
+    pub id: i32,
+    pub user_id: i32,
+    pub organization_id: i32,
+    /// 变化前后列表里有这个任务的用户：所有者和负责人
+    #[serde(default)]
+    pub audience: Vec<i32>,
+}
+
+impl TaskChange {
+    pub fn concerns(&self, user_id: i32) -> bool {
+        self.user_id == user_id || self.audience.contains(&user_id)
+    }
+}
+
+/// 进程内的任务变化广播。订阅者跟不上时会丢掉最早的事件
+#[derive(Clone)]
+pub struct TaskEvents {
+    sender: broadcast::Sender<TaskChange>,
+}
+
+impl TaskEvents {
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity);
+        Self { sender }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<TaskChange> {
+        self.sender.subscribe()
+    }
+
+    pub fn publish(&self, change: TaskChange) {
+        // 没有订阅者时 send 返回错误，忽略即可
+        let _ = self.sender.send(change);
+    }
+}
+
+/// 监听数据库通知，转发给订阅者，同时清掉本实例里对应用户的读缓存。
+/// 这样其它实例（或导入、后台任务）写入后缓存也能及时失效
+pub async fn listen(
+    pool: PgPool,
+    events: TaskEvents,
+    cache: Arc<TaskCache>,
+    token: CancellationToken,
+) {
+    loop {
+        let mut listener = tokio::select! {
+            connected = connect(&pool) => match connected {
+                Ok(listener) => listener,
+                Err(e) => {
+                    eprintln!("failed to listen for task changes: {e}");
+                    tokio::select! {
+                        _ = tokio::time::sleep(Duration::from_secs(5)) => continue,
+                        _ = token.cancelled() => return,
+                    }
+                }
+            },
+            _ = token.cancelled() => return,
+        };
+
+        loop {
+            tokio::select! {
+                notification = listener.recv() => match notification {
+                    Ok(notification) => {
+                        match serde_json::from_str::<TaskChange>(notification.payload()) {
+                            Ok(change) => {
+                                cache.invalidate(change.user_id);
+                                for user_id in &change.audience {
+                                    cache.invalidate(*user_id);
+                                }
+                                events.publish(change);
+                            }
+                            Err(e) => eprintln!("invalid task change notification: {e}"),
+                        }
+                    }
+                    // 连接断开时 recv 会自己重连，只有重连失败才会走到这里
+                    Err(e) => {
+                        eprintln!("lost task change listener: {e}");
+                        break;
+                    }
+                },
+                _ = token.cancelled() => return,
+            }
+        }
+    }
+}
+
+async fn connect(pool: &PgPool) -> Result<PgListener, sqlx::Error> {
+    let mut listener = PgListener::connect_with(pool).await?;
+    listener.listen(CHANNEL).await?;
+    Ok(listener)
+}
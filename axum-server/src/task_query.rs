@@ -0,0 +1,523 @@
+//! 任务查询语言，例如 `status:open priority>=3 due<7d tag:backend -tag:wontfix "login bug"`。
+//!
+//! 多个条件之间是 AND，`-` 取反，没有字段名的词或引号字符串在标题和描述里搜索。
+//! 所有值都通过 `push_bind` 绑定，不会拼接进 SQL。
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, TimeZone, Utc};
+use serde::Serialize;
+use sqlx::{Postgres, QueryBuilder};
+use std::fmt;
+
+use crate::models::task::TASK_STATUSES;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ParseError {
+    /// 出错位置，按字符计数，从 0 开始
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at position {}", self.message, self.position)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+impl Op {
+    fn sql(self) -> &'static str {
+        match self {
+            Op::Eq => " = ",
+            Op::Ne => " <> ",
+            Op::Gt => " > ",
+            Op::Ge => " >= ",
+            Op::Lt => " < ",
+            Op::Le => " <= ",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DateField {
+    Due,
+    Created,
+    Updated,
+}
+
+impl DateField {
+    fn column(self) -> &'static str {
+        match self {
+            DateField::Due => "due_date",
+            DateField::Created => "created_at",
+            DateField::Updated => "updated_at",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DateValue {
+    /// 某一天（UTC），`:` 表示当天之内
+    Day(NaiveDate),
+    /// 相对现在的偏移，例如 `7d`、`-2w`
+    Relative(Duration),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Condition {
+    Text(String),
+    Title(String),
+    Status(Vec<String>),
+    Priority(Op, i32),
+    Category(String),
+    Tag(String),
+    Date(DateField, Op, DateValue),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Term {
+    pub negated: bool,
+    pub condition: Condition,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TaskQuery {
+    pub terms: Vec<Term>,
+}
+
+impl TaskQuery {
+    pub fn parse(input: &str) -> Result<Self, ParseError> {
+        Parser {
+            chars: input.chars().collect(),
+            pos: 0,
+        }
+        .parse()
+    }
+
+    /// 含有日期条件时，结果会随当前时间变化（相对时间、`today`）
+    pub fn has_date_condition(&self) -> bool {
+        self.terms
+            .iter()
+            .any(|term| matches!(term.condition, Condition::Date(..)))
+    }
+
+    /// 把条件追加到 `WHERE ...` 之后，每个条件以 ` AND ` 开头
+    pub fn push_sql(&self, query: &mut QueryBuilder<'_, Postgres>, now: DateTime<Utc>) {
+        for term in &self.terms {
+            query.push(if term.negated { " AND NOT (" } else { " AND (" });
+            push_condition(query, &term.condition, now);
+            query.push(")");
+        }
+    }
+}
+
+fn push_condition(
+    query: &mut QueryBuilder<'_, Postgres>,
+    condition: &Condition,
+    now: DateTime<Utc>,
+) {
+    match condition {
+        Condition::Text(text) => {
+            let pattern = like_pattern(text);
+            query.push("title ILIKE ");
+            query.push_bind(pattern.clone());
+            query.push(" OR description ILIKE ");
+            query.push_bind(pattern);
+        }
+        Condition::Title(text) => {
+            query.push("title ILIKE ");
+            query.push_bind(like_pattern(text));
+        }
+        Condition::Status(statuses) => {
+            query.push("status = ANY(");
+            query.push_bind(statuses.clone());
+            query.push(")");
+        }
+        Condition::Priority(op, value) => {
+            query.push("priority");
+            query.push(op.sql());
+            query.push_bind(*value);
+        }
+        Condition::Category(category) => {
+            query.push("category = ");
+            query.push_bind(category.clone());
+        }
+        Condition::Tag(tag) => {
+            query.push_bind(tag.clone());
+            query.push(" = ANY(tags)");
+        }
+        Condition::Date(field, op, DateValue::Day(day)) => {
+            let start = Utc.from_utc_datetime(&day.and_hms_opt(0, 0, 0).unwrap());
+            let end = start + Duration::days(1);
+            query.push(field.column());
+            match op {
+                Op::Eq => {
+                    query.push(" >= ");
+                    query.push_bind(start);
+                    query.push(" AND ");
+                    query.push(field.column());
+                    query.push(" < ");
+                    query.push_bind(end);
+                }
+                Op::Ne => {
+                    query.push(" < ");
+                    query.push_bind(start);
+                    query.push(" OR ");
+                    query.push(field.column());
+                    query.push(" >= ");
+                    query.push_bind(end);
+                }
+                Op::Lt | Op::Ge => {
+                    query.push(op.sql());
+                    query.push_bind(start);
+                }
+                Op::Le | Op::Gt => {
+                    query.push(if *op == Op::Le { " < " } else { " >= " });
+                    query.push_bind(end);
+                }
+            }
+        }
+        Condition::Date(field, op, DateValue::Relative(offset)) => {
+            query.push(field.column());
+            query.push(op.sql());
+            // 解析时已经检查过范围；`now` 比解析时晚一点也不会溢出
+            let resolved =
+                now.checked_add_signed(*offset)
+                    .unwrap_or(if *offset < Duration::zero() {
+                        DateTime::<Utc>::MIN_UTC
+                    } else {
+                        DateTime::<Utc>::MAX_UTC
+                    });
+            query.push_bind(resolved);
+        }
+    }
+}
+
+fn like_pattern(text: &str) -> String {
+    let escaped = text
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{escaped}%")
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn parse(mut self) -> Result<TaskQuery, ParseError> {
+        let mut terms = Vec::new();
+        loop {
+            while self.peek().is_some_and(char::is_whitespace) {
+                self.pos += 1;
+            }
+            if self.peek().is_none() {
+                return Ok(TaskQuery { terms });
+            }
+            terms.push(self.term()?);
+        }
+    }
+
+    fn term(&mut self) -> Result<Term, ParseError> {
+        let negated = self.peek() == Some('-')
+            && self
+                .chars
+                .get(self.pos + 1)
+                .is_some_and(|c| !c.is_whitespace());
+        if negated {
+            self.pos += 1;
+        }
+
+        if self.peek() == Some('"') {
+            let text = self.quoted()?;
+            return Ok(Term {
+                negated,
+                condition: Condition::Text(text),
+            });
+        }
+
+        let start = self.pos;
+        while self
+            .peek()
+            .is_some_and(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            self.pos += 1;
+        }
+        let name: String = self.chars[start..self.pos].iter().collect();
+
+        let Some(op) = self.op() else {
+            // 普通搜索词
+            self.pos = start;
+            let text = self.bare();
+            return Ok(Term {
+                negated,
+                condition: Condition::Text(text),
+            });
+        };
+        if name.is_empty() {
+            return Err(self.error(start, "expected a field name before the operator"));
+        }
+
+        let value_start = self.pos;
+        let value = if self.peek() == Some('"') {
+            self.quoted()?
+        } else {
+            self.bare()
+        };
+        if value.is_empty() {
+            return Err(self.error(value_start, format!("expected a value for `{name}`")));
+        }
+
+        let condition = field_condition(&name, op, &value).map_err(|(at_value, message)| {
+            self.error(if at_value { value_start } else { start }, message)
+        })?;
+        Ok(Term { negated, condition })
+    }
+
+    fn op(&mut self) -> Option<Op> {
+        let (op, len) = match (self.peek(), self.chars.get(self.pos + 1).copied()) {
+            (Some('>'), Some('=')) => (Op::Ge, 2),
+            (Some('<'), Some('=')) => (Op::Le, 2),
+            (Some('!'), Some('=')) => (Op::Ne, 2),
+            (Some('>'), _) => (Op::Gt, 1),
+            (Some('<'), _) => (Op::Lt, 1),
+            (Some(':'), _) | (Some('='), _) => (Op::Eq, 1),
+            _ => return None,
+        };
+        self.pos += len;
+        Some(op)
+    }
+
+    fn bare(&mut self) -> String {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| !c.is_whitespace()) {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos].iter().collect()
+    }
+
+    fn quoted(&mut self) -> Result<String, ParseError> {
+        let start = self.pos;
+        self.pos += 1;
+        let mut text = String::new();
+        loop {
+            match self.peek() {
+                None => return Err(self.error(start, "unterminated quoted string")),
+                Some('"') => {
+                    self.pos += 1;
+                    return Ok(text);
+                }
+                Some('\\') if self.chars.get(self.pos + 1).is_some() => {
+                    text.push(self.chars[self.pos + 1]);
+                    self.pos += 2;
+                }
+                Some(c) => {
+                    text.push(c);
+                    self.pos += 1;
+                }
+            }
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn error(&self, position: usize, message: impl Into<String>) -> ParseError {
+        ParseError {
+            position,
+            message: message.into(),
+        }
+    }
+}
+
+/// 出错时返回 (错误是否在值上, 错误信息)
+fn field_condition(name: &str, op: Op, value: &str) -> Result<Condition, (bool, String)> {
+    let equality_only = |condition: Condition| {
+        if matches!(op, Op::Eq) {
+            Ok(condition)
+        } else {
+            Err((false, format!("`{name}` only supports `:`")))
+        }
+    };
+
+    match name {
+        "status" | "is" => {
+            let statuses = match value {
+                "open" => vec!["pending".to_owned(), "in_progress".to_owned()],
+                "done" | "closed" => vec!["completed".to_owned()],
+                other if TASK_STATUSES.contains(&other) => vec![other.to_owned()],
+                other => return Err((true, format!("unknown status `{other}`"))),
+            };
+            match op {
+                Op::Eq => Ok(Condition::Status(statuses)),
+                _ => Err((false, format!("`{name}` only supports `:`"))),
+            }
+        }
+        "priority" => value
+            .parse()
+            .map(|priority| Condition::Priority(op, priority))
+            .map_err(|_| (true, format!("priority must be a number, got `{value}`"))),
+        "category" => equality_only(Condition::Category(value.to_owned())),
+        "tag" => equality_only(Condition::Tag(value.to_owned())),
+        "title" => equality_only(Condition::Title(value.to_owned())),
+        "due" | "created" | "updated" => {
+            let field = match name {
+                "due" => DateField::Due,
+                "created" => DateField::Created,
+                _ => DateField::Updated,
+            };
+            let date = parse_date(value).ok_or_else(|| {
+                (
+                    true,
+                    format!("expected a date like 2025-03-01, today or 7d, got `{value}`"),
+                )
+            })?;
+            if matches!(date, DateValue::Relative(_)) && matches!(op, Op::Eq | Op::Ne) {
+                return Err((
+                    false,
+                    format!("relative dates need <, <=, > or >= on `{name}`"),
+                ));
+            }
+            // 超出范围的日期拼 SQL 时会溢出，或者被 Postgres 拒绝
+            let in_range = match date {
+                DateValue::Day(day) => MAX_YEARS.contains(&day.year()),
+                DateValue::Relative(offset) => Utc::now()
+                    .checked_add_signed(offset)
+                    .is_some_and(|resolved| MAX_YEARS.contains(&resolved.year())),
+            };
+            if !in_range {
+                return Err((
+                    true,
+                    format!(
+                        "`{value}` is out of range, dates must fall in the years {}-{}",
+                        MAX_YEARS.start(),
+                        MAX_YEARS.end()
+                    ),
+                ));
+            }
+            Ok(Condition::Date(field, op, date))
+        }
+        other => Err((false, format!("unknown field `{other}`"))),
+    }
+}
+
+/// 日期条件允许的年份
+const MAX_YEARS: std::ops::RangeInclusive<i32> = 1..=9999;
+
+fn parse_date(value: &str) -> Option<DateValue> {
+    match value {
+        "today" => return Some(DateValue::Day(Utc::now().date_naive())),
+        "now" => return Some(DateValue::Relative(Duration::zero())),
+        _ => {}
+    }
+    if let Ok(day) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Some(DateValue::Day(day));
+    }
+
+    let unit = value.chars().last()?;
+    let amount: i64 = value[..value.len() - unit.len_utf8()].parse().ok()?;
+    let offset = match unit {
+        'h' => Duration::try_hours(amount)?,
+        'd' => Duration::try_days(amount)?,
+        'w' => Duration::try_weeks(amount)?,
+        _ => return None,
+    };
+    Some(DateValue::Relative(offset))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_the_documented_example() {
+        let query = TaskQuery::parse(
+            r#"status:open priority>=3 due<7d tag:backend -tag:wontfix "login bug""#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            query.terms,
+            vec![
+                Term {
+                    negated: false,
+                    condition: Condition::Status(vec!["pending".into(), "in_progress".into()]),
+                },
+                Term {
+                    negated: false,
+                    condition: Condition::Priority(Op::Ge, 3),
+                },
+                Term {
+                    negated: false,
+                    condition: Condition::Date(
+                        DateField::Due,
+                        Op::Lt,
+                        DateValue::Relative(Duration::days(7))
+                    ),
+                },
+                Term {
+                    negated: false,
+                    condition: Condition::Tag("backend".into()),
+                },
+                Term {
+                    negated: true,
+                    condition: Condition::Tag("wontfix".into()),
+                },
+                Term {
+                    negated: false,
+                    condition: Condition::Text("login bug".into()),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_error_positions() {
+        let err = TaskQuery::parse("status:open priority>=high").unwrap_err();
+        assert_eq!(err.position, 22);
+
+        let err = TaskQuery::parse("owner:me").unwrap_err();
+        assert_eq!(err.position, 0);
+
+        let err = TaskQuery::parse(r#"tag:a "unterminated"#).unwrap_err();
+        assert_eq!(err.position, 6);
+    }
+
+    #[test]
+    fn rejects_dates_out_of_range() {
+        for query in ["due<100000000d", "due>-100000000d", "due>-1000000w"] {
+            let err = TaskQuery::parse(&format!("tag:a {query}")).unwrap_err();
+            assert_eq!(err.position, 10, "{query}");
+            assert!(err.message.contains("out of range"), "{query}: {err}");
+        }
+        let err = TaskQuery::parse("due<99999999h").unwrap_err();
+        assert_eq!(err.position, 4);
+
+        TaskQuery::parse("due<3650d due>9999-12-31").unwrap();
+    }
+
+    #[test]
+    fn binds_values_instead_of_inlining_them() {
+        let query = TaskQuery::parse("\"'; DROP TABLE tasks; --\" priority:2").unwrap();
+        let mut builder = QueryBuilder::new("SELECT * FROM tasks WHERE user_id = ");
+        builder.push_bind(1);
+        query.push_sql(&mut builder, Utc::now());
+
+        let sql = builder.sql();
+        assert!(!sql.contains("DROP"), "{sql}");
+        assert!(sql.ends_with("AND (priority = $4)"), "{sql}");
+    }
+}
@@ -0,0 +1,255 @@
+use anyhow::{bail, Context};
+use arc_swap::ArcSwap;
+use axum::{extract::ConnectInfo, Router};
+use hyper::body::Incoming;
+use hyper_util::{
+    rt::{TokioExecutor, TokioIo},
+    server::{conn::auto, graceful::GracefulShutdown},
+    service::TowerToHyperService,
+};
+use rustls::{
+    crypto::{ring, CryptoProvider},
+    pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer},
+    server::{ClientHello, ResolvesServerCert, WebPkiClientVerifier},
+    sign::CertifiedKey,
+    RootCertStore, ServerConfig,
+};
+use sha2::{Digest, Sha256};
+use std::{
+    future::Future,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+use tokio::{net::TcpListener, task::JoinSet};
+use tokio_rustls::TlsAcceptor;
+use tokio_util::sync::CancellationToken;
+use tower::ServiceExt;
+
+use crate::{config::TlsConfig, shutdown};
+
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 双向 TLS 时客户端出示的证书，handler 可以从请求扩展里取出
+#[derive(Debug, Clone)]
+pub struct ClientCertificate {
+    /// DER 编码的 SHA-256，十六进制小写
+    pub fingerprint: String,
+}
+
+impl ClientCertificate {
+    pub fn from_der(der: &[u8]) -> Self {
+        Self {
+            fingerprint: hex::encode(Sha256::digest(der)),
+        }
+    }
+
+    /// 从 PEM 计算指纹，用于登记证书
+    pub fn from_pem(pem: &str) -> anyhow::Result<Self> {
+        let der = CertificateDer::from_pem_slice(pem.as_bytes())
+            .context("certificate is not a PEM encoded X.509 certificate")?;
+        Ok(Self::from_der(&der))
+    }
+}
+
+/// 证书文件变化时热加载。新握手使用新证书，已有连接不受影响
+#[derive(Debug)]
+pub struct CertReloader {
+    cert_path: PathBuf,
+    key_path: PathBuf,
+    current: ArcSwap<CertifiedKey>,
+    modified: Mutex<(SystemTime, SystemTime)>,
+}
+
+impl CertReloader {
+    pub fn load(
+        cert_path: impl Into<PathBuf>,
+        key_path: impl Into<PathBuf>,
+    ) -> anyhow::Result<Self> {
+        let cert_path = cert_path.into();
+        let key_path = key_path.into();
+        let modified = (modified(&cert_path)?, modified(&key_path)?);
+        let key = load_certified_key(&cert_path, &key_path)?;
+        Ok(Self {
+            cert_path,
+            key_path,
+            current: ArcSwap::from_pointee(key),
+            modified: Mutex::new(modified),
+        })
+    }
+
+    pub fn current(&self) -> Arc<CertifiedKey> {
+        self.current.load_full()
+    }
+
+    /// 文件修改时间变化时重新加载，返回是否换了证书。
+    /// 证书和私钥不匹配（比如只写了一半）时保留旧证书，下次再试
+    pub fn reload_if_changed(&self) -> anyhow::Result<bool> {
+        let modified = (modified(&self.cert_path)?, modified(&self.key_path)?);
+        if *self.modified.lock().unwrap() == modified {
+            return Ok(false);
+        }
+
+        let key = load_certified_key(&self.cert_path, &self.key_path)?;
+        self.current.store(Arc::new(key));
+        *self.modified.lock().unwrap() = modified;
+        Ok(true)
+    }
+}
+
+impl ResolvesServerCert for CertReloader {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(self.current())
+    }
+}
+
+fn modified(path: &Path) -> anyhow::Result<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .with_context(|| format!("failed to stat {}", path.display()))
+}
+
+fn load_certified_key(cert_path: &Path, key_path: &Path) -> anyhow::Result<CertifiedKey> {
+    let certs = CertificateDer::pem_file_iter(cert_path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .with_context(|| format!("failed to read certificates from {}", cert_path.display()))?;
+    if certs.is_empty() {
+        bail!("no certificates found in {}", cert_path.display());
+    }
+    let key = PrivateKeyDer::from_pem_file(key_path)
+        .with_context(|| format!("failed to read private key from {}", key_path.display()))?;
+    let signing_key = ring::sign::any_supported_type(&key)?;
+
+    let certified = CertifiedKey::new(certs, signing_key);
+    certified
+        .keys_match()
+        .context("certificate does not match private key")?;
+    Ok(certified)
+}
+
+pub async fn reload_periodically(
+    reloader: Arc<CertReloader>,
+    interval: Duration,
+    token: CancellationToken,
+) {
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        tokio::select! {
+            _ = token.cancelled() => return,
+            _ = ticker.tick() => {}
+        }
+        match reloader.reload_if_changed() {
+            Ok(true) => println!("reloaded TLS certificate"),
+            Ok(false) => {}
+            Err(e) => eprintln!("failed to reload TLS certificate, keeping the old one: {e:#}"),
+        }
+    }
+}
+
+pub fn server_config(tls: &TlsConfig, reloader: Arc<CertReloader>) -> anyhow::Result<ServerConfig> {
+    let provider = Arc::new(ring::default_provider());
+    let builder = ServerConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()?;
+
+    let builder = match &tls.client_ca_path {
+        Some(path) => builder.with_client_cert_verifier(client_verifier(
+            path,
+            tls.client_auth_required,
+            provider,
+        )?),
+        None => builder.with_no_client_auth(),
+    };
+
+    let mut config = builder.with_cert_resolver(reloader);
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(config)
+}
+
+fn client_verifier(
+    ca_path: &Path,
+    required: bool,
+    provider: Arc<CryptoProvider>,
+) -> anyhow::Result<Arc<dyn rustls::server::danger::ClientCertVerifier>> {
+    let mut roots = RootCertStore::empty();
+    for cert in CertificateDer::pem_file_iter(ca_path)
+        .with_context(|| format!("failed to read client CA from {}", ca_path.display()))?
+    {
+        roots.add(cert?)?;
+    }
+
+    let builder = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider);
+    let builder = if required {
+        builder
+    } else {
+        builder.allow_unauthenticated()
+    };
+    Ok(builder.build()?)
+}
+
+/// 和 `serve_with_shutdown` 一样的关闭语义，只是连接先经过 TLS 握手。
+/// 每个请求带上 `ConnectInfo<SocketAddr>`，有客户端证书时再带上 `ClientCertificate`
+pub async fn serve_tls<F>(
+    listener: TcpListener,
+    acceptor: TlsAcceptor,
+    app: Router,
+    signal: F,
+    drain_timeout: Duration,
+) -> anyhow::Result<()>
+where
+    F: Future<Output = ()> + Send,
+{
+    let graceful = GracefulShutdown::new();
+    let builder = auto::Builder::new(TokioExecutor::new());
+    let mut connections = JoinSet::new();
+    tokio::pin!(signal);
+
+    loop {
+        let (stream, addr) = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    // 文件描述符耗尽等情况，稍等再继续
+                    eprintln!("failed to accept connection: {e}");
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    continue;
+                }
+            },
+            _ = &mut signal => break,
+        };
+        while connections.try_join_next().is_some() {}
+
+        let acceptor = acceptor.clone();
+        let app = app.clone();
+        let builder = builder.clone();
+        let watcher = graceful.watcher();
+        connections.spawn(async move {
+            let stream =
+                match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                    Ok(Ok(stream)) => stream,
+                    _ => return,
+                };
+            let client_certificate = stream
+                .get_ref()
+                .1
+                .peer_certificates()
+                .and_then(|certs| certs.first())
+                .map(|cert| ClientCertificate::from_der(cert));
+
+            let service = app.map_request(move |mut request: hyper::Request<Incoming>| {
+                request.extensions_mut().insert(ConnectInfo(addr));
+                if let Some(certificate) = &client_certificate {
+                    request.extensions_mut().insert(certificate.clone());
+                }
+                request
+            });
+            let connection = builder.serve_connection_with_upgrades(
+                TokioIo::new(stream),
+                TowerToHyperService::new(service),
+            );
+            let _ = watcher.watch(connection.into_owned()).await;
+        });
+    }
+
+    drop(listener);
+    shutdown::drain(graceful, connections, drain_timeout).await
+}
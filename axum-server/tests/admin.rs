@@ -0,0 +1,311 @@
+mod common;
+
+use axum::{
+    extract::{Path, Query, State},
+    response::IntoResponse,
+};
+use axum_server::{
+    config::AuthConfig,
+    handlers::calendar::{self, FeedParams},
+    jwt_keys::{self, KeyRing},
+    services::{
+        auth_service::{self, ClientInfo},
+        user_service,
+    },
+    token,
+};
+use sqlx::PgPool;
+use std::{
+    io::Write,
+    process::{Command, Output, Stdio},
+    sync::Arc,
+    time::Duration,
+};
+
+const SECRET: &str = "admin-test-secret";
+
+fn config() -> AuthConfig {
+    AuthConfig {
+        jwt_secret: SECRET.to_owned(),
+        keys: KeyRing::new(SECRET),
+        keys_reload_interval: Duration::from_secs(60),
+        access_token_ttl: Duration::from_secs(60),
+        refresh_token_ttl: Duration::from_secs(3600),
+        oidc: None,
+    }
+}
+
+fn email() -> String {
+    format!("admin-{}@example.com", common::unique_suffix())
+}
+
+async fn login(pool: &PgPool, email: &str, password: &str) -> bool {
+    auth_service::login(
+        pool,
+        &config(),
+        email,
+        password.to_owned(),
+        ClientInfo::default(),
+    )
+    .await
+    .is_ok()
+}
+
+/// 用测试数据库运行 `axum-server-admin`
+fn admin(args: &[&str], stdin: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_axum-server-admin"))
+        .args(args)
+        .env("DATABASE_URL", std::env::var("TEST_DATABASE_URL").unwrap())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(stdin).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(
+        output.status.success(),
+        "{args:?} failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    output
+}
+
+#[tokio::test]
+async fn disabling_and_resetting_passwords_revoke_sessions() {
+    let Some(pool) = common::test_pool().await else {
+        return;
+    };
+    let email = email();
+    user_service::create_user(&pool, &email, "Ops", "first password".to_owned(), None)
+        .await
+        .unwrap();
+    let tokens = auth_service::login(
+        &pool,
+        &config(),
+        &email,
+        "first password".to_owned(),
+        ClientInfo::default(),
+    )
+    .await
+    .unwrap();
+
+    user_service::set_disabled(&pool, &email, true)
+        .await
+        .unwrap();
+    assert!(!login(&pool, &email, "first password").await);
+    assert!(
+        auth_service::refresh(&pool, &config(), &tokens.refresh_token)
+            .await
+            .is_err()
+    );
+
+    let user = user_service::set_disabled(&pool, &email, false)
+        .await
+        .unwrap();
+    assert!(user.disabled_at.is_none());
+    assert!(login(&pool, &email, "first password").await);
+
+    user_service::reset_password(&pool, &email, "second password".to_owned())
+        .await
+        .unwrap();
+    assert!(!login(&pool, &email, "first password").await);
+    assert!(login(&pool, &email, "second password").await);
+
+    let too_short = user_service::reset_password(&pool, &email, "short".to_owned()).await;
+    assert!(too_short.is_err());
+}
+
+/// 订阅地址能否打开
+async fn feed_works(pool: &PgPool, token: &str) -> bool {
+    calendar::calendar_feed(
+        State(Arc::new(pool.clone())),
+        Path(format!("{token}.ics")),
+        Query(FeedParams { kind: None }),
+    )
+    .await
+    .map(IntoResponse::into_response)
+    .is_ok()
+}
+
+#[tokio::test]
+async fn disabled_users_calendar_feeds_stop_working() {
+    let Some(pool) = common::test_pool().await else {
+        return;
+    };
+    let email = email();
+    let user = user_service::create_user(&pool, &email, "Ops", "first password".to_owned(), None)
+        .await
+        .unwrap();
+    let feed = token::generate();
+    sqlx::query(
+        "INSERT INTO calendar_feeds (organization_id, user_id, token_hash) VALUES ($1, $2, $3)",
+    )
+    .bind(user.organization_id)
+    .bind(user.id)
+    .bind(token::hash(&feed))
+    .execute(&pool)
+    .await
+    .unwrap();
+    assert!(feed_works(&pool, &feed).await);
+
+    user_service::set_disabled(&pool, &email, true)
+        .await
+        .unwrap();
+    assert!(!feed_works(&pool, &feed).await);
+
+    user_service::set_disabled(&pool, &email, false)
+        .await
+        .unwrap();
+    assert!(feed_works(&pool, &feed).await);
+}
+
+#[tokio::test]
+async fn rotated_keys_sign_new_tokens_and_retire_old_ones() {
+    let Some(pool) = common::test_pool().await else {
+        return;
+    };
+    // 密钥环是全局的，从空表开始
+    sqlx::query("DELETE FROM jwt_signing_keys")
+        .execute(&pool)
+        .await
+        .unwrap();
+    let config = config();
+    let before = auth_service::register(
+        &pool,
+        &config,
+        &email(),
+        "correct horse".to_owned(),
+        "",
+        ClientInfo::default(),
+    )
+    .await
+    .unwrap()
+    .access_token;
+    assert_eq!(auth_service::access_token_kid(&before), None);
+
+    // 旧 token 在宽限期内仍然有效，新 token 带上新密钥的 kid
+    let first = jwt_keys::rotate(&pool, SECRET, Duration::from_secs(60))
+        .await
+        .unwrap();
+    config.keys.reload(&pool).await.unwrap();
+    let claims = auth_service::decode_access_token(&config, &before).unwrap();
+    let after =
+        auth_service::encode_access_token(&config, claims.sub, claims.org, claims.sid).unwrap();
+    assert_eq!(
+        auth_service::access_token_kid(&after),
+        Some(first.to_string())
+    );
+    assert!(auth_service::decode_access_token(&config, &after).is_ok());
+
+    // 宽限期为 0 时之前的密钥立刻失效
+    jwt_keys::rotate(&pool, SECRET, Duration::ZERO)
+        .await
+        .unwrap();
+    config.keys.reload(&pool).await.unwrap();
+    assert!(auth_service::decode_access_token(&config, &before).is_err());
+    assert!(auth_service::decode_access_token(&config, &after).is_err());
+    let latest =
+        auth_service::encode_access_token(&config, claims.sub, claims.org, claims.sid).unwrap();
+    assert!(auth_service::decode_access_token(&config, &latest).is_ok());
+
+    // 其它实例用还没加载的密钥签发的 token，第一次遇到时重新加载
+    let other_instance = self::config();
+    assert!(auth_service::decode_access_token(&other_instance, &latest).is_err());
+    other_instance
+        .keys
+        .reload_for_unknown(&pool, &auth_service::access_token_kid(&latest).unwrap())
+        .await
+        .unwrap();
+    assert!(auth_service::decode_access_token(&other_instance, &latest).is_ok());
+
+    sqlx::query("DELETE FROM jwt_signing_keys")
+        .execute(&pool)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn cli_manages_users_and_moves_tasks_between_them() {
+    let Some(pool) = common::test_pool().await else {
+        return;
+    };
+    let (source, target) = (email(), email());
+
+    admin(
+        &["create-user", "--email", &target, "--password-stdin"],
+        b"cli password\n",
+    );
+    assert!(login(&pool, &target, "cli password").await);
+
+    let seeded = admin(&["seed", "--email", &source], b"");
+    let stdout = String::from_utf8(seeded.stdout).unwrap();
+    let password = stdout
+        .lines()
+        .find_map(|line| line.strip_prefix("password: "))
+        .unwrap();
+    assert!(login(&pool, &source, password).await);
+
+    let exported = admin(
+        &["export-tasks", "--email", &source, "--format", "ndjson"],
+        b"",
+    );
+    assert_eq!(exported.stdout.split(|b| *b == b'\n').count() - 1, 8);
+
+    let imported = admin(
+        &["import-tasks", "--email", &target, "--format", "ndjson"],
+        &exported.stdout,
+    );
+    let report: serde_json::Value = serde_json::from_slice(&imported.stdout).unwrap();
+    assert_eq!(report["valid"], 8, "{report}");
+
+    let count: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM tasks t JOIN users u ON u.id = t.user_id WHERE u.email = $1",
+    )
+    .bind(&target)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(count, 8);
+
+    admin(&["disable-user", "--email", &target], b"");
+    assert!(!login(&pool, &target, "cli password").await);
+}
+
+#[tokio::test]
+async fn first_user_administers_the_organization_until_demoted() {
+    let Some(pool) = common::test_pool().await else {
+        return;
+    };
+    let (first, second) = (email(), email());
+    let owner = user_service::create_user(&pool, &first, "", "first password".to_owned(), None)
+        .await
+        .unwrap();
+    assert!(owner.is_admin());
+    let member = user_service::create_user(
+        &pool,
+        &second,
+        "",
+        "second password".to_owned(),
+        Some(owner.organization_id),
+    )
+    .await
+    .unwrap();
+    assert_eq!(member.role, "member");
+
+    // 组织至少保留一个管理员
+    assert!(user_service::set_role(&pool, &first, "member")
+        .await
+        .is_err());
+    assert!(user_service::set_role(&pool, &second, "owner")
+        .await
+        .is_err());
+    assert!(user_service::set_role(&pool, &second, "admin")
+        .await
+        .unwrap()
+        .is_admin());
+    let demoted = user_service::set_role(&pool, &first, "member")
+        .await
+        .unwrap();
+    assert!(!demoted.is_admin());
+}
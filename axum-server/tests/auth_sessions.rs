@@ -0,0 +1,152 @@
+mod common;
+
+use axum_server::{
+    config::AuthConfig,
+    jwt_keys::KeyRing,
+    services::auth_service::{self, ClientInfo},
+};
+use std::time::Duration;
+
+fn config() -> AuthConfig {
+    AuthConfig {
+        jwt_secret: "test-secret".to_owned(),
+        keys: KeyRing::new("test-secret"),
+        keys_reload_interval: Duration::from_secs(60),
+        access_token_ttl: Duration::from_secs(60),
+        refresh_token_ttl: Duration::from_secs(3600),
+        oidc: None,
+    }
+}
+
+fn email() -> String {
+    format!("auth-{}@example.com", common::unique_suffix())
+}
+
+#[tokio::test]
+async fn login_checks_password() {
+    let Some(pool) = common::test_pool().await else {
+        return;
+    };
+    let config = config();
+    let email = email();
+    auth_service::register(
+        &pool,
+        &config,
+        &email,
+        "correct horse".to_owned(),
+        "",
+        ClientInfo::default(),
+    )
+    .await
+    .unwrap();
+
+    let wrong = auth_service::login(
+        &pool,
+        &config,
+        &email,
+        "battery staple".to_owned(),
+        ClientInfo::default(),
+    )
+    .await;
+    assert!(wrong.is_err());
+
+    let tokens = auth_service::login(
+        &pool,
+        &config,
+        &email.to_uppercase(),
+        "correct horse".to_owned(),
+        ClientInfo::default(),
+    )
+    .await
+    .unwrap();
+    let claims = auth_service::decode_access_token(&config, &tokens.access_token).unwrap();
+    assert!(
+        auth_service::session_is_active(&pool, claims.sub, claims.sid)
+            .await
+            .unwrap()
+    );
+}
+
+#[tokio::test]
+async fn refresh_rotates_and_detects_reuse() {
+    let Some(pool) = common::test_pool().await else {
+        return;
+    };
+    let config = config();
+    let first = auth_service::register(
+        &pool,
+        &config,
+        &email(),
+        "correct horse".to_owned(),
+        "",
+        ClientInfo::default(),
+    )
+    .await
+    .unwrap();
+    let claims = auth_service::decode_access_token(&config, &first.access_token).unwrap();
+
+    let second = auth_service::refresh(&pool, &config, &first.refresh_token)
+        .await
+        .unwrap();
+    assert_ne!(first.refresh_token, second.refresh_token);
+
+    // 旧 token 再次使用，整个 session 失效，新 token 也不能再用
+    assert!(auth_service::refresh(&pool, &config, &first.refresh_token)
+        .await
+        .is_err());
+    assert!(
+        !auth_service::session_is_active(&pool, claims.sub, claims.sid)
+            .await
+            .unwrap()
+    );
+    assert!(auth_service::refresh(&pool, &config, &second.refresh_token)
+        .await
+        .is_err());
+
+    let reason: String = sqlx::query_scalar("SELECT revoked_reason FROM sessions WHERE id = $1")
+        .bind(claims.sid)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(reason, "refresh_token_reuse");
+}
+
+#[tokio::test]
+async fn revoking_other_sessions_keeps_current() {
+    let Some(pool) = common::test_pool().await else {
+        return;
+    };
+    let config = config();
+    let email = email();
+    let current = auth_service::register(
+        &pool,
+        &config,
+        &email,
+        "correct horse".to_owned(),
+        "",
+        ClientInfo::default(),
+    )
+    .await
+    .unwrap();
+    auth_service::login(
+        &pool,
+        &config,
+        &email,
+        "correct horse".to_owned(),
+        ClientInfo::default(),
+    )
+    .await
+    .unwrap();
+    let claims = auth_service::decode_access_token(&config, &current.access_token).unwrap();
+
+    let revoked = auth_service::revoke_other_sessions(&pool, claims.sub, Some(claims.sid))
+        .await
+        .unwrap();
+    assert_eq!(revoked, 1);
+
+    let sessions = auth_service::list_sessions(&pool, claims.sub, Some(claims.sid))
+        .await
+        .unwrap();
+    assert_eq!(sessions.len(), 1);
+    assert!(sessions[0].current);
+}
@@ -0,0 +1,47 @@
+#![allow(dead_code)]
+
+use sqlx::PgPool;
+
+/// 需要 Postgres 的测试通过 `TEST_DATABASE_URL` 指定数据库，没有设置时跳过
+pub async fn test_pool() -> Option<PgPool> {
+    let Ok(url) = std::env::var("TEST_DATABASE_URL") else {
+        eprintln!("TEST_DATABASE_URL not set, skipping");
+        return None;
+    };
+    let pool = PgPool::connect(&url)
+        .await
+        .expect("connect to TEST_DATABASE_URL");
+    sqlx::migrate!().run(&pool).await.expect("run migrations");
+    Some(pool)
+}
+
+/// 每个测试用独立的组织和用户，避免互相影响
+pub async fn create_user(pool: &PgPool, organization_id: i32, label: &str) -> i32 {
+    let email = format!("{label}-{}@example.com", unique_suffix());
+    sqlx::query_scalar("INSERT INTO users (organization_id, email) VALUES ($1, $2) RETURNING id")
+        .bind(organization_id)
+        .bind(email)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+pub async fn create_organization(pool: &PgPool, name: &str) -> i32 {
+    sqlx::query_scalar("INSERT INTO organizations (name) VALUES ($1) RETURNING id")
+        .bind(name)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+pub fn unique_suffix() -> String {
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    static COUNTER: AtomicU32 = AtomicU32::new(0);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    format!("{nanos}-{}", COUNTER.fetch_add(1, Ordering::Relaxed))
+}
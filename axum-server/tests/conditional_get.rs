@@ -0,0 +1,203 @@
+mod common;
+
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+    response::Response,
+    Router,
+};
+use axum_server::{
+    app::create_app,
+    auth::{AuthUser, Scope},
+    config::Config,
+    models::token::CreatePersonalAccessToken,
+    rate_limit,
+    services::auth_service,
+    state::AppState,
+};
+use serde_json::json;
+use sqlx::PgPool;
+use std::time::Duration;
+use tower::ServiceExt;
+
+struct Client {
+    app: Router,
+    token: String,
+}
+
+impl Client {
+    async fn new(pool: &PgPool, cache_ttl: Duration) -> Self {
+        let mut config = Config::from_env().unwrap();
+        config.task_cache_ttl = cache_ttl;
+        let backend = rate_limit::connect(&config.rate_limit).await.unwrap();
+        let app = create_app(AppState::new(pool.clone(), config, backend));
+
+        let organization_id = common::create_organization(pool, "conditional").await;
+        let user = AuthUser {
+            user_id: common::create_user(pool, organization_id, "conditional").await,
+            organization_id,
+            session_id: None,
+            scopes: Scope::ALL.to_vec(),
+        };
+        let request = CreatePersonalAccessToken {
+            name: "conditional".to_owned(),
+            scopes: vec![Scope::TasksWrite],
+            expires_at: None,
+        };
+        let token = auth_service::create_personal_token(pool, &user, &request)
+            .await
+            .unwrap()
+            .token;
+
+        Self { app, token }
+    }
+
+    async fn get(&self, uri: &str, header: Option<(header::HeaderName, &str)>) -> Response {
+        let mut request = Request::get(uri).header(header::AUTHORIZATION, self.bearer());
+        if let Some((name, value)) = header {
+            request = request.header(name, value);
+        }
+        self.app
+            .clone()
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+    }
+
+    async fn create_task(&self, title: &str) -> i64 {
+        let body = json!({
+            "title": title,
+            "description": "",
+            "category": "work",
+            "priority": 1,
+            "due_date": "2030-01-01T00:00:00Z",
+        });
+        let response = self
+            .app
+            .clone()
+            .oneshot(
+                Request::post("/api/tasks")
+                    .header(header::AUTHORIZATION, self.bearer())
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        serde_json::from_slice::<serde_json::Value>(&bytes).unwrap()["id"]
+            .as_i64()
+            .unwrap()
+    }
+
+    fn bearer(&self) -> String {
+        format!("Bearer {}", self.token)
+    }
+}
+
+fn etag(response: &Response) -> String {
+    response.headers()[header::ETAG]
+        .to_str()
+        .unwrap()
+        .to_owned()
+}
+
+async fn assert_list_revalidates(client: &Client) {
+    client.create_task("first").await;
+
+    let first = client.get("/api/tasks", None).await;
+    assert_eq!(first.status(), StatusCode::OK);
+    let tag = etag(&first);
+    assert!(tag.starts_with("W/\""));
+    assert!(first.headers().contains_key(header::LAST_MODIFIED));
+
+    let unchanged = client
+        .get("/api/tasks", Some((header::IF_NONE_MATCH, &tag)))
+        .await;
+    assert_eq!(unchanged.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(etag(&unchanged), tag);
+
+    // 不同的过滤条件有不同的 ETag
+    let filtered = client.get("/api/tasks?category=work", None).await;
+    assert_ne!(etag(&filtered), tag);
+
+    client.create_task("second").await;
+    let changed = client
+        .get("/api/tasks", Some((header::IF_NONE_MATCH, &tag)))
+        .await;
+    assert_eq!(changed.status(), StatusCode::OK);
+    assert_ne!(etag(&changed), tag);
+}
+
+#[tokio::test]
+async fn list_returns_not_modified_until_a_task_changes() {
+    let Some(pool) = common::test_pool().await else {
+        return;
+    };
+    let client = Client::new(&pool, Duration::ZERO).await;
+    assert_list_revalidates(&client).await;
+}
+
+#[tokio::test]
+async fn cached_list_is_invalidated_by_writes() {
+    let Some(pool) = common::test_pool().await else {
+        return;
+    };
+    let client = Client::new(&pool, Duration::from_secs(60)).await;
+    assert_list_revalidates(&client).await;
+}
+
+#[tokio::test]
+async fn single_task_honours_if_modified_since() {
+    let Some(pool) = common::test_pool().await else {
+        return;
+    };
+    let client = Client::new(&pool, Duration::ZERO).await;
+    let id = client.create_task("single").await;
+    let uri = format!("/api/tasks/{id}");
+
+    let response = client.get(&uri, None).await;
+    let last_modified = response.headers()[header::LAST_MODIFIED]
+        .to_str()
+        .unwrap()
+        .to_owned();
+
+    let unchanged = client
+        .get(&uri, Some((header::IF_MODIFIED_SINCE, &last_modified)))
+        .await;
+    assert_eq!(unchanged.status(), StatusCode::NOT_MODIFIED);
+
+    let stale = client
+        .get(
+            &uri,
+            Some((header::IF_MODIFIED_SINCE, "Mon, 01 Jan 2001 00:00:00 GMT")),
+        )
+        .await;
+    assert_eq!(stale.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn date_relative_lists_do_not_send_last_modified() {
+    let Some(pool) = common::test_pool().await else {
+        return;
+    };
+    let client = Client::new(&pool, Duration::ZERO).await;
+    client.create_task("due soon").await;
+
+    // 结果随时间变化，不能按任务的修改时间判断
+    let uri = "/api/tasks?q=due%3C7d";
+    let response = client.get(uri, None).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(!response.headers().contains_key(header::LAST_MODIFIED));
+
+    let revalidated = client
+        .get(
+            uri,
+            Some((header::IF_MODIFIED_SINCE, "Fri, 01 Jan 2100 00:00:00 GMT")),
+        )
+        .await;
+    assert_eq!(revalidated.status(), StatusCode::OK);
+}
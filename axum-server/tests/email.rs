@@ -0,0 +1,400 @@
+mod common;
+
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+    Router,
+};
+use axum_server::{
+    app::create_app,
+    auth::{AuthUser, Scope},
+    config::{Config, EmailConfig},
+    db::tenant,
+    email::{self, SendNotification, SmtpTls},
+    jobs::{Job, JobContext},
+    models::{notification::UpdateNotificationPreferences, task::CreateTask},
+    rate_limit,
+    services::notification_service,
+    state::AppState,
+};
+use chrono::{Duration as ChronoDuration, TimeZone, Utc};
+use sqlx::PgPool;
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::TcpListener,
+};
+use tower::ServiceExt;
+
+/// 收件人和邮件原文
+type Received = (Vec<String>, String);
+
+/// 只实现发信需要的几个命令的本地 SMTP 服务器，收到的邮件保存在内存里
+#[derive(Clone, Default)]
+struct SmtpSink {
+    messages: Arc<Mutex<Vec<Received>>>,
+}
+
+impl SmtpSink {
+    async fn start() -> (Self, u16) {
+        let sink = Self::default();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let accepted = sink.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(accepted.clone().session(stream));
+            }
+        });
+        (sink, port)
+    }
+
+    async fn session(self, stream: tokio::net::TcpStream) {
+        let (read, mut write) = stream.into_split();
+        let mut lines = BufReader::new(read).lines();
+        let mut recipients = Vec::new();
+        write.write_all(b"220 sink ready\r\n").await.unwrap();
+        while let Ok(Some(line)) = lines.next_line().await {
+            let command = line.to_ascii_uppercase();
+            let reply: &[u8] = if command.starts_with("EHLO") || command.starts_with("HELO") {
+                b"250 sink\r\n"
+            } else if let Some(to) = command.strip_prefix("RCPT TO:") {
+                recipients.push(to.trim_matches(['<', '>', ' ']).to_ascii_lowercase());
+                b"250 OK\r\n"
+            } else if command == "DATA" {
+                write.write_all(b"354 go ahead\r\n").await.unwrap();
+                let mut data = String::new();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    if line == "." {
+                        break;
+                    }
+                    data.push_str(line.strip_prefix('.').unwrap_or(&line));
+                    data.push('\n');
+                }
+                self.messages
+                    .lock()
+                    .unwrap()
+                    .push((std::mem::take(&mut recipients), data));
+                b"250 queued\r\n"
+            } else if command == "QUIT" {
+                write.write_all(b"221 bye\r\n").await.unwrap();
+                return;
+            } else {
+                b"250 OK\r\n"
+            };
+            write.write_all(reply).await.unwrap();
+        }
+    }
+
+    /// 发给某个地址的邮件，测试之间用不同的收件人区分
+    fn received(&self, to: &str) -> Vec<String> {
+        self.messages
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(recipients, _)| recipients.iter().any(|r| r == to))
+            .map(|(_, data)| data.clone())
+            .collect()
+    }
+}
+
+struct Fixture {
+    pool: PgPool,
+    config: Arc<Config>,
+    sink: SmtpSink,
+    user: AuthUser,
+    email: String,
+}
+
+impl Fixture {
+    async fn new(pool: PgPool) -> Self {
+        let (sink, port) = SmtpSink::start().await;
+        let mut config = Config::from_env().unwrap();
+        config.public_url = "http://tasks.test".to_owned();
+        config.email = Some(EmailConfig {
+            smtp_host: "127.0.0.1".to_owned(),
+            smtp_port: port,
+            smtp_username: None,
+            smtp_password: None,
+            smtp_tls: SmtpTls::None,
+            from: "Tasks <tasks@example.com>".parse().unwrap(),
+            reminder_lead: Duration::from_secs(3600),
+            digest_hour: 7,
+        });
+
+        let organization_id = common::create_organization(&pool, "email").await;
+        let user_id = common::create_user(&pool, organization_id, "email").await;
+        let email: String = sqlx::query_scalar("SELECT email FROM users WHERE id = $1")
+            .bind(user_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        Self {
+            pool,
+            config: Arc::new(config),
+            sink,
+            user: AuthUser {
+                user_id,
+                organization_id,
+                session_id: None,
+                scopes: Scope::ALL.to_vec(),
+            },
+            email,
+        }
+    }
+
+    async fn create_task(&self, title: &str, due_in: ChronoDuration) -> i32 {
+        let mut tx = tenant::begin(&self.pool, &self.user).await.unwrap();
+        let task = axum_server::db::task_repo::insert_task(
+            &mut *tx,
+            self.user.user_id,
+            &CreateTask {
+                title: title.to_owned(),
+                description: String::new(),
+                category: "work".to_owned(),
+                priority: 3,
+                due_date: Utc::now() + due_in,
+                recurrence: None,
+                tags: Vec::new(),
+                estimate_seconds: None,
+            },
+        )
+        .await
+        .unwrap();
+        tx.commit().await.unwrap();
+        task.id
+    }
+
+    /// 执行这个用户排队中的邮件任务（不启动 worker，其它测试的任务留在队列里）
+    /// 和 worker 一样先标记为 running，执行完再标记完成。其它测试同时调用
+    /// `schedule_due` 时，`unique_key` 在执行期间仍然有效，不会重复入队
+    async fn deliver_queued(&self) -> usize {
+        let jobs: Vec<(i64, serde_json::Value)> = sqlx::query_as(
+            "UPDATE jobs SET status = 'running', locked_by = 'test', locked_at = NOW()
+             WHERE kind = $1 AND status = 'pending' AND (payload->>'user_id')::int = $2
+             RETURNING id, payload",
+        )
+        .bind(SendNotification::KIND)
+        .bind(self.user.user_id)
+        .fetch_all(&self.pool)
+        .await
+        .unwrap();
+        for (id, payload) in &jobs {
+            let job: SendNotification = serde_json::from_value(payload.clone()).unwrap();
+            job.run(&self.context(*id)).await.unwrap();
+            sqlx::query(
+                "UPDATE jobs SET status = 'completed', finished_at = NOW(), locked_by = NULL
+                 WHERE id = $1",
+            )
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .unwrap();
+        }
+        jobs.len()
+    }
+
+    fn context(&self, job_id: i64) -> JobContext {
+        JobContext {
+            pool: self.pool.clone(),
+            config: self.config.clone(),
+            job_id,
+            attempt: 1,
+        }
+    }
+
+    async fn unsubscribe_token(&self) -> String {
+        sqlx::query_scalar(
+            "SELECT unsubscribe_token FROM notification_preferences WHERE user_id = $1",
+        )
+        .bind(self.user.user_id)
+        .fetch_one(&self.pool)
+        .await
+        .unwrap()
+    }
+}
+
+#[tokio::test]
+async fn reminders_are_rendered_sent_once_and_carry_unsubscribe_headers() {
+    let Some(pool) = common::test_pool().await else {
+        return;
+    };
+    let fixture = Fixture::new(pool).await;
+    let task_id = fixture
+        .create_task("Ship <release>", ChronoDuration::minutes(30))
+        .await;
+    fixture.create_task("Later", ChronoDuration::days(3)).await;
+
+    email::schedule_due(&fixture.pool, &fixture.config, Utc::now())
+        .await
+        .unwrap();
+    assert_eq!(fixture.deliver_queued().await, 1);
+
+    let received = fixture.sink.received(&fixture.email);
+    assert_eq!(received.len(), 1);
+    let message = &received[0];
+    let token = fixture.unsubscribe_token().await;
+    assert!(
+        message.contains(r#"Subject: Reminder: "Ship <release>" is due"#),
+        "{message}"
+    );
+    assert!(message.contains(&format!(
+        "List-Unsubscribe: <http://tasks.test/unsubscribe/{token}?category=reminders>"
+    )));
+    assert!(message.contains("List-Unsubscribe-Post: List-Unsubscribe=One-Click"));
+    assert!(message.contains("multipart/alternative"));
+    assert!(message.contains("Content-Type: text/plain"));
+    assert!(message.contains("Content-Type: text/html"));
+    assert!(message.contains("Ship &lt;release&gt;"));
+    assert!(message.contains(&format!("http://tasks.test/api/tasks/{task_id}")));
+
+    // 已经发过的提醒不会再次排队，同一个 key 的通知重复执行也只发一次
+    email::schedule_due(&fixture.pool, &fixture.config, Utc::now())
+        .await
+        .unwrap();
+    assert_eq!(fixture.deliver_queued().await, 0);
+    let assignment = || SendNotification {
+        user_id: fixture.user.user_id,
+        notification: email::Notification::Assignment {
+            task_id,
+            title: "Ship <release>".to_owned(),
+            assigned_by: "Grace".to_owned(),
+        },
+        template_version: 1,
+        dedupe_key: Some(format!("assignment-test:{task_id}")),
+    };
+    assignment().run(&fixture.context(0)).await.unwrap();
+    assignment().run(&fixture.context(0)).await.unwrap();
+    let received = fixture.sink.received(&fixture.email);
+    assert_eq!(received.len(), 2);
+    assert!(received[1].contains(r#"Subject: Grace assigned you "Ship <release>""#));
+}
+
+#[tokio::test]
+async fn daily_digest_lists_open_tasks_after_the_configured_hour() {
+    let Some(pool) = common::test_pool().await else {
+        return;
+    };
+    let fixture = Fixture::new(pool).await;
+    fixture
+        .create_task("Overdue report", -ChronoDuration::days(2))
+        .await;
+    notification_service::update_preferences(
+        &fixture.pool,
+        &fixture.user,
+        &UpdateNotificationPreferences {
+            daily_digest: Some(true),
+            reminders: Some(false),
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+
+    let today = Utc::now().date_naive();
+    let early = Utc.from_utc_datetime(&today.and_hms_opt(6, 0, 0).unwrap());
+    email::schedule_due(&fixture.pool, &fixture.config, early)
+        .await
+        .unwrap();
+    assert_eq!(fixture.deliver_queued().await, 0);
+
+    let late = Utc.from_utc_datetime(&today.and_hms_opt(8, 0, 0).unwrap());
+    email::schedule_due(&fixture.pool, &fixture.config, late)
+        .await
+        .unwrap();
+    assert_eq!(fixture.deliver_queued().await, 1);
+
+    let received = fixture.sink.received(&fixture.email);
+    assert_eq!(received.len(), 1);
+    assert!(received[0].contains(&format!("Subject: Your tasks for {today}: 1 open")));
+    assert!(received[0].contains("Overdue report"));
+    assert!(received[0].contains("overdue"));
+}
+
+#[tokio::test]
+async fn unsubscribe_links_and_preferences_stop_emails() {
+    let Some(pool) = common::test_pool().await else {
+        return;
+    };
+    let fixture = Fixture::new(pool).await;
+    let backend = rate_limit::connect(&fixture.config.rate_limit)
+        .await
+        .unwrap();
+    let app = create_app(AppState::new(
+        fixture.pool.clone(),
+        (*fixture.config).clone(),
+        backend,
+    ));
+
+    let preferences = notification_service::get_preferences(&fixture.pool, &fixture.user)
+        .await
+        .unwrap();
+    assert!(preferences.assignments && preferences.reminders && !preferences.daily_digest);
+    let token = fixture.unsubscribe_token().await;
+    let uri = format!("/unsubscribe/{token}?category=assignments");
+
+    // 打开链接只显示确认页面，不会退订
+    let page = send(&app, Request::get(&uri)).await;
+    assert_eq!(page.0, StatusCode::OK);
+    assert!(page.1.contains("Stop receiving assignment emails?"));
+    // 页面不用接口的 CSP，只允许把表单提交回本站
+    let response = app
+        .clone()
+        .oneshot(Request::get(&uri).body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(
+        response.headers()[header::CONTENT_SECURITY_POLICY],
+        "default-src 'none'; form-action 'self'; frame-ancestors 'none'"
+    );
+    let one_click = send(
+        &app,
+        Request::post(&uri).header(header::CONTENT_TYPE, "application/x-www-form-urlencoded"),
+    )
+    .await;
+    assert_eq!(one_click.0, StatusCode::OK);
+    let invalid = send(&app, Request::post("/unsubscribe/nope")).await;
+    assert_eq!(invalid.0, StatusCode::NOT_FOUND);
+
+    let preferences = notification_service::get_preferences(&fixture.pool, &fixture.user)
+        .await
+        .unwrap();
+    assert!(!preferences.assignments);
+    assert!(preferences.reminders);
+
+    let task_id = fixture
+        .create_task("Assigned", ChronoDuration::days(1))
+        .await;
+    email::notify(
+        &fixture.pool,
+        &fixture.config,
+        fixture.user.user_id,
+        email::Notification::Assignment {
+            task_id,
+            title: "Assigned".to_owned(),
+            assigned_by: "Grace".to_owned(),
+        },
+        None,
+    )
+    .await
+    .unwrap()
+    .unwrap();
+    assert_eq!(fixture.deliver_queued().await, 1);
+    assert!(fixture.sink.received(&fixture.email).is_empty());
+}
+
+async fn send(app: &Router, request: axum::http::request::Builder) -> (StatusCode, String) {
+    let response = app
+        .clone()
+        .oneshot(request.body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let status = response.status();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, String::from_utf8_lossy(&body).into_owned())
+}
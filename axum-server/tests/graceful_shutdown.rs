@@ -0,0 +1,191 @@
+use axum::{routing::get, Router};
+use axum_server::{
+    background::BackgroundJobs,
+    shutdown::{drain_first, serve_with_shutdown, Draining},
+};
+use std::{
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    sync::oneshot,
+};
+
+async fn slow(delay: Duration) -> &'static str {
+    tokio::time::sleep(delay).await;
+    "done"
+}
+
+fn slow_app(delay: Duration) -> Router {
+    Router::new().route("/slow", get(move || slow(delay)))
+}
+
+async fn get_slow(addr: SocketAddr) -> std::io::Result<String> {
+    let mut stream = TcpStream::connect(addr).await?;
+    stream
+        .write_all(b"GET /slow HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .await?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await?;
+    Ok(response)
+}
+
+#[tokio::test]
+async fn drains_in_flight_requests_before_exiting() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (tx, rx) = oneshot::channel::<()>();
+
+    let server = tokio::spawn(serve_with_shutdown(
+        listener,
+        slow_app(Duration::from_millis(300)),
+        async move {
+            let _ = rx.await;
+        },
+        Duration::from_secs(5),
+    ));
+
+    let in_flight = tokio::spawn(get_slow(addr));
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    tx.send(()).unwrap();
+
+    let response = in_flight.await.unwrap().unwrap();
+    assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+    assert!(response.ends_with("done"), "{response}");
+
+    server.await.unwrap().unwrap();
+    assert!(TcpStream::connect(addr).await.is_err());
+}
+
+#[tokio::test]
+async fn gives_up_after_drain_timeout() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (tx, rx) = oneshot::channel::<()>();
+
+    let server = tokio::spawn(serve_with_shutdown(
+        listener,
+        slow_app(Duration::from_secs(30)),
+        async move {
+            let _ = rx.await;
+        },
+        Duration::from_millis(200),
+    ));
+
+    let _in_flight = tokio::spawn(get_slow(addr));
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    tx.send(()).unwrap();
+
+    let result = tokio::time::timeout(Duration::from_secs(2), server)
+        .await
+        .expect("server should stop once the drain timeout elapses")
+        .unwrap();
+    assert!(result.is_err());
+}
+
+/// 请求被中止时 future 被丢弃，放在 handler 里的资源随之释放
+struct Released(Arc<AtomicBool>);
+
+impl Drop for Released {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+}
+
+#[tokio::test]
+async fn aborts_requests_that_never_finish() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (tx, rx) = oneshot::channel::<()>();
+    let released = Arc::new(AtomicBool::new(false));
+
+    let flag = released.clone();
+    let app = Router::new().route(
+        "/slow",
+        get(move || async move {
+            let _held = Released(flag);
+            std::future::pending::<&'static str>().await
+        }),
+    );
+    let server = tokio::spawn(serve_with_shutdown(
+        listener,
+        app,
+        async move {
+            let _ = rx.await;
+        },
+        Duration::from_millis(200),
+    ));
+
+    let in_flight = tokio::spawn(get_slow(addr));
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    tx.send(()).unwrap();
+
+    let result = tokio::time::timeout(Duration::from_secs(2), server)
+        .await
+        .expect("server should stop once the drain timeout elapses")
+        .unwrap();
+    assert!(result.is_err());
+    assert!(released.load(Ordering::SeqCst));
+
+    // 连接被断开，客户端读到的是空响应
+    let response = tokio::time::timeout(Duration::from_secs(2), in_flight)
+        .await
+        .expect("the connection should be closed")
+        .unwrap();
+    assert!(response.map_or(true, |response| response.is_empty()));
+}
+
+#[tokio::test]
+async fn reports_draining_before_it_stops_accepting() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (tx, rx) = oneshot::channel::<()>();
+    let draining = Draining::default();
+
+    let signal = drain_first(
+        async move {
+            let _ = rx.await;
+        },
+        draining.clone(),
+        Duration::from_millis(500),
+    );
+    let server = tokio::spawn(serve_with_shutdown(
+        listener,
+        slow_app(Duration::from_millis(10)),
+        signal,
+        Duration::from_secs(5),
+    ));
+
+    assert!(!draining.is_draining());
+    tx.send(()).unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    // 已经是 draining，但新连接仍然会被处理
+    assert!(draining.is_draining());
+    let response = get_slow(addr).await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+
+    server.await.unwrap().unwrap();
+    assert!(TcpStream::connect(addr).await.is_err());
+}
+
+#[tokio::test]
+async fn stops_background_jobs() {
+    let jobs = BackgroundJobs::new();
+    let stopped = Arc::new(AtomicBool::new(false));
+
+    let flag = stopped.clone();
+    jobs.spawn(|token| async move {
+        token.cancelled().await;
+        flag.store(true, Ordering::SeqCst);
+    });
+
+    assert!(jobs.shutdown(Duration::from_secs(1)).await);
+    assert!(stopped.load(Ordering::SeqCst));
+}
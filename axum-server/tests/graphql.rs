@@ -0,0 +1,301 @@
+mod common;
+
+use axum_server::{
+    app::create_app,
+    auth::{AuthUser, Scope},
+    config::Config,
+    models::token::CreatePersonalAccessToken,
+    rate_limit,
+    services::auth_service,
+    state::AppState,
+    task_events,
+};
+use futures::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use std::{net::SocketAddr, time::Duration};
+use tokio_tungstenite::tungstenite::{client::IntoClientRequest, Message};
+use tokio_util::sync::CancellationToken;
+
+struct Server {
+    addr: SocketAddr,
+    write_token: String,
+    read_token: String,
+    _listener: tokio_util::sync::DropGuard,
+}
+
+impl Server {
+    async fn start(pool: &PgPool, configure: impl FnOnce(&mut Config)) -> Self {
+        let mut config = Config::from_env().unwrap();
+        configure(&mut config);
+        let backend = rate_limit::connect(&config.rate_limit).await.unwrap();
+        let state = AppState::new(pool.clone(), config, backend);
+
+        let token = CancellationToken::new();
+        tokio::spawn(task_events::listen(
+            pool.clone(),
+            state.task_events.clone(),
+            state.task_cache.clone(),
+            token.clone(),
+        ));
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = create_app(state);
+        tokio::spawn(async move {
+            axum::serve(
+                listener,
+                app.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .await
+            .unwrap()
+        });
+
+        let organization_id = common::create_organization(pool, "graphql").await;
+        let user = AuthUser {
+            user_id: common::create_user(pool, organization_id, "graphql").await,
+            organization_id,
+            session_id: None,
+            scopes: Scope::ALL.to_vec(),
+        };
+        let create_token = |scope| {
+            let user = user.clone();
+            async move {
+                let request = CreatePersonalAccessToken {
+                    name: format!("graphql {scope}"),
+                    scopes: vec![scope],
+                    expires_at: None,
+                };
+                auth_service::create_personal_token(pool, &user, &request)
+                    .await
+                    .unwrap()
+                    .token
+            }
+        };
+
+        Self {
+            addr,
+            write_token: create_token(Scope::TasksWrite).await,
+            read_token: create_token(Scope::TasksRead).await,
+            _listener: token.drop_guard(),
+        }
+    }
+
+    async fn execute(&self, token: &str, query: &str, variables: Value) -> Value {
+        reqwest::Client::new()
+            .post(format!("http://{}/graphql", self.addr))
+            .bearer_auth(token)
+            .json(&json!({ "query": query, "variables": variables }))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap()
+    }
+
+    async fn create_task(&self, title: &str) -> Value {
+        self.execute(
+            &self.write_token,
+            "mutation ($input: CreateTaskInput!) { createTask(input: $input) { id title } }",
+            json!({ "input": task_input(title, 1) }),
+        )
+        .await
+    }
+}
+
+fn task_input(title: &str, priority: i32) -> Value {
+    json!({
+        "title": title,
+        "description": "",
+        "category": "work",
+        "priority": priority,
+        "dueDate": "2030-01-01T00:00:00Z",
+        "tags": ["graphql"],
+    })
+}
+
+type Socket =
+    tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>;
+
+async fn send_json(socket: &mut Socket, message: Value) {
+    socket
+        .send(Message::Text(message.to_string()))
+        .await
+        .unwrap();
+}
+
+async fn next_json(socket: &mut Socket) -> Value {
+    let message = tokio::time::timeout(Duration::from_secs(5), socket.next())
+        .await
+        .expect("timed out waiting for a websocket message")
+        .unwrap()
+        .unwrap();
+    serde_json::from_str(message.to_text().unwrap()).unwrap()
+}
+
+fn error_code(response: &Value) -> &str {
+    response["errors"][0]["extensions"]["code"]
+        .as_str()
+        .unwrap_or_else(|| panic!("expected an error: {response}"))
+}
+
+#[tokio::test]
+async fn queries_tasks_with_owner_and_history() {
+    let Some(pool) = common::test_pool().await else {
+        return;
+    };
+    let server = Server::start(&pool, |_| {}).await;
+
+    let created = server.create_task("first").await;
+    let id = created["data"]["createTask"]["id"].as_i64().unwrap();
+    server.create_task("second").await;
+    let updated = server
+        .execute(
+            &server.write_token,
+            r#"mutation ($id: Int!) { updateTask(id: $id, input: { status: "completed" }) { status } }"#,
+            json!({ "id": id }),
+        )
+        .await;
+    assert_eq!(updated["data"]["updateTask"]["status"], "completed");
+
+    let response = server
+        .execute(
+            &server.read_token,
+            r#"{
+                me { id }
+                tasks(filter: { q: "tag:graphql" }) {
+                    id title tags
+                    owner { id email }
+                    history { oldStatus newStatus }
+                }
+            }"#,
+            json!({}),
+        )
+        .await;
+    let tasks = response["data"]["tasks"].as_array().unwrap();
+    assert_eq!(tasks.len(), 2, "{response}");
+    for task in tasks {
+        assert_eq!(task["owner"]["id"], response["data"]["me"]["id"]);
+    }
+    let history = &tasks[0]["history"];
+    assert_eq!(history[0]["newStatus"], "pending");
+    assert_eq!(history[1]["oldStatus"], "pending");
+    assert_eq!(history[1]["newStatus"], "completed");
+}
+
+#[tokio::test]
+async fn mutations_share_rest_validation_and_scopes() {
+    let Some(pool) = common::test_pool().await else {
+        return;
+    };
+    let server = Server::start(&pool, |_| {}).await;
+    let mutation = "mutation ($input: CreateTaskInput!) { createTask(input: $input) { id } }";
+
+    let invalid = server
+        .execute(
+            &server.write_token,
+            mutation,
+            json!({ "input": task_input("bad", 9) }),
+        )
+        .await;
+    assert_eq!(error_code(&invalid), "UNPROCESSABLE_ENTITY");
+
+    let read_only = server
+        .execute(
+            &server.read_token,
+            mutation,
+            json!({ "input": task_input("nope", 1) }),
+        )
+        .await;
+    assert_eq!(error_code(&read_only), "FORBIDDEN");
+
+    let bad_query = server
+        .execute(
+            &server.read_token,
+            r#"{ tasks(filter: { q: "priority>>1" }) { id } }"#,
+            json!({}),
+        )
+        .await;
+    assert_eq!(error_code(&bad_query), "BAD_REQUEST");
+}
+
+#[tokio::test]
+async fn rejects_queries_over_the_limits() {
+    let Some(pool) = common::test_pool().await else {
+        return;
+    };
+    let server = Server::start(&pool, |config| {
+        config.graphql.max_depth = 2;
+        config.graphql.max_complexity = 30;
+    })
+    .await;
+
+    let too_deep = server
+        .execute(&server.read_token, "{ tasks { owner { id } } }", json!({}))
+        .await;
+    assert!(too_deep["errors"][0]["message"]
+        .as_str()
+        .unwrap()
+        .contains("nested too deep"));
+
+    // 列表字段按 10 倍计算复杂度
+    let too_complex = server
+        .execute(
+            &server.read_token,
+            "{ tasks { id title description category } }",
+            json!({}),
+        )
+        .await;
+    assert!(too_complex["errors"][0]["message"]
+        .as_str()
+        .unwrap()
+        .contains("too complex"));
+}
+
+#[tokio::test]
+async fn subscription_receives_task_changes() {
+    let Some(pool) = common::test_pool().await else {
+        return;
+    };
+    let server = Server::start(&pool, |_| {}).await;
+
+    let mut request = format!("ws://{}/graphql/ws", server.addr)
+        .into_client_request()
+        .unwrap();
+    request.headers_mut().insert(
+        "sec-websocket-protocol",
+        "graphql-transport-ws".parse().unwrap(),
+    );
+    let (mut socket, _) = tokio_tungstenite::connect_async(request).await.unwrap();
+
+    send_json(
+        &mut socket,
+        json!({
+            "type": "connection_init",
+            "payload": { "Authorization": format!("Bearer {}", server.read_token) },
+        }),
+    )
+    .await;
+    send_json(
+        &mut socket,
+        json!({
+            "id": "1",
+            "type": "subscribe",
+            "payload": { "query": "subscription { taskChanged { op taskId task { title } } }" },
+        }),
+    )
+    .await;
+    assert_eq!(next_json(&mut socket).await["type"], "connection_ack");
+
+    // 等订阅真正开始监听后再写入
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    let created = server.create_task("pushed").await;
+
+    let event = next_json(&mut socket).await;
+    assert_eq!(event["type"], "next", "{event}");
+    let change = &event["payload"]["data"]["taskChanged"];
+    assert_eq!(change["op"], "INSERT");
+    assert_eq!(change["taskId"], created["data"]["createTask"]["id"]);
+    assert_eq!(change["task"]["title"], "pushed");
+}
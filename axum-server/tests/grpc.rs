@@ -0,0 +1,250 @@
+mod common;
+
+use axum_server::{
+    auth::{AuthUser, Scope},
+    config::Config,
+    grpc::{
+        self,
+        pb::{
+            task_service_client::TaskServiceClient, CreateTaskRequest, DeleteTaskRequest,
+            GetTaskRequest, ListTasksRequest, TagList, UpdateTaskRequest,
+        },
+    },
+    models::token::CreatePersonalAccessToken,
+    rate_limit,
+    services::auth_service,
+    state::AppState,
+};
+use sqlx::PgPool;
+use tonic::{transport::Channel, Code, Request};
+
+struct Server {
+    client: TaskServiceClient<Channel>,
+    write_token: String,
+    read_token: String,
+}
+
+impl Server {
+    async fn start(pool: &PgPool) -> Self {
+        let config = Config::from_env().unwrap();
+        let backend = rate_limit::connect(&config.rate_limit).await.unwrap();
+        let state = AppState::new(pool.clone(), config, backend);
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            grpc::serve(listener, &state, std::future::pending())
+                .await
+                .unwrap()
+        });
+        let client = TaskServiceClient::connect(format!("http://{addr}"))
+            .await
+            .unwrap();
+
+        let organization_id = common::create_organization(pool, "grpc").await;
+        let user = AuthUser {
+            user_id: common::create_user(pool, organization_id, "grpc").await,
+            organization_id,
+            session_id: None,
+            scopes: Scope::ALL.to_vec(),
+        };
+        let create_token = |scope| {
+            let user = user.clone();
+            async move {
+                let request = CreatePersonalAccessToken {
+                    name: format!("grpc {scope}"),
+                    scopes: vec![scope],
+                    expires_at: None,
+                };
+                auth_service::create_personal_token(pool, &user, &request)
+                    .await
+                    .unwrap()
+                    .token
+            }
+        };
+
+        Self {
+            client,
+            write_token: create_token(Scope::TasksWrite).await,
+            read_token: create_token(Scope::TasksRead).await,
+        }
+    }
+}
+
+fn authorized<T>(token: &str, message: T) -> Request<T> {
+    let mut request = Request::new(message);
+    request
+        .metadata_mut()
+        .insert("authorization", format!("Bearer {token}").parse().unwrap());
+    request
+}
+
+fn create_request(title: &str, priority: i32) -> CreateTaskRequest {
+    CreateTaskRequest {
+        title: title.to_owned(),
+        description: String::new(),
+        category: "work".to_owned(),
+        priority,
+        due_date: Some(prost_types::Timestamp {
+            seconds: 1_893_456_000,
+            nanos: 0,
+        }),
+        recurrence: None,
+        tags: vec!["grpc".to_owned()],
+        estimate_seconds: Some(3600),
+    }
+}
+
+#[tokio::test]
+async fn crud_and_streaming_list() {
+    let Some(pool) = common::test_pool().await else {
+        return;
+    };
+    let mut server = Server::start(&pool).await;
+    let token = server.write_token.clone();
+
+    let mut ids = Vec::new();
+    for (title, priority) in [("first", 1), ("second", 3), ("third", 3)] {
+        let task = server
+            .client
+            .create_task(authorized(&token, create_request(title, priority)))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(task.title, title);
+        assert_eq!(task.due_date.unwrap().seconds, 1_893_456_000);
+        assert_eq!(task.reporter_id, task.user_id);
+        assert!(task.assignee_ids.is_empty());
+        assert!(!task.rank.is_empty());
+        assert_eq!(task.estimate_seconds, Some(3600));
+        assert_eq!(task.time_spent_seconds, 0);
+        ids.push(task.id);
+    }
+
+    let updated = server
+        .client
+        .update_task(authorized(
+            &token,
+            UpdateTaskRequest {
+                id: ids[0],
+                status: Some("completed".to_owned()),
+                tags: Some(TagList { tags: Vec::new() }),
+                estimate_seconds: Some(0),
+                ..Default::default()
+            },
+        ))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(updated.status, "completed");
+    assert!(updated.tags.is_empty());
+    assert_eq!(updated.estimate_seconds, None);
+    assert_eq!(updated.title, "first");
+
+    let fetched = server
+        .client
+        .get_task(authorized(
+            &server.read_token,
+            GetTaskRequest { id: ids[1] },
+        ))
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(fetched.title, "second");
+
+    let mut stream = server
+        .client
+        .list_tasks(authorized(
+            &server.read_token,
+            ListTasksRequest {
+                q: Some("tag:grpc priority:3".to_owned()),
+                ..Default::default()
+            },
+        ))
+        .await
+        .unwrap()
+        .into_inner();
+    let mut titles = Vec::new();
+    while let Some(task) = stream.message().await.unwrap() {
+        titles.push(task.title);
+    }
+    assert_eq!(titles, ["second", "third"]);
+
+    server
+        .client
+        .delete_task(authorized(&token, DeleteTaskRequest { id: ids[2] }))
+        .await
+        .unwrap();
+    let missing = server
+        .client
+        .get_task(authorized(&token, GetTaskRequest { id: ids[2] }))
+        .await
+        .unwrap_err();
+    assert_eq!(missing.code(), Code::NotFound);
+}
+
+#[tokio::test]
+async fn maps_auth_and_validation_errors_to_status_codes() {
+    let Some(pool) = common::test_pool().await else {
+        return;
+    };
+    let mut server = Server::start(&pool).await;
+
+    let anonymous = server
+        .client
+        .get_task(GetTaskRequest { id: 1 })
+        .await
+        .unwrap_err();
+    assert_eq!(anonymous.code(), Code::Unauthenticated);
+
+    let bad_token = server
+        .client
+        .get_task(authorized("pat_nope", GetTaskRequest { id: 1 }))
+        .await
+        .unwrap_err();
+    assert_eq!(bad_token.code(), Code::Unauthenticated);
+
+    let read_only = server
+        .client
+        .create_task(authorized(&server.read_token, create_request("nope", 1)))
+        .await
+        .unwrap_err();
+    assert_eq!(read_only.code(), Code::PermissionDenied);
+
+    let invalid = server
+        .client
+        .create_task(authorized(&server.write_token, create_request("bad", 9)))
+        .await
+        .unwrap_err();
+    assert_eq!(invalid.code(), Code::InvalidArgument);
+    assert!(invalid.message().contains("priority"));
+
+    for (seconds, nanos) in [(i64::MAX, 0), (0, -1), (0, 1_000_000_000)] {
+        let mut request = create_request("bad", 1);
+        request.due_date = Some(prost_types::Timestamp { seconds, nanos });
+        let invalid = server
+            .client
+            .create_task(authorized(&server.write_token, request))
+            .await
+            .unwrap_err();
+        assert_eq!(invalid.code(), Code::InvalidArgument);
+        assert!(invalid.message().contains("due_date"));
+    }
+
+    // 查询错误在流的第一条消息处返回
+    let mut stream = server
+        .client
+        .list_tasks(authorized(
+            &server.read_token,
+            ListTasksRequest {
+                q: Some("priority>>1".to_owned()),
+                ..Default::default()
+            },
+        ))
+        .await
+        .unwrap()
+        .into_inner();
+    let bad_query = stream.message().await.unwrap_err();
+    assert_eq!(bad_query.code(), Code::InvalidArgument);
+    assert_eq!(bad_query.message().matches("position").count(), 1);
+}
@@ -0,0 +1,98 @@
+mod common;
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+    Router,
+};
+use axum_server::{app::create_app, config::Config, rate_limit, state::AppState};
+use serde_json::Value;
+use sqlx::{postgres::PgPoolOptions, PgPool};
+use std::time::Duration;
+use tower::ServiceExt;
+
+async fn state(pool: PgPool) -> AppState {
+    let config = Config::from_env().unwrap();
+    let backend = rate_limit::connect(&config.rate_limit).await.unwrap();
+    AppState::new(pool, config, backend)
+}
+
+/// 连不上的数据库，获取连接很快失败
+fn unreachable_pool() -> PgPool {
+    PgPoolOptions::new()
+        .acquire_timeout(Duration::from_millis(500))
+        .connect_lazy("postgres://postgres@127.0.0.1:1/unreachable")
+        .unwrap()
+}
+
+async fn get(app: &Router, uri: &str) -> (StatusCode, String) {
+    let response = app
+        .clone()
+        .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let status = response.status();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, String::from_utf8(bytes.to_vec()).unwrap())
+}
+
+#[tokio::test]
+async fn healthz_does_not_need_the_database() {
+    let app = create_app(state(unreachable_pool()).await);
+    let (status, _) = get(&app, "/healthz").await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn readyz_fails_without_the_database() {
+    let app = create_app(state(unreachable_pool()).await);
+    let (status, body) = get(&app, "/readyz").await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    let body: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(body["status"], "not_ready");
+}
+
+#[tokio::test]
+async fn readyz_fails_while_draining() {
+    let Some(pool) = common::test_pool().await else {
+        return;
+    };
+    let state = state(pool).await;
+    let draining = state.draining.clone();
+    let app = create_app(state);
+
+    let (status, _) = get(&app, "/readyz").await;
+    assert_eq!(status, StatusCode::OK);
+
+    draining.start();
+    let (status, body) = get(&app, "/readyz").await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    let body: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(body["status"], "draining");
+}
+
+#[tokio::test]
+async fn metrics_count_and_time_requests() {
+    let app = create_app(state(unreachable_pool()).await);
+    get(&app, "/healthz").await;
+
+    let (status, body) = get(&app, "/metrics").await;
+    assert_eq!(status, StatusCode::OK);
+    let labels = r#"method="GET",route="/healthz",status="200""#;
+    assert!(
+        body.contains(&format!("http_requests_total{{{labels}}} 1")),
+        "{body}"
+    );
+    assert!(
+        body.contains(&format!(
+            "http_request_duration_seconds_count{{{labels}}} 1"
+        )),
+        "{body}"
+    );
+    assert!(
+        body.contains("http_request_duration_seconds_bucket{"),
+        "{body}"
+    );
+}
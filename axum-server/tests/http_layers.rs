@@ -0,0 +1,114 @@
+use axum::{
+    body::Body,
+    http::{header, Method, Request, StatusCode},
+    Router,
+};
+use axum_server::{
+    app::create_app,
+    config::{Config, DEFAULT_CONTENT_SECURITY_POLICY},
+    rate_limit,
+    state::AppState,
+};
+use sqlx::PgPool;
+use tower::ServiceExt;
+
+/// 这些请求不访问数据库，用 lazy pool 即可
+async fn app(configure: impl FnOnce(&mut Config)) -> Router {
+    let mut config = Config::from_env().unwrap();
+    configure(&mut config);
+    let pool = PgPool::connect_lazy(&config.database_url).unwrap();
+    let backend = rate_limit::connect(&config.rate_limit).await.unwrap();
+    create_app(AppState::new(pool, config, backend))
+}
+
+#[tokio::test]
+async fn adds_security_headers() {
+    let app = app(|_| {}).await;
+    let response = app
+        .oneshot(Request::get("/healthz").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+
+    let headers = response.headers();
+    assert_eq!(headers[header::X_CONTENT_TYPE_OPTIONS], "nosniff");
+    assert_eq!(
+        headers[header::CONTENT_SECURITY_POLICY],
+        DEFAULT_CONTENT_SECURITY_POLICY
+    );
+    assert!(headers[header::STRICT_TRANSPORT_SECURITY]
+        .to_str()
+        .unwrap()
+        .starts_with("max-age="));
+}
+
+#[tokio::test]
+async fn answers_preflight_for_allowed_origins_only() {
+    let app = app(|config| {
+        config.http.cors_origins = vec!["https://app.example.com".to_owned()];
+    })
+    .await;
+    let preflight = |origin: &str| {
+        Request::builder()
+            .method(Method::OPTIONS)
+            .uri("/api/tasks")
+            .header(header::ORIGIN, origin)
+            .header(header::ACCESS_CONTROL_REQUEST_METHOD, "POST")
+            .header(header::ACCESS_CONTROL_REQUEST_HEADERS, "authorization")
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    let allowed = app
+        .clone()
+        .oneshot(preflight("https://app.example.com"))
+        .await
+        .unwrap();
+    assert_eq!(
+        allowed.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+        "https://app.example.com"
+    );
+
+    let denied = app
+        .oneshot(preflight("https://evil.example"))
+        .await
+        .unwrap();
+    assert!(!denied
+        .headers()
+        .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN));
+}
+
+#[tokio::test]
+async fn compresses_when_the_client_accepts_it() {
+    let app = app(|_| {}).await;
+    for encoding in ["gzip", "br", "zstd"] {
+        let response = app
+            .clone()
+            .oneshot(
+                Request::get("/metrics")
+                    .header(header::ACCEPT_ENCODING, encoding)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_ENCODING], encoding);
+    }
+}
+
+#[tokio::test]
+async fn rejects_bodies_over_the_limit() {
+    let app = app(|config| config.http.body_limit = 16).await;
+    let response = app
+        .oneshot(
+            Request::post("/api/auth/login")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(
+                    r#"{"email": "someone@example.com", "password": "long enough"}"#,
+                ))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+}
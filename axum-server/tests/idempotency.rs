@@ -0,0 +1,226 @@
+mod common;
+
+use axum::{
+    body::Body,
+    http::{header, Method, Request, StatusCode},
+    Router,
+};
+use axum_server::{
+    app::create_app,
+    auth::{AuthUser, Scope},
+    config::Config,
+    models::token::CreatePersonalAccessToken,
+    rate_limit,
+    services::auth_service,
+    state::AppState,
+};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use tower::ServiceExt;
+
+struct Client {
+    app: Router,
+    user: AuthUser,
+    token: String,
+}
+
+impl Client {
+    async fn new(pool: &PgPool) -> Self {
+        let mut config = Config::from_env().unwrap();
+        config.http.body_limit = 4096;
+        let backend = rate_limit::connect(&config.rate_limit).await.unwrap();
+        let app = create_app(AppState::new(pool.clone(), config, backend));
+
+        let organization_id = common::create_organization(pool, "idempotency").await;
+        let user = AuthUser {
+            user_id: common::create_user(pool, organization_id, "idempotency").await,
+            organization_id,
+            session_id: None,
+            scopes: Scope::ALL.to_vec(),
+        };
+        let request = CreatePersonalAccessToken {
+            name: "idempotency".to_owned(),
+            scopes: vec![Scope::TasksWrite],
+            expires_at: None,
+        };
+        let token = auth_service::create_personal_token(pool, &user, &request)
+            .await
+            .unwrap()
+            .token;
+        Self { app, user, token }
+    }
+
+    async fn post(
+        &self,
+        uri: &str,
+        key: &str,
+        body: String,
+    ) -> (StatusCode, Option<String>, Value) {
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri(uri)
+            .header(header::AUTHORIZATION, format!("Bearer {}", self.token))
+            .header(header::CONTENT_TYPE, "application/json")
+            .header("idempotency-key", key)
+            .body(Body::from(body))
+            .unwrap();
+        let response = self.app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let replayed = response
+            .headers()
+            .get("idempotent-replayed")
+            .map(|value| value.to_str().unwrap().to_owned());
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (
+            status,
+            replayed,
+            serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+        )
+    }
+
+    async fn create_task(&self, key: &str, title: &str) -> (StatusCode, Option<String>, Value) {
+        let body = json!({
+            "title": title,
+            "description": "",
+            "category": "work",
+            "priority": 1,
+            "due_date": "2030-01-01T00:00:00Z",
+        });
+        self.post("/api/tasks", key, body.to_string()).await
+    }
+}
+
+async fn task_count(pool: &PgPool, user_id: i32) -> i64 {
+    sqlx::query_scalar("SELECT COUNT(*) FROM tasks WHERE user_id = $1")
+        .bind(user_id)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn retries_replay_the_stored_response() {
+    let Some(pool) = common::test_pool().await else {
+        return;
+    };
+    let client = Client::new(&pool).await;
+
+    let (status, replayed, first) = client.create_task("create-1", "once").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(replayed, None);
+
+    let (status, replayed, second) = client.create_task("create-1", "once").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(replayed.as_deref(), Some("true"));
+    assert_eq!(first, second);
+    assert_eq!(task_count(&pool, client.user.user_id).await, 1);
+}
+
+#[tokio::test]
+async fn rejects_the_same_key_with_a_different_request() {
+    let Some(pool) = common::test_pool().await else {
+        return;
+    };
+    let client = Client::new(&pool).await;
+
+    let (status, _, _) = client.create_task("create-2", "first").await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _, _) = client.create_task("create-2", "second").await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    // 查询参数不同也是不同的请求：试运行的结果不能被当成真正的导入
+    let rows = json!([{
+        "title": "imported",
+        "description": "",
+        "category": "work",
+        "priority": 1,
+        "due_date": "2030-01-01T00:00:00Z",
+    }])
+    .to_string();
+    let (status, _, report) = client
+        .post("/api/tasks/import?dry_run=true", "import-1", rows.clone())
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(report["dry_run"], true);
+    let (status, _, _) = client
+        .post("/api/tasks/import?dry_run=false", "import-1", rows)
+        .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(task_count(&pool, client.user.user_id).await, 1);
+}
+
+#[tokio::test]
+async fn conflicts_while_in_flight_until_the_lock_expires() {
+    let Some(pool) = common::test_pool().await else {
+        return;
+    };
+    let client = Client::new(&pool).await;
+    let (status, _, _) = client.create_task("create-3", "first").await;
+    assert_eq!(status, StatusCode::OK);
+
+    // 模拟第一次请求还在处理：没有响应，占用还没到期
+    sqlx::query(
+        "UPDATE idempotency_keys
+         SET status_code = NULL, response_body = NULL, locked_until = NOW() + INTERVAL '1 minute'
+         WHERE user_id = $1 AND key = 'create-3'",
+    )
+    .bind(client.user.user_id)
+    .execute(&pool)
+    .await
+    .unwrap();
+    let (status, _, _) = client.create_task("create-3", "first").await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    // 处理它的进程没了，占用到期后重试重新执行
+    sqlx::query(
+        "UPDATE idempotency_keys SET locked_until = NOW() - INTERVAL '1 second'
+         WHERE user_id = $1 AND key = 'create-3'",
+    )
+    .bind(client.user.user_id)
+    .execute(&pool)
+    .await
+    .unwrap();
+    let (status, replayed, _) = client.create_task("create-3", "first").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(replayed, None);
+    assert_eq!(task_count(&pool, client.user.user_id).await, 2);
+}
+
+#[tokio::test]
+async fn expired_keys_can_be_reused() {
+    let Some(pool) = common::test_pool().await else {
+        return;
+    };
+    let client = Client::new(&pool).await;
+    let (status, _, _) = client.create_task("create-4", "first").await;
+    assert_eq!(status, StatusCode::OK);
+
+    sqlx::query(
+        "UPDATE idempotency_keys SET expires_at = NOW() - INTERVAL '1 second'
+         WHERE user_id = $1 AND key = 'create-4'",
+    )
+    .bind(client.user.user_id)
+    .execute(&pool)
+    .await
+    .unwrap();
+    let (status, replayed, task) = client.create_task("create-4", "second").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(replayed, None);
+    assert_eq!(task["title"], "second");
+}
+
+#[tokio::test]
+async fn bodies_over_the_route_limit_are_rejected() {
+    let Some(pool) = common::test_pool().await else {
+        return;
+    };
+    let client = Client::new(&pool).await;
+    let (status, _, _) = client.create_task("create-5", &"x".repeat(8192)).await;
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+
+    // 没有占用 key
+    let (status, _, _) = client.create_task("create-5", "fits").await;
+    assert_eq!(status, StatusCode::OK);
+}
@@ -0,0 +1,276 @@
+mod common;
+
+use axum::{
+    body::Body,
+    http::{header, Method, Request, StatusCode},
+    Router,
+};
+use axum_server::{
+    app::create_app,
+    auth::{AuthUser, Scope},
+    config::Config,
+    models::token::CreatePersonalAccessToken,
+    rate_limit,
+    services::auth_service,
+    state::AppState,
+};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use tower::ServiceExt;
+
+struct Client {
+    app: Router,
+    token: String,
+}
+
+impl Client {
+    async fn new(pool: &PgPool, app: &Router) -> Self {
+        let organization_id = common::create_organization(pool, "import").await;
+        let user = AuthUser {
+            user_id: common::create_user(pool, organization_id, "import").await,
+            organization_id,
+            session_id: None,
+            scopes: Scope::ALL.to_vec(),
+        };
+        let request = CreatePersonalAccessToken {
+            name: "import".to_owned(),
+            scopes: vec![Scope::TasksRead, Scope::TasksWrite],
+            expires_at: None,
+        };
+        let token = auth_service::create_personal_token(pool, &user, &request)
+            .await
+            .unwrap()
+            .token;
+        Self {
+            app: app.clone(),
+            token,
+        }
+    }
+
+    async fn request(&self, method: Method, uri: &str, body: String) -> (StatusCode, Vec<u8>) {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::AUTHORIZATION, format!("Bearer {}", self.token))
+            .body(Body::from(body))
+            .unwrap();
+        let response = self.app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, bytes.to_vec())
+    }
+
+    async fn import(&self, query: &str, body: String) -> Value {
+        let (status, report) = self
+            .request(Method::POST, &format!("/api/tasks/import?{query}"), body)
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        serde_json::from_slice(&report).unwrap()
+    }
+
+    async fn export(&self, format: &str) -> Vec<u8> {
+        let (status, body) = self
+            .request(
+                Method::GET,
+                &format!("/api/tasks/export?format={format}"),
+                String::new(),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        body
+    }
+
+    async fn titles(&self) -> Vec<String> {
+        let tasks: Vec<Value> = serde_json::from_slice(&self.export("json").await).unwrap();
+        tasks
+            .iter()
+            .map(|task| task["title"].as_str().unwrap().to_owned())
+            .collect()
+    }
+}
+
+async fn app(pool: &PgPool) -> Router {
+    let config = Config::from_env().unwrap();
+    let backend = rate_limit::connect(&config.rate_limit).await.unwrap();
+    create_app(AppState::new(pool.clone(), config, backend))
+}
+
+const CSV: &str = "\
+title,description,category,priority,due_date,recurrence,tags,estimate_seconds
+Write report,\"quarterly, draft\",work,2,2030-01-01T00:00:00Z,,finance;q1,3600
+Buy milk,,personal,1,2030-01-02T09:30:00Z,,,
+";
+
+#[tokio::test]
+async fn csv_round_trips() {
+    let Some(pool) = common::test_pool().await else {
+        return;
+    };
+    let app = app(&pool).await;
+    let client = Client::new(&pool, &app).await;
+
+    let report = client.import("format=csv", CSV.to_owned()).await;
+    assert_eq!(report["total"], 2);
+    assert_eq!(report["valid"], 2);
+    assert_eq!(report["rows"][0]["status"], "created");
+
+    let exported = client.export("csv").await;
+    let mut reader = csv::Reader::from_reader(exported.as_slice());
+    let rows: Vec<csv::StringRecord> = reader.records().map(Result::unwrap).collect();
+    let headers = reader.headers().unwrap().clone();
+    let column = |row: &csv::StringRecord, name: &str| {
+        let index = headers.iter().position(|header| header == name).unwrap();
+        row[index].to_owned()
+    };
+    assert_eq!(rows.len(), 2);
+    assert_eq!(column(&rows[0], "title"), "Write report");
+    assert_eq!(column(&rows[0], "description"), "quarterly, draft");
+    assert_eq!(column(&rows[0], "tags"), "finance;q1");
+    assert_eq!(column(&rows[0], "estimate_seconds"), "3600");
+    assert_eq!(column(&rows[1], "category"), "personal");
+
+    // 导出的 CSV 可以原样导入到另一个账号
+    let other = Client::new(&pool, &app).await;
+    let report = other
+        .import("format=csv", String::from_utf8(exported).unwrap())
+        .await;
+    assert_eq!(report["valid"], 2);
+    assert_eq!(other.titles().await, ["Write report", "Buy milk"]);
+}
+
+#[tokio::test]
+async fn json_round_trips() {
+    let Some(pool) = common::test_pool().await else {
+        return;
+    };
+    let app = app(&pool).await;
+    let client = Client::new(&pool, &app).await;
+    let report = client.import("format=csv", CSV.to_owned()).await;
+    assert_eq!(report["valid"], 2);
+
+    let exported = client.export("json").await;
+    let other = Client::new(&pool, &app).await;
+    let report = other
+        .import("format=json", String::from_utf8(exported.clone()).unwrap())
+        .await;
+    assert_eq!(report["valid"], 2);
+
+    let strip = |body: &[u8]| -> Vec<Value> {
+        let tasks: Vec<Value> = serde_json::from_slice(body).unwrap();
+        tasks
+            .into_iter()
+            .map(|task| {
+                json!([
+                    task["title"],
+                    task["description"],
+                    task["due_date"],
+                    task["tags"]
+                ])
+            })
+            .collect()
+    };
+    assert_eq!(strip(&other.export("json").await), strip(&exported));
+
+    // ndjson 每行一个任务
+    let ndjson = other.export("ndjson").await;
+    assert_eq!(String::from_utf8(ndjson).unwrap().lines().count(), 2);
+}
+
+#[tokio::test]
+async fn dry_run_only_validates() {
+    let Some(pool) = common::test_pool().await else {
+        return;
+    };
+    let app = app(&pool).await;
+    let client = Client::new(&pool, &app).await;
+
+    let report = client
+        .import("format=csv&dry_run=true", CSV.to_owned())
+        .await;
+    assert_eq!(report["dry_run"], true);
+    assert_eq!(report["valid"], 2);
+    assert_eq!(report["rows"][0]["status"], "valid");
+    assert!(report["rows"][0].get("id").is_none());
+    assert!(client.titles().await.is_empty());
+}
+
+#[tokio::test]
+async fn reports_rows_that_fail_without_aborting_the_import() {
+    let Some(pool) = common::test_pool().await else {
+        return;
+    };
+    let app = app(&pool).await;
+    let client = Client::new(&pool, &app).await;
+
+    let task = |title: &str, description: &str| {
+        json!({
+            "title": title,
+            "description": description,
+            "category": "work",
+            "priority": 1,
+            "due_date": "2030-01-01T00:00:00Z",
+        })
+    };
+    let body = json!([
+        task("first", ""),
+        task("", ""),
+        // 校验能通过，但 Postgres 的 text 不能存 NUL
+        task("nul", "bad\u{0}byte"),
+        task("last", ""),
+        "not a task",
+    ]);
+    let report = client.import("format=json", body.to_string()).await;
+
+    assert_eq!(report["total"], 5);
+    assert_eq!(report["valid"], 2);
+    assert_eq!(report["invalid"], 3);
+    let statuses: Vec<&str> = report["rows"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|row| row["status"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        statuses,
+        ["created", "invalid", "invalid", "created", "invalid"]
+    );
+    let error = report["rows"][2]["errors"][0].as_str().unwrap();
+    assert!(error.starts_with("could not be saved"), "{error}");
+    assert_eq!(client.titles().await, ["first", "last"]);
+}
+
+#[tokio::test]
+async fn ndjson_rows_are_numbered_by_line() {
+    let Some(pool) = common::test_pool().await else {
+        return;
+    };
+    let app = app(&pool).await;
+    let client = Client::new(&pool, &app).await;
+
+    let task = json!({
+        "title": "spaced",
+        "description": "",
+        "category": "work",
+        "priority": 1,
+        "due_date": "2030-01-01T00:00:00Z",
+    });
+    // 空行不算任务，但行号照样往后数
+    let body = format!("{task}\n\n  \n{{\"title\": \n{task}\n");
+    let report = client.import("format=ndjson&dry_run=true", body).await;
+
+    assert_eq!(report["total"], 3);
+    let rows: Vec<(u64, &str)> = report["rows"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|row| {
+            (
+                row["row"].as_u64().unwrap(),
+                row["status"].as_str().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(rows, [(1, "valid"), (4, "invalid"), (5, "valid")]);
+}
@@ -0,0 +1,286 @@
+mod common;
+
+use async_trait::async_trait;
+use axum_server::{
+    config::Config,
+    jobs::{self, EnqueueOptions, Job, JobContext, QueueLimits, Registry},
+};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::{
+    collections::HashMap,
+    sync::{Arc, LazyLock, Mutex},
+    time::Duration,
+};
+use tokio_util::sync::CancellationToken;
+
+/// 每个任务 key 已经执行了几次，以及同时在执行的数量和峰值
+#[derive(Default)]
+struct Runs {
+    attempts: u32,
+    running: u32,
+    peak: u32,
+}
+
+static RUNS: LazyLock<Mutex<HashMap<String, Runs>>> = LazyLock::new(Default::default);
+
+fn runs<T>(key: &str, f: impl FnOnce(&mut Runs) -> T) -> T {
+    f(RUNS.lock().unwrap().entry(key.to_owned()).or_default())
+}
+
+/// 前 `failures` 次执行失败
+#[derive(Serialize, Deserialize)]
+struct Flaky {
+    key: String,
+    failures: u32,
+}
+
+#[async_trait]
+impl Job for Flaky {
+    const KIND: &'static str = "test.flaky";
+    const MAX_ATTEMPTS: i32 = 3;
+
+    async fn run(self, _ctx: &JobContext) -> anyhow::Result<()> {
+        let attempts = runs(&self.key, |runs| {
+            runs.attempts += 1;
+            runs.attempts
+        });
+        if attempts <= self.failures {
+            anyhow::bail!("attempt {attempts} failed");
+        }
+        Ok(())
+    }
+
+    fn retry_delay(_attempt: i32) -> Duration {
+        Duration::ZERO
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Slow {
+    key: String,
+}
+
+#[async_trait]
+impl Job for Slow {
+    const KIND: &'static str = "test.slow";
+
+    async fn run(self, _ctx: &JobContext) -> anyhow::Result<()> {
+        runs(&self.key, |runs| {
+            runs.running += 1;
+            runs.peak = runs.peak.max(runs.running);
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        runs(&self.key, |runs| runs.running -= 1);
+        Ok(())
+    }
+}
+
+/// 只消费一个测试专用的队列，测试之间互不影响
+struct Worker {
+    queue: String,
+    token: CancellationToken,
+    handle: tokio::task::JoinHandle<()>,
+}
+
+impl Worker {
+    fn start(pool: &PgPool, concurrency: usize) -> Self {
+        let queue = format!("test-{}", common::unique_suffix());
+        let mut config = Config::from_env().unwrap();
+        config.jobs.queues = QueueLimits(vec![(queue.clone(), concurrency)]);
+        config.jobs.poll_interval = Duration::from_millis(20);
+        let registry = Registry::new().register::<Flaky>().register::<Slow>();
+        let token = CancellationToken::new();
+        let handle = tokio::spawn(jobs::run_worker(
+            pool.clone(),
+            Arc::new(config),
+            registry,
+            token.clone(),
+        ));
+        Self {
+            queue,
+            token,
+            handle,
+        }
+    }
+
+    fn options(&self) -> EnqueueOptions {
+        EnqueueOptions {
+            queue: Some(self.queue.clone()),
+            ..Default::default()
+        }
+    }
+
+    async fn stop(self) {
+        self.token.cancel();
+        self.handle.await.unwrap();
+    }
+}
+
+/// 等任务结束，返回最终状态、执行次数和最后的错误
+async fn wait_finished(pool: &PgPool, id: i64) -> (String, i32, Option<String>) {
+    for _ in 0..250 {
+        let row: (String, i32, Option<String>) =
+            sqlx::query_as("SELECT status, attempts, last_error FROM jobs WHERE id = $1")
+                .bind(id)
+                .fetch_one(pool)
+                .await
+                .unwrap();
+        if row.0 == "completed" || row.0 == "failed" {
+            return row;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("job {id} did not finish");
+}
+
+fn key() -> String {
+    format!("job-{}", common::unique_suffix())
+}
+
+#[tokio::test]
+async fn retries_until_success_or_attempts_run_out() {
+    let Some(pool) = common::test_pool().await else {
+        return;
+    };
+    let worker = Worker::start(&pool, 2);
+
+    let recovering = key();
+    let job = Flaky {
+        key: recovering.clone(),
+        failures: 2,
+    };
+    let id = jobs::enqueue(&pool, &job, worker.options())
+        .await
+        .unwrap()
+        .unwrap();
+    let (status, attempts, last_error) = wait_finished(&pool, id).await;
+    assert_eq!((status.as_str(), attempts), ("completed", 3));
+    assert_eq!(last_error, None);
+
+    let broken = key();
+    let job = Flaky {
+        key: broken.clone(),
+        failures: 10,
+    };
+    let id = jobs::enqueue(&pool, &job, worker.options())
+        .await
+        .unwrap()
+        .unwrap();
+    let (status, attempts, last_error) = wait_finished(&pool, id).await;
+    assert_eq!((status.as_str(), attempts), ("failed", 3));
+    assert_eq!(last_error.as_deref(), Some("attempt 3 failed"));
+    assert_eq!(runs(&broken, |runs| runs.attempts), 3);
+
+    worker.stop().await;
+}
+
+#[tokio::test]
+async fn delayed_jobs_wait_and_unique_keys_deduplicate() {
+    let Some(pool) = common::test_pool().await else {
+        return;
+    };
+    let worker = Worker::start(&pool, 1);
+    let unique_key = key();
+    let unique = || EnqueueOptions {
+        unique_key: Some(unique_key.clone()),
+        ..worker.options()
+    };
+
+    let delayed = jobs::enqueue(
+        &pool,
+        &Slow { key: key() },
+        EnqueueOptions {
+            queue: Some(worker.queue.clone()),
+            ..EnqueueOptions::delayed(Duration::from_secs(3600))
+        },
+    )
+    .await
+    .unwrap()
+    .unwrap();
+
+    let job = Slow { key: key() };
+    let first = jobs::enqueue(&pool, &job, unique()).await.unwrap().unwrap();
+    assert_eq!(jobs::enqueue(&pool, &job, unique()).await.unwrap(), None);
+    wait_finished(&pool, first).await;
+
+    // 完成后同一个 key 可以再次入队
+    let again = jobs::enqueue(&pool, &job, unique()).await.unwrap().unwrap();
+    wait_finished(&pool, again).await;
+
+    let status: String = sqlx::query_scalar("SELECT status FROM jobs WHERE id = $1")
+        .bind(delayed)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(status, "pending");
+
+    worker.stop().await;
+}
+
+#[tokio::test]
+async fn limits_concurrency_per_queue() {
+    let Some(pool) = common::test_pool().await else {
+        return;
+    };
+    let worker = Worker::start(&pool, 2);
+    let shared = key();
+
+    let mut ids = Vec::new();
+    for _ in 0..6 {
+        let job = Slow {
+            key: shared.clone(),
+        };
+        ids.push(
+            jobs::enqueue(&pool, &job, worker.options())
+                .await
+                .unwrap()
+                .unwrap(),
+        );
+    }
+    for id in ids {
+        assert_eq!(wait_finished(&pool, id).await.0, "completed");
+    }
+    assert_eq!(runs(&shared, |runs| runs.peak), 2);
+
+    worker.stop().await;
+}
+
+#[tokio::test]
+async fn reaper_requeues_jobs_of_dead_workers() {
+    let Some(pool) = common::test_pool().await else {
+        return;
+    };
+    let queue = format!("test-{}", common::unique_suffix());
+    let mut ids = Vec::new();
+    for attempts in [1, 3] {
+        let id: i64 = sqlx::query_scalar(
+            "INSERT INTO jobs (queue, kind, payload, status, attempts, max_attempts,
+                               locked_by, locked_at)
+             VALUES ($1, 'test.flaky', '{}', 'running', $2, 3, 'gone', NOW() - INTERVAL '1 hour')
+             RETURNING id",
+        )
+        .bind(&queue)
+        .bind(attempts)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        ids.push(id);
+    }
+
+    let reaped = jobs::reap_stale(&pool, Duration::from_secs(600))
+        .await
+        .unwrap();
+    assert!(reaped >= 2);
+
+    let statuses: Vec<(String, Option<String>)> =
+        sqlx::query_as("SELECT status, locked_by FROM jobs WHERE id = ANY($1) ORDER BY id")
+            .bind(&ids)
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+    assert_eq!(
+        statuses,
+        [("pending".to_owned(), None), ("failed".to_owned(), None)]
+    );
+}
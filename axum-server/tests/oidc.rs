@@ -0,0 +1,323 @@
+mod common;
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    routing::{get, post},
+    Form, Json, Router,
+};
+use axum_server::{
+    config::{AuthConfig, OidcConfig},
+    jwt_keys::KeyRing,
+    models::user::TokenResponse,
+    services::{
+        auth_service::{self, ClientInfo},
+        oidc_service::OidcClient,
+    },
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use jsonwebtoken::{EncodingKey, Header};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use url::Url;
+
+const CLIENT_ID: &str = "task-service";
+const PRIVATE_KEY: &[u8] = include_bytes!("fixtures/oidc_rsa_key.pem");
+const JWKS: &str = include_str!("fixtures/oidc_jwks.json");
+
+/// 授权码对应的 PKCE challenge 和要签进 ID token 的 claims
+struct Grant {
+    code_challenge: String,
+    claims: Value,
+}
+
+/// 本地的 OIDC 提供方：discovery、JWKS 和 token 接口，ID token 用固定的 RSA 密钥签名
+struct MockProvider {
+    issuer: String,
+    grants: Mutex<HashMap<String, Grant>>,
+}
+
+async fn discovery(State(mock): State<Arc<MockProvider>>) -> Json<Value> {
+    Json(json!({
+        "issuer": mock.issuer,
+        "authorization_endpoint": format!("{}/authorize", mock.issuer),
+        "token_endpoint": format!("{}/token", mock.issuer),
+        "jwks_uri": format!("{}/jwks", mock.issuer),
+    }))
+}
+
+async fn jwks() -> Json<Value> {
+    Json(serde_json::from_str(JWKS).unwrap())
+}
+
+async fn token(
+    State(mock): State<Arc<MockProvider>>,
+    Form(form): Form<HashMap<String, String>>,
+) -> Result<Json<Value>, StatusCode> {
+    if form.get("grant_type").map(String::as_str) != Some("authorization_code")
+        || form.get("client_id").map(String::as_str) != Some(CLIENT_ID)
+    {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let code = form.get("code").ok_or(StatusCode::BAD_REQUEST)?;
+    let grant = mock
+        .grants
+        .lock()
+        .unwrap()
+        .remove(code)
+        .ok_or(StatusCode::BAD_REQUEST)?;
+
+    let verifier = form.get("code_verifier").ok_or(StatusCode::BAD_REQUEST)?;
+    if URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes())) != grant.code_challenge {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let mut header = Header::new(jsonwebtoken::Algorithm::RS256);
+    header.kid = Some("test-key".to_owned());
+    let key = EncodingKey::from_rsa_pem(PRIVATE_KEY).unwrap();
+    let id_token = jsonwebtoken::encode(&header, &grant.claims, &key).unwrap();
+    Ok(Json(
+        json!({ "id_token": id_token, "token_type": "Bearer" }),
+    ))
+}
+
+async fn start_provider() -> Arc<MockProvider> {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let mock = Arc::new(MockProvider {
+        issuer: format!("http://{}", listener.local_addr().unwrap()),
+        grants: Mutex::new(HashMap::new()),
+    });
+    let app = Router::new()
+        .route("/.well-known/openid-configuration", get(discovery))
+        .route("/jwks", get(jwks))
+        .route("/token", post(token))
+        .with_state(mock.clone());
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    mock
+}
+
+fn auth_config(mock: &MockProvider) -> AuthConfig {
+    auth_config_for(mock, None)
+}
+
+fn auth_config_for(mock: &MockProvider, organization_id: Option<i32>) -> AuthConfig {
+    AuthConfig {
+        jwt_secret: "test-secret".to_owned(),
+        keys: KeyRing::new("test-secret"),
+        keys_reload_interval: Duration::from_secs(60),
+        access_token_ttl: Duration::from_secs(60),
+        refresh_token_ttl: Duration::from_secs(3600),
+        oidc: Some(OidcConfig {
+            issuer_url: mock.issuer.clone(),
+            client_id: CLIENT_ID.to_owned(),
+            client_secret: None,
+            redirect_url: "http://localhost:3000/api/auth/oidc/callback".to_owned(),
+            scopes: "openid email".to_owned(),
+            organization_id,
+        }),
+    }
+}
+
+/// 模拟浏览器走一遍：拿授权地址，提供方签发授权码，再回调
+async fn login(
+    pool: &PgPool,
+    client: &OidcClient,
+    mock: &MockProvider,
+    config: &AuthConfig,
+    extra_claims: Value,
+) -> Result<TokenResponse, String> {
+    let url = Url::parse(&client.authorization_url(pool).await.unwrap()).unwrap();
+    let params: HashMap<_, _> = url.query_pairs().into_owned().collect();
+    assert_eq!(params["code_challenge_method"], "S256");
+
+    let mut claims = json!({
+        "iss": mock.issuer,
+        "aud": CLIENT_ID,
+        "sub": "subject",
+        "nonce": params["nonce"],
+        "iat": chrono::Utc::now().timestamp(),
+        "exp": chrono::Utc::now().timestamp() + 300,
+    });
+    claims
+        .as_object_mut()
+        .unwrap()
+        .extend(extra_claims.as_object().unwrap().clone());
+
+    let code = common::unique_suffix();
+    mock.grants.lock().unwrap().insert(
+        code.clone(),
+        Grant {
+            code_challenge: params["code_challenge"].clone(),
+            claims,
+        },
+    );
+
+    client
+        .complete_login(pool, config, &code, &params["state"], ClientInfo::default())
+        .await
+        .map_err(|e| e.0.to_string())
+}
+
+fn user_id(config: &AuthConfig, tokens: &TokenResponse) -> i32 {
+    auth_service::decode_access_token(config, &tokens.access_token)
+        .unwrap()
+        .sub
+}
+
+#[tokio::test]
+async fn provisions_a_user_then_reuses_the_link() {
+    let Some(pool) = common::test_pool().await else {
+        return;
+    };
+    let mock = start_provider().await;
+    let config = auth_config(&mock);
+    let client = OidcClient::new(config.oidc.clone().unwrap());
+    let subject = common::unique_suffix();
+    let email = format!("sso-{subject}@example.com");
+
+    let first = login(
+        &pool,
+        &client,
+        &mock,
+        &config,
+        json!({ "sub": subject, "email": email, "name": "SSO User" }),
+    )
+    .await
+    .unwrap();
+    let second = login(
+        &pool,
+        &client,
+        &mock,
+        &config,
+        json!({ "sub": subject, "email": email }),
+    )
+    .await
+    .unwrap();
+    assert_eq!(user_id(&config, &first), user_id(&config, &second));
+
+    let users: HashSet<i32> =
+        sqlx::query_scalar("SELECT user_id FROM user_identities WHERE subject = $1")
+            .bind(&subject)
+            .fetch_all(&pool)
+            .await
+            .unwrap()
+            .into_iter()
+            .collect();
+    assert_eq!(users.len(), 1);
+}
+
+#[tokio::test]
+async fn verified_email_links_an_existing_user() {
+    let Some(pool) = common::test_pool().await else {
+        return;
+    };
+    let organization_id = common::create_organization(&pool, "sso").await;
+    let mock = start_provider().await;
+    let config = auth_config_for(&mock, Some(organization_id));
+    let client = OidcClient::new(config.oidc.clone().unwrap());
+
+    let existing = common::create_user(&pool, organization_id, "sso").await;
+    let email: String = sqlx::query_scalar("SELECT email FROM users WHERE id = $1")
+        .bind(existing)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+
+    // 未验证的邮箱不能接管已有账号
+    let unverified = login(
+        &pool,
+        &client,
+        &mock,
+        &config,
+        json!({ "sub": common::unique_suffix(), "email": email, "email_verified": false }),
+    )
+    .await;
+    assert!(unverified.is_err());
+
+    let tokens = login(
+        &pool,
+        &client,
+        &mock,
+        &config,
+        json!({ "sub": common::unique_suffix(), "email": email, "email_verified": true }),
+    )
+    .await
+    .unwrap();
+    assert_eq!(user_id(&config, &tokens), existing);
+}
+
+#[tokio::test]
+async fn verified_email_does_not_link_users_of_other_organizations() {
+    let Some(pool) = common::test_pool().await else {
+        return;
+    };
+    let configured = common::create_organization(&pool, "sso").await;
+    let other = common::create_organization(&pool, "other").await;
+    let existing = common::create_user(&pool, other, "other").await;
+    let email: String = sqlx::query_scalar("SELECT email FROM users WHERE id = $1")
+        .bind(existing)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    let mock = start_provider().await;
+
+    // 配置的组织不是用户所在的组织，或者根本没配置，都不能按邮箱关联
+    for config in [auth_config_for(&mock, Some(configured)), auth_config(&mock)] {
+        let client = OidcClient::new(config.oidc.clone().unwrap());
+        let error = login(
+            &pool,
+            &client,
+            &mock,
+            &config,
+            json!({ "sub": common::unique_suffix(), "email": email, "email_verified": true }),
+        )
+        .await
+        .unwrap_err();
+        assert!(error.contains("already exists"), "{error}");
+    }
+
+    let identities: i64 =
+        sqlx::query_scalar("SELECT COUNT(*) FROM user_identities WHERE user_id = $1")
+            .bind(existing)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!(identities, 0);
+}
+
+#[tokio::test]
+async fn rejects_tokens_for_another_audience_or_nonce() {
+    let Some(pool) = common::test_pool().await else {
+        return;
+    };
+    let mock = start_provider().await;
+    let config = auth_config(&mock);
+    let client = OidcClient::new(config.oidc.clone().unwrap());
+    let email = format!("sso-{}@example.com", common::unique_suffix());
+
+    let wrong_audience = login(
+        &pool,
+        &client,
+        &mock,
+        &config,
+        json!({ "email": email, "aud": "another-client" }),
+    )
+    .await;
+    assert!(wrong_audience.unwrap_err().contains("invalid ID token"));
+
+    let wrong_nonce = login(
+        &pool,
+        &client,
+        &mock,
+        &config,
+        json!({ "email": email, "nonce": "replayed" }),
+    )
+    .await;
+    assert!(wrong_nonce.unwrap_err().contains("nonce"));
+}
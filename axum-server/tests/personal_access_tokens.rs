@@ -0,0 +1,103 @@
+mod common;
+
+use axum_server::{
+    auth::{AuthUser, Scope},
+    models::token::CreatePersonalAccessToken,
+    services::auth_service,
+};
+
+async fn owner(pool: &sqlx::PgPool) -> AuthUser {
+    let organization_id = common::create_organization(pool, "tokens").await;
+    AuthUser {
+        user_id: common::create_user(pool, organization_id, "tokens").await,
+        organization_id,
+        session_id: None,
+        scopes: Scope::ALL.to_vec(),
+    }
+}
+
+fn request(scopes: Vec<Scope>) -> CreatePersonalAccessToken {
+    CreatePersonalAccessToken {
+        name: "ci".to_owned(),
+        scopes,
+        expires_at: None,
+    }
+}
+
+#[tokio::test]
+async fn token_authenticates_with_its_scopes() {
+    let Some(pool) = common::test_pool().await else {
+        return;
+    };
+    let user = owner(&pool).await;
+
+    let created =
+        auth_service::create_personal_token(&pool, &user, &request(vec![Scope::TasksRead]))
+            .await
+            .unwrap();
+    assert!(created.token.starts_with(&created.info.token_prefix));
+
+    let authed = auth_service::authenticate_personal_token(&pool, &created.token)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(authed.user_id, user.user_id);
+    assert!(authed.has_scope(Scope::TasksRead));
+    assert!(!authed.has_scope(Scope::TasksWrite));
+
+    let tokens = auth_service::list_personal_tokens(&pool, user.user_id)
+        .await
+        .unwrap();
+    assert!(tokens[0].last_used_at.is_some());
+}
+
+#[tokio::test]
+async fn revoked_and_expired_tokens_are_rejected() {
+    let Some(pool) = common::test_pool().await else {
+        return;
+    };
+    let user = owner(&pool).await;
+
+    let revoked = auth_service::create_personal_token(&pool, &user, &request(vec![Scope::Admin]))
+        .await
+        .unwrap();
+    assert!(
+        auth_service::revoke_personal_token(&pool, user.user_id, revoked.info.id)
+            .await
+            .unwrap()
+    );
+    assert!(
+        auth_service::authenticate_personal_token(&pool, &revoked.token)
+            .await
+            .unwrap()
+            .is_none()
+    );
+
+    let expired = auth_service::create_personal_token(&pool, &user, &request(vec![Scope::Admin]))
+        .await
+        .unwrap();
+    sqlx::query("UPDATE personal_access_tokens SET expires_at = NOW() WHERE id = $1")
+        .bind(expired.info.id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    assert!(
+        auth_service::authenticate_personal_token(&pool, &expired.token)
+            .await
+            .unwrap()
+            .is_none()
+    );
+}
+
+#[tokio::test]
+async fn cannot_grant_scopes_the_caller_lacks() {
+    let Some(pool) = common::test_pool().await else {
+        return;
+    };
+    let mut user = owner(&pool).await;
+    user.scopes = vec![Scope::TasksWrite];
+
+    let result =
+        auth_service::create_personal_token(&pool, &user, &request(vec![Scope::Admin])).await;
+    assert!(result.is_err());
+}
@@ -0,0 +1,116 @@
+use axum::{
+    body::Body,
+    extract::ConnectInfo,
+    http::{header, Request, StatusCode},
+    routing::get,
+    Router,
+};
+use axum_server::{
+    config::Config,
+    rate_limit::{KeyStrategy, MemoryBackend, RateLimiter},
+    state::AppState,
+};
+use sqlx::PgPool;
+use std::{net::SocketAddr, sync::Arc};
+use tower::ServiceExt;
+
+/// 按 IP 限流时不会访问数据库，用 lazy pool 即可
+fn app(trusted_proxies: usize) -> Router {
+    let config = Config::from_env().unwrap();
+    let pool = PgPool::connect_lazy(&config.database_url).unwrap();
+    let backend = Arc::new(MemoryBackend::new());
+    let state = AppState::new(pool, config, backend.clone());
+    let limiter = RateLimiter::new(
+        backend,
+        "test",
+        "2/60".parse().unwrap(),
+        KeyStrategy::Ip,
+        trusted_proxies,
+    );
+    Router::new()
+        .route("/", get(|| async { "ok" }))
+        .layer(limiter.layer(state))
+}
+
+async fn send(app: &Router, peer: &str, forwarded: Option<&str>) -> axum::response::Response {
+    let mut request = Request::get("/");
+    if let Some(forwarded) = forwarded {
+        request = request.header("x-forwarded-for", forwarded);
+    }
+    let mut request = request.body(Body::empty()).unwrap();
+    let peer: SocketAddr = format!("{peer}:40000").parse().unwrap();
+    request.extensions_mut().insert(ConnectInfo(peer));
+    app.clone().oneshot(request).await.unwrap()
+}
+
+#[tokio::test]
+async fn rejects_with_retry_after_once_the_bucket_is_empty() {
+    let app = app(0);
+    for remaining in ["1", "0"] {
+        let response = send(&app, "192.0.2.1", None).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["ratelimit-remaining"], remaining);
+    }
+
+    let response = send(&app, "192.0.2.1", None).await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    let retry_after: u64 = response.headers()[header::RETRY_AFTER]
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!((1..=30).contains(&retry_after), "{retry_after}");
+
+    // 其他客户端有自己的桶
+    let response = send(&app, "192.0.2.2", None).await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn ignores_forwarded_for_without_trusted_proxies() {
+    let app = app(0);
+    for i in 0..2 {
+        let forwarded = format!("198.51.100.{i}");
+        let response = send(&app, "192.0.2.1", Some(&forwarded)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+    let response = send(&app, "192.0.2.1", Some("198.51.100.99")).await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[tokio::test]
+async fn spoofed_forwarded_for_entries_do_not_get_a_fresh_bucket() {
+    let app = app(1);
+    // 代理在 10.0.0.1，它追加的最后一项才是真实客户端；左边的是客户端自己填的
+    for i in 0..2 {
+        let forwarded = format!("203.0.113.{i}, 198.51.100.7");
+        let response = send(&app, "10.0.0.1", Some(&forwarded)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+    let response = send(&app, "10.0.0.1", Some("203.0.113.99, 198.51.100.7")).await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    // 同一个代理后面的另一个客户端不受影响
+    let response = send(&app, "10.0.0.1", Some("198.51.100.8")).await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn counts_proxy_hops_from_the_right() {
+    let app = app(2);
+    // 两层代理：客户端、第一层代理的地址依次被追加
+    for _ in 0..2 {
+        let response = send(&app, "10.0.0.2", Some("6.6.6.6, 198.51.100.7, 10.0.0.1")).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+    let response = send(&app, "10.0.0.2", Some("7.7.7.7, 198.51.100.7, 10.0.0.1")).await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    // 条目不够时退回对端地址，伪造的头也拿不到新桶
+    let response = send(&app, "10.0.0.2", Some("198.51.100.9")).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = send(&app, "10.0.0.2", Some("198.51.100.10")).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = send(&app, "10.0.0.2", Some("198.51.100.11")).await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+}
@@ -0,0 +1,91 @@
+mod common;
+
+use axum_server::{
+    auth::{AuthUser, Scope},
+    config::Config,
+    db::routing::DbRouter,
+};
+use sqlx::{postgres::PgPoolOptions, PgPool};
+use std::{sync::Arc, time::Duration};
+
+/// 用同一个数据库充当副本，靠 `application_name` 区分读到了哪个连接池
+fn replica_of(pool: &PgPool, name: &str) -> PgPool {
+    let options = (*pool.connect_options()).clone().application_name(name);
+    PgPoolOptions::new().connect_lazy_with(options)
+}
+
+async fn read_from(router: &DbRouter, user: &AuthUser) -> String {
+    let mut tx = router.begin_read(user).await.unwrap();
+    sqlx::query_scalar("SELECT current_setting('application_name')")
+        .fetch_one(&mut *tx)
+        .await
+        .unwrap()
+}
+
+async fn user(pool: &PgPool) -> AuthUser {
+    let organization_id = common::create_organization(pool, "replicas").await;
+    AuthUser {
+        user_id: common::create_user(pool, organization_id, "replicas").await,
+        organization_id,
+        session_id: None,
+        scopes: Scope::ALL.to_vec(),
+    }
+}
+
+#[tokio::test]
+async fn reads_go_to_healthy_replicas_except_right_after_a_write() {
+    let Some(pool) = common::test_pool().await else {
+        return;
+    };
+    let primary = PgPoolOptions::new().connect_lazy_with(
+        (*pool.connect_options())
+            .clone()
+            .application_name("primary"),
+    );
+    let mut config = Config::from_env().unwrap().database;
+    config.read_your_writes = Duration::from_millis(500);
+    let router = DbRouter::new(
+        Arc::new(primary),
+        vec![replica_of(&pool, "replica")],
+        &config,
+    );
+    let user = user(&pool).await;
+
+    // 第一次健康检查之前不用副本
+    assert_eq!(read_from(&router, &user).await, "primary");
+    router.check_replicas().await;
+    assert_eq!(read_from(&router, &user).await, "replica");
+
+    router.record_write(user.user_id);
+    assert_eq!(read_from(&router, &user).await, "primary");
+    tokio::time::sleep(config.read_your_writes).await;
+    assert_eq!(read_from(&router, &user).await, "replica");
+
+    // 副本连不上时改读主库，直到下一次健康检查通过
+    router.close().await;
+    assert_eq!(read_from(&router, &user).await, "primary");
+    assert_eq!(read_from(&router, &user).await, "primary");
+}
+
+#[tokio::test]
+async fn unreachable_replicas_fail_the_health_check() {
+    let Some(pool) = common::test_pool().await else {
+        return;
+    };
+    let options = (*pool.connect_options()).clone().port(1);
+    let unreachable = PgPoolOptions::new()
+        .acquire_timeout(Duration::from_secs(2))
+        .connect_lazy_with(options);
+    let router = DbRouter::new(
+        Arc::new(pool.clone()),
+        vec![unreachable, replica_of(&pool, "replica")],
+        &Config::from_env().unwrap().database,
+    );
+    router.check_replicas().await;
+
+    // 只剩一个健康的副本，所有用户都分到它
+    for _ in 0..3 {
+        let user = user(&pool).await;
+        assert_eq!(read_from(&router, &user).await, "replica");
+    }
+}
@@ -0,0 +1,365 @@
+mod common;
+
+use axum::{
+    body::Body,
+    http::{header, Method, Request, StatusCode},
+    Router,
+};
+use axum_server::{
+    app::create_app,
+    auth::{AuthUser, Scope},
+    config::Config,
+    models::token::CreatePersonalAccessToken,
+    rate_limit,
+    services::auth_service,
+    state::AppState,
+};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use tower::ServiceExt;
+
+struct Member {
+    id: i32,
+    token: String,
+}
+
+/// 同一个组织里的三个用户，外加另一个组织的用户
+struct Team {
+    app: Router,
+    owner: Member,
+    alice: Member,
+    bob: Member,
+    outsider: i32,
+}
+
+impl Team {
+    async fn new(pool: &PgPool) -> Self {
+        let config = Config::from_env().unwrap();
+        let backend = rate_limit::connect(&config.rate_limit).await.unwrap();
+        let app = create_app(AppState::new(pool.clone(), config, backend));
+
+        let organization_id = common::create_organization(pool, "team").await;
+        let other_organization = common::create_organization(pool, "other team").await;
+        Self {
+            app,
+            owner: member(pool, organization_id, "owner").await,
+            alice: member(pool, organization_id, "alice").await,
+            bob: member(pool, organization_id, "bob").await,
+            outsider: common::create_user(pool, other_organization, "outsider").await,
+        }
+    }
+
+    async fn request(
+        &self,
+        member: &Member,
+        method: Method,
+        uri: &str,
+        body: Option<Value>,
+    ) -> (StatusCode, Value) {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::AUTHORIZATION, format!("Bearer {}", member.token))
+            .header(header::CONTENT_TYPE, "application/json");
+        let body = body.map_or_else(Body::empty, |body| Body::from(body.to_string()));
+        let response = self
+            .app
+            .clone()
+            .oneshot(request.body(body).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (
+            status,
+            serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+        )
+    }
+
+    async fn create_task(&self, member: &Member, title: &str) -> i64 {
+        let body = json!({
+            "title": title,
+            "description": "",
+            "category": "work",
+            "priority": 1,
+            "due_date": "2030-01-01T00:00:00Z",
+        });
+        let (status, task) = self
+            .request(member, Method::POST, "/api/tasks", Some(body))
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        task["id"].as_i64().unwrap()
+    }
+
+    async fn task_ids(&self, member: &Member, query: &str) -> Vec<i64> {
+        let (status, tasks) = self
+            .request(member, Method::GET, &format!("/api/tasks?{query}"), None)
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        ids(&tasks)
+    }
+
+    async fn notifications(&self, member: &Member, query: &str) -> Vec<Value> {
+        let (status, notifications) = self
+            .request(
+                member,
+                Method::GET,
+                &format!("/api/notifications?{query}"),
+                None,
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        notifications.as_array().unwrap().clone()
+    }
+}
+
+async fn member(pool: &PgPool, organization_id: i32, label: &str) -> Member {
+    let user = AuthUser {
+        user_id: common::create_user(pool, organization_id, label).await,
+        organization_id,
+        session_id: None,
+        scopes: Scope::ALL.to_vec(),
+    };
+    let request = CreatePersonalAccessToken {
+        name: label.to_owned(),
+        scopes: vec![Scope::TasksWrite],
+        expires_at: None,
+    };
+    let token = auth_service::create_personal_token(pool, &user, &request)
+        .await
+        .unwrap()
+        .token;
+    Member {
+        id: user.user_id,
+        token,
+    }
+}
+
+fn ids(values: &Value) -> Vec<i64> {
+    values
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|value| value["id"].as_i64().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn assignees_can_work_on_and_hand_off_tasks() {
+    let Some(pool) = common::test_pool().await else {
+        return;
+    };
+    let team = Team::new(&pool).await;
+    let task = team.create_task(&team.owner, "handoff").await;
+    let assignees = format!("/api/tasks/{task}/assignees");
+
+    let (status, assigned) = team
+        .request(
+            &team.owner,
+            Method::POST,
+            &assignees,
+            Some(json!({ "user_ids": [team.alice.id] })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(assigned["assignee_ids"], json!([team.alice.id]));
+    assert_eq!(assigned["reporter_id"], json!(team.owner.id));
+
+    // 负责人能看到并修改任务，但不能删除
+    assert_eq!(team.task_ids(&team.alice, "assignee=me").await, [task]);
+    assert_eq!(team.task_ids(&team.alice, "").await, [task]);
+    let (status, _) = team
+        .request(
+            &team.alice,
+            Method::PUT,
+            &format!("/api/tasks/{task}"),
+            Some(json!({ "status": "in_progress" })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = team
+        .request(
+            &team.alice,
+            Method::DELETE,
+            &format!("/api/tasks/{task}"),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let notifications = team.notifications(&team.alice, "unread=true").await;
+    assert_eq!(notifications.len(), 1);
+    assert_eq!(notifications[0]["kind"], "assigned");
+    assert_eq!(notifications[0]["task_id"], json!(task));
+    assert_eq!(notifications[0]["actor_id"], json!(team.owner.id));
+    assert_eq!(notifications[0]["title"], "handoff");
+    let (status, read) = team
+        .request(
+            &team.alice,
+            Method::POST,
+            &format!("/api/notifications/{}/read", notifications[0]["id"]),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(read["read_at"].is_string());
+    assert!(team
+        .notifications(&team.alice, "unread=true")
+        .await
+        .is_empty());
+    assert_eq!(team.notifications(&team.alice, "").await.len(), 1);
+
+    // 负责人把任务转交给别人后自己就看不到了，操作者自己不会收到通知
+    let (status, reassigned) = team
+        .request(
+            &team.alice,
+            Method::PUT,
+            &assignees,
+            Some(json!({ "user_ids": [team.bob.id] })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(reassigned["assignee_ids"], json!([team.bob.id]));
+    assert!(team.task_ids(&team.alice, "").await.is_empty());
+    assert_eq!(team.notifications(&team.alice, "").await.len(), 1);
+    let notifications = team.notifications(&team.bob, "").await;
+    assert_eq!(notifications[0]["kind"], "assigned");
+    assert_eq!(notifications[0]["actor_id"], json!(team.alice.id));
+
+    let (status, unassigned) = team
+        .request(
+            &team.owner,
+            Method::DELETE,
+            &format!("{assignees}/{}", team.bob.id),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(unassigned["assignee_ids"], json!([]));
+    assert_eq!(team.task_ids(&team.owner, "assignee=none").await, [task]);
+    assert!(team.task_ids(&team.bob, "").await.is_empty());
+
+    let kinds: Vec<Value> = team
+        .notifications(&team.bob, "unread=true")
+        .await
+        .iter()
+        .map(|notification| notification["kind"].clone())
+        .collect();
+    assert_eq!(kinds, [json!("unassigned"), json!("assigned")]);
+    let (status, marked) = team
+        .request(&team.bob, Method::POST, "/api/notifications/read", None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(marked["updated"], 2);
+}
+
+#[tokio::test]
+async fn only_active_members_of_the_organization_can_be_assigned() {
+    let Some(pool) = common::test_pool().await else {
+        return;
+    };
+    let team = Team::new(&pool).await;
+    let task = team.create_task(&team.owner, "validation").await;
+    let assignees = format!("/api/tasks/{task}/assignees");
+
+    let (status, _) = team
+        .request(
+            &team.owner,
+            Method::POST,
+            &assignees,
+            Some(json!({ "user_ids": [team.outsider] })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    sqlx::query("UPDATE users SET disabled_at = NOW() WHERE id = $1")
+        .bind(team.bob.id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    let (status, _) = team
+        .request(
+            &team.owner,
+            Method::PUT,
+            &assignees,
+            Some(json!({ "user_ids": [team.alice.id, team.bob.id] })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert!(team.task_ids(&team.alice, "").await.is_empty());
+
+    // 不是所有者也不是负责人的用户当作任务不存在
+    let (status, _) = team
+        .request(
+            &team.alice,
+            Method::POST,
+            &assignees,
+            Some(json!({ "user_ids": [team.alice.id] })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    assert_eq!(team.task_ids(&team.owner, "reporter=me").await, [task]);
+    assert!(team
+        .task_ids(&team.owner, &format!("reporter={}", team.alice.id))
+        .await
+        .is_empty());
+    let (status, _) = team
+        .request(
+            &team.owner,
+            Method::GET,
+            "/api/tasks?assignee=someone",
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn assignment_changes_the_assignees_list_etag() {
+    let Some(pool) = common::test_pool().await else {
+        return;
+    };
+    let team = Team::new(&pool).await;
+    let task = team.create_task(&team.owner, "etag").await;
+
+    let list = |etag: Option<String>| {
+        let mut request = Request::get("/api/tasks").header(
+            header::AUTHORIZATION,
+            format!("Bearer {}", team.alice.token),
+        );
+        if let Some(etag) = etag {
+            request = request.header(header::IF_NONE_MATCH, etag);
+        }
+        team.app
+            .clone()
+            .oneshot(request.body(Body::empty()).unwrap())
+    };
+    let response = list(None).await.unwrap();
+    let etag = response.headers()[header::ETAG]
+        .to_str()
+        .unwrap()
+        .to_owned();
+    assert_eq!(
+        list(Some(etag.clone())).await.unwrap().status(),
+        StatusCode::NOT_MODIFIED
+    );
+
+    let (status, _) = team
+        .request(
+            &team.owner,
+            Method::POST,
+            &format!("/api/tasks/{task}/assignees"),
+            Some(json!({ "user_ids": [team.alice.id] })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let response = list(Some(etag)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    assert_eq!(ids(&serde_json::from_slice(&bytes).unwrap()), [task]);
+}
@@ -0,0 +1,274 @@
+mod common;
+
+use axum::{
+    body::Body,
+    http::{header, Method, Request, StatusCode},
+    Router,
+};
+use axum_server::{
+    app::create_app,
+    auth::{AuthUser, Scope},
+    config::Config,
+    jobs::{Job, JobContext},
+    models::token::CreatePersonalAccessToken,
+    rank::{self, RebalanceRanks},
+    rate_limit,
+    services::auth_service,
+    state::AppState,
+};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use std::sync::Arc;
+use tower::ServiceExt;
+
+struct Board {
+    pool: PgPool,
+    app: Router,
+    user: AuthUser,
+    token: String,
+}
+
+impl Board {
+    async fn new(pool: &PgPool) -> Self {
+        let mut config = Config::from_env().unwrap();
+        // 重新分布的测试要连续拖动很多次
+        config.rate_limit.tasks_write = "1000/60".parse().unwrap();
+        let backend = rate_limit::connect(&config.rate_limit).await.unwrap();
+        let app = create_app(AppState::new(pool.clone(), config, backend));
+
+        let organization_id = common::create_organization(pool, "board").await;
+        let user = AuthUser {
+            user_id: common::create_user(pool, organization_id, "board").await,
+            organization_id,
+            session_id: None,
+            scopes: Scope::ALL.to_vec(),
+        };
+        let request = CreatePersonalAccessToken {
+            name: "board".to_owned(),
+            scopes: vec![Scope::TasksWrite],
+            expires_at: None,
+        };
+        let token = auth_service::create_personal_token(pool, &user, &request)
+            .await
+            .unwrap()
+            .token;
+        Self {
+            pool: pool.clone(),
+            app,
+            user,
+            token,
+        }
+    }
+
+    async fn request(&self, method: Method, uri: &str, body: Option<Value>) -> (StatusCode, Value) {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::AUTHORIZATION, format!("Bearer {}", self.token))
+            .header(header::CONTENT_TYPE, "application/json");
+        let body = body.map_or_else(Body::empty, |body| Body::from(body.to_string()));
+        let response = self
+            .app
+            .clone()
+            .oneshot(request.body(body).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (
+            status,
+            serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+        )
+    }
+
+    async fn create_task(&self, title: &str) -> i64 {
+        let body = json!({
+            "title": title,
+            "description": "",
+            "category": "work",
+            "priority": 1,
+            "due_date": "2030-01-01T00:00:00Z",
+        });
+        let (status, task) = self.request(Method::POST, "/api/tasks", Some(body)).await;
+        assert_eq!(status, StatusCode::OK);
+        task["id"].as_i64().unwrap()
+    }
+
+    async fn move_task(&self, task: i64, body: Value) -> (StatusCode, Value) {
+        self.request(
+            Method::PATCH,
+            &format!("/api/tasks/{task}/move"),
+            Some(body),
+        )
+        .await
+    }
+
+    /// 按看板顺序列出任务
+    async fn board(&self, query: &str) -> Vec<i64> {
+        let (status, tasks) = self
+            .request(Method::GET, &format!("/api/tasks?sort=rank{query}"), None)
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        tasks
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|task| task["id"].as_i64().unwrap())
+            .collect()
+    }
+}
+
+#[tokio::test]
+async fn moves_reorder_columns_without_touching_other_tasks() {
+    let Some(pool) = common::test_pool().await else {
+        return;
+    };
+    let board = Board::new(&pool).await;
+    let [a, b, c, d] = [
+        board.create_task("a").await,
+        board.create_task("b").await,
+        board.create_task("c").await,
+        board.create_task("d").await,
+    ];
+    assert_eq!(board.board("").await, [a, b, c, d]);
+
+    let (status, moved) = board.move_task(d, json!({ "after_id": a })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(board.board("").await, [a, d, b, c]);
+    let (_, first) = board
+        .request(Method::GET, &format!("/api/tasks/{a}"), None)
+        .await;
+    assert!(first["rank"].as_str().unwrap() < moved["rank"].as_str().unwrap());
+
+    let (status, _) = board.move_task(c, json!({ "before_id": a })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(board.board("").await, [c, a, d, b]);
+
+    // 换列时不传相邻任务就排到新列的最后；列按状态名排序
+    let (status, moved) = board.move_task(a, json!({ "status": "in_progress" })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(moved["status"], "in_progress");
+    let (status, _) = board
+        .move_task(b, json!({ "status": "in_progress", "before_id": a }))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(board.board("").await, [b, a, c, d]);
+    assert_eq!(board.board("&status=pending").await, [c, d]);
+
+    // 普通更新换列时也排到最后
+    let (status, _) = board
+        .request(
+            Method::PUT,
+            &format!("/api/tasks/{c}"),
+            Some(json!({ "status": "in_progress" })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(board.board("").await, [b, a, c, d]);
+    let (status, _) = board
+        .move_task(c, json!({ "after_id": b, "before_id": a }))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(board.board("").await, [b, c, a, d]);
+}
+
+#[tokio::test]
+async fn invalid_moves_are_rejected() {
+    let Some(pool) = common::test_pool().await else {
+        return;
+    };
+    let board = Board::new(&pool).await;
+    let [a, b, c] = [
+        board.create_task("a").await,
+        board.create_task("b").await,
+        board.create_task("c").await,
+    ];
+
+    for body in [
+        json!({ "status": "archived" }),
+        json!({ "after_id": a, "before_id": a }),
+        json!({ "after_id": c }),
+        // 相邻任务必须在目标列里
+        json!({ "status": "completed", "after_id": a }),
+        // 顺序反了
+        json!({ "after_id": b, "before_id": a }),
+    ] {
+        let (status, _) = board.move_task(c, body.clone()).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{body}");
+    }
+    assert_eq!(board.board("").await, [a, b, c]);
+
+    let (status, _) = board.move_task(i32::MAX.into(), json!({})).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = board
+        .request(Method::GET, "/api/tasks?sort=title", None)
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn long_ranks_schedule_a_rebalance_that_keeps_the_order() {
+    let Some(pool) = common::test_pool().await else {
+        return;
+    };
+    let board = Board::new(&pool).await;
+    let first = board.create_task("first").await;
+    let last = board.create_task("last").await;
+
+    // 每次都插在 first 后面，rank 一直变长
+    let mut expected = vec![first];
+    for i in 0..60 {
+        let task = board.create_task(&format!("task {i}")).await;
+        let (status, _) = board.move_task(task, json!({ "after_id": first })).await;
+        assert_eq!(status, StatusCode::OK);
+        expected.insert(1, task);
+    }
+    expected.push(last);
+    assert_eq!(board.board("").await, expected);
+
+    let job = RebalanceRanks {
+        organization_id: board.user.organization_id,
+        status: "pending".to_owned(),
+    };
+    let unique_key = job.unique_key();
+    let queued: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM jobs WHERE kind = $1 AND unique_key = $2 AND status = 'pending'",
+    )
+    .bind(RebalanceRanks::KIND)
+    .bind(&unique_key)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(queued, 1);
+
+    let context = JobContext {
+        pool: pool.clone(),
+        config: Arc::new(Config::from_env().unwrap()),
+        job_id: 0,
+        attempt: 1,
+    };
+    job.run(&context).await.unwrap();
+    assert_eq!(board.board("").await, expected);
+    let ranks: Vec<String> =
+        sqlx::query_scalar("SELECT rank FROM tasks WHERE organization_id = $1 ORDER BY rank")
+            .bind(board.user.organization_id)
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+    assert_eq!(ranks, rank::spread(expected.len()));
+
+    // 重新分布之后照常插入
+    let (status, _) = board.move_task(last, json!({ "after_id": first })).await;
+    assert_eq!(status, StatusCode::OK);
+    expected.pop();
+    expected.insert(1, last);
+    assert_eq!(board.board("").await, expected);
+    // 已经手动执行过，不留给其他进程的 worker
+    sqlx::query("DELETE FROM jobs WHERE unique_key = $1")
+        .bind(&unique_key)
+        .execute(&board.pool)
+        .await
+        .unwrap();
+}
@@ -0,0 +1,277 @@
+mod common;
+
+use axum_server::{
+    auth::{AuthUser, Scope},
+    db::tenant,
+};
+use chrono::Utc;
+use sqlx::PgPool;
+
+struct Tenants {
+    a: AuthUser,
+    b: AuthUser,
+    task_a: i32,
+    task_b: i32,
+}
+
+async fn setup(pool: &PgPool) -> Tenants {
+    let org_a = common::create_organization(pool, "tenant a").await;
+    let org_b = common::create_organization(pool, "tenant b").await;
+    let a = AuthUser {
+        user_id: common::create_user(pool, org_a, "a").await,
+        organization_id: org_a,
+        session_id: None,
+        scopes: Scope::ALL.to_vec(),
+    };
+    let b = AuthUser {
+        user_id: common::create_user(pool, org_b, "b").await,
+        organization_id: org_b,
+        session_id: None,
+        scopes: Scope::ALL.to_vec(),
+    };
+
+    let task_a = insert_task(pool, &a, "a's task").await;
+    let task_b = insert_task(pool, &b, "b's task").await;
+
+    Tenants {
+        a,
+        b,
+        task_a,
+        task_b,
+    }
+}
+
+async fn insert_task(pool: &PgPool, user: &AuthUser, title: &str) -> i32 {
+    let mut tx = tenant::begin(pool, user).await.unwrap();
+    let id = sqlx::query_scalar(
+        "INSERT INTO tasks (title, description, category, priority, status, due_date, user_id)
+         VALUES ($1, '', 'work', 1, 'pending', $2, $3) RETURNING id",
+    )
+    .bind(title)
+    .bind(Utc::now())
+    .bind(user.user_id)
+    .fetch_one(&mut *tx)
+    .await
+    .unwrap();
+    tx.commit().await.unwrap();
+    id
+}
+
+#[tokio::test]
+async fn queries_without_tenant_filters_only_see_own_organization() {
+    let Some(pool) = common::test_pool().await else {
+        return;
+    };
+    let t = setup(&pool).await;
+
+    let mut tx = tenant::begin(&pool, &t.a).await.unwrap();
+    // 模拟漏写 WHERE 条件的 handler
+    let ids: Vec<i32> = sqlx::query_scalar("SELECT id FROM tasks")
+        .fetch_all(&mut *tx)
+        .await
+        .unwrap();
+    assert!(ids.contains(&t.task_a));
+    assert!(!ids.contains(&t.task_b));
+
+    let history: Vec<i32> = sqlx::query_scalar("SELECT task_id FROM task_history")
+        .fetch_all(&mut *tx)
+        .await
+        .unwrap();
+    assert!(!history.contains(&t.task_b));
+
+    let users: Vec<i32> = sqlx::query_scalar("SELECT id FROM users")
+        .fetch_all(&mut *tx)
+        .await
+        .unwrap();
+    assert_eq!(users, vec![t.a.user_id]);
+}
+
+#[tokio::test]
+async fn cannot_update_or_delete_other_organizations_rows() {
+    let Some(pool) = common::test_pool().await else {
+        return;
+    };
+    let t = setup(&pool).await;
+
+    let mut tx = tenant::begin(&pool, &t.a).await.unwrap();
+    let updated = sqlx::query("UPDATE tasks SET title = 'hijacked' WHERE id = $1")
+        .bind(t.task_b)
+        .execute(&mut *tx)
+        .await
+        .unwrap();
+    assert_eq!(updated.rows_affected(), 0);
+
+    let deleted = sqlx::query("DELETE FROM tasks WHERE id = $1")
+        .bind(t.task_b)
+        .execute(&mut *tx)
+        .await
+        .unwrap();
+    assert_eq!(deleted.rows_affected(), 0);
+    tx.commit().await.unwrap();
+
+    let title: String = sqlx::query_scalar("SELECT title FROM tasks WHERE id = $1")
+        .bind(t.task_b)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(title, "b's task");
+}
+
+#[tokio::test]
+async fn cannot_write_rows_into_another_organization() {
+    let Some(pool) = common::test_pool().await else {
+        return;
+    };
+    let t = setup(&pool).await;
+
+    // 显式写别的组织
+    let mut tx = tenant::begin(&pool, &t.a).await.unwrap();
+    let result = sqlx::query(
+        "INSERT INTO tasks (title, description, category, priority, status, due_date, user_id, organization_id)
+         VALUES ('x', '', 'work', 1, 'pending', NOW(), $1, $2)",
+    )
+    .bind(t.a.user_id)
+    .bind(t.b.organization_id)
+    .execute(&mut *tx)
+    .await;
+    assert!(result.is_err());
+    drop(tx);
+
+    // 以别的组织的用户身份写入
+    let mut tx = tenant::begin(&pool, &t.a).await.unwrap();
+    let result = sqlx::query(
+        "INSERT INTO tasks (title, description, category, priority, status, due_date, user_id)
+         VALUES ('x', '', 'work', 1, 'pending', NOW(), $1)",
+    )
+    .bind(t.b.user_id)
+    .execute(&mut *tx)
+    .await;
+    assert!(result.is_err());
+    drop(tx);
+
+    // 把自己的任务移到别的组织
+    let mut tx = tenant::begin(&pool, &t.a).await.unwrap();
+    let result = sqlx::query("UPDATE tasks SET organization_id = $1 WHERE id = $2")
+        .bind(t.b.organization_id)
+        .bind(t.task_a)
+        .execute(&mut *tx)
+        .await;
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn shared_views_stay_inside_the_organization() {
+    let Some(pool) = common::test_pool().await else {
+        return;
+    };
+    let t = setup(&pool).await;
+
+    let mut tx = tenant::begin(&pool, &t.b).await.unwrap();
+    let view_id: i32 = sqlx::query_scalar(
+        "INSERT INTO saved_views (user_id, name, query, shared) VALUES ($1, 'b view', 'tag:x', TRUE)
+         RETURNING id",
+    )
+    .bind(t.b.user_id)
+    .fetch_one(&mut *tx)
+    .await
+    .unwrap();
+    tx.commit().await.unwrap();
+
+    let mut tx = tenant::begin(&pool, &t.a).await.unwrap();
+    let visible = axum_server::db::view_repo::find_visible(&mut *tx, t.a.user_id, view_id)
+        .await
+        .unwrap();
+    assert!(visible.is_none());
+}
+
+#[tokio::test]
+async fn tenant_role_without_context_sees_nothing() {
+    let Some(pool) = common::test_pool().await else {
+        return;
+    };
+    let t = setup(&pool).await;
+
+    let mut tx = pool.begin().await.unwrap();
+    sqlx::query(&format!("SET LOCAL ROLE {}", tenant::TENANT_ROLE))
+        .execute(&mut *tx)
+        .await
+        .unwrap();
+    let ids: Vec<i32> = sqlx::query_scalar("SELECT id FROM tasks WHERE id = ANY($1)")
+        .bind(vec![t.task_a, t.task_b])
+        .fetch_all(&mut *tx)
+        .await
+        .unwrap();
+    assert!(ids.is_empty());
+}
+
+#[tokio::test]
+async fn tenant_role_cannot_read_tables_it_was_not_granted() {
+    let Some(pool) = common::test_pool().await else {
+        return;
+    };
+    let t = setup(&pool).await;
+
+    for table in [
+        "sessions",
+        "refresh_tokens",
+        "personal_access_tokens",
+        "jwt_signing_keys",
+        "user_identities",
+        "client_certificates",
+        "sent_notifications",
+        "jobs",
+    ] {
+        let mut tx = tenant::begin(&pool, &t.a).await.unwrap();
+        let result = sqlx::query(&format!("SELECT * FROM {table}"))
+            .execute(&mut *tx)
+            .await;
+        let error = result.expect_err(table).to_string();
+        assert!(error.contains("permission denied"), "{table}: {error}");
+    }
+}
+
+#[tokio::test]
+async fn collection_versions_stay_inside_the_organization() {
+    let Some(pool) = common::test_pool().await else {
+        return;
+    };
+    let t = setup(&pool).await;
+
+    let mut tx = tenant::begin(&pool, &t.a).await.unwrap();
+    let users: Vec<i32> = sqlx::query_scalar("SELECT user_id FROM task_collection_versions")
+        .fetch_all(&mut *tx)
+        .await
+        .unwrap();
+    assert_eq!(users, vec![t.a.user_id]);
+}
+
+#[tokio::test]
+async fn tenant_role_cannot_read_password_hashes_or_change_roles() {
+    let Some(pool) = common::test_pool().await else {
+        return;
+    };
+    let t = setup(&pool).await;
+
+    let mut tx = tenant::begin(&pool, &t.a).await.unwrap();
+    let user = axum_server::db::user_repo::find_by_id(&mut *tx, t.a.user_id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(user.password_hash.is_none());
+    tx.commit().await.unwrap();
+
+    for statement in [
+        "SELECT password_hash FROM users",
+        "SELECT * FROM users",
+        "UPDATE users SET role = 'admin'",
+        "DELETE FROM users",
+    ] {
+        let mut tx = tenant::begin(&pool, &t.a).await.unwrap();
+        let error = sqlx::query(statement)
+            .execute(&mut *tx)
+            .await
+            .expect_err(statement)
+            .to_string();
+        assert!(error.contains("permission denied"), "{statement}: {error}");
+    }
+}
@@ -0,0 +1,517 @@
+mod common;
+
+use axum::{
+    body::Body,
+    http::{header, Method, Request, StatusCode},
+    Router,
+};
+use axum_server::{
+    app::create_app,
+    auth::{AuthUser, Scope},
+    config::Config,
+    models::token::CreatePersonalAccessToken,
+    rate_limit,
+    services::auth_service,
+    state::AppState,
+};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use tower::ServiceExt;
+
+struct Member {
+    id: i32,
+    token: String,
+}
+
+struct Team {
+    app: Router,
+    organization_id: i32,
+    owner: Member,
+    alice: Member,
+    /// 同一个用户的 admin token，用来查看别人的时间表
+    admin: Member,
+}
+
+impl Team {
+    async fn new(pool: &PgPool) -> Self {
+        let config = Config::from_env().unwrap();
+        let backend = rate_limit::connect(&config.rate_limit).await.unwrap();
+        let app = create_app(AppState::new(pool.clone(), config, backend));
+
+        let organization_id = common::create_organization(pool, "billing").await;
+        let owner = common::create_user(pool, organization_id, "owner").await;
+        let alice = common::create_user(pool, organization_id, "alice").await;
+        Self {
+            app,
+            organization_id,
+            owner: member(pool, organization_id, owner, Scope::TasksWrite).await,
+            alice: member(pool, organization_id, alice, Scope::TasksWrite).await,
+            admin: member(pool, organization_id, owner, Scope::Admin).await,
+        }
+    }
+
+    async fn send(
+        &self,
+        member: &Member,
+        method: Method,
+        uri: &str,
+        body: Option<Value>,
+    ) -> (StatusCode, String) {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::AUTHORIZATION, format!("Bearer {}", member.token))
+            .header(header::CONTENT_TYPE, "application/json");
+        let body = body.map_or_else(Body::empty, |body| Body::from(body.to_string()));
+        let response = self
+            .app
+            .clone()
+            .oneshot(request.body(body).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, String::from_utf8(bytes.to_vec()).unwrap())
+    }
+
+    async fn request(
+        &self,
+        member: &Member,
+        method: Method,
+        uri: &str,
+        body: Option<Value>,
+    ) -> (StatusCode, Value) {
+        let (status, body) = self.send(member, method, uri, body).await;
+        (status, serde_json::from_str(&body).unwrap_or(Value::Null))
+    }
+
+    async fn create_task(&self, member: &Member, title: &str) -> i64 {
+        let body = json!({
+            "title": title,
+            "description": "",
+            "category": "work",
+            "priority": 1,
+            "due_date": "2030-01-01T00:00:00Z",
+            "estimate_seconds": 7200,
+        });
+        let (status, task) = self
+            .request(member, Method::POST, "/api/tasks", Some(body))
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(task["estimate_seconds"], 7200);
+        task["id"].as_i64().unwrap()
+    }
+
+    async fn log(&self, member: &Member, task: i64, started_at: &str, ended_at: &str) -> Value {
+        let (status, entry) = self
+            .request(
+                member,
+                Method::POST,
+                &format!("/api/tasks/{task}/time-entries"),
+                Some(json!({ "started_at": started_at, "ended_at": ended_at })),
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK, "{entry}");
+        entry
+    }
+
+    async fn time_spent(&self, task: i64) -> i64 {
+        let (status, task) = self
+            .request(
+                &self.owner,
+                Method::GET,
+                &format!("/api/tasks/{task}"),
+                None,
+            )
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        task["time_spent_seconds"].as_i64().unwrap()
+    }
+}
+
+async fn member(pool: &PgPool, organization_id: i32, user_id: i32, scope: Scope) -> Member {
+    let user = AuthUser {
+        user_id,
+        organization_id,
+        session_id: None,
+        scopes: Scope::ALL.to_vec(),
+    };
+    let request = CreatePersonalAccessToken {
+        name: scope.to_string(),
+        scopes: vec![scope],
+        expires_at: None,
+    };
+    let token = auth_service::create_personal_token(pool, &user, &request)
+        .await
+        .unwrap()
+        .token;
+    Member { id: user_id, token }
+}
+
+#[tokio::test]
+async fn one_timer_runs_per_user_and_entries_add_up() {
+    let Some(pool) = common::test_pool().await else {
+        return;
+    };
+    let team = Team::new(&pool).await;
+    let design = team.create_task(&team.owner, "design").await;
+    let review = team.create_task(&team.owner, "review").await;
+
+    let (status, timer) = team
+        .request(
+            &team.owner,
+            Method::POST,
+            &format!("/api/tasks/{design}/timer"),
+            Some(json!({ "note": "wireframes" })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(timer["ended_at"].is_null());
+    // 计时器已经走了半小时
+    sqlx::query(
+        "UPDATE time_entries SET started_at = started_at - INTERVAL '30 minutes' WHERE id = $1",
+    )
+    .bind(timer["id"].as_i64().unwrap())
+    .execute(&pool)
+    .await
+    .unwrap();
+
+    // 开始另一个计时器时，前一个自动停止
+    let (status, _) = team
+        .request(
+            &team.owner,
+            Method::POST,
+            &format!("/api/tasks/{review}/timer"),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, running) = team
+        .request(&team.owner, Method::GET, "/api/timer", None)
+        .await;
+    assert_eq!(running["task_id"], review);
+    assert_eq!(team.time_spent(design).await, 1800);
+
+    let (status, stopped) = team
+        .request(&team.owner, Method::POST, "/api/timer/stop", None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(stopped["ended_at"].is_string());
+    let (status, _) = team
+        .request(&team.owner, Method::POST, "/api/timer/stop", None)
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (_, running) = team
+        .request(&team.owner, Method::GET, "/api/timer", None)
+        .await;
+    assert!(running.is_null());
+
+    let entry = team
+        .log(
+            &team.owner,
+            design,
+            "2025-03-03T09:00:00Z",
+            "2025-03-03T10:30:00Z",
+        )
+        .await;
+    assert_eq!(entry["duration_seconds"], 5400);
+    assert_eq!(team.time_spent(design).await, 1800 + 5400);
+
+    for (started_at, ended_at) in [
+        // 和上一条重叠
+        ("2025-03-03T10:00:00Z", "2025-03-03T11:00:00Z"),
+        ("2025-03-03T11:00:00Z", "2025-03-03T11:00:00Z"),
+        ("2025-03-03T09:00:00Z", "2025-03-04T10:00:00Z"),
+        ("2099-01-01T09:00:00Z", "2099-01-01T10:00:00Z"),
+    ] {
+        let (status, _) = team
+            .request(
+                &team.owner,
+                Method::POST,
+                &format!("/api/tasks/{design}/time-entries"),
+                Some(json!({ "started_at": started_at, "ended_at": ended_at })),
+            )
+            .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{started_at}");
+    }
+
+    // 预估和实际放在一起比较；0 取消预估
+    let (_, task) = team
+        .request(
+            &team.owner,
+            Method::GET,
+            &format!("/api/tasks/{design}"),
+            None,
+        )
+        .await;
+    assert_eq!(task["estimate_seconds"], 7200);
+    let (status, task) = team
+        .request(
+            &team.owner,
+            Method::PUT,
+            &format!("/api/tasks/{design}"),
+            Some(json!({ "estimate_seconds": 0 })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(task["estimate_seconds"].is_null());
+
+    let (status, _) = team
+        .request(
+            &team.owner,
+            Method::DELETE,
+            &format!("/api/time-entries/{}", entry["id"]),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(team.time_spent(design).await, 1800);
+}
+
+#[tokio::test]
+async fn assignees_log_their_own_time_and_timesheets_export_it() {
+    let Some(pool) = common::test_pool().await else {
+        return;
+    };
+    let team = Team::new(&pool).await;
+    let design = team.create_task(&team.owner, "design").await;
+    let review = team.create_task(&team.owner, "review, final").await;
+
+    // 不是负责人时看不到任务
+    let (status, _) = team
+        .request(
+            &team.alice,
+            Method::POST,
+            &format!("/api/tasks/{design}/timer"),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = team
+        .request(
+            &team.owner,
+            Method::POST,
+            &format!("/api/tasks/{design}/assignees"),
+            Some(json!({ "user_ids": [team.alice.id] })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let alice_entry = team
+        .log(
+            &team.alice,
+            design,
+            "2025-03-03T09:00:00Z",
+            "2025-03-03T10:00:00Z",
+        )
+        .await;
+    // 不同用户的记录可以重叠
+    team.log(
+        &team.owner,
+        design,
+        "2025-03-03T09:30:00Z",
+        "2025-03-03T10:00:00Z",
+    )
+    .await;
+    team.log(
+        &team.owner,
+        review,
+        "2025-03-03T13:00:00Z",
+        "2025-03-03T15:00:00Z",
+    )
+    .await;
+    team.log(
+        &team.owner,
+        review,
+        "2025-03-05T23:30:00Z",
+        "2025-03-06T00:15:00Z",
+    )
+    .await;
+    team.log(
+        &team.owner,
+        review,
+        "2025-03-07T08:00:00Z",
+        "2025-03-07T09:00:00Z",
+    )
+    .await;
+    assert_eq!(team.time_spent(design).await, 3600 + 1800);
+
+    let (_, entries) = team
+        .request(
+            &team.owner,
+            Method::GET,
+            &format!("/api/tasks/{design}/time-entries"),
+            None,
+        )
+        .await;
+    assert_eq!(entries.as_array().unwrap().len(), 2);
+    let (status, _) = team
+        .request(
+            &team.owner,
+            Method::DELETE,
+            &format!("/api/time-entries/{}", alice_entry["id"]),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, timesheet) = team
+        .request(
+            &team.owner,
+            Method::GET,
+            "/api/timesheet?from=2025-03-03&to=2025-03-06",
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(timesheet["user_id"], team.owner.id);
+    assert_eq!(timesheet["total_seconds"], 1800 + 7200 + 2700);
+    let days: Vec<i64> = timesheet["days"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|day| day["seconds"].as_i64().unwrap())
+        .collect();
+    // 跨午夜的记录算在开始那天
+    assert_eq!(days, [9000, 0, 2700, 0]);
+    assert_eq!(timesheet["tasks"][0]["task_id"], review);
+    assert_eq!(timesheet["tasks"][0]["seconds"], 9900);
+    assert_eq!(timesheet["tasks"][0]["estimate_seconds"], 7200);
+    assert_eq!(timesheet["tasks"][0]["time_spent_seconds"], 9900 + 3600);
+    assert_eq!(timesheet["entries"].as_array().unwrap().len(), 3);
+
+    let (status, csv) = team
+        .send(
+            &team.owner,
+            Method::GET,
+            "/api/timesheet?from=2025-03-03&to=2025-03-03&format=csv",
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(
+        lines[0],
+        "date,task_id,task_title,started_at,ended_at,duration_seconds,hours,note"
+    );
+    assert_eq!(lines.len(), 3);
+    assert!(lines[2].starts_with(&format!("2025-03-03,{review},\"review, final\",")));
+    assert!(lines[2].ends_with(",7200,2.00,"));
+
+    // 别人的时间表需要 admin；删除任务后记录仍然保留
+    let alice_timesheet = format!(
+        "/api/timesheet?from=2025-03-03&to=2025-03-03&user={}",
+        team.alice.id
+    );
+    let (status, _) = team
+        .request(&team.owner, Method::GET, &alice_timesheet, None)
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = team
+        .request(
+            &team.owner,
+            Method::DELETE,
+            &format!("/api/tasks/{design}"),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, timesheet) = team
+        .request(&team.admin, Method::GET, &alice_timesheet, None)
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(timesheet["total_seconds"], 3600);
+    assert!(timesheet["entries"][0]["task_id"].is_null());
+
+    for query in [
+        "from=2025-03-05&to=2025-03-03",
+        "from=2024-01-01&to=2025-03-03",
+        "format=xml",
+    ] {
+        let (status, _) = team
+            .request(
+                &team.owner,
+                Method::GET,
+                &format!("/api/timesheet?{query}"),
+                None,
+            )
+            .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{query}");
+    }
+}
+
+#[tokio::test]
+async fn only_organization_admins_see_other_timesheets() {
+    let Some(pool) = common::test_pool().await else {
+        return;
+    };
+    let team = Team::new(&pool).await;
+    let design = team.create_task(&team.owner, "design").await;
+    let (status, _) = team
+        .request(
+            &team.owner,
+            Method::POST,
+            &format!("/api/tasks/{design}/assignees"),
+            Some(json!({ "user_ids": [team.alice.id] })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    team.log(
+        &team.alice,
+        design,
+        "2025-03-03T09:00:00Z",
+        "2025-03-03T10:00:00Z",
+    )
+    .await;
+
+    // 成员给自己建了 admin scope 的 token 也看不到别人的时间表
+    let alice_admin = member(&pool, team.organization_id, team.alice.id, Scope::Admin).await;
+    let (status, _) = team
+        .request(
+            &alice_admin,
+            Method::GET,
+            &format!("/api/timesheet?user={}", team.owner.id),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    // 取消分配之后，自己的时间表里也不再显示任务标题
+    let (status, _) = team
+        .request(
+            &team.owner,
+            Method::PUT,
+            &format!("/api/tasks/{design}/assignees"),
+            Some(json!({ "user_ids": [] })),
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, timesheet) = team
+        .request(
+            &team.alice,
+            Method::GET,
+            "/api/timesheet?from=2025-03-03&to=2025-03-03",
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(timesheet["entries"][0]["task_id"], design);
+    assert!(timesheet["entries"][0]["task_title"].is_null());
+    assert!(timesheet["tasks"][0]["title"].is_null());
+
+    let (status, timesheet) = team
+        .request(
+            &team.admin,
+            Method::GET,
+            &format!(
+                "/api/timesheet?from=2025-03-03&to=2025-03-03&user={}",
+                team.alice.id
+            ),
+            None,
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(timesheet["entries"][0]["task_title"], "design");
+}
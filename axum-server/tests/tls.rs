@@ -0,0 +1,154 @@
+use axum::{extract::Extension, routing::get, Router};
+use axum_server::{
+    config::TlsConfig,
+    tls::{self, CertReloader, ClientCertificate},
+};
+use rustls::{
+    crypto::ring,
+    pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer, ServerName},
+    ClientConfig, RootCertStore,
+};
+use std::{path::PathBuf, sync::Arc, time::Duration};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    sync::oneshot,
+};
+use tokio_rustls::{TlsAcceptor, TlsConnector};
+
+fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/tls")
+        .join(name)
+}
+
+fn der(name: &str) -> CertificateDer<'static> {
+    CertificateDer::from_pem_file(fixture(name)).unwrap()
+}
+
+/// 把证书复制到临时目录，测试里可以随意覆盖
+fn scratch_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "axum-server-tls-{}-{:?}",
+        std::process::id(),
+        std::thread::current().id()
+    ));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn install(dir: &std::path::Path, cert: &str, key: &str) {
+    std::fs::copy(fixture(cert), dir.join("cert.pem")).unwrap();
+    std::fs::copy(fixture(key), dir.join("key.pem")).unwrap();
+    // 有些文件系统的修改时间精度不够，手动推进
+    let later = std::time::SystemTime::now() + Duration::from_secs(1);
+    for file in ["cert.pem", "key.pem"] {
+        std::fs::File::options()
+            .write(true)
+            .open(dir.join(file))
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+    }
+}
+
+#[test]
+fn reloads_changed_certificates_and_keeps_the_old_one_on_error() {
+    let dir = scratch_dir();
+    install(&dir, "server.pem", "server-key.pem");
+    let reloader = CertReloader::load(dir.join("cert.pem"), dir.join("key.pem")).unwrap();
+    assert_eq!(reloader.current().cert[0], der("server.pem"));
+    assert!(!reloader.reload_if_changed().unwrap());
+
+    // 证书换了但私钥还是旧的：不切换
+    install(&dir, "server2.pem", "server-key.pem");
+    assert!(reloader.reload_if_changed().is_err());
+    assert_eq!(reloader.current().cert[0], der("server.pem"));
+
+    std::thread::sleep(Duration::from_millis(10));
+    install(&dir, "server2.pem", "server2-key.pem");
+    assert!(reloader.reload_if_changed().unwrap());
+    assert_eq!(reloader.current().cert[0], der("server2.pem"));
+}
+
+async fn whoami(certificate: Option<Extension<ClientCertificate>>) -> String {
+    match certificate {
+        Some(Extension(certificate)) => certificate.fingerprint,
+        None => "anonymous".to_owned(),
+    }
+}
+
+fn client(with_certificate: bool) -> TlsConnector {
+    let mut roots = RootCertStore::empty();
+    roots.add(der("ca.pem")).unwrap();
+    let builder = ClientConfig::builder_with_provider(Arc::new(ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_root_certificates(roots);
+    let config = if with_certificate {
+        builder
+            .with_client_auth_cert(
+                vec![der("client.pem")],
+                PrivateKeyDer::from_pem_file(fixture("client-key.pem")).unwrap(),
+            )
+            .unwrap()
+    } else {
+        builder.with_no_client_auth()
+    };
+    TlsConnector::from(Arc::new(config))
+}
+
+async fn request_whoami(addr: std::net::SocketAddr, connector: &TlsConnector) -> String {
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let mut stream = connector
+        .connect(ServerName::try_from("localhost").unwrap(), stream)
+        .await
+        .unwrap();
+    stream
+        .write_all(b"GET /whoami HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .await
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    response
+}
+
+#[tokio::test]
+async fn serves_tls_with_optional_client_certificates() {
+    let config = TlsConfig {
+        cert_path: fixture("server.pem"),
+        key_path: fixture("server-key.pem"),
+        client_ca_path: Some(fixture("ca.pem")),
+        client_auth_required: false,
+        reload_interval: Duration::from_secs(10),
+    };
+    let reloader = Arc::new(CertReloader::load(&config.cert_path, &config.key_path).unwrap());
+    let acceptor = TlsAcceptor::from(Arc::new(tls::server_config(&config, reloader).unwrap()));
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let app = Router::new().route("/whoami", get(whoami));
+    let (stop, stopped) = oneshot::channel::<()>();
+    let server = tokio::spawn(tls::serve_tls(
+        listener,
+        acceptor,
+        app,
+        async move {
+            stopped.await.ok();
+        },
+        Duration::from_secs(5),
+    ));
+
+    let expected =
+        ClientCertificate::from_pem(&std::fs::read_to_string(fixture("client.pem")).unwrap())
+            .unwrap();
+    let with_certificate = request_whoami(addr, &client(true)).await;
+    assert!(with_certificate.starts_with("HTTP/1.1 200"));
+    assert!(with_certificate.ends_with(&expected.fingerprint));
+
+    let anonymous = request_whoami(addr, &client(false)).await;
+    assert!(anonymous.ends_with("anonymous"));
+
+    stop.send(()).unwrap();
+    server.await.unwrap().unwrap();
+}